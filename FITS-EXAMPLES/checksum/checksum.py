@@ -0,0 +1,113 @@
+#!/usr/bin/env python3
+"""Writes the checksummed reference file for the `reference_checksums` test.
+
+The CHECKSUM and DATASUM cards are computed by this script alone, with the
+standard library, so that the verification of the crate is checked against
+checksums that share no code with it. The sums are accumulated in 16-bit halves
+as cfitsio does, the encoding follows appendix J of the FITS standard. The
+HDUs are those of the FOS example next to this directory.
+
+    FOSy19g0309t_c2f.fits  every HDU with CHECKSUM and DATASUM
+"""
+
+import os
+import struct
+
+BLOCK = 2880
+CARD = 80
+
+
+def ones_complement_sum(data, total=0):
+    """Sum of the big-endian 32-bit words in 1's complement arithmetic."""
+    hi, lo = total >> 16, total & 0xFFFF
+    for h, l in struct.iter_unpack(">HH", data):
+        hi += h
+        lo += l
+    while True:
+        hicarry, locarry = hi >> 16, lo >> 16
+        if not hicarry and not locarry:
+            break
+        hi = (hi & 0xFFFF) + locarry
+        lo = (lo & 0xFFFF) + hicarry
+    return hi << 16 | lo
+
+
+def char_encode(value):
+    """The 16 character ASCII encoding of the complement of `value`. FITS standard appendix J"""
+    exclude = list(range(0x3A, 0x41)) + list(range(0x5B, 0x61))
+    value = ~value & 0xFFFFFFFF
+    asc = [0] * 16
+    for i in range(4):
+        byte = value >> (24 - 8 * i) & 0xFF
+        ch = [byte // 4 + 0x30] * 4
+        ch[0] += byte % 4
+        check = True
+        while check:
+            check = False
+            for k in exclude:
+                for j in (0, 2):
+                    if ch[j] == k or ch[j + 1] == k:
+                        ch[j] += 1
+                        ch[j + 1] -= 1
+                        check = True
+        for j in range(4):
+            asc[4 * j + i] = ch[j]
+    return bytes(asc[15:] + asc[:15]).decode("ascii")
+
+
+def hdus(data):
+    """Splits a FITS file into the headers without END and the data units with their fill."""
+    offset = 0
+    while offset < len(data):
+        cards, keywords = [], {}
+        while True:
+            card = data[offset : offset + CARD].decode("ascii")
+            offset += CARD
+            if card.startswith("END "):
+                break
+            cards.append(card)
+            if card[8:10] == "= ":
+                keywords[card[:8].strip()] = card[10:].split("/")[0].strip()
+        offset += -offset % BLOCK
+        naxis = int(keywords["NAXIS"])
+        size = 0
+        if naxis:
+            size = abs(int(keywords["BITPIX"])) // 8 * int(keywords.get("GCOUNT", 1))
+            axes = 1
+            for n in range(1, naxis + 1):
+                axes *= int(keywords["NAXIS%d" % n])
+            size *= int(keywords.get("PCOUNT", 0)) + axes
+        size += -size % BLOCK
+        yield cards, data[offset : offset + size]
+        offset += size
+
+
+def card(keyword, value, comment):
+    return "%-80s" % ("%-8s= %-20s / %s" % (keyword, "'%s'" % value, comment))
+
+
+def main():
+    os.chdir(os.path.dirname(os.path.abspath(__file__)))
+    with open("../FOSy19g0309t_c2f.fits", "rb") as f:
+        original = f.read()
+    out = b""
+    for cards, data in hdus(original):
+        datasum = ones_complement_sum(data)
+        cards = [c for c in cards if c[:8] not in ("CHECKSUM", "DATASUM ")]
+        cards += [
+            card("CHECKSUM", "0" * 16, "HDU checksum"),
+            card("DATASUM", str(datasum), "data unit checksum"),
+            "%-80s" % "END",
+        ]
+        header = "".join(cards)
+        header = (header + " " * (-len(header) % BLOCK)).encode("ascii")
+        checksum = char_encode(ones_complement_sum(header, datasum))
+        header = header.replace(b"0" * 16, checksum.encode("ascii"), 1)
+        assert ones_complement_sum(header, datasum) == 0xFFFFFFFF
+        out += header + data
+    with open("FOSy19g0309t_c2f.fits", "wb") as f:
+        f.write(out)
+
+
+if __name__ == "__main__":
+    main()
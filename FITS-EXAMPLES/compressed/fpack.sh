@@ -0,0 +1,12 @@
+#!/bin/sh
+# Writes the fpack reference files used by the `fpack_fixtures` test, one per
+# compression algorithm, from the 16-bit UIT image next to this directory.
+# The fpack_ prefix keeps them apart from the files written by reference.py.
+set -e
+cd "$(dirname "$0")"
+original=../UITfuv2582gc.fits
+fpack -r -S "$original" > fpack_rice.fits.fz
+fpack -g1 -S "$original" > fpack_gzip1.fits.fz
+fpack -g2 -S "$original" > fpack_gzip2.fits.fz
+fpack -p -S "$original" > fpack_plio.fits.fz
+fpack -h -s 0 -S "$original" > fpack_hcompress.fits.fz
@@ -0,0 +1,242 @@
+#!/usr/bin/env python3
+"""Writes tile-compressed reference files for the `reference_fixtures` test.
+
+The files are encoded by this script alone, with the standard library, so that
+the decoder of the crate is checked against an encoder that shares no code with
+it. GZIP_1 and GZIP_2 tiles are compressed by zlib, RICE_1 tiles and the
+subtractive dithering follow the algorithms of FITS standard section 10. The
+pixels are those of the 16-bit UIT image next to this directory, in tiles of
+one row as written by fpack.
+
+    gzip1.fits.fz        GZIP_1 of the 16-bit image
+    gzip2.fits.fz        GZIP_2, i.e. shuffled bytes, of the 16-bit image
+    rice.fits.fz         RICE_1 of the 16-bit image
+    rice_dither.fits.fz  RICE_1 of the image divided by 7 as 32-bit floats,
+                         quantized with SUBTRACTIVE_DITHER_1
+"""
+
+import gzip
+import os
+import struct
+
+BLOCK = 2880
+
+
+def card(keyword, value=None):
+    if value is None:
+        text = keyword
+    elif isinstance(value, bool):
+        text = "%-8s= %20s" % (keyword, "T" if value else "F")
+    elif isinstance(value, str):
+        text = "%-8s= '%-8s'" % (keyword, value.replace("'", "''"))
+    else:
+        text = "%-8s= %20s" % (keyword, value)
+    return "%-80s" % text
+
+
+def header(cards):
+    text = "".join(cards + [card("END")])
+    return text.encode("ascii") + b" " * (-len(text) % BLOCK)
+
+
+def padded(data):
+    return data + b"\0" * (-len(data) % BLOCK)
+
+
+def read_image(path):
+    with open(path, "rb") as f:
+        data = f.read()
+    keywords, scaling, offset = {}, [], 0
+    while True:
+        record = data[offset : offset + 80].decode("ascii")
+        offset += 80
+        if record.startswith("END "):
+            break
+        if record[8:10] == "= ":
+            keywords[record[:8].strip()] = record[10:].split("/")[0].strip()
+        if record[:8] in ("BSCALE  ", "BZERO   "):
+            scaling.append(record)
+    offset += -offset % BLOCK
+    assert keywords["BITPIX"] == "16"
+    nx, ny = int(keywords["NAXIS1"]), int(keywords["NAXIS2"])
+    pixels = struct.unpack(">%dh" % (nx * ny), data[offset : offset + 2 * nx * ny])
+    return nx, ny, list(pixels), scaling
+
+
+class Bits:
+    """Writes bits, the most significant first."""
+
+    def __init__(self):
+        self.bytes, self.value, self.count = bytearray(), 0, 0
+
+    def write(self, value, n):
+        for i in reversed(range(n)):
+            self.value = self.value << 1 | (value >> i) & 1
+            self.count += 1
+            if self.count == 8:
+                self.bytes.append(self.value)
+                self.value, self.count = 0, 0
+
+    def finish(self):
+        if self.count:
+            self.write(0, 8 - self.count)
+        return bytes(self.bytes)
+
+
+def rice(values, bytepix, block_size=32):
+    """RICE_1 of signed integers of `bytepix` bytes. FITS standard section 10.4.1"""
+    fsbits, fsmax = {1: (3, 6), 2: (4, 14), 4: (5, 25)}[bytepix]
+    bbits = 8 * bytepix
+    modulus = 1 << bbits
+    bits = Bits()
+    bits.write(values[0] % modulus, bbits)
+    last = values[0]
+    for start in range(0, len(values), block_size):
+        diffs = []
+        for v in values[start : start + block_size]:
+            d = (v - last + modulus // 2) % modulus - modulus // 2
+            diffs.append(2 * d if d >= 0 else -2 * d - 1)
+            last = v
+        total = sum(diffs)
+        mean = max((total - len(diffs) // 2 - 1) / len(diffs), 0)
+        fs, p = 0, int(mean) >> 1
+        while p > 0:
+            p >>= 1
+            fs += 1
+        if fs >= fsmax:
+            bits.write(fsmax + 1, fsbits)
+            for d in diffs:
+                bits.write(d, bbits)
+        elif fs == 0 and total == 0:
+            bits.write(0, fsbits)
+        else:
+            bits.write(fs + 1, fsbits)
+            for d in diffs:
+                bits.write(0, d >> fs)
+                bits.write(1, 1)
+                bits.write(d & ((1 << fs) - 1), fs)
+    return bits.finish()
+
+
+def randoms():
+    """The dither offsets, a Park-Miller sequence in single precision. FITS standard 10.2.1"""
+    a, m, seed, values = 16807.0, 2147483647.0, 1.0, []
+    for _ in range(10000):
+        temp = a * seed
+        seed = temp - m * int(temp / m)
+        values.append(struct.unpack("f", struct.pack("f", seed / m))[0])
+    assert seed == 1043618065.0
+    return values
+
+
+def nint(x):
+    return int(x + 0.5) if x >= 0 else int(x - 0.5)
+
+
+def dither(tiles, zdither0, scale):
+    """Quantizes the float tiles with SUBTRACTIVE_DITHER_1. FITS standard section 10.2"""
+    r = randoms()
+    quantized, zeros = [], []
+    for tile, values in enumerate(tiles, 1):
+        seed = (tile - 1 + zdither0 - 1) % 10000
+        following = int(r[seed] * 500)
+        zero = min(values)
+        ints = []
+        for v in values:
+            ints.append(nint((v - zero) / scale + r[following] - 0.5))
+            following += 1
+            if following == 10000:
+                seed = (seed + 1) % 10000
+                following = int(r[seed] * 500)
+        quantized.append(ints)
+        zeros.append(zero)
+    return quantized, zeros
+
+
+def compressed(name, nx, ny, zbitpix, zcmptype, tiles, parameters=(), scales=None, extra=()):
+    heap, rows = b"", b""
+    for n, tile in enumerate(tiles):
+        rows += struct.pack(">ii", len(tile), len(heap))
+        if scales is not None:
+            rows += struct.pack(">dd", *scales[n])
+        heap += tile
+    fields = [("COMPRESSED_DATA", "1PB(%d)" % max(len(t) for t in tiles))]
+    if scales is not None:
+        fields += [("ZSCALE", "1D"), ("ZZERO", "1D")]
+    cards = [
+        card("XTENSION", "BINTABLE"),
+        card("BITPIX", 8),
+        card("NAXIS", 2),
+        card("NAXIS1", len(rows) // len(tiles)),
+        card("NAXIS2", len(tiles)),
+        card("PCOUNT", len(heap)),
+        card("GCOUNT", 1),
+        card("TFIELDS", len(fields)),
+    ]
+    for n, (ttype, tform) in enumerate(fields, 1):
+        cards += [card("TTYPE%d" % n, ttype), card("TFORM%d" % n, tform)]
+    cards += [
+        card("ZIMAGE", True),
+        card("ZTILE1", nx),
+        card("ZTILE2", 1),
+        card("ZCMPTYPE", zcmptype),
+    ]
+    for n, (zname, zval) in enumerate(parameters, 1):
+        cards += [card("ZNAME%d" % n, zname), card("ZVAL%d" % n, zval)]
+    cards += [card(*k) if isinstance(k, tuple) else card(k) for k in extra]
+    cards += [
+        card("ZSIMPLE", True),
+        card("ZBITPIX", zbitpix),
+        card("ZNAXIS", 2),
+        card("ZNAXIS1", nx),
+        card("ZNAXIS2", ny),
+        card("EXTNAME", "COMPRESSED_IMAGE"),
+    ]
+    primary = header(
+        [card("SIMPLE", True), card("BITPIX", 8), card("NAXIS", 0), card("EXTEND", True)]
+    )
+    with open(name, "wb") as f:
+        f.write(primary + header(cards) + padded(rows + heap))
+
+
+def shuffle(data, size):
+    return b"".join(data[i::size] for i in range(size))
+
+
+def main():
+    os.chdir(os.path.dirname(os.path.abspath(__file__)))
+    # BSCALE and BZERO of the integer image are kept as they are
+    nx, ny, pixels, scaling = read_image("../UITfuv2582gc.fits")
+    rows = [pixels[y * nx : (y + 1) * nx] for y in range(ny)]
+    big_endian = [struct.pack(">%dh" % nx, *row) for row in rows]
+
+    gzip1 = [gzip.compress(row, mtime=0) for row in big_endian]
+    compressed("gzip1.fits.fz", nx, ny, 16, "GZIP_1", gzip1, extra=scaling)
+    gzip2 = [gzip.compress(shuffle(row, 2), mtime=0) for row in big_endian]
+    compressed("gzip2.fits.fz", nx, ny, 16, "GZIP_2", gzip2, extra=scaling)
+    rice1 = [rice(row, 2) for row in rows]
+    parameters = [("BLOCKSIZE", 32), ("BYTEPIX", 2)]
+    compressed("rice.fits.fz", nx, ny, 16, "RICE_1", rice1, parameters, extra=scaling)
+
+    # the same single precision values as `(pixel as f64 / 7.0) as f32`
+    floats = [
+        [struct.unpack("f", struct.pack("f", p / 7.0))[0] for p in row] for row in rows
+    ]
+    scale, zdither0 = 0.05, 42
+    quantized, zeros = dither(floats, zdither0, scale)
+    tiles = [rice(ints, 4) for ints in quantized]
+    compressed(
+        "rice_dither.fits.fz",
+        nx,
+        ny,
+        -32,
+        "RICE_1",
+        tiles,
+        [("BLOCKSIZE", 32), ("BYTEPIX", 4)],
+        [(scale, zero) for zero in zeros],
+        [("ZQUANTIZ", "SUBTRACTIVE_DITHER_1"), ("ZDITHER0", zdither0)],
+    )
+
+
+if __name__ == "__main__":
+    main()
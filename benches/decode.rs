@@ -0,0 +1,99 @@
+//! Decoding throughput of data arrays for every BITPIX, compared with copying the same bytes.
+//! The images of FITS-EXAMPLES are converted to each BITPIX before they are decoded.
+//!
+//! Run with `cargo bench --bench decode`.
+
+use std::time::{Duration, Instant};
+
+use fitsio::{
+    builder::{FitsBuilder, HduBuilder},
+    parse_fits,
+    types::data_array::DataArray,
+    write_fits,
+};
+
+const IMAGES: [(&str, usize); 5] = [
+    ("UITfuv2582gc.fits", 0),
+    ("WFPC2ASSNu5780205bx.fits", 0),
+    ("WFPC2u5780205r_c0fx.fits", 0),
+    ("FGSf64y0106m_a1f.fits", 0),
+    ("NICMOSn4hk12010_mos.fits", 1),
+];
+
+const BITPIX: [i8; 6] = [8, 16, 32, 64, -32, -64];
+
+fn main() {
+    println!(
+        "{:<28} {:>6} {:>10} {:>12} {:>12}",
+        "image", "BITPIX", "bytes", "decode MB/s", "copy MB/s"
+    );
+    for (name, hdu) in IMAGES {
+        let bytes = std::fs::read(format!(
+            "{}/FITS-EXAMPLES/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        ))
+        .unwrap();
+        let fits = parse_fits(&bytes).unwrap();
+        let data_array = fits.hdus()[hdu].data_array().as_ref().unwrap();
+        let values = data_array.physical_values();
+        for bitpix in BITPIX {
+            let file = file(convert(&values, data_array.dimensions(), bitpix));
+            let size = file.len();
+            let decode = throughput(size, || parse_fits(std::hint::black_box(&file)).unwrap());
+            let copy = throughput(size, || std::hint::black_box(&file).to_vec());
+            println!(
+                "{:<28} {:>6} {:>10} {:>12.0} {:>12.0}",
+                name, bitpix, size, decode, copy
+            );
+        }
+    }
+}
+
+/// The values as a data array of type `bitpix`, integers are scaled to the range of the type.
+fn convert(values: &[f64], dimensions: &[u32], bitpix: i8) -> DataArray {
+    let dimensions = dimensions.to_vec();
+    let finite = values.iter().copied().filter(|v| v.is_finite());
+    let min = finite.clone().fold(f64::INFINITY, f64::min);
+    let max = finite.fold(f64::NEG_INFINITY, f64::max);
+    let (low, high) = match bitpix {
+        8 => (0.0, u8::MAX as f64),
+        16 => (i16::MIN as f64, i16::MAX as f64),
+        32 => (i32::MIN as f64, i32::MAX as f64),
+        64 => (-(2f64.powi(52)), 2f64.powi(52)),
+        -32 => {
+            let values = values.iter().map(|&v| v as f32).collect();
+            return DataArray::from_f32(values, dimensions, None, None);
+        }
+        _ => return DataArray::from_f64(values.to_vec(), dimensions, None, None),
+    };
+    // one step is left for BLANK
+    let bscale = match max > min {
+        true => (max - min) / (high - low - 1.0),
+        false => 1.0,
+    };
+    let bzero = min - (low + 1.0) * bscale;
+    DataArray::quantize(values, dimensions, bitpix, bzero, bscale, Some(low as i64)).unwrap()
+}
+
+/// A FITS file with `data_array` as the primary HDU.
+fn file(data_array: DataArray) -> Vec<u8> {
+    let fits = FitsBuilder::new()
+        .primary(HduBuilder::new(Some(data_array)))
+        .build()
+        .unwrap();
+    let mut bytes = Vec::new();
+    write_fits(&mut bytes, &fits).unwrap();
+    bytes
+}
+
+/// Runs `f` repeatedly for at least half a second and returns the throughput in MB/s.
+fn throughput<T>(size: usize, mut f: impl FnMut() -> T) -> f64 {
+    let start = Instant::now();
+    let mut runs = 0;
+    while start.elapsed() < Duration::from_millis(500) {
+        std::hint::black_box(f());
+        runs += 1;
+    }
+    (size * runs) as f64 / start.elapsed().as_secs_f64() / 1e6
+}
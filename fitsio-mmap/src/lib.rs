@@ -0,0 +1,15 @@
+//! Read-only memory maps for the `mmap` feature of `fitsio`. Mapping a file is the only
+//! unsafe operation it needs, so it lives in this crate and `fitsio` forbids unsafe code.
+
+use std::{fs::File, io};
+
+pub use memmap2::Mmap;
+
+/// Maps `file` into memory for reading.
+///
+/// Modifying or truncating the file while it is mapped is undefined behaviour, which the caller
+/// has to rule out like for any memory map.
+pub fn map(file: &File) -> io::Result<Mmap> {
+    // SAFETY: the map is read-only, the caller keeps the file unchanged while it is mapped
+    unsafe { Mmap::map(file) }
+}
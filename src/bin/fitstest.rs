@@ -1,9 +1,9 @@
-use fitsio::parse_fits;
+use fitsio::reader::FitsReader;
 use fitsio::types::data_array::DataArray;
 use fitsio::types::FitsError;
+use std::env;
 use std::fs::File;
 use std::io::BufWriter;
-use std::{env, io::Read};
 use tracing::{error, instrument, Level};
 use tracing_subscriber::FmtSubscriber;
 
@@ -19,51 +19,63 @@ fn main() -> Result<(), FitsError> {
 
     let (in_file, out_file) = open_files(args)?;
 
-    //read the fits file
-    let mut reader = std::io::BufReader::new(in_file);
-    let mut buffer = [0u8; 4000];
-    let mut bytes = Vec::new();
-    loop {
-        let bytes_read = reader.read(&mut buffer)?;
-        if bytes_read == 0 {
-            break;
-        }
-        bytes.extend_from_slice(&buffer[..bytes_read]);
-    }
-
-    let res = parse_fits(&bytes);
-    match res {
-        Ok(fits) => match fits.primary_hdu() {
-            Some(hdu) => match hdu.data_array() {
-                Some(data_array) => match data_array {
-                    DataArray::U8(data, dimensions, _, _) => {
-                        let bit_depth = png::BitDepth::Eight;
-                        write_png(data, dimensions[0], dimensions[1], bit_depth, out_file);
-                    }
-                    DataArray::I16(data, dimensions, _, _) => {
-                        let bit_depth = png::BitDepth::Eight;
-                        let data = data.iter().map(|&x| (x * 50) as u8).collect::<Vec<u8>>();
-                        write_png(&data, dimensions[0], dimensions[1], bit_depth, out_file);
-                    }
-                    DataArray::I32(_data, _, _, _) => todo!(),
-                    DataArray::I64(_data, _, _, _) => todo!(),
-                    DataArray::F32(data, dimensions, _, _) => {
-                        let bit_depth = png::BitDepth::Sixteen;
-                        let data = data
-                            .iter()
-                            .flat_map(|&x| ((x * 1000.0) as u16).to_be_bytes())
-                            .collect::<Vec<u8>>();
-                        write_png(&data, dimensions[0], dimensions[1], bit_depth, out_file);
-                    }
-                    DataArray::F64(_data, _, _, _) => todo!(),
-                },
-                None => {
-                    println!("No data array found.");
-                }
-            },
-            None => panic!("No primary hdu found"),
+    //read the data array of the primary hdu
+    let mut reader = FitsReader::new(std::io::BufReader::new(in_file))?;
+    match reader.read_data_array(0)? {
+        Some(data_array) => match data_array {
+            DataArray::U8(data, dimensions, ..) => {
+                let bit_depth = png::BitDepth::Eight;
+                write_png(&data, dimensions[0], dimensions[1], bit_depth, out_file);
+            }
+            DataArray::I16(data, dimensions, ..) => {
+                let bit_depth = png::BitDepth::Eight;
+                let data = data.iter().map(|&x| (x * 50) as u8).collect::<Vec<u8>>();
+                write_png(&data, dimensions[0], dimensions[1], bit_depth, out_file);
+            }
+            DataArray::I32(_data, ..) => todo!(),
+            DataArray::I64(_data, ..) => todo!(),
+            DataArray::F32(data, dimensions, _, _) => {
+                let bit_depth = png::BitDepth::Sixteen;
+                let data = data
+                    .iter()
+                    .flat_map(|&x| ((x * 1000.0) as u16).to_be_bytes())
+                    .collect::<Vec<u8>>();
+                write_png(&data, dimensions[0], dimensions[1], bit_depth, out_file);
+            }
+            DataArray::F64(_data, _, _, _) => todo!(),
+            DataArray::U16(data, dimensions, _) => {
+                let bit_depth = png::BitDepth::Sixteen;
+                let data = data
+                    .iter()
+                    .flat_map(|x| x.to_be_bytes())
+                    .collect::<Vec<u8>>();
+                write_png(&data, dimensions[0], dimensions[1], bit_depth, out_file);
+            }
+            DataArray::I8(data, dimensions, _) => {
+                let bit_depth = png::BitDepth::Eight;
+                let data = data.iter().map(|&x| (x as u8) ^ 0x80).collect::<Vec<u8>>();
+                write_png(&data, dimensions[0], dimensions[1], bit_depth, out_file);
+            }
+            DataArray::U32(data, dimensions, _) => {
+                let bit_depth = png::BitDepth::Sixteen;
+                let data = data
+                    .iter()
+                    .flat_map(|&x| ((x >> 16) as u16).to_be_bytes())
+                    .collect::<Vec<u8>>();
+                write_png(&data, dimensions[0], dimensions[1], bit_depth, out_file);
+            }
+            DataArray::U64(data, dimensions, _) => {
+                let bit_depth = png::BitDepth::Sixteen;
+                let data = data
+                    .iter()
+                    .flat_map(|&x| ((x >> 48) as u16).to_be_bytes())
+                    .collect::<Vec<u8>>();
+                write_png(&data, dimensions[0], dimensions[1], bit_depth, out_file);
+            }
         },
-        Err(e) => return Err(e),
+        None => {
+            println!("No data array found.");
+        }
     }
 
     //write the fits file
@@ -74,7 +86,7 @@ fn main() -> Result<(), FitsError> {
 }
 
 #[instrument]
-fn write_png(data: &[u8], width: u32, height: u32, bit_depth: png::BitDepth, file: File) -> () {
+fn write_png(data: &[u8], width: u32, height: u32, bit_depth: png::BitDepth, file: File) {
     let mut w = &mut BufWriter::new(file);
     //    let mut image_buffer = Vec::new();
 
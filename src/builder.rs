@@ -0,0 +1,603 @@
+use crate::{
+    types::{
+        binary_table::BinaryTable, data_array::DataArray, header::Header, keyword::Keyword,
+        keyword_record::KeywordRecord, value::Value, Fits, FitsError, HDU,
+    },
+    writer,
+};
+
+/// Creates a fits structure from scratch. The mandatory keywords of every HDU are generated
+/// from its data array, so that the headers always describe the data they belong to.
+///
+/// # Examples
+///
+/// ```
+/// use fitsio::builder::{FitsBuilder, HduBuilder};
+/// use fitsio::types::{data_array::DataArray, keyword::Keyword, value::Value};
+///
+/// let frame = DataArray::from_i16(vec![0; 6], vec![3, 2], None, None);
+/// let fits = FitsBuilder::new()
+///     .primary(HduBuilder::new(Some(frame)).keyword(
+///         Keyword::Instrume,
+///         Value::CharacterString(String::from("CAMERA")),
+///         Some("capturing instrument"),
+///     ))
+///     .build()
+///     .unwrap();
+/// assert_eq!(fits.hdus().len(), 1);
+/// ```
+#[derive(Debug, Default)]
+pub struct FitsBuilder<'a> {
+    primary: Option<HduBuilder<'a>>,
+    extensions: Vec<HduBuilder<'a>>,
+}
+
+impl<'a> FitsBuilder<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the primary HDU. Without one, an empty primary HDU is generated.
+    pub fn primary(self, hdu: HduBuilder<'a>) -> Self {
+        Self {
+            primary: Some(hdu),
+            ..self
+        }
+    }
+
+    /// Appends an image or binary table extension.
+    pub fn extension(mut self, hdu: HduBuilder<'a>) -> Self {
+        self.extensions.push(hdu);
+        self
+    }
+
+    pub fn build(self) -> Result<Fits<'a>, FitsError> {
+        let extend = !self.extensions.is_empty();
+        let mut hdus = vec![self.primary.unwrap_or_default().build_primary(extend)?];
+        for extension in self.extensions {
+            hdus.push(extension.build_extension()?);
+        }
+        Ok(Fits::from(hdus))
+    }
+}
+
+/// Collects the data array or binary table and the user keywords of a single HDU.
+#[derive(Debug, Default)]
+pub struct HduBuilder<'a> {
+    data: Option<DataArray>,
+    binary_table: Option<BinaryTable>,
+    keyword_records: Vec<KeywordRecord<'a>>,
+}
+
+enum HduKind {
+    Primary { extend: bool },
+    Image,
+    BinaryTable,
+}
+
+impl<'a> HduBuilder<'a> {
+    pub fn new(data: Option<DataArray>) -> Self {
+        Self {
+            data,
+            binary_table: None,
+            keyword_records: Vec::new(),
+        }
+    }
+
+    /// A binary table extension. The column start offsets are ignored, fields are laid out in
+    /// the order of the columns and variable length arrays are stored in the heap.
+    pub fn binary_table(table: BinaryTable) -> Self {
+        Self {
+            data: None,
+            binary_table: Some(table),
+            keyword_records: Vec::new(),
+        }
+    }
+
+    /// Appends a keyword record after the generated mandatory keywords.
+    pub fn keyword(self, keyword: Keyword, value: Value<'a>, comment: Option<&'a str>) -> Self {
+        self.record(KeywordRecord::new(keyword, value, comment))
+    }
+
+    pub fn record(mut self, record: KeywordRecord<'a>) -> Self {
+        self.keyword_records.push(record);
+        self
+    }
+
+    /// Builds the primary HDU, with EXTEND = T if extensions follow.
+    pub(crate) fn build_primary(self, extend: bool) -> Result<HDU<'a>, FitsError> {
+        self.build(HduKind::Primary { extend })
+    }
+
+    /// Builds an image or binary table extension.
+    pub(crate) fn build_extension(self) -> Result<HDU<'a>, FitsError> {
+        let kind = match self.binary_table {
+            Some(_) => HduKind::BinaryTable,
+            None => HduKind::Image,
+        };
+        self.build(kind)
+    }
+
+    /// Generates SIMPLE or XTENSION, BITPIX, NAXIS, NAXISn, EXTEND, PCOUNT and GCOUNT as well as
+    /// BZERO and BSCALE, if they differ from their defaults, and BLANK of integer arrays.
+    /// FITS standard section 4.4.1
+    fn build(self, kind: HduKind) -> Result<HDU<'a>, FitsError> {
+        // a tile-compressed image keeps BZERO, BSCALE and BLANK of the image in the header of
+        // its binary table. FITS standard section 10.1
+        let is_table = self.binary_table.is_some();
+        if let Some(record) = self.keyword_records.iter().find(|record| {
+            is_generated(record.keyword())
+                && !(is_table
+                    && matches!(
+                        record.keyword(),
+                        Keyword::BZero | Keyword::BScale | Keyword::Blank
+                    ))
+        }) {
+            return Err(FitsError::InvalidHdu(format!(
+                "keyword {} is generated from the data array",
+                record.keyword().name()
+            )));
+        }
+        if let Some(table) = self.binary_table {
+            return match kind {
+                HduKind::BinaryTable => binary_table(table, self.keyword_records),
+                _ => Err(FitsError::InvalidHdu(String::from(
+                    "a binary table cannot be the primary HDU",
+                ))),
+            };
+        }
+        if let Some(data) = &self.data {
+            check_dimensions(data)?;
+        }
+
+        let mut records = vec![match kind {
+            HduKind::Primary { .. } => {
+                KeywordRecord::new(Keyword::Simple, Value::Logical(true), None)
+            }
+            HduKind::Image | HduKind::BinaryTable => KeywordRecord::new(
+                Keyword::Xtension,
+                Value::CharacterString(String::from("IMAGE")),
+                None,
+            ),
+        }];
+        let (bitpix, dimensions) = match &self.data {
+            Some(data) => (data.bitpix(), data.dimensions()),
+            None => (8, &[][..]),
+        };
+        records.push(KeywordRecord::new(
+            Keyword::BitPix,
+            Value::Integer(bitpix as i64),
+            None,
+        ));
+        records.push(KeywordRecord::new(
+            Keyword::NAxis,
+            Value::Integer(dimensions.len() as i64),
+            None,
+        ));
+        for (n, &d) in dimensions.iter().enumerate() {
+            records.push(KeywordRecord::new(
+                Keyword::NAxisn(n as u16 + 1),
+                Value::Integer(d as i64),
+                None,
+            ));
+        }
+        match kind {
+            HduKind::Primary { extend: true } => records.push(KeywordRecord::new(
+                Keyword::Extend,
+                Value::Logical(true),
+                None,
+            )),
+            HduKind::Primary { extend: false } => {}
+            HduKind::Image | HduKind::BinaryTable => {
+                records.push(KeywordRecord::new(Keyword::PCount, Value::Integer(0), None));
+                records.push(KeywordRecord::new(Keyword::GCount, Value::Integer(1), None));
+            }
+        }
+        if let Some(data) = &self.data {
+            let bzero = data.bzero();
+            if bzero != 0.0 {
+                // integral offsets below 2^53, e.g. of unsigned integers up to 32 bits, are written
+                // as integers, larger ones such as 2^63 as reals
+                let value = match bzero.fract() == 0.0 && bzero.abs() < 2f64.powi(53) {
+                    true => Value::Integer(bzero as i64),
+                    false => Value::Real(bzero),
+                };
+                records.push(KeywordRecord::new(Keyword::BZero, value, None));
+            }
+            if data.bscale() != 1.0 {
+                records.push(KeywordRecord::new(
+                    Keyword::BScale,
+                    Value::Real(data.bscale()),
+                    None,
+                ));
+            }
+            if let Some(blank) = data.blank() {
+                records.push(KeywordRecord::new(
+                    Keyword::Blank,
+                    Value::Integer(blank),
+                    None,
+                ));
+            }
+        }
+        records.extend(self.keyword_records);
+        Ok(HDU::new(Header::from(records), self.data))
+    }
+}
+
+/// Generates the mandatory keywords of a binary table extension and the field descriptions
+/// TTYPEn, TFORMn, TUNITn, TDIMn, TNULLn, TSCALn and TZEROn. PCOUNT is the size of the heap.
+/// FITS standard section 7.3.1
+fn binary_table<'a>(
+    table: BinaryTable,
+    keyword_records: Vec<KeywordRecord<'a>>,
+) -> Result<HDU<'a>, FitsError> {
+    let layout = writer::binary_table(&table)?;
+    let number_of_rows = table.rows().len();
+    let mut records = vec![
+        KeywordRecord::new(
+            Keyword::Xtension,
+            Value::CharacterString(String::from("BINTABLE")),
+            None,
+        ),
+        KeywordRecord::new(Keyword::BitPix, Value::Integer(8), None),
+        KeywordRecord::new(Keyword::NAxis, Value::Integer(2), None),
+        KeywordRecord::new(
+            Keyword::NAxisn(1),
+            Value::Integer(layout.row_length as i64),
+            None,
+        ),
+        KeywordRecord::new(
+            Keyword::NAxisn(2),
+            Value::Integer(number_of_rows as i64),
+            None,
+        ),
+        KeywordRecord::new(
+            Keyword::PCount,
+            Value::Integer(layout.heap.len() as i64),
+            None,
+        ),
+        KeywordRecord::new(Keyword::GCount, Value::Integer(1), None),
+        KeywordRecord::new(
+            Keyword::Tfields,
+            Value::Integer(table.columns().len() as i64),
+            None,
+        ),
+    ];
+    for (i, (column, format)) in table.columns().iter().zip(&layout.formats).enumerate() {
+        let n = i as u16 + 1;
+        if let Some(name) = column.name() {
+            records.push(KeywordRecord::new(
+                Keyword::TTypen(n),
+                Value::CharacterString(name.to_string()),
+                None,
+            ));
+        }
+        records.push(KeywordRecord::new(
+            Keyword::TFormn(n),
+            Value::CharacterString(format.to_string()),
+            None,
+        ));
+        if let Some(unit) = column.unit() {
+            records.push(KeywordRecord::new(
+                Keyword::TUnitn(n),
+                Value::CharacterString(unit.to_string()),
+                None,
+            ));
+        }
+        let dimensions = column.dimensions();
+        if dimensions != [format.repeat()] {
+            let dimensions = dimensions
+                .iter()
+                .map(|d| d.to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+            records.push(KeywordRecord::new(
+                Keyword::TDimn(n),
+                Value::CharacterString(format!("({})", dimensions)),
+                None,
+            ));
+        }
+        if let Some(null) = column.null() {
+            records.push(KeywordRecord::new(
+                Keyword::TNulln(n),
+                Value::Integer(null),
+                None,
+            ));
+        }
+        if column.scale() != 1.0 {
+            records.push(KeywordRecord::new(
+                Keyword::TScaln(n),
+                Value::Real(column.scale()),
+                None,
+            ));
+        }
+        if column.zero() != 0.0 {
+            records.push(KeywordRecord::new(
+                Keyword::TZeron(n),
+                Value::Real(column.zero()),
+                None,
+            ));
+        }
+    }
+    records.extend(keyword_records);
+    let data = DataArray::from_u8(
+        layout.rows,
+        vec![layout.row_length as u32, number_of_rows as u32],
+        None,
+        None,
+    );
+    Ok(HDU::new(Header::from(records), Some(data))
+        .with_heap(layout.heap)
+        .with_binary_table(table))
+}
+
+/// Keywords that describe the structure of the HDU and must not be set by the user.
+fn is_generated(keyword: &Keyword) -> bool {
+    matches!(
+        keyword,
+        Keyword::Simple
+            | Keyword::Xtension
+            | Keyword::BitPix
+            | Keyword::NAxis
+            | Keyword::NAxisn(_)
+            | Keyword::Extend
+            | Keyword::PCount
+            | Keyword::GCount
+            | Keyword::BZero
+            | Keyword::BScale
+            | Keyword::Blank
+            | Keyword::Tfields
+            | Keyword::TTypen(_)
+            | Keyword::TFormn(_)
+            | Keyword::TUnitn(_)
+            | Keyword::TDimn(_)
+            | Keyword::TNulln(_)
+            | Keyword::TScaln(_)
+            | Keyword::TZeron(_)
+            | Keyword::THeap
+            | Keyword::End
+    )
+}
+
+/// The number of axes is limited to 999 and the product of the dimensions has to match the
+/// number of elements. FITS standard section 4.4.1.1
+fn check_dimensions(data: &DataArray) -> Result<(), FitsError> {
+    let dimensions = data.dimensions();
+    if dimensions.is_empty() || dimensions.len() > 999 {
+        return Err(FitsError::InvalidHdu(format!(
+            "data array has {} axes, expected 1 to 999",
+            dimensions.len()
+        )));
+    }
+    let expected = dimensions.iter().map(|&d| d as usize).product::<usize>();
+    if data.len() != expected {
+        return Err(FitsError::InvalidHdu(format!(
+            "data array has {} elements, but dimensions {:?} require {}",
+            data.len(),
+            dimensions,
+            expected
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        binary_table::{ArrayDescriptor, BinaryColumn, BinaryFormat, BinaryType, Cell},
+        header::{FitsHeader, HeaderKind},
+    };
+    use crate::{parse_fits, write_fits};
+
+    #[test]
+    fn fits_builder() {
+        let fits = FitsBuilder::new()
+            .primary(
+                HduBuilder::new(Some(DataArray::from_u16(
+                    vec![1, 2, 3, 4, 5, 40000],
+                    vec![3, 2],
+                )))
+                .keyword(
+                    Keyword::Object,
+                    Value::CharacterString(String::from("M31")),
+                    Some("target"),
+                ),
+            )
+            .extension(HduBuilder::new(Some(DataArray::from_f32(
+                vec![0.5; 4],
+                vec![4],
+                None,
+                Some(2.0),
+            ))))
+            .build()
+            .unwrap();
+
+        let keywords = |hdu: &HDU| {
+            hdu.header()
+                .keyword_records()
+                .iter()
+                .map(|record| record.keyword().clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            keywords(&fits.hdus()[0]),
+            vec![
+                Keyword::Simple,
+                Keyword::BitPix,
+                Keyword::NAxis,
+                Keyword::NAxisn(1),
+                Keyword::NAxisn(2),
+                Keyword::Extend,
+                Keyword::BZero,
+                Keyword::Object,
+            ]
+        );
+        assert_eq!(
+            keywords(&fits.hdus()[1]),
+            vec![
+                Keyword::Xtension,
+                Keyword::BitPix,
+                Keyword::NAxis,
+                Keyword::NAxisn(1),
+                Keyword::PCount,
+                Keyword::GCount,
+                Keyword::BScale,
+            ]
+        );
+
+        let mut bytes = Vec::new();
+        write_fits(&mut bytes, &fits).unwrap();
+        assert_eq!(bytes.len(), 4 * 2880);
+        let parsed = parse_fits(&bytes).unwrap();
+        assert_eq!(parsed.hdus().len(), 2);
+        assert_eq!(parsed.hdus()[0].header().dimensions(), &vec![3, 2]);
+        assert_eq!(parsed.hdus()[0].header().bzero(), Some(32768.0));
+        assert_eq!(parsed.hdus()[0].data_array(), fits.hdus()[0].data_array());
+        assert_eq!(parsed.hdus()[1].header().header_kind(), &HeaderKind::Image);
+        assert_eq!(parsed.hdus()[1].data_array(), fits.hdus()[1].data_array());
+    }
+
+    #[test]
+    fn fits_builder_empty_primary() {
+        let fits = FitsBuilder::new().build().unwrap();
+        let header = fits.hdus()[0].header();
+        assert_eq!(header.len(), 3);
+        assert_eq!(header.naxis(), 0);
+        assert_eq!(header.bitpix(), Some(8));
+        assert!(fits.hdus()[0].data_array().is_none());
+    }
+
+    #[test]
+    fn hdu_builder_generated_keyword() {
+        let result = FitsBuilder::new()
+            .primary(HduBuilder::new(None).keyword(Keyword::NAxis, Value::Integer(2), None))
+            .build();
+        assert_eq!(
+            result,
+            Err(FitsError::InvalidHdu(String::from(
+                "keyword NAXIS is generated from the data array"
+            )))
+        );
+    }
+
+    #[test]
+    fn hdu_builder_bad_dimensions() {
+        let result = FitsBuilder::new()
+            .extension(HduBuilder::new(Some(DataArray::from_u8(
+                vec![0; 5],
+                vec![3, 2],
+                None,
+                None,
+            ))))
+            .build();
+        assert_eq!(
+            result,
+            Err(FitsError::InvalidHdu(String::from(
+                "data array has 5 elements, but dimensions [3, 2] require 6"
+            )))
+        );
+        let result = FitsBuilder::new()
+            .primary(HduBuilder::new(Some(DataArray::from_u8(
+                vec![0],
+                vec![],
+                None,
+                None,
+            ))))
+            .build();
+        assert!(matches!(result, Err(FitsError::InvalidHdu(_))));
+    }
+
+    #[test]
+    fn fits_builder_binary_table() {
+        let table = BinaryTable::new(
+            vec![
+                BinaryColumn::new(0, BinaryFormat::new(3, BinaryType::Character))
+                    .with_name(Some(String::from("NAME"))),
+                BinaryColumn::new(
+                    3,
+                    BinaryFormat::variable_length(BinaryType::I32, ArrayDescriptor::P(None)),
+                )
+                .with_name(Some(String::from("COUNTS")))
+                .with_unit(Some(String::from("ct"))),
+                BinaryColumn::new(11, BinaryFormat::new(1, BinaryType::I16))
+                    .with_name(Some(String::from("INDEX")))
+                    .with_scaling(1.0, 32768.0),
+            ],
+            vec![
+                vec![
+                    Cell::Character(String::from("ab")),
+                    Cell::I32(vec![1, 2, 3]),
+                    Cell::U16(vec![40000]),
+                ],
+                vec![
+                    Cell::Character(String::from("cde")),
+                    Cell::I32(vec![]),
+                    Cell::U16(vec![1]),
+                ],
+            ],
+        );
+        let fits = FitsBuilder::new()
+            .extension(HduBuilder::binary_table(table))
+            .build()
+            .unwrap();
+        let mut bytes = Vec::new();
+        write_fits(&mut bytes, &fits).unwrap();
+        let parsed = parse_fits(&bytes).unwrap();
+        let hdu = &parsed.hdus()[1];
+        assert_eq!(hdu.header().header_kind(), &HeaderKind::BinaryTable);
+        assert_eq!(hdu.header().dimensions(), &vec![13, 2]);
+        assert_eq!(hdu.heap().len(), 12);
+        let table = hdu.binary_table().unwrap();
+        assert_eq!(table.columns()[1].format().to_string(), "1PJ(3)");
+        assert_eq!(table.columns()[1].unit(), Some("ct"));
+        assert_eq!(table.cell(0, 0).and_then(Cell::as_str), Some("ab"));
+        assert_eq!(
+            table.values::<i32>("COUNTS"),
+            Some(vec![&[1, 2, 3][..], &[][..]])
+        );
+        assert_eq!(
+            table.values::<u16>("INDEX"),
+            Some(vec![&[40000][..], &[1][..]])
+        );
+    }
+
+    #[test]
+    fn fits_builder_binary_table_primary() {
+        let result = FitsBuilder::new()
+            .primary(HduBuilder::binary_table(BinaryTable::new(vec![], vec![])))
+            .build();
+        assert_eq!(
+            result,
+            Err(FitsError::InvalidHdu(String::from(
+                "a binary table cannot be the primary HDU"
+            )))
+        );
+    }
+
+    #[test]
+    fn fits_builder_blank() {
+        let values = [1.5, f64::NAN, -3.0, 0.0];
+        let data = DataArray::quantize(&values, vec![2, 2], 16, 0.0, 0.5, None).unwrap();
+        let fits = FitsBuilder::new()
+            .primary(HduBuilder::new(Some(data)))
+            .build()
+            .unwrap();
+        let mut bytes = Vec::new();
+        write_fits(&mut bytes, &fits).unwrap();
+        let parsed = parse_fits(&bytes).unwrap();
+        let header = parsed.hdus()[0].header();
+        assert_eq!(header.blank(), Some(-32768));
+        assert_eq!(header.bscale(), Some(0.5));
+        let data = parsed.hdus()[0].data_array().as_ref().unwrap();
+        assert_eq!(data.mask(), vec![true, false, true, true]);
+        assert_eq!(data.get::<f64>(&[0, 0]), Ok(1.5));
+        assert!(data.get::<f64>(&[1, 0]).unwrap().is_nan());
+        assert_eq!(data.get_defined::<f64>(&[0, 1]), Ok(Some(-3.0)));
+
+        let result = FitsBuilder::new()
+            .primary(HduBuilder::new(None).keyword(Keyword::Blank, Value::Integer(0), None))
+            .build();
+        assert!(matches!(result, Err(FitsError::InvalidHdu(_))));
+    }
+}
@@ -0,0 +1,163 @@
+use crate::types::{header::Header, keyword::Keyword, value::Value};
+
+/// The value CHECKSUM has while the checksum of an HDU is computed. FITS standard section 4.4.2.7
+pub const CHECKSUM_PLACEHOLDER: &str = "0000000000000000";
+
+/// Whether a checksum keyword matches the bytes of the HDU.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Status {
+    /// The keyword is not in the header.
+    Missing,
+    Valid,
+    /// The keyword does not match, or its value cannot be read.
+    Invalid,
+}
+
+/// The result of verifying CHECKSUM and DATASUM of one HDU.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Verification {
+    /// CHECKSUM, computed over the header and the data unit.
+    pub checksum: Status,
+    /// DATASUM, computed over the data unit.
+    pub datasum: Status,
+}
+
+impl Verification {
+    /// Neither keyword is invalid. Keywords that are missing cannot be verified.
+    pub fn is_valid(&self) -> bool {
+        self.checksum != Status::Invalid && self.datasum != Status::Invalid
+    }
+}
+
+/// Adds the bytes as big-endian 32-bit words to `sum` in 1's complement arithmetic, i.e. carries
+/// out of the highest bit are added back in. A trailing partial word is padded with zeros.
+/// FITS standard section 4.4.2.7
+pub fn checksum(bytes: &[u8], sum: u32) -> u32 {
+    // the carries are kept in the upper half and folded back once per chunk, before they overflow
+    let mut sum = sum as u64;
+    for chunk in bytes.chunks(1 << 20) {
+        let mut words = chunk.chunks_exact(4);
+        for word in words.by_ref() {
+            sum += u32::from_be_bytes(word.try_into().unwrap()) as u64;
+        }
+        let mut last = [0; 4];
+        last[..words.remainder().len()].copy_from_slice(words.remainder());
+        sum += u32::from_be_bytes(last) as u64;
+        sum = fold(sum);
+    }
+    fold(sum) as u32
+}
+
+fn fold(mut sum: u64) -> u64 {
+    while sum >> 32 != 0 {
+        sum = (sum & 0xffff_ffff) + (sum >> 32);
+    }
+    sum
+}
+
+/// Encodes `sum` as 16 ASCII letters and digits. Placed in a header whose CHECKSUM has been the
+/// placeholder, the complement of the HDU checksum makes the checksum of the HDU -0, i.e. all
+/// bits set. FITS standard section 4.4.2.7 and appendix J
+pub fn encode(sum: u32, complement: bool) -> String {
+    // punctuation characters between the digits and the letters are avoided
+    const EXCLUDE: [u8; 13] = [
+        0x3a, 0x3b, 0x3c, 0x3d, 0x3e, 0x3f, 0x40, 0x5b, 0x5c, 0x5d, 0x5e, 0x5f, 0x60,
+    ];
+    let value = match complement {
+        true => !sum,
+        false => sum,
+    };
+    let mut ascii = [0u8; 16];
+    for (i, byte) in value.to_be_bytes().into_iter().enumerate() {
+        let mut ch = [byte / 4 + b'0'; 4];
+        ch[0] += byte % 4;
+        let mut check = true;
+        while check {
+            check = false;
+            for exclude in EXCLUDE {
+                for j in [0, 2] {
+                    if ch[j] == exclude || ch[j + 1] == exclude {
+                        ch[j] += 1;
+                        ch[j + 1] -= 1;
+                        check = true;
+                    }
+                }
+            }
+        }
+        for (j, c) in ch.into_iter().enumerate() {
+            ascii[4 * j + i] = c;
+        }
+    }
+    // the string is rotated by one character to line up with the words of the card
+    ascii.rotate_right(1);
+    String::from_utf8(ascii.to_vec()).unwrap()
+}
+
+/// Checks CHECKSUM and DATASUM of an HDU against the checksums of its header and data unit,
+/// both including their fill.
+pub fn verify(header: &Header, header_sum: u32, data_sum: u32) -> Verification {
+    let checksum = match header.get(Keyword::CheckSum) {
+        None => Status::Missing,
+        Some(_) if checksum(&data_sum.to_be_bytes(), header_sum) == u32::MAX => Status::Valid,
+        Some(_) => Status::Invalid,
+    };
+    let datasum = match header.get(Keyword::DataSum) {
+        None => Status::Missing,
+        Some(value) if datasum(value) == Some(data_sum) => Status::Valid,
+        Some(_) => Status::Invalid,
+    };
+    Verification { checksum, datasum }
+}
+
+/// DATASUM is an unsigned integer written as a character string.
+fn datasum(value: &Value) -> Option<u32> {
+    match value {
+        Value::CharacterString(s) => s.trim().parse().ok(),
+        Value::Integer(n) => u32::try_from(*n).ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksum() {
+        assert_eq!(super::checksum(&[], 0), 0);
+        assert_eq!(super::checksum(&[0, 0, 0, 1, 0, 0, 0, 2], 3), 6);
+        assert_eq!(super::checksum(&[0xff, 0xff, 0xff, 0xff, 0, 0, 0, 2], 0), 2);
+        assert_eq!(super::checksum(&[0x80, 0, 0, 0, 0x80, 0, 0, 0], 0), 1);
+        assert_eq!(super::checksum(&[0, 0, 1], 0), 0x100);
+        // the sum does not depend on how the bytes are split
+        let bytes = (0..3_000_000u32)
+            .flat_map(|n| n.wrapping_mul(2654435761).to_be_bytes())
+            .collect::<Vec<_>>();
+        let (a, b) = bytes.split_at(4 * 1_234_567);
+        assert_eq!(
+            super::checksum(&bytes, 0),
+            super::checksum(b, super::checksum(a, 0))
+        );
+    }
+
+    #[test]
+    fn encode() {
+        for sum in [0, 1, 0x7fff_ffff, 0xdead_beef, u32::MAX] {
+            let encoded = super::encode(sum, false);
+            assert_eq!(encoded.len(), 16);
+            assert!(
+                encoded.bytes().all(|c| c.is_ascii_alphanumeric()),
+                "{}",
+                encoded
+            );
+            // the words of the string without the offset of the placeholder add up to the sum
+            let mut words = encoded.into_bytes();
+            words.rotate_left(1);
+            let offset = super::checksum(CHECKSUM_PLACEHOLDER.as_bytes(), 0);
+            // 0 and all bits set are both zero in 1's complement
+            let difference = super::checksum(&words, !offset);
+            assert_eq!(difference % u32::MAX, sum % u32::MAX);
+        }
+        assert_eq!(super::encode(0, true), super::encode(u32::MAX, false));
+    }
+}
@@ -0,0 +1,1700 @@
+//! Tile-compressed images, which are stored as binary tables with one row per tile, e.g. in
+//! files written by fpack. FITS standard section 10
+
+mod bits;
+mod gzip;
+mod hcompress;
+mod plio;
+mod quantize;
+mod rice;
+
+use crate::builder::HduBuilder;
+use crate::types::{
+    binary_table::{ArrayDescriptor, BinaryColumn, BinaryFormat, BinaryTable, BinaryType, Cell},
+    data_array::DataArray,
+    data_view,
+    header::{FitsHeader, Header, HeaderKind},
+    keyword::Keyword,
+    keyword_record::KeywordRecord,
+    value::Value,
+    Fits, FitsError, HDU,
+};
+
+/// The algorithm the tiles are compressed with, ZCMPTYPE. FITS standard section 10.4
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Algorithm {
+    Rice,
+    Gzip1,
+    /// GZIP_1 after shuffling the bytes of the pixels by significance.
+    Gzip2,
+    /// The IRAF pixel list algorithm for integer masks.
+    Plio,
+    Hcompress,
+}
+
+impl Algorithm {
+    /// The value of ZCMPTYPE.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Algorithm::Rice => "RICE_1",
+            Algorithm::Gzip1 => "GZIP_1",
+            Algorithm::Gzip2 => "GZIP_2",
+            Algorithm::Plio => "PLIO_1",
+            Algorithm::Hcompress => "HCOMPRESS_1",
+        }
+    }
+
+    fn from_name(name: &str) -> Result<Self, FitsError> {
+        match name.trim() {
+            // RICE_ONE was written by early versions of fpack
+            "RICE_1" | "RICE_ONE" => Ok(Algorithm::Rice),
+            "GZIP_1" => Ok(Algorithm::Gzip1),
+            "GZIP_2" => Ok(Algorithm::Gzip2),
+            "PLIO_1" => Ok(Algorithm::Plio),
+            "HCOMPRESS_1" => Ok(Algorithm::Hcompress),
+            name => Err(FitsError::UnsupportedCompression(format!(
+                "ZCMPTYPE = '{}'",
+                name
+            ))),
+        }
+    }
+}
+
+/// How floating point pixels are converted to integers before they are compressed, ZQUANTIZ.
+/// FITS standard section 10.2
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Quantization {
+    /// The pixels are rounded to the nearest multiple of ZSCALE.
+    NoDither,
+    /// A random offset between 0 and 1 is added before rounding and subtracted after
+    /// decompression, so that the quantization does not bias averages.
+    SubtractiveDither1,
+    /// Like `SubtractiveDither1`, but pixels equal to zero are preserved exactly.
+    SubtractiveDither2,
+    /// The pixels are not quantized but compressed losslessly, which only GZIP supports.
+    Lossless,
+}
+
+impl Quantization {
+    /// The value of ZQUANTIZ.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Quantization::NoDither => "NO_DITHER",
+            Quantization::SubtractiveDither1 => "SUBTRACTIVE_DITHER_1",
+            Quantization::SubtractiveDither2 => "SUBTRACTIVE_DITHER_2",
+            Quantization::Lossless => "NONE",
+        }
+    }
+
+    fn from_name(name: &str) -> Result<Self, FitsError> {
+        match name.trim() {
+            "NO_DITHER" => Ok(Quantization::NoDither),
+            "SUBTRACTIVE_DITHER_1" => Ok(Quantization::SubtractiveDither1),
+            "SUBTRACTIVE_DITHER_2" => Ok(Quantization::SubtractiveDither2),
+            "NONE" => Ok(Quantization::Lossless),
+            name => Err(FitsError::UnsupportedCompression(format!(
+                "ZQUANTIZ = '{}'",
+                name
+            ))),
+        }
+    }
+}
+
+/// Whether `header` belongs to a tile-compressed image, i.e. a binary table with ZIMAGE = T.
+/// FITS standard section 10.1
+pub fn is_compressed_image(header: &Header) -> bool {
+    *header.header_kind() == HeaderKind::BinaryTable
+        && header.get(Keyword::ZImage) == Some(&Value::Logical(true))
+}
+
+/// Decompresses a tile-compressed image into an image HDU. Its header is restored from the
+/// Z keywords of the table, the keywords that describe the table are removed and all other
+/// keywords are kept. FITS standard section 10
+pub fn decompress<'a>(hdu: &HDU<'a>) -> Result<HDU<'a>, FitsError> {
+    let header = hdu.header();
+    if !is_compressed_image(header) {
+        return Err(FitsError::InvalidHdu(String::from(
+            "HDU is not a tile-compressed image",
+        )));
+    }
+    let table = hdu.binary_table().ok_or_else(|| {
+        FitsError::InvalidHdu(String::from("compressed image has no binary table"))
+    })?;
+    let image = TiledImage::new(header)?;
+    let restored = image.header(header);
+    let data = match image.dimensions.is_empty() {
+        true => None,
+        false => Some(image.data_array(&restored, image.pixels(header, table)?)),
+    };
+    Ok(HDU::new(restored, data))
+}
+
+/// Replaces every tile-compressed image in `fits` with the decompressed image. An image that
+/// was compressed from a primary array replaces the empty primary HDU in front of it.
+pub fn decompress_fits(fits: Fits) -> Result<Fits, FitsError> {
+    let mut hdus = fits
+        .into_hdus()
+        .into_iter()
+        .map(|hdu| match is_compressed_image(hdu.header()) {
+            true => decompress(&hdu),
+            false => Ok(hdu),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if hdus.len() > 1
+        && *hdus[1].header().header_kind() == HeaderKind::Primary
+        && !hdus[0].header().has_data_array()
+    {
+        hdus.remove(0);
+    }
+    Ok(Fits::from(hdus))
+}
+
+/// Compresses images tile by tile, the inverse of [`decompress`]. Integer images are compressed
+/// losslessly. Floating point images are quantized with `SubtractiveDither1` and a ZSCALE of a
+/// quarter of the noise of each tile by default, like fpack does.
+///
+/// # Examples
+///
+/// ```
+/// use fitsio::builder::{FitsBuilder, HduBuilder};
+/// use fitsio::compression::{decompress_fits, Algorithm, Compressor};
+/// use fitsio::types::data_array::DataArray;
+///
+/// let frame = DataArray::from_i16((0..600).collect(), vec![30, 20], None, None);
+/// let fits = FitsBuilder::new()
+///     .primary(HduBuilder::new(Some(frame)))
+///     .build()
+///     .unwrap();
+/// let compressed = Compressor::new(Algorithm::Rice)
+///     .tile(vec![30, 5])
+///     .compress_fits(fits)
+///     .unwrap();
+/// assert_eq!(compressed.hdus().len(), 2);
+///
+/// let fits = decompress_fits(compressed).unwrap();
+/// let data = fits.hdus()[0].data_array().as_ref().unwrap();
+/// assert_eq!(data.as_slice::<i16>().unwrap()[599], 599);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Compressor {
+    algorithm: Algorithm,
+    tile: Option<Vec<usize>>,
+    quantization: Quantization,
+    level: f64,
+    dither_seed: i64,
+}
+
+impl Default for Compressor {
+    fn default() -> Self {
+        Self::new(Algorithm::Rice)
+    }
+}
+
+impl Compressor {
+    pub fn new(algorithm: Algorithm) -> Self {
+        Self {
+            algorithm,
+            tile: None,
+            quantization: Quantization::SubtractiveDither1,
+            level: 4.0,
+            dither_seed: 1,
+        }
+    }
+
+    /// Sets ZTILEn, the length of each axis of a tile. Axes that are left out have a length
+    /// of one. By default a tile is a row of the image, or 16 rows for HCOMPRESS, which only
+    /// compresses tiles of one or two axes.
+    pub fn tile(self, tile: Vec<usize>) -> Self {
+        Self {
+            tile: Some(tile),
+            ..self
+        }
+    }
+
+    /// How floating point pixels are quantized, ignored for integer images. `Lossless` is only
+    /// supported by GZIP.
+    pub fn quantization(self, quantization: Quantization) -> Self {
+        Self {
+            quantization,
+            ..self
+        }
+    }
+
+    /// Sets the quantization level of floating point pixels, where ZSCALE of a tile is its
+    /// noise divided by `level`. A negative `level` is used as ZSCALE of all tiles instead.
+    pub fn quantize_level(self, level: f64) -> Self {
+        Self { level, ..self }
+    }
+
+    /// Sets ZDITHER0, between 1 and 10000, which selects the dither offsets of the first tile.
+    pub fn dither_seed(self, seed: i64) -> Self {
+        Self {
+            dither_seed: seed,
+            ..self
+        }
+    }
+
+    /// Compresses the image of a primary HDU or image extension into a binary table extension.
+    /// The keywords of the image are kept, those that describe its structure are renamed to
+    /// their Z keywords. FITS standard section 10.1
+    pub fn compress<'a>(&self, hdu: &HDU<'a>) -> Result<HDU<'a>, FitsError> {
+        let header = hdu.header();
+        let data = match (header.header_kind(), hdu.data_array()) {
+            (HeaderKind::Primary | HeaderKind::Image, Some(data)) if !data.is_empty() => data,
+            _ => {
+                return Err(FitsError::InvalidHdu(String::from(
+                    "HDU has no image to compress",
+                )))
+            }
+        };
+        let image = self.tiled_image(data)?;
+        let pixels = Pixels::stored(data);
+        let blank = matches!(&pixels, Pixels::Floats(v) if v.iter().any(|v| v.is_nan()));
+        let table = image.table(&pixels, self.level)?;
+        image
+            .records(header, blank)
+            .into_iter()
+            .fold(HduBuilder::binary_table(table), HduBuilder::record)
+            .build_extension()
+    }
+
+    /// Replaces every image in `fits` with the compressed image. A primary array is moved to
+    /// the first extension behind an empty primary HDU, as fpack does.
+    pub fn compress_fits<'a>(&self, fits: Fits<'a>) -> Result<Fits<'a>, FitsError> {
+        let mut hdus = Vec::new();
+        for hdu in fits.into_hdus() {
+            let is_image = matches!(
+                hdu.header().header_kind(),
+                HeaderKind::Primary | HeaderKind::Image
+            ) && hdu.data_array().as_ref().is_some_and(|d| !d.is_empty());
+            if !is_image {
+                hdus.push(hdu);
+                continue;
+            }
+            if hdus.is_empty() {
+                hdus.push(HduBuilder::new(None).build_primary(true)?);
+            }
+            hdus.push(self.compress(&hdu)?);
+        }
+        Ok(Fits::from(hdus))
+    }
+
+    /// The tiles and parameters of compressing `data`.
+    fn tiled_image(&self, data: &DataArray) -> Result<TiledImage, FitsError> {
+        let bitpix = data.bitpix();
+        let dimensions = data
+            .dimensions()
+            .iter()
+            .map(|&d| d as usize)
+            .collect::<Vec<_>>();
+        let tile = match &self.tile {
+            Some(tile) if tile.len() > dimensions.len() || tile.contains(&0) => {
+                return Err(FitsError::InvalidHdu(format!(
+                    "tiles of {:?} pixels do not fit an image of {} axes",
+                    tile,
+                    dimensions.len()
+                )))
+            }
+            Some(tile) => dimensions
+                .iter()
+                .enumerate()
+                .map(|(i, &d)| tile.get(i).copied().unwrap_or(1).min(d))
+                .collect(),
+            None => dimensions
+                .iter()
+                .enumerate()
+                .map(|(i, &d)| match i {
+                    0 => d,
+                    1 if self.algorithm == Algorithm::Hcompress => d.min(16),
+                    _ => 1,
+                })
+                .collect::<Vec<_>>(),
+        };
+        let quantization = match bitpix > 0 {
+            true => Quantization::Lossless,
+            false => self.quantization,
+        };
+        let unsupported = match self.algorithm {
+            Algorithm::Hcompress if tile.iter().skip(2).any(|&t| t > 1) => {
+                Some("tiles of more than 2 axes")
+            }
+            Algorithm::Rice if bitpix == 64 => Some("64 bit integers"),
+            Algorithm::Gzip1 | Algorithm::Gzip2 => None,
+            _ if bitpix < 0 && quantization == Quantization::Lossless => {
+                Some("floating point pixels without quantization")
+            }
+            _ => None,
+        };
+        if let Some(unsupported) = unsupported {
+            return Err(FitsError::UnsupportedCompression(format!(
+                "{} of {}",
+                self.algorithm.name(),
+                unsupported
+            )));
+        }
+        if !(1..=10000).contains(&self.dither_seed) {
+            return Err(FitsError::UnsupportedCompression(format!(
+                "ZDITHER0 = {}",
+                self.dither_seed
+            )));
+        }
+        Ok(TiledImage {
+            algorithm: self.algorithm,
+            quantization,
+            bitpix,
+            dimensions,
+            tile,
+            dither_seed: self.dither_seed,
+            block_size: 32,
+            // quantized pixels are 32 bit integers
+            bytepix: match bitpix {
+                8 => 1,
+                16 => 2,
+                _ => 4,
+            },
+            smooth: false,
+        })
+    }
+}
+
+/// The keywords that describe how an image is split into tiles and compressed.
+struct TiledImage {
+    algorithm: Algorithm,
+    quantization: Quantization,
+    /// ZBITPIX, BITPIX of the image.
+    bitpix: i8,
+    /// ZNAXISn, the length of each axis of the image.
+    dimensions: Vec<usize>,
+    /// ZTILEn, the length of each axis of a tile.
+    tile: Vec<usize>,
+    /// ZDITHER0, the first dither offset.
+    dither_seed: i64,
+    /// BLOCKSIZE and BYTEPIX of Rice compression.
+    block_size: usize,
+    bytepix: usize,
+    /// SMOOTH of HCOMPRESS.
+    smooth: bool,
+}
+
+/// A decoded tile or image.
+enum Pixels {
+    Integers(Vec<i64>),
+    Floats(Vec<f64>),
+}
+
+impl Pixels {
+    /// The stored values of a data array, before BZERO and BSCALE are applied.
+    fn stored(data: &DataArray) -> Self {
+        match data {
+            DataArray::U8(v, ..) => Pixels::Integers(v.iter().map(|&v| v as i64).collect()),
+            DataArray::I16(v, ..) => Pixels::Integers(widen(v.clone())),
+            DataArray::I32(v, ..) => Pixels::Integers(widen(v.clone())),
+            DataArray::I64(v, ..) => Pixels::Integers(v.clone()),
+            DataArray::F32(v, ..) => Pixels::Floats(v.iter().map(|&v| v as f64).collect()),
+            DataArray::F64(v, ..) => Pixels::Floats(v.clone()),
+            DataArray::I8(v, ..) => {
+                Pixels::Integers(v.iter().map(|&v| (v as u8 ^ 0x80) as i64).collect())
+            }
+            DataArray::U16(v, ..) => {
+                Pixels::Integers(v.iter().map(|&v| (v ^ 0x8000) as i16 as i64).collect())
+            }
+            DataArray::U32(v, ..) => {
+                Pixels::Integers(v.iter().map(|&v| (v ^ 0x8000_0000) as i32 as i64).collect())
+            }
+            DataArray::U64(v, ..) => Pixels::Integers(
+                v.iter()
+                    .map(|&v| (v ^ 0x8000_0000_0000_0000) as i64)
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// A compressed tile, the cells of a row of the table.
+struct EncodedTile {
+    compressed: Option<Cell>,
+    /// Floating point pixels that could not be quantized, compressed losslessly.
+    gzip: Option<Vec<u8>>,
+    /// ZSCALE and ZZERO of quantized pixels.
+    scaling: Option<(f64, f64)>,
+}
+
+impl TiledImage {
+    fn new(header: &Header) -> Result<Self, FitsError> {
+        let algorithm = Algorithm::from_name(&header.get_str(Keyword::ZCmpType)?)?;
+        let bitpix = header.get_i64(Keyword::ZBitPix)?;
+        if ![8, 16, 32, 64, -32, -64].contains(&bitpix) {
+            return Err(FitsError::UnsupportedCompression(format!(
+                "ZBITPIX = {}",
+                bitpix
+            )));
+        }
+        let naxis = size(header, Keyword::ZNaxis, None)?;
+        let dimensions = (1..=naxis)
+            .map(|n| size(header, znaxis(n), None))
+            .collect::<Result<Vec<_>, _>>()?;
+        // tiles are rows of the image by default
+        let tile = dimensions
+            .iter()
+            .enumerate()
+            .map(|(i, &d)| {
+                let default = if i == 0 { d.max(1) } else { 1 };
+                match size(header, Keyword::ZTilen(i as u16 + 1), Some(default))? {
+                    0 => Err(FitsError::InvalidHdu(format!(
+                        "ZTILE{} must be positive",
+                        i + 1
+                    ))),
+                    t => Ok(t),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let quantization = match (bitpix < 0, header.get_str(Keyword::ZQuantiz)) {
+            (false, _) => Quantization::Lossless,
+            (true, Ok(name)) => Quantization::from_name(&name)?,
+            (true, Err(FitsError::KeywordNotFound(_))) => Quantization::NoDither,
+            (true, Err(e)) => return Err(e),
+        };
+        let dither_seed = match header.get_i64(Keyword::ZDither0) {
+            Err(FitsError::KeywordNotFound(_)) => 1,
+            seed => seed?,
+        };
+        Ok(Self {
+            algorithm,
+            quantization,
+            bitpix: bitpix as i8,
+            dimensions,
+            tile,
+            dither_seed,
+            block_size: parameter(header, "BLOCKSIZE").unwrap_or(32) as usize,
+            bytepix: parameter(header, "BYTEPIX").unwrap_or(4) as usize,
+            smooth: parameter(header, "SMOOTH").unwrap_or(0) != 0,
+        })
+    }
+
+    /// The header of the image. FITS standard section 10.1.1
+    fn header<'a>(&self, header: &Header<'a>) -> Header<'a> {
+        let restore = |from: Keyword, to: Keyword| {
+            header
+                .record(from)
+                .map(|record| KeywordRecord::new(to, record.value().clone(), *record.comment()))
+        };
+        let primary = restore(Keyword::ZSimple, Keyword::Simple);
+        let is_primary = primary.is_some();
+        let mut records = vec![primary.unwrap_or_else(|| {
+            restore(Keyword::ZTension, Keyword::Xtension).unwrap_or_else(|| {
+                KeywordRecord::new(
+                    Keyword::Xtension,
+                    Value::CharacterString(String::from("IMAGE   ")),
+                    Some("IMAGE extension"),
+                )
+            })
+        })];
+        records.extend(restore(Keyword::ZBitPix, Keyword::BitPix));
+        records.extend(restore(Keyword::ZNaxis, Keyword::NAxis));
+        for n in 1..=self.dimensions.len() {
+            records.extend(restore(znaxis(n), Keyword::NAxisn(n as u16)));
+        }
+        match is_primary {
+            true => {
+                records.extend(restore(Keyword::ZExtend, Keyword::Extend));
+                records.extend(restore(Keyword::ZBlocked, Keyword::Blocked));
+            }
+            false => {
+                records.push(
+                    restore(Keyword::ZPCount, Keyword::PCount).unwrap_or_else(|| {
+                        KeywordRecord::new(Keyword::PCount, Value::Integer(0), None)
+                    }),
+                );
+                records.push(
+                    restore(Keyword::ZGCount, Keyword::GCount).unwrap_or_else(|| {
+                        KeywordRecord::new(Keyword::GCount, Value::Integer(1), None)
+                    }),
+                );
+            }
+        }
+        let zblank = Keyword::from("ZBLANK");
+        for record in header.keyword_records() {
+            let keyword = record.keyword();
+            let name = keyword.name();
+            match keyword {
+                Keyword::ZheckSum => records.extend(restore(Keyword::ZheckSum, Keyword::CheckSum)),
+                Keyword::ZDataSum => records.extend(restore(Keyword::ZDataSum, Keyword::DataSum)),
+                // BLANK of an integer image may have been moved to ZBLANK
+                _ if *keyword == zblank && self.bitpix > 0 && !header.contains(Keyword::Blank) => {
+                    records.extend(restore(zblank.clone(), Keyword::Blank))
+                }
+                Keyword::Xtension
+                | Keyword::BitPix
+                | Keyword::NAxis
+                | Keyword::NAxisn(_)
+                | Keyword::PCount
+                | Keyword::GCount
+                | Keyword::Tfields
+                | Keyword::THeap
+                | Keyword::CheckSum
+                | Keyword::DataSum
+                | Keyword::End => {}
+                // the fields of the table and the compression keywords
+                _ if name.starts_with('T') && keyword.index().is_some() => {}
+                _ if is_compression_keyword(&name) => {}
+                Keyword::ExtName if matches!(record.value(), Value::CharacterString(s) if s.trim() == "COMPRESSED_IMAGE") =>
+                    {}
+                _ => records.push(record.clone()),
+            }
+        }
+        // headers made by the builder carry no END record until they are written
+        if header.contains(Keyword::End) {
+            records.push(KeywordRecord::new(
+                Keyword::End,
+                Value::CharacterString(String::new()),
+                None,
+            ));
+        }
+        Header::from(records)
+    }
+
+    /// Number of tiles, the number of rows of the table.
+    fn tiles(&self) -> usize {
+        self.dimensions
+            .iter()
+            .zip(&self.tile)
+            .map(|(d, t)| d.div_ceil(*t))
+            .product()
+    }
+
+    /// The first pixel and the shape of tile `index`. Tiles are numbered with the first axis
+    /// varying fastest, those at the upper edges are cut off by the image.
+    fn tile(&self, mut index: usize) -> (Vec<usize>, Vec<usize>) {
+        self.dimensions
+            .iter()
+            .zip(&self.tile)
+            .map(|(&d, &t)| {
+                let n = d.div_ceil(t);
+                let start = index % n * t;
+                index /= n;
+                (start, t.min(d - start))
+            })
+            .unzip()
+    }
+
+    /// Decodes all tiles into the pixels of the image.
+    fn pixels(&self, header: &Header, table: &BinaryTable) -> Result<Pixels, FitsError> {
+        if table.rows().len() != self.tiles() {
+            return Err(FitsError::InvalidHdu(format!(
+                "compressed image has {} tiles, but the table has {} rows",
+                self.tiles(),
+                table.rows().len()
+            )));
+        }
+        let length = self.dimensions.iter().product();
+        let mut image = match self.bitpix > 0 {
+            true => Pixels::Integers(vec![0; length]),
+            false => Pixels::Floats(vec![0.0; length]),
+        };
+        let tiles = Tiles::new(header, table);
+        for index in 0..self.tiles() {
+            let (start, shape) = self.tile(index);
+            let error = |reason| FitsError::BadCompressedTile {
+                tile: index + 1,
+                reason,
+            };
+            let pixels = self
+                .decode_tile(&tiles, index, shape.iter().product())
+                .map_err(error)?;
+            match (&mut image, pixels) {
+                (Pixels::Integers(image), Pixels::Integers(pixels)) => {
+                    copy(image, &self.dimensions, &start, &shape, &pixels)
+                }
+                (Pixels::Floats(image), Pixels::Floats(pixels)) => {
+                    copy(image, &self.dimensions, &start, &shape, &pixels)
+                }
+                _ => {
+                    return Err(error(String::from(
+                        "floating point values in an integer image",
+                    )))
+                }
+            }
+        }
+        Ok(image)
+    }
+
+    /// Decodes one tile of `count` pixels from the first column that holds data for it, and
+    /// restores the floating point values of quantized pixels.
+    fn decode_tile(&self, tiles: &Tiles, index: usize, count: usize) -> Result<Pixels, String> {
+        let pixels = if let Some(cell) = tiles.cell(tiles.compressed, index) {
+            self.decode(cell, count)?
+        } else if let Some(cell) = tiles.cell(tiles.gzip, index) {
+            // pixels that could not be quantized are compressed losslessly
+            let bytes = cell
+                .values::<u8>()
+                .ok_or("GZIP_COMPRESSED_DATA must hold bytes")?;
+            let bytes = gzip::decompress(bytes)?;
+            match bytes.len() / count.max(1) {
+                size @ (4 | 8) => Pixels::Floats(floats(&bytes, size)),
+                _ => {
+                    return Err(format!(
+                        "{} bytes cannot hold {} pixels",
+                        bytes.len(),
+                        count
+                    ))
+                }
+            }
+        } else if let Some(cell) = tiles.cell(tiles.uncompressed, index) {
+            match cell {
+                Cell::U8(v) => Pixels::Integers(v.iter().map(|&v| v as i64).collect()),
+                Cell::I16(v) => Pixels::Integers(v.iter().map(|&v| v as i64).collect()),
+                Cell::I32(v) => Pixels::Integers(v.iter().map(|&v| v as i64).collect()),
+                Cell::I64(v) => Pixels::Integers(v.clone()),
+                Cell::F32(v) => Pixels::Floats(v.iter().map(|&v| v as f64).collect()),
+                Cell::F64(v) => Pixels::Floats(v.clone()),
+                _ => return Err(String::from("UNCOMPRESSED_DATA has an unsupported type")),
+            }
+        } else {
+            return Err(String::from("tile has no data"));
+        };
+        let length = match &pixels {
+            Pixels::Integers(v) => v.len(),
+            Pixels::Floats(v) => v.len(),
+        };
+        if length != count {
+            return Err(format!("tile has {} pixels, expected {}", length, count));
+        }
+        match pixels {
+            Pixels::Integers(values) if self.bitpix < 0 => {
+                if self.quantization == Quantization::Lossless {
+                    return Err(String::from(
+                        "lossless floating point pixels must be compressed with GZIP",
+                    ));
+                }
+                let scale = tiles
+                    .real(tiles.zscale, index)
+                    .or(tiles.scale)
+                    .ok_or("ZSCALE is missing")?;
+                let zero = tiles
+                    .real(tiles.zzero, index)
+                    .or(tiles.zero)
+                    .ok_or("ZZERO is missing")?;
+                Ok(Pixels::Floats(quantize::dequantize(
+                    &values,
+                    self.quantization,
+                    index + 1,
+                    self.dither_seed,
+                    scale,
+                    zero,
+                    tiles.integer(tiles.zblank, index).or(tiles.blank),
+                )))
+            }
+            pixels => Ok(pixels),
+        }
+    }
+
+    /// Decompresses a cell of COMPRESSED_DATA.
+    fn decode(&self, cell: &Cell, count: usize) -> Result<Pixels, String> {
+        let bytes = || {
+            cell.values::<u8>()
+                .ok_or_else(|| String::from("COMPRESSED_DATA must hold bytes"))
+        };
+        Ok(match self.algorithm {
+            Algorithm::Rice => Pixels::Integers(rice::decompress(
+                bytes()?,
+                count,
+                self.bytepix,
+                self.block_size,
+            )?),
+            Algorithm::Hcompress => {
+                Pixels::Integers(hcompress::decompress(bytes()?, count, self.smooth)?)
+            }
+            Algorithm::Plio => {
+                let words = match cell {
+                    Cell::I16(words) => words.clone(),
+                    cell => data_view::decode(
+                        cell.values::<u8>()
+                            .ok_or("COMPRESSED_DATA must hold 16 bit integers")?,
+                    ),
+                };
+                Pixels::Integers(plio::decompress(&words, count)?)
+            }
+            Algorithm::Gzip1 | Algorithm::Gzip2 => {
+                let bytes = gzip::decompress(bytes()?)?;
+                let size = bytes.len() / count.max(1);
+                if ![1, 2, 4, 8].contains(&size) || size * count != bytes.len() {
+                    return Err(format!(
+                        "{} bytes cannot hold {} pixels",
+                        bytes.len(),
+                        count
+                    ));
+                }
+                let bytes = match self.algorithm {
+                    Algorithm::Gzip2 => gzip::unshuffle(&bytes, size),
+                    _ => bytes,
+                };
+                match self.bitpix < 0 && self.quantization == Quantization::Lossless {
+                    true => Pixels::Floats(floats(&bytes, size)),
+                    false => Pixels::Integers(integers(&bytes, size)),
+                }
+            }
+        })
+    }
+
+    /// The data array of the image, with BZERO, BSCALE and BLANK of the restored header.
+    fn data_array(&self, header: &Header, pixels: Pixels) -> DataArray {
+        let dimensions = self.dimensions.iter().map(|&d| d as u32).collect();
+        let (bzero, bscale) = (header.bzero(), header.bscale());
+        let data_array = match pixels {
+            Pixels::Integers(v) => match self.bitpix {
+                8 => DataArray::from_u8(
+                    v.iter().map(|&v| v as u8).collect(),
+                    dimensions,
+                    bzero,
+                    bscale,
+                ),
+                16 => DataArray::from_i16(
+                    v.iter().map(|&v| v as i16).collect(),
+                    dimensions,
+                    bzero,
+                    bscale,
+                ),
+                32 => DataArray::from_i32(
+                    v.iter().map(|&v| v as i32).collect(),
+                    dimensions,
+                    bzero,
+                    bscale,
+                ),
+                _ => DataArray::from_i64(v, dimensions, bzero, bscale),
+            },
+            Pixels::Floats(v) => match self.bitpix {
+                -32 => DataArray::from_f32(
+                    v.iter().map(|&v| v as f32).collect(),
+                    dimensions,
+                    bzero,
+                    bscale,
+                ),
+                _ => DataArray::from_f64(v, dimensions, bzero, bscale),
+            },
+        };
+        data_array.with_blank(header.blank()).into_unsigned()
+    }
+
+    /// The keywords of the compressed image that follow those of the table, the inverse of
+    /// [`TiledImage::header`]. `blank` adds ZBLANK for undefined floating point pixels.
+    /// FITS standard section 10.1.1
+    fn records<'a>(&self, header: &Header<'a>, blank: bool) -> Vec<KeywordRecord<'a>> {
+        let record = |keyword: Keyword, value: Value<'a>| KeywordRecord::new(keyword, value, None);
+        let string = |s: &str| Value::CharacterString(s.to_string());
+        let rename = |from: Keyword, to: Keyword| {
+            header
+                .record(from)
+                .map(|record| KeywordRecord::new(to, record.value().clone(), *record.comment()))
+        };
+        let mut records = vec![
+            KeywordRecord::new(
+                Keyword::ZImage,
+                Value::Logical(true),
+                Some("extension contains a compressed image"),
+            ),
+            record(Keyword::ZCmpType, string(self.algorithm.name())),
+            record(Keyword::ZBitPix, Value::Integer(self.bitpix as i64)),
+            record(
+                Keyword::ZNaxis,
+                Value::Integer(self.dimensions.len() as i64),
+            ),
+        ];
+        for (n, &d) in self.dimensions.iter().enumerate() {
+            records.push(record(znaxis(n + 1), Value::Integer(d as i64)));
+        }
+        for (n, &t) in self.tile.iter().enumerate() {
+            records.push(record(
+                Keyword::ZTilen(n as u16 + 1),
+                Value::Integer(t as i64),
+            ));
+        }
+        let parameters: &[(&str, i64)] = match self.algorithm {
+            Algorithm::Rice => &[
+                ("BLOCKSIZE", self.block_size as i64),
+                ("BYTEPIX", self.bytepix as i64),
+            ],
+            Algorithm::Hcompress => &[("SCALE", 0), ("SMOOTH", self.smooth as i64)],
+            _ => &[],
+        };
+        for (i, &(name, value)) in parameters.iter().enumerate() {
+            records.push(record(Keyword::ZNAMEi(i as u16 + 1), string(name)));
+            records.push(record(Keyword::ZVALi(i as u16 + 1), Value::Integer(value)));
+        }
+        if self.bitpix < 0 {
+            records.push(record(Keyword::ZQuantiz, string(self.quantization.name())));
+            if matches!(
+                self.quantization,
+                Quantization::SubtractiveDither1 | Quantization::SubtractiveDither2
+            ) {
+                records.push(record(Keyword::ZDither0, Value::Integer(self.dither_seed)));
+            }
+            if blank {
+                records.push(record(
+                    Keyword::from("ZBLANK"),
+                    Value::Integer(quantize::NULL_VALUE),
+                ));
+            }
+        }
+        let is_primary = *header.header_kind() == HeaderKind::Primary;
+        match is_primary {
+            true => {
+                records.push(record(Keyword::ZSimple, Value::Logical(true)));
+                records.extend(rename(Keyword::Extend, Keyword::ZExtend));
+                records.extend(rename(Keyword::Blocked, Keyword::ZBlocked));
+            }
+            false => {
+                records.push(
+                    rename(Keyword::Xtension, Keyword::ZTension)
+                        .unwrap_or_else(|| record(Keyword::ZTension, string("IMAGE   "))),
+                );
+                records.push(record(Keyword::ZPCount, Value::Integer(0)));
+                records.push(record(Keyword::ZGCount, Value::Integer(1)));
+            }
+        }
+        for record in header.keyword_records() {
+            match record.keyword() {
+                Keyword::CheckSum => records.extend(rename(Keyword::CheckSum, Keyword::ZheckSum)),
+                Keyword::DataSum => records.extend(rename(Keyword::DataSum, Keyword::ZDataSum)),
+                Keyword::Simple
+                | Keyword::Xtension
+                | Keyword::BitPix
+                | Keyword::NAxis
+                | Keyword::NAxisn(_)
+                | Keyword::Extend
+                | Keyword::Blocked
+                | Keyword::PCount
+                | Keyword::GCount
+                | Keyword::End => {}
+                _ => records.push(record.clone()),
+            }
+        }
+        if is_primary && !header.contains(Keyword::ExtName) {
+            records.push(record(Keyword::ExtName, string("COMPRESSED_IMAGE")));
+        }
+        records
+    }
+
+    /// Compresses the pixels tile by tile into the rows of the table, the inverse of
+    /// [`TiledImage::pixels`]. Floating point pixels are quantized with `level`.
+    fn table(&self, image: &Pixels, level: f64) -> Result<BinaryTable, FitsError> {
+        let tiles = (0..self.tiles())
+            .map(|index| {
+                let (start, shape) = self.tile(index);
+                let pixels = match image {
+                    Pixels::Integers(image) => {
+                        Pixels::Integers(extract(image, &self.dimensions, &start, &shape))
+                    }
+                    Pixels::Floats(image) => {
+                        Pixels::Floats(extract(image, &self.dimensions, &start, &shape))
+                    }
+                };
+                self.encode_tile(pixels, index, &shape, level)
+                    .map_err(|reason| {
+                        FitsError::UnsupportedCompression(format!("tile {}: {}", index + 1, reason))
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let data_type = match self.algorithm {
+            Algorithm::Plio => BinaryType::I16,
+            _ => BinaryType::Byte,
+        };
+        let has_gzip = tiles.iter().any(|tile| tile.gzip.is_some());
+        let is_quantized = self.bitpix < 0 && self.quantization != Quantization::Lossless;
+        let mut columns = vec![column("COMPRESSED_DATA", array(data_type))];
+        if has_gzip {
+            columns.push(column("GZIP_COMPRESSED_DATA", array(BinaryType::Byte)));
+        }
+        if is_quantized {
+            columns.push(column("ZSCALE", BinaryFormat::new(1, BinaryType::F64)));
+            columns.push(column("ZZERO", BinaryFormat::new(1, BinaryType::F64)));
+        }
+        let rows = tiles
+            .into_iter()
+            .map(|tile| {
+                let mut row = vec![tile.compressed.unwrap_or(match data_type {
+                    BinaryType::I16 => Cell::I16(Vec::new()),
+                    _ => Cell::U8(Vec::new()),
+                })];
+                if has_gzip {
+                    row.push(Cell::U8(tile.gzip.unwrap_or_default()));
+                }
+                if is_quantized {
+                    let (scale, zero) = tile.scaling.unwrap_or((1.0, 0.0));
+                    row.push(Cell::F64(vec![scale]));
+                    row.push(Cell::F64(vec![zero]));
+                }
+                row
+            })
+            .collect();
+        Ok(BinaryTable::new(columns, rows))
+    }
+
+    /// Compresses tile `index` with the given shape, the inverse of
+    /// [`TiledImage::decode_tile`]. Floating point pixels that cannot be quantized are
+    /// compressed losslessly with GZIP instead.
+    fn encode_tile(
+        &self,
+        pixels: Pixels,
+        index: usize,
+        shape: &[usize],
+        level: f64,
+    ) -> Result<EncodedTile, String> {
+        let gzip = |bytes: &[u8]| gzip::compress(bytes).map_err(|e| e.to_string());
+        let size = self.bitpix.unsigned_abs() as usize / 8;
+        let mut tile = EncodedTile {
+            compressed: None,
+            gzip: None,
+            scaling: None,
+        };
+        match pixels {
+            Pixels::Integers(values) => tile.compressed = Some(self.encode(&values, shape)?),
+            Pixels::Floats(values) if self.quantization == Quantization::Lossless => {
+                let bytes = float_bytes(&values, size);
+                let bytes = match self.algorithm {
+                    Algorithm::Gzip2 => gzip::shuffle(&bytes, size),
+                    _ => bytes,
+                };
+                tile.compressed = Some(Cell::U8(gzip(&bytes)?));
+            }
+            Pixels::Floats(values) => match quantize::quantize(
+                &values,
+                shape[0],
+                self.quantization,
+                level,
+                index + 1,
+                self.dither_seed,
+            ) {
+                Some(quantized) => {
+                    tile.compressed = Some(self.encode(&quantized.values, shape)?);
+                    tile.scaling = Some((quantized.scale, quantized.zero));
+                }
+                None => tile.gzip = Some(gzip(&float_bytes(&values, size))?),
+            },
+        }
+        Ok(tile)
+    }
+
+    /// Compresses the integer pixels of a tile into a cell of COMPRESSED_DATA, the inverse of
+    /// [`TiledImage::decode`].
+    fn encode(&self, values: &[i64], shape: &[usize]) -> Result<Cell, String> {
+        Ok(match self.algorithm {
+            Algorithm::Rice => Cell::U8(rice::compress(values, self.bytepix, self.block_size)?),
+            Algorithm::Hcompress => Cell::U8(hcompress::compress(
+                values,
+                shape.get(1).copied().unwrap_or(1),
+                shape[0],
+            )),
+            Algorithm::Plio => Cell::I16(plio::compress(values)?),
+            Algorithm::Gzip1 | Algorithm::Gzip2 => {
+                // quantized pixels are 32 bit integers
+                let size = match self.bitpix > 0 {
+                    true => self.bitpix as usize / 8,
+                    false => 4,
+                };
+                let bytes = integer_bytes(values, size);
+                let bytes = match self.algorithm {
+                    Algorithm::Gzip2 => gzip::shuffle(&bytes, size),
+                    _ => bytes,
+                };
+                Cell::U8(gzip::compress(&bytes).map_err(|e| e.to_string())?)
+            }
+        })
+    }
+}
+
+/// The columns of the table and the keywords that give the tiles and their scaling.
+/// FITS standard section 10.1.2
+struct Tiles<'t> {
+    table: &'t BinaryTable,
+    compressed: Option<usize>,
+    gzip: Option<usize>,
+    uncompressed: Option<usize>,
+    zscale: Option<usize>,
+    zzero: Option<usize>,
+    zblank: Option<usize>,
+    /// ZSCALE, ZZERO and ZBLANK given as keywords, for all tiles.
+    scale: Option<f64>,
+    zero: Option<f64>,
+    blank: Option<i64>,
+}
+
+impl<'t> Tiles<'t> {
+    fn new(header: &Header, table: &'t BinaryTable) -> Self {
+        Self {
+            table,
+            compressed: table.column_index("COMPRESSED_DATA"),
+            gzip: table.column_index("GZIP_COMPRESSED_DATA"),
+            uncompressed: table.column_index("UNCOMPRESSED_DATA"),
+            zscale: table.column_index("ZSCALE"),
+            zzero: table.column_index("ZZERO"),
+            zblank: table.column_index("ZBLANK"),
+            scale: header.get_f64("ZSCALE").ok(),
+            zero: header.get_f64("ZZERO").ok(),
+            blank: header.get_i64("ZBLANK").ok(),
+        }
+    }
+
+    /// The cell of `column` in row `index`, `None` if it is empty.
+    fn cell(&self, column: Option<usize>, index: usize) -> Option<&'t Cell> {
+        let cell = self.table.cell(index, column?)?;
+        let empty = match cell {
+            Cell::U8(v) => v.is_empty(),
+            Cell::I16(v) => v.is_empty(),
+            Cell::I32(v) => v.is_empty(),
+            Cell::I64(v) => v.is_empty(),
+            Cell::F32(v) => v.is_empty(),
+            Cell::F64(v) | Cell::Real(v) => v.is_empty(),
+            _ => false,
+        };
+        (!empty).then_some(cell)
+    }
+
+    fn real(&self, column: Option<usize>, index: usize) -> Option<f64> {
+        match self.cell(column, index)? {
+            Cell::F64(v) | Cell::Real(v) => v.first().copied(),
+            Cell::F32(v) => v.first().map(|&v| v as f64),
+            _ => None,
+        }
+    }
+
+    fn integer(&self, column: Option<usize>, index: usize) -> Option<i64> {
+        match self.cell(column, index)? {
+            Cell::I16(v) => v.first().map(|&v| v as i64),
+            Cell::I32(v) => v.first().map(|&v| v as i64),
+            Cell::I64(v) => v.first().copied(),
+            _ => None,
+        }
+    }
+}
+
+/// The reserved keywords of a compressed image that describe the compression, or that hold
+/// mandatory keywords of the image and are restored separately. Other keywords starting with Z,
+/// such as ZENITH, belong to the image. FITS standard section 10.1
+fn is_compression_keyword(name: &str) -> bool {
+    let indexed = |prefix: &str| {
+        name.strip_prefix(prefix)
+            .is_some_and(|n| !n.is_empty() && n.bytes().all(|c| c.is_ascii_digit()))
+    };
+    matches!(
+        name,
+        "ZIMAGE"
+            | "ZCMPTYPE"
+            | "ZBITPIX"
+            | "ZNAXIS"
+            | "ZMASKCMP"
+            | "ZQUANTIZ"
+            | "ZDITHER0"
+            | "ZSCALE"
+            | "ZZERO"
+            | "ZBLANK"
+            | "ZSIMPLE"
+            | "ZTENSION"
+            | "ZEXTEND"
+            | "ZBLOCKED"
+            | "ZPCOUNT"
+            | "ZGCOUNT"
+    ) || ["ZNAXIS", "ZTILE", "ZNAME", "ZVAL"]
+        .into_iter()
+        .any(indexed)
+}
+
+/// ZNAXISn, only ZNAXIS1 and ZNAXIS2 have their own keywords.
+fn znaxis(n: usize) -> Keyword {
+    Keyword::from(format!("ZNAXIS{}", n).as_str())
+}
+
+/// A non-negative integer keyword, `default` if it is missing.
+fn size(header: &Header, keyword: Keyword, default: Option<usize>) -> Result<usize, FitsError> {
+    match (header.get_i64(&keyword), default) {
+        (Err(FitsError::KeywordNotFound(_)), Some(default)) => Ok(default),
+        (value, _) => usize::try_from(value?)
+            .map_err(|_| FitsError::InvalidHdu(format!("{} must not be negative", keyword.name()))),
+    }
+}
+
+/// The value of the compression parameter `name`, given by ZNAMEi and ZVALi.
+/// FITS standard section 10.1.1
+fn parameter(header: &Header, name: &str) -> Option<i64> {
+    let (i, _) = header.indexed(Keyword::ZNAMEi).find(|(_, record)| {
+        matches!(record.value(), Value::CharacterString(s) if s.trim().eq_ignore_ascii_case(name))
+    })?;
+    header.get_i64(Keyword::ZVALi(i)).ok()
+}
+
+/// Big-endian integers of `size` bytes, bytes are unsigned.
+fn integers(bytes: &[u8], size: usize) -> Vec<i64> {
+    match size {
+        1 => bytes.iter().map(|&v| v as i64).collect(),
+        2 => widen(data_view::decode::<i16>(bytes)),
+        4 => widen(data_view::decode::<i32>(bytes)),
+        _ => data_view::decode(bytes),
+    }
+}
+
+fn widen<T: Into<i64>>(values: Vec<T>) -> Vec<i64> {
+    values.into_iter().map(Into::into).collect()
+}
+
+/// Big-endian floating point numbers of `size` bytes.
+fn floats(bytes: &[u8], size: usize) -> Vec<f64> {
+    match size {
+        4 => data_view::decode::<f32>(bytes)
+            .into_iter()
+            .map(|v| v as f64)
+            .collect(),
+        _ => data_view::decode(bytes),
+    }
+}
+
+/// Big-endian integers of `size` bytes, the inverse of [`integers`].
+fn integer_bytes(values: &[i64], size: usize) -> Vec<u8> {
+    match size {
+        1 => values.iter().map(|&v| v as u8).collect(),
+        2 => values
+            .iter()
+            .flat_map(|&v| (v as i16).to_be_bytes())
+            .collect(),
+        4 => values
+            .iter()
+            .flat_map(|&v| (v as i32).to_be_bytes())
+            .collect(),
+        _ => values.iter().flat_map(|&v| v.to_be_bytes()).collect(),
+    }
+}
+
+/// Big-endian floating point numbers of `size` bytes, the inverse of [`floats`].
+fn float_bytes(values: &[f64], size: usize) -> Vec<u8> {
+    match size {
+        4 => values
+            .iter()
+            .flat_map(|&v| (v as f32).to_be_bytes())
+            .collect(),
+        _ => values.iter().flat_map(|&v| v.to_be_bytes()).collect(),
+    }
+}
+
+fn column(name: &str, format: BinaryFormat) -> BinaryColumn {
+    BinaryColumn::new(0, format).with_name(Some(name.to_string()))
+}
+
+fn array(data_type: BinaryType) -> BinaryFormat {
+    BinaryFormat::variable_length(data_type, ArrayDescriptor::P(None))
+}
+
+/// The offsets in the image of the runs of a tile along the first axis, in the order of the
+/// pixels of the tile.
+fn runs<'t>(
+    dimensions: &'t [usize],
+    start: &'t [usize],
+    shape: &'t [usize],
+) -> impl Iterator<Item = usize> + 't {
+    let mut position = start.to_vec();
+    (0..shape[1..].iter().product()).map(move |_| {
+        let index = position
+            .iter()
+            .zip(dimensions)
+            .rev()
+            .fold(0, |index, (&p, &d)| index * d + p);
+        for k in 1..shape.len() {
+            position[k] += 1;
+            if position[k] < start[k] + shape[k] {
+                break;
+            }
+            position[k] = start[k];
+        }
+        index
+    })
+}
+
+/// Copies the pixels of a tile into the image, one run along the first axis at a time.
+fn copy<T: Copy>(
+    image: &mut [T],
+    dimensions: &[usize],
+    start: &[usize],
+    shape: &[usize],
+    pixels: &[T],
+) {
+    for (run, index) in pixels
+        .chunks_exact(shape[0])
+        .zip(runs(dimensions, start, shape))
+    {
+        image[index..index + run.len()].copy_from_slice(run);
+    }
+}
+
+/// The pixels of a tile of the image, the inverse of [`copy`].
+fn extract<T: Copy>(image: &[T], dimensions: &[usize], start: &[usize], shape: &[usize]) -> Vec<T> {
+    runs(dimensions, start, shape)
+        .flat_map(|index| image[index..index + shape[0]].iter().copied())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::FitsBuilder;
+
+    fn gzip(bytes: &[u8]) -> Vec<u8> {
+        gzip::compress(bytes).unwrap()
+    }
+
+    /// A compressed image HDU with the keywords and the table.
+    fn compressed<'a>(records: Vec<(&str, Value<'a>)>, table: BinaryTable) -> HDU<'a> {
+        let hdu = records.into_iter().fold(
+            HduBuilder::binary_table(table).keyword(Keyword::ZImage, Value::Logical(true), None),
+            |hdu, (name, value)| hdu.keyword(Keyword::from(name), value, None),
+        );
+        hdu.build_extension().unwrap()
+    }
+
+    fn string(s: &str) -> Value<'static> {
+        Value::CharacterString(s.to_string())
+    }
+
+    #[test]
+    fn decompress_floats() {
+        // the first row is quantized with SUBTRACTIVE_DITHER_2, the second could not be
+        // quantized and the third is Rice coded
+        let quantized = [4i32, quantize::ZERO_VALUE as i32, -5];
+        let floats = [1.5f32, -2.25, 1e30];
+        let columns = vec![
+            column("COMPRESSED_DATA", array(BinaryType::Byte)),
+            column("GZIP_COMPRESSED_DATA", array(BinaryType::Byte)),
+            column("ZSCALE", BinaryFormat::new(1, BinaryType::F64)),
+            column("ZBLANK", BinaryFormat::new(1, BinaryType::I32)),
+        ];
+        let row = |compressed: Vec<u8>, gzip: Vec<u8>, scale: f64| {
+            vec![
+                Cell::U8(compressed),
+                Cell::U8(gzip),
+                Cell::F64(vec![scale]),
+                Cell::I32(vec![-5]),
+            ]
+        };
+        let bytes = |values: &[i32]| {
+            values
+                .iter()
+                .flat_map(|v| v.to_be_bytes())
+                .collect::<Vec<_>>()
+        };
+        let rows = [
+            row(gzip(&bytes(&quantized)), vec![], 0.5),
+            row(
+                vec![],
+                gzip(
+                    &floats
+                        .iter()
+                        .flat_map(|v| v.to_be_bytes())
+                        .collect::<Vec<_>>(),
+                ),
+                0.5,
+            ),
+            row(vec![0, 0, 0, 7, 0], vec![], 2.0),
+        ];
+        let records = |algorithm: &str| {
+            vec![
+                ("ZCMPTYPE", string(algorithm)),
+                ("ZBITPIX", Value::Integer(-32)),
+                ("ZNAXIS", Value::Integer(2)),
+                ("ZNAXIS1", Value::Integer(3)),
+                ("ZNAXIS2", Value::Integer(3)),
+                ("ZQUANTIZ", string("SUBTRACTIVE_DITHER_2")),
+                ("ZDITHER0", Value::Integer(42)),
+                ("ZZERO", Value::Real(10.0)),
+            ]
+        };
+        let image = decompress(&compressed(
+            records("GZIP_1"),
+            BinaryTable::new(columns.clone(), rows[..2].to_vec()),
+        ));
+        assert!(matches!(image, Err(FitsError::InvalidHdu(_))));
+
+        let mut dither = quantize::Dither::new(1, 42);
+        let first = ((4.0 - dither.next() + 0.5) * 0.5 + 10.0) as f32;
+        let image = decompress(&compressed(
+            records("GZIP_1"),
+            BinaryTable::new(
+                columns.clone(),
+                vec![rows[0].clone(), rows[1].clone(), rows[0].clone()],
+            ),
+        ))
+        .unwrap();
+        let values = image
+            .data_array()
+            .as_ref()
+            .unwrap()
+            .as_slice::<f32>()
+            .unwrap();
+        assert_eq!(values[0], first);
+        assert_eq!(values[1], 0.0);
+        assert!(values[2].is_nan());
+        assert_eq!(values[3..6], floats);
+        // a different dither offset for the third tile
+        assert_ne!(values[6], first);
+
+        let image = decompress(&compressed(
+            records("RICE_1"),
+            BinaryTable::new(
+                columns,
+                vec![rows[1].clone(), rows[2].clone(), rows[2].clone()],
+            ),
+        ))
+        .unwrap();
+        let values = image
+            .data_array()
+            .as_ref()
+            .unwrap()
+            .as_slice::<f32>()
+            .unwrap();
+        assert_eq!(values[..3], floats);
+        assert!(values[3..].iter().all(|v| (23.0..=25.0).contains(v)));
+    }
+
+    #[test]
+    fn decompress_integers() {
+        // a 4 × 3 image in tiles of 3 × 2, cut off at the upper edges
+        let pixels = (0..12)
+            .map(|i| (i / 4 * 10 + i % 4) as i16)
+            .collect::<Vec<_>>();
+        let tiles = [(0, 0, 3, 2), (3, 0, 1, 2), (0, 2, 3, 1), (3, 2, 1, 1)];
+        let pixel = |x: usize, y: usize| pixels[y * 4 + x];
+        let rows = tiles
+            .iter()
+            .map(|&(x, y, nx, ny)| {
+                let tile = (y..y + ny)
+                    .flat_map(|y| (x..x + nx).map(move |x| pixel(x, y)))
+                    .collect::<Vec<_>>();
+                let bytes = tile
+                    .iter()
+                    .flat_map(|v| v.to_be_bytes())
+                    .collect::<Vec<_>>();
+                vec![Cell::U8(gzip(&gzip::shuffle(&bytes, 2)))]
+            })
+            .collect();
+        let table = BinaryTable::new(
+            vec![column("COMPRESSED_DATA", array(BinaryType::Byte))],
+            rows,
+        );
+        let hdu = compressed(
+            vec![
+                ("ZCMPTYPE", string("GZIP_2")),
+                ("ZBITPIX", Value::Integer(16)),
+                ("ZNAXIS", Value::Integer(2)),
+                ("ZNAXIS1", Value::Integer(4)),
+                ("ZNAXIS2", Value::Integer(3)),
+                ("ZTILE1", Value::Integer(3)),
+                ("ZTILE2", Value::Integer(2)),
+                ("EXTNAME", string("COMPRESSED_IMAGE")),
+                ("OBJECT", string("M31")),
+            ],
+            table,
+        );
+        let image = decompress(&hdu).unwrap();
+        let header = image.header();
+        assert_eq!(*header.header_kind(), HeaderKind::Image);
+        assert_eq!(header.get_i64(Keyword::BitPix), Ok(16));
+        assert_eq!(header.dimensions(), &vec![4, 3]);
+        assert_eq!(header.get_i64(Keyword::PCount), Ok(0));
+        assert_eq!(header.get_i64(Keyword::GCount), Ok(1));
+        assert_eq!(header.get_str(Keyword::Object).as_deref(), Ok("M31"));
+        assert!(!header.contains(Keyword::ExtName));
+        assert!(!header
+            .keyword_records()
+            .iter()
+            .any(|r| r.keyword().name().starts_with(['Z', 'T'])));
+        assert_eq!(
+            *image.data_array(),
+            Some(DataArray::from_i16(pixels, vec![4, 3], None, None))
+        );
+
+        assert_eq!(
+            decompress(&compressed(vec![], BinaryTable::default())),
+            Err(FitsError::KeywordNotFound(Keyword::ZCmpType))
+        );
+        assert!(decompress(&image).is_err());
+    }
+
+    /// An image HDU built with an OBJECT keyword, the primary HDU if `primary`.
+    fn image<'a>(data: DataArray, primary: bool) -> HDU<'a> {
+        let hdu = HduBuilder::new(Some(data)).keyword(Keyword::Object, string("M31"), None);
+        let fits = match primary {
+            true => FitsBuilder::new().primary(hdu),
+            false => FitsBuilder::new().extension(hdu),
+        };
+        fits.build().unwrap().into_hdus().pop().unwrap()
+    }
+
+    #[test]
+    fn compress_integers() {
+        let values = (0..35)
+            .map(|i| (i * 37 % 11) as i16 - 3)
+            .collect::<Vec<_>>();
+        let data = || DataArray::from_i16(values.clone(), vec![7, 5], None, None);
+        for algorithm in [
+            Algorithm::Rice,
+            Algorithm::Gzip1,
+            Algorithm::Gzip2,
+            Algorithm::Hcompress,
+        ] {
+            let hdu = image(data(), false);
+            let compressed = Compressor::new(algorithm)
+                .tile(vec![3, 2])
+                .compress(&hdu)
+                .unwrap();
+            let header = compressed.header();
+            assert!(is_compressed_image(header));
+            assert_eq!(
+                header.get_str(Keyword::ZCmpType).as_deref(),
+                Ok(algorithm.name())
+            );
+            assert_eq!(header.get_i64(Keyword::ZTilen(2)), Ok(2));
+            assert_eq!(header.get_i64("ZNAXIS2"), Ok(5));
+            assert!(!header.contains(Keyword::ZQuantiz));
+            assert_eq!(compressed.binary_table().unwrap().rows().len(), 9);
+            assert_eq!(decompress(&compressed).unwrap(), hdu);
+        }
+
+        // unsigned integers keep BZERO
+        let hdu = image(DataArray::from_u16(vec![0, 1, 65535, 7], vec![4]), true);
+        for algorithm in [Algorithm::Rice, Algorithm::Gzip1] {
+            let compressed = Compressor::new(algorithm).compress(&hdu).unwrap();
+            let header = compressed.header();
+            assert_eq!(header.get_i64(Keyword::BZero), Ok(32768));
+            assert_eq!(header.get_bool(Keyword::ZSimple), Ok(true));
+            assert_eq!(decompress(&compressed).unwrap(), hdu);
+        }
+        // PLIO only stores non-negative values
+        let hdu = image(
+            DataArray::from_i32(vec![0, 3, 3, 1 << 20], vec![2, 2], None, None),
+            false,
+        );
+        let compressed = Compressor::new(Algorithm::Plio).compress(&hdu).unwrap();
+        assert_eq!(decompress(&compressed).unwrap(), hdu);
+        let hdu = image(data(), false);
+        assert!(matches!(
+            Compressor::new(Algorithm::Plio).compress(&hdu),
+            Err(FitsError::UnsupportedCompression(_))
+        ));
+        assert!(matches!(
+            Compressor::new(Algorithm::Rice)
+                .tile(vec![1, 1, 1])
+                .compress(&hdu),
+            Err(FitsError::InvalidHdu(_))
+        ));
+        assert!(matches!(
+            Compressor::new(Algorithm::Rice)
+                .dither_seed(0)
+                .compress(&hdu),
+            Err(FitsError::UnsupportedCompression(_))
+        ));
+    }
+
+    #[test]
+    fn compress_floats() {
+        // noise with a standard deviation of about 1, an undefined pixel, a zero and a
+        // constant row that cannot be quantized
+        let mut values = (0..1200)
+            .map(|i| 100.0 + ((i * 7919 % 347) as f32 / 100.0))
+            .collect::<Vec<_>>();
+        values[5] = f32::NAN;
+        values[6] = 0.0;
+        values[40..80].fill(3.0);
+        let hdu = image(
+            DataArray::from_f32(values.clone(), vec![40, 30], None, None),
+            false,
+        );
+        let compressed = Compressor::new(Algorithm::Rice)
+            .quantization(Quantization::SubtractiveDither2)
+            .dither_seed(17)
+            .compress(&hdu)
+            .unwrap();
+        let header = compressed.header();
+        assert_eq!(
+            header.get_str(Keyword::ZQuantiz).as_deref(),
+            Ok("SUBTRACTIVE_DITHER_2")
+        );
+        assert_eq!(header.get_i64(Keyword::ZDither0), Ok(17));
+        assert_eq!(header.get_i64("ZBLANK"), Ok(quantize::NULL_VALUE));
+        let table = compressed.binary_table().unwrap();
+        assert!(table.column_index("GZIP_COMPRESSED_DATA").is_some());
+        let scales = table.values::<f64>("ZSCALE").unwrap();
+
+        let image = decompress(&compressed).unwrap();
+        assert_eq!(image.header(), hdu.header());
+        let restored = image
+            .data_array()
+            .as_ref()
+            .unwrap()
+            .as_slice::<f32>()
+            .unwrap();
+        assert!(restored[5].is_nan());
+        assert_eq!(restored[6], 0.0);
+        assert_eq!(restored[40..80], values[40..80]);
+        for (i, (v, r)) in values.iter().zip(restored).enumerate().skip(7) {
+            assert!((v - r).abs() <= scales[i / 40][0] as f32 / 2.0 + 1e-4);
+        }
+
+        let compressed = Compressor::new(Algorithm::Gzip2)
+            .quantization(Quantization::Lossless)
+            .compress(&hdu)
+            .unwrap();
+        assert_eq!(
+            compressed.header().get_str(Keyword::ZQuantiz).as_deref(),
+            Ok("NONE")
+        );
+        let image = decompress(&compressed).unwrap();
+        assert_eq!(image.header(), hdu.header());
+        let restored = image
+            .data_array()
+            .as_ref()
+            .unwrap()
+            .as_slice::<f32>()
+            .unwrap();
+        assert!(values
+            .iter()
+            .zip(restored)
+            .all(|(v, r)| v.to_bits() == r.to_bits()));
+        assert!(matches!(
+            Compressor::new(Algorithm::Rice)
+                .quantization(Quantization::Lossless)
+                .compress(&hdu),
+            Err(FitsError::UnsupportedCompression(_))
+        ));
+    }
+
+    #[test]
+    fn compress_z_keywords() {
+        // only the reserved compression keywords are dropped, not others starting with Z
+        let data = DataArray::from_i16(vec![1, 2, 3, 4], vec![2, 2], None, None);
+        let hdu = HduBuilder::new(Some(data))
+            .keyword(Keyword::from("ZENITH"), Value::Real(12.5), None)
+            .keyword(Keyword::from("ZTILEX"), string("X"), None);
+        let fits = FitsBuilder::new().extension(hdu).build().unwrap();
+        let hdu = fits.into_hdus().pop().unwrap();
+        let compressed = Compressor::new(Algorithm::Rice).compress(&hdu).unwrap();
+        assert_eq!(compressed.header().get_f64("ZENITH"), Ok(12.5));
+        assert_eq!(
+            compressed
+                .header()
+                .record(Keyword::ZTension)
+                .map(|r| r.value()),
+            hdu.header().record(Keyword::Xtension).map(|r| r.value())
+        );
+        let image = decompress(&compressed).unwrap();
+        assert_eq!(image.header().get_f64("ZENITH"), Ok(12.5));
+        assert_eq!(image.header().get_str("ZTILEX").as_deref(), Ok("X"));
+        assert_eq!(image, hdu);
+        assert!(is_compression_keyword("ZNAXIS12"));
+        assert!(is_compression_keyword("ZVAL3"));
+        assert!(!is_compression_keyword("ZNAME"));
+        assert!(!is_compression_keyword("ZENITH"));
+    }
+
+    #[test]
+    fn reference_fixtures() {
+        // encoded by FITS-EXAMPLES/compressed/reference.py, which shares no code with the crate
+        let read = |name: &str| {
+            std::fs::read(format!(
+                "{}/FITS-EXAMPLES/{}",
+                env!("CARGO_MANIFEST_DIR"),
+                name
+            ))
+            .unwrap()
+        };
+        let bytes = read("UITfuv2582gc.fits");
+        let original = crate::parse_fits(&bytes).unwrap();
+        let original = original.hdus()[0].data_array().as_ref().unwrap();
+        for name in ["gzip1", "gzip2", "rice"] {
+            let bytes = read(&format!("compressed/{}.fits.fz", name));
+            let fits = crate::parse_fits(&bytes).unwrap();
+            let image = decompress(&fits.hdus()[1]).unwrap();
+            assert_eq!(
+                image.data_array().as_ref(),
+                Some(original),
+                "{} pixels differ from the original",
+                name
+            );
+        }
+
+        // the dithered values are within half a quantization step of the original
+        let bytes = read("compressed/rice_dither.fits.fz");
+        let fits = crate::parse_fits(&bytes).unwrap();
+        let image = decompress(&fits.hdus()[1]).unwrap();
+        let pixels = image
+            .data_array()
+            .as_ref()
+            .unwrap()
+            .as_slice::<f32>()
+            .unwrap();
+        let stored = original.as_slice::<i16>().unwrap();
+        assert_eq!(pixels.len(), stored.len());
+        for (&pixel, &stored) in pixels.iter().zip(stored) {
+            let value = (stored as f64 / 7.0) as f32;
+            assert!(
+                (pixel - value).abs() <= 0.025 + 1e-4,
+                "{} != {}",
+                pixel,
+                value
+            );
+        }
+    }
+
+    #[test]
+    #[ignore = "needs the fpack_*.fits.fz files written by FITS-EXAMPLES/compressed/fpack.sh"]
+    fn fpack_fixtures() {
+        let read = |name: &str| {
+            std::fs::read(format!(
+                "{}/FITS-EXAMPLES/{}",
+                env!("CARGO_MANIFEST_DIR"),
+                name
+            ))
+            .unwrap()
+        };
+        let bytes = read("UITfuv2582gc.fits");
+        let original = crate::parse_fits(&bytes).unwrap();
+        for name in ["rice", "gzip1", "gzip2", "plio", "hcompress"] {
+            let bytes = read(&format!("compressed/fpack_{}.fits.fz", name));
+            let fits = crate::parse_fits(&bytes).unwrap();
+            let image = decompress(&fits.hdus()[1]).unwrap();
+            assert_eq!(
+                image.data_array(),
+                original.hdus()[0].data_array(),
+                "{} pixels differ from the original",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn compress_fits() {
+        let data = DataArray::from_u8(vec![1, 2, 3, 4, 5, 6], vec![3, 2], None, None);
+        let fits = FitsBuilder::new()
+            .primary(HduBuilder::new(Some(data)))
+            .extension(HduBuilder::binary_table(BinaryTable::default()))
+            .build()
+            .unwrap();
+        let compressed = Compressor::default().compress_fits(fits).unwrap();
+        let hdus = compressed.hdus();
+        assert_eq!(hdus.len(), 3);
+        assert!(hdus[0].data_array().is_none());
+        assert_eq!(hdus[0].header().get_bool(Keyword::Extend), Ok(true));
+        assert!(is_compressed_image(hdus[1].header()));
+        assert_eq!(
+            hdus[1].header().get_str(Keyword::ExtName).as_deref(),
+            Ok("COMPRESSED_IMAGE")
+        );
+        assert!(!is_compressed_image(hdus[2].header()));
+        assert!(Compressor::default().compress(&hdus[2]).is_err());
+
+        let fits = decompress_fits(compressed).unwrap();
+        assert_eq!(fits.hdus().len(), 2);
+        assert_eq!(
+            fits.hdus()[0]
+                .data_array()
+                .as_ref()
+                .unwrap()
+                .as_slice::<u8>(),
+            Some(&[1, 2, 3, 4, 5, 6][..])
+        );
+    }
+}
@@ -0,0 +1,145 @@
+/// Reads a byte stream bit by bit, most significant bit first.
+pub(crate) struct BitReader<'a> {
+    bytes: &'a [u8],
+    /// Number of bits read so far.
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    /// The next `n` bits as an unsigned integer, `n` is at most 64.
+    pub(crate) fn read(&mut self, mut n: u32) -> Result<u64, String> {
+        let mut value = 0u64;
+        while n > 0 {
+            let byte = self.byte()? as u64;
+            let available = 8 - (self.position % 8) as u32;
+            let take = available.min(n);
+            let bits = (byte >> (available - take)) & ((1 << take) - 1);
+            value = (value << take) | bits;
+            self.position += take as usize;
+            n -= take;
+        }
+        Ok(value)
+    }
+
+    /// Counts the zero bits up to the next one bit, which is consumed as well.
+    pub(crate) fn zeros(&mut self) -> Result<u32, String> {
+        let mut count = 0;
+        loop {
+            let offset = (self.position % 8) as u32;
+            let rest = self.byte()? << offset;
+            if rest == 0 {
+                count += 8 - offset;
+                self.position += (8 - offset) as usize;
+                continue;
+            }
+            let zeros = rest.leading_zeros();
+            self.position += zeros as usize + 1;
+            return Ok(count + zeros);
+        }
+    }
+
+    /// Skips the rest of a partially read byte.
+    pub(crate) fn align(&mut self) {
+        self.position = self.position.div_ceil(8) * 8;
+    }
+
+    fn byte(&self) -> Result<u8, String> {
+        self.bytes
+            .get(self.position / 8)
+            .copied()
+            .ok_or_else(|| String::from("compressed data ends unexpectedly"))
+    }
+}
+
+/// Writes a byte stream bit by bit, most significant bit first.
+#[derive(Default)]
+pub(crate) struct BitWriter {
+    bytes: Vec<u8>,
+    /// Number of bits written so far.
+    position: usize,
+}
+
+impl BitWriter {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes the lowest `n` bits of `value`, `n` is at most 64.
+    pub(crate) fn write(&mut self, value: u64, mut n: u32) {
+        while n > 0 {
+            if self.position.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+            let available = 8 - (self.position % 8) as u32;
+            let take = available.min(n);
+            let bits = (value >> (n - take)) & ((1 << take) - 1);
+            *self.bytes.last_mut().unwrap() |= (bits << (available - take)) as u8;
+            self.position += take as usize;
+            n -= take;
+        }
+    }
+
+    /// Writes `count` zero bits followed by a one bit.
+    pub(crate) fn zeros(&mut self, mut count: u32) {
+        while count > 32 {
+            self.write(0, 32);
+            count -= 32;
+        }
+        self.write(1, count + 1);
+    }
+
+    /// Fills the rest of a partially written byte with zeros.
+    pub(crate) fn align(&mut self) {
+        self.position = self.bytes.len() * 8;
+    }
+
+    /// The bytes written so far, the last one filled up with zeros.
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bit_reader() {
+        let mut reader = BitReader::new(&[0b1011_0000, 0b0000_0001, 0xff]);
+        assert_eq!(reader.read(1), Ok(1));
+        assert_eq!(reader.read(3), Ok(0b011));
+        assert_eq!(reader.zeros(), Ok(11));
+        assert_eq!(reader.read(4), Ok(0xf));
+        reader.align();
+        assert!(reader.read(1).is_err());
+        let mut reader = BitReader::new(&[0x12, 0x34, 0x56, 0x78, 0x9a]);
+        reader.read(4).unwrap();
+        assert_eq!(reader.read(32), Ok(0x2345_6789));
+    }
+
+    #[test]
+    fn bit_writer() {
+        let mut writer = BitWriter::new();
+        writer.write(1, 1);
+        writer.write(0b011, 3);
+        writer.zeros(11);
+        writer.write(0xf, 4);
+        assert_eq!(writer.into_bytes(), vec![0b1011_0000, 0b0000_0001, 0xf0]);
+        let mut writer = BitWriter::new();
+        writer.write(0x1, 4);
+        writer.write(0x2345_6789, 32);
+        writer.zeros(40);
+        writer.align();
+        writer.write(1, 1);
+        let bytes = writer.into_bytes();
+        let mut reader = BitReader::new(&bytes);
+        assert_eq!(reader.read(36), Ok(0x1_2345_6789));
+        assert_eq!(reader.zeros(), Ok(40));
+        reader.align();
+        assert_eq!(reader.read(8), Ok(0x80));
+    }
+}
@@ -0,0 +1,69 @@
+use std::io::{Read, Write};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+
+/// Inflates a gzip stream. FITS standard section 10.4.2
+pub(crate) fn decompress(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    let mut inflated = Vec::new();
+    GzDecoder::new(bytes)
+        .read_to_end(&mut inflated)
+        .map_err(|e| format!("gzip stream is corrupt: {}", e))?;
+    Ok(inflated)
+}
+
+/// Deflates `bytes` into a gzip stream. FITS standard section 10.4.2
+pub(crate) fn compress(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes)?;
+    encoder.finish()
+}
+
+/// Groups the bytes of values of `size` bytes by significance for GZIP_2, the inverse of
+/// [`unshuffle`].
+pub(crate) fn shuffle(bytes: &[u8], size: usize) -> Vec<u8> {
+    (0..size)
+        .flat_map(|b| bytes.iter().skip(b).step_by(size).copied())
+        .collect()
+}
+
+/// Restores the byte order of GZIP_2, which stores the most significant bytes of all values
+/// first, then the next bytes and so on, so that similar bytes are compressed together.
+pub(crate) fn unshuffle(bytes: &[u8], size: usize) -> Vec<u8> {
+    let count = bytes.len() / size;
+    let mut values = vec![0; count * size];
+    for (b, plane) in bytes.chunks_exact(count.max(1)).take(size).enumerate() {
+        for (i, &byte) in plane.iter().enumerate() {
+            values[i * size + b] = byte;
+        }
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn decompress() {
+        let bytes = super::compress(b"compressed tile").unwrap();
+        assert_eq!(super::decompress(&bytes), Ok(b"compressed tile".to_vec()));
+        assert!(super::decompress(&bytes[..10]).is_err());
+    }
+
+    #[test]
+    fn unshuffle() {
+        assert_eq!(
+            super::unshuffle(&[0x01, 0x03, 0x05, 0x02, 0x04, 0x06], 2),
+            vec![0x01, 0x02, 0x03, 0x04, 0x05, 0x06]
+        );
+        assert_eq!(super::unshuffle(&[7, 8], 1), vec![7, 8]);
+    }
+
+    #[test]
+    fn shuffle() {
+        let bytes = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06];
+        assert_eq!(
+            super::shuffle(&bytes, 2),
+            vec![0x01, 0x03, 0x05, 0x02, 0x04, 0x06]
+        );
+        assert_eq!(super::unshuffle(&super::shuffle(&bytes, 3), 3), bytes);
+    }
+}
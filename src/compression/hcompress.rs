@@ -0,0 +1,730 @@
+use super::bits::{BitReader, BitWriter};
+
+/// The first two bytes of an H-compressed tile.
+const MAGIC: [u8; 2] = [0xdd, 0x99];
+
+/// Decodes an H-compressed tile of `count` pixels. The tile holds the coefficients of the
+/// H-transform of the pixels, divided by the scale factor and coded bit plane by bit plane as
+/// quadtrees. With `smooth` the coefficients lost to the scaling are interpolated.
+/// FITS standard section 10.4.4
+pub(crate) fn decompress(bytes: &[u8], count: usize, smooth: bool) -> Result<Vec<i64>, String> {
+    let mut bits = BitReader::new(bytes);
+    if [bits.read(8)?, bits.read(8)?] != MAGIC.map(u64::from) {
+        return Err(String::from(
+            "H-compressed data does not start with the magic number",
+        ));
+    }
+    // nx is the number of rows, ny the length of a row
+    let nx = bits.read(32)? as u32 as i32;
+    let ny = bits.read(32)? as u32 as i32;
+    let scale = bits.read(32)? as u32 as i32 as i64;
+    let sum = bits.read(64)? as i64;
+    let planes = [
+        bits.read(8)? as u8,
+        bits.read(8)? as u8,
+        bits.read(8)? as u8,
+    ];
+    if nx <= 0 || ny <= 0 || nx as usize * ny as usize != count {
+        return Err(format!(
+            "H-compressed tile of {} × {} pixels does not have {} pixels",
+            nx, ny, count
+        ));
+    }
+    let (nx, ny) = (nx as usize, ny as usize);
+    let mut a = Decoder { bits }.decode(nx, ny, planes)?;
+    a[0] = sum;
+    if scale > 1 {
+        a.iter_mut().for_each(|v| *v *= scale);
+    }
+    inverse(&mut a, nx, ny, smooth, scale);
+    Ok(a)
+}
+
+/// H-compresses a tile of nx rows of length ny losslessly, the inverse of [`decompress`] with
+/// a scale factor of 0. FITS standard section 10.4.4
+pub(crate) fn compress(pixels: &[i64], nx: usize, ny: usize) -> Vec<u8> {
+    let mut a = pixels.to_vec();
+    transform(&mut a, nx, ny);
+    let mut bits = BitWriter::new();
+    MAGIC.iter().for_each(|&b| bits.write(b as u64, 8));
+    for v in [nx as u64, ny as u64, 0] {
+        bits.write(v, 32);
+    }
+    bits.write(a[0] as u64, 64);
+    a[0] = 0;
+    // the number of bit planes of the largest coefficient of the first quadrant, of the two
+    // quadrants of differences in one direction and of the last quadrant
+    let (nx2, ny2) = (nx.div_ceil(2), ny.div_ceil(2));
+    let mut largest = [0u64; 3];
+    for (k, v) in a.iter().enumerate() {
+        let q = usize::from(k / ny >= nx2) + usize::from(k % ny >= ny2);
+        largest[q] = largest[q].max(v.unsigned_abs());
+    }
+    let planes = largest.map(|v| (u64::BITS - v.leading_zeros()) as u8);
+    planes.iter().for_each(|&p| bits.write(p as u64, 8));
+    let magnitudes = a.iter().map(|v| v.abs()).collect::<Vec<_>>();
+    let mut encoder = Encoder { bits };
+    encoder.quadtree(&magnitudes, ny, nx2, ny2, planes[0]);
+    encoder.quadtree(&magnitudes[ny2..], ny, nx2, ny / 2, planes[1]);
+    encoder.quadtree(&magnitudes[ny * nx2..], ny, nx / 2, ny2, planes[1]);
+    if let Some(magnitudes) = magnitudes.get(ny * nx2 + ny2..) {
+        encoder.quadtree(magnitudes, ny, nx / 2, ny / 2, planes[2]);
+    }
+    let mut bits = encoder.bits;
+    bits.write(0, 4);
+    bits.align();
+    for v in a.iter().filter(|v| **v != 0) {
+        bits.write(u64::from(*v < 0), 1);
+    }
+    bits.into_bytes()
+}
+
+/// The codes of the nybbles 0 to 15 with their lengths, with the bits in reverse order, since
+/// the quadtree is written back to front.
+const CODES: [(u8, u8); 16] = [
+    (0x3e, 6),
+    (0x00, 3),
+    (0x01, 3),
+    (0x08, 4),
+    (0x02, 3),
+    (0x09, 4),
+    (0x1a, 5),
+    (0x1b, 5),
+    (0x03, 3),
+    (0x1c, 5),
+    (0x0a, 4),
+    (0x1d, 5),
+    (0x0b, 4),
+    (0x1e, 5),
+    (0x3f, 6),
+    (0x0c, 4),
+];
+
+struct Encoder {
+    bits: BitWriter,
+}
+
+impl Encoder {
+    /// Encodes the bit planes of an nqx × nqy quadrant of `a`, which has rows of length `n`,
+    /// the inverse of [`Decoder::quadtree`]. A plane is written as a bitmap if its quadtree
+    /// would not be shorter.
+    fn quadtree(&mut self, a: &[i64], n: usize, nqx: usize, nqy: usize, planes: u8) {
+        let log2n = log2(nqx.max(nqy));
+        let (nqx2, nqy2) = (nqx.div_ceil(2), nqy.div_ceil(2));
+        let limit = 8 * (nqx2 * nqy2 / 2 + nqx2 * nqy2 % 2).max(1);
+        let mut scratch = vec![0u8; nqx2 * nqy2];
+        for bit in (0..planes).rev() {
+            blocks(nqx, nqy, n, |k, s, right, below| {
+                let bit = |i: usize| (a[i] >> bit) as u8 & 1;
+                scratch[k] = bit(s) << 3;
+                if right {
+                    scratch[k] |= bit(s + 1) << 2;
+                }
+                if below {
+                    scratch[k] |= bit(s + n) << 1;
+                }
+                if right && below {
+                    scratch[k] |= bit(s + n + 1);
+                }
+            });
+            // the codes of the non-zero nybbles, from the bottom level of the tree to the top,
+            // one bit per element
+            let mut code = Vec::new();
+            let mut level = scratch.clone();
+            let (mut nx, mut ny) = (nqx2, nqy2);
+            for k in 0..log2n.max(1) {
+                if k > 0 {
+                    reduce(&mut level, nx, ny);
+                    nx = nx.div_ceil(2);
+                    ny = ny.div_ceil(2);
+                }
+                for &v in level[..nx * ny].iter().filter(|v| **v != 0) {
+                    let (c, length) = CODES[v as usize];
+                    code.extend((0..length).map(|b| c >> b & 1));
+                }
+                if code.len() >= limit {
+                    break;
+                }
+            }
+            if code.len() >= limit {
+                self.bits.write(0, 4);
+                scratch.iter().for_each(|&v| self.bits.write(v as u64, 4));
+                continue;
+            }
+            if code.is_empty() {
+                let (c, length) = CODES[0];
+                code.extend((0..length).map(|b| c >> b & 1));
+            }
+            self.bits.write(0xf, 4);
+            code.iter()
+                .rev()
+                .for_each(|&b| self.bits.write(b as u64, 1));
+        }
+    }
+}
+
+/// Replaces the nx × ny elements at the start of `b` with one nybble per 2 × 2 block, with
+/// a bit set for each non-zero element, the inverse of [`Decoder::expand`].
+fn reduce(b: &mut [u8], nx: usize, ny: usize) {
+    blocks(nx, ny, ny, |k, s, right, below| {
+        let set = |v: u8| u8::from(v != 0);
+        let mut v = set(b[s]) << 3;
+        if right {
+            v |= set(b[s + 1]) << 2;
+        }
+        if below {
+            v |= set(b[s + ny]) << 1;
+        }
+        if right && below {
+            v |= set(b[s + ny + 1]);
+        }
+        b[k] = v;
+    });
+}
+
+struct Decoder<'a> {
+    bits: BitReader<'a>,
+}
+
+impl Decoder<'_> {
+    /// The coefficients of the four quadrants, followed by their signs.
+    fn decode(&mut self, nx: usize, ny: usize, planes: [u8; 3]) -> Result<Vec<i64>, String> {
+        let mut a = vec![0; nx * ny];
+        let (nx2, ny2) = (nx.div_ceil(2), ny.div_ceil(2));
+        self.quadtree(&mut a, ny, nx2, ny2, planes[0])?;
+        self.quadtree(&mut a[ny2..], ny, nx2, ny / 2, planes[1])?;
+        self.quadtree(&mut a[ny * nx2..], ny, nx / 2, ny2, planes[1])?;
+        if let Some(a) = a.get_mut(ny * nx2 + ny2..) {
+            self.quadtree(a, ny, nx / 2, ny / 2, planes[2])?;
+        }
+        if self.bits.read(4)? != 0 {
+            return Err(String::from(
+                "H-compressed data does not end with a zero nybble",
+            ));
+        }
+        self.bits.align();
+        for v in a.iter_mut().filter(|v| **v != 0) {
+            if self.bits.read(1)? == 1 {
+                *v = -*v;
+            }
+        }
+        Ok(a)
+    }
+
+    /// Decodes the bit planes of an nqx × nqy quadrant of `a`, which has rows of length `n`.
+    /// Each plane is either a quadtree, starting at 0xf, or a bitmap with 4 pixels per nybble.
+    fn quadtree(
+        &mut self,
+        a: &mut [i64],
+        n: usize,
+        nqx: usize,
+        nqy: usize,
+        planes: u8,
+    ) -> Result<(), String> {
+        let log2n = log2(nqx.max(nqy));
+        let mut scratch = vec![0u8; nqx.div_ceil(2).max(1) * nqy.div_ceil(2).max(1)];
+        for bit in (0..planes).rev() {
+            match self.bits.read(4)? {
+                0 => {
+                    for s in &mut scratch[..nqx.div_ceil(2) * nqy.div_ceil(2)] {
+                        *s = self.bits.read(4)? as u8;
+                    }
+                }
+                0xf => {
+                    scratch[0] = self.huffman()?;
+                    // the sizes of the levels of the tree, each twice the size of the one
+                    // above, rounded down where the quadrant does not fill a power of 2
+                    let (mut nx, mut ny, mut nfx, mut nfy) = (1, 1, nqx, nqy);
+                    let mut c = 1 << log2n;
+                    for _ in 1..log2n {
+                        c >>= 1;
+                        nx <<= 1;
+                        ny <<= 1;
+                        match nfx <= c {
+                            true => nx -= 1,
+                            false => nfx -= c,
+                        }
+                        match nfy <= c {
+                            true => ny -= 1,
+                            false => nfy -= c,
+                        }
+                        self.expand(&mut scratch, nx, ny)?;
+                    }
+                }
+                code => return Err(format!("bad quadtree code {:#x}", code)),
+            }
+            let p = 1 << bit;
+            // the bits of a nybble belong to a 2 × 2 block, starting with the top left pixel
+            blocks(nqx, nqy, n, |k, s, right, below| {
+                let v = scratch[k];
+                for (present, i, mask) in [
+                    (true, s, 8),
+                    (right, s + 1, 4),
+                    (below, s + n, 2),
+                    (right && below, s + n + 1, 1),
+                ] {
+                    if present && v & mask != 0 {
+                        a[i] |= p;
+                    }
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Expands the (nx + 1) / 2 × (ny + 1) / 2 nybbles at the start of `b` to one bit per
+    /// element of an nx × ny array, then replaces the non-zero elements with the nybbles of the
+    /// next level, which are stored in reverse order.
+    fn expand(&mut self, b: &mut [u8], nx: usize, ny: usize) -> Result<(), String> {
+        let (nx2, ny2) = (nx.div_ceil(2), ny.div_ceil(2));
+        for i in (0..nx2).rev() {
+            for j in (0..ny2).rev() {
+                b[2 * (ny * i + j)] = b[ny2 * i + j];
+            }
+        }
+        blocks(nx, ny, ny, |_, s, right, below| {
+            let v = b[s];
+            b[s] = (v >> 3) & 1;
+            if right {
+                b[s + 1] = (v >> 2) & 1;
+            }
+            if below {
+                b[s + ny] = (v >> 1) & 1;
+            }
+            if right && below {
+                b[s + ny + 1] = v & 1;
+            }
+        });
+        for v in b[..nx * ny].iter_mut().rev().filter(|v| **v != 0) {
+            *v = self.huffman()?;
+        }
+        Ok(())
+    }
+
+    /// A nybble coded with the fixed Huffman code of HCOMPRESS.
+    fn huffman(&mut self) -> Result<u8, String> {
+        let c = self.bits.read(3)?;
+        if c < 4 {
+            return Ok(1 << c);
+        }
+        let c = c << 1 | self.bits.read(1)?;
+        match c {
+            8 => return Ok(3),
+            9 => return Ok(5),
+            10 => return Ok(10),
+            11 => return Ok(12),
+            12 => return Ok(15),
+            _ => (),
+        }
+        let c = c << 1 | self.bits.read(1)?;
+        match c {
+            26 => return Ok(6),
+            27 => return Ok(7),
+            28 => return Ok(9),
+            29 => return Ok(11),
+            30 => return Ok(13),
+            _ => (),
+        }
+        match c << 1 | self.bits.read(1)? {
+            62 => Ok(0),
+            _ => Ok(14),
+        }
+    }
+}
+
+/// Calls `f(k, s, right, below)` for the 2 × 2 blocks of an nx × ny array with rows of length
+/// `n`, where `k` counts the blocks and `s` is the index of the first element. Blocks at an
+/// odd edge have no element to the right or below.
+fn blocks(nx: usize, ny: usize, n: usize, mut f: impl FnMut(usize, usize, bool, bool)) {
+    let mut k = 0;
+    for i in (0..nx).step_by(2) {
+        for j in (0..ny).step_by(2) {
+            f(k, n * i + j, j + 1 < ny, i + 1 < nx);
+            k += 1;
+        }
+    }
+}
+
+/// The smallest m with 2^m >= n.
+fn log2(n: usize) -> u32 {
+    n.max(1).next_power_of_two().trailing_zeros()
+}
+
+/// The inverse H-transform of an nx × ny array, in place. Each pass doubles the size of the
+/// image, from the sum of all pixels to the full resolution.
+fn inverse(a: &mut [i64], nx: usize, ny: usize, smooth: bool, scale: i64) {
+    let log2n = log2(nx.max(ny));
+    if log2n == 0 {
+        return;
+    }
+    let mut tmp = Vec::with_capacity(nx.max(ny).div_ceil(2));
+    let mut shift = 1;
+    let mut bit0 = 1i64 << (log2n - 1);
+    let mut bit1 = bit0 << 1;
+    let mut mask0 = -bit0;
+    let mut mask1 = mask0 << 1;
+    let mask2 = mask0 << 2;
+    let mut prnd0 = bit0 >> 1;
+    let mut prnd1 = bit1 >> 1;
+    let prnd2 = bit1;
+    let mut nrnd0 = prnd0 - 1;
+    let mut nrnd1 = prnd1 - 1;
+    let nrnd2 = prnd2 - 1;
+    // rounds to a multiple of the bit given by the mask
+    let round = |h: i64, prnd: i64, nrnd: i64, mask: i64| match h >= 0 {
+        true => (h + prnd) & mask,
+        false => (h + nrnd) & mask,
+    };
+    a[0] = round(a[0], prnd2, nrnd2, mask2);
+    let (mut nxtop, mut nytop, mut nxf, mut nyf) = (1, 1, nx, ny);
+    let mut c = 1 << log2n;
+    for k in (0..log2n).rev() {
+        c >>= 1;
+        nxtop <<= 1;
+        nytop <<= 1;
+        match nxf <= c {
+            true => nxtop -= 1,
+            false => nxf -= c,
+        }
+        match nyf <= c {
+            true => nytop -= 1,
+            false => nyf -= c,
+        }
+        // the last pass divides by 4
+        if k == 0 {
+            nrnd0 = 0;
+            shift = 2;
+        }
+        for i in 0..nxtop {
+            unshuffle(a, ny * i, nytop, 1, &mut tmp);
+        }
+        for j in 0..nytop {
+            unshuffle(a, j, nxtop, ny, &mut tmp);
+        }
+        if smooth {
+            self::smooth(a, nxtop, nytop, ny, scale);
+        }
+        let (oddx, oddy) = (nxtop % 2, nytop % 2);
+        let mut i = 0;
+        while i + oddx < nxtop {
+            let s00 = ny * i;
+            let s10 = s00 + ny;
+            let mut j = 0;
+            while j + oddy < nytop {
+                let (s00, s10) = (s00 + j, s10 + j);
+                let mut h0 = a[s00];
+                let mut hx = round(a[s10], prnd1, nrnd1, mask1);
+                let mut hy = round(a[s00 + 1], prnd1, nrnd1, mask1);
+                let hc = round(a[s10 + 1], prnd0, nrnd0, mask0);
+                // the lowest bits of the differences are carried over to the sum
+                let lowbit0 = hc & bit0;
+                hx = if hx >= 0 { hx - lowbit0 } else { hx + lowbit0 };
+                hy = if hy >= 0 { hy - lowbit0 } else { hy + lowbit0 };
+                let lowbit1 = (hc ^ hx ^ hy) & bit1;
+                h0 = match (h0 >= 0, lowbit0 == 0) {
+                    (true, _) => h0 + lowbit0 - lowbit1,
+                    (false, true) => h0 + lowbit1,
+                    (false, false) => h0 + lowbit0 - lowbit1,
+                };
+                a[s10 + 1] = (h0 + hx + hy + hc) >> shift;
+                a[s10] = (h0 + hx - hy - hc) >> shift;
+                a[s00 + 1] = (h0 - hx + hy - hc) >> shift;
+                a[s00] = (h0 - hx - hy + hc) >> shift;
+                j += 2;
+            }
+            if oddy == 1 {
+                let (s00, s10) = (s00 + j, s10 + j);
+                let hx = round(a[s10], prnd1, nrnd1, mask1);
+                let lowbit1 = hx & bit1;
+                let h0 = if a[s00] >= 0 {
+                    a[s00] - lowbit1
+                } else {
+                    a[s00] + lowbit1
+                };
+                a[s10] = (h0 + hx) >> shift;
+                a[s00] = (h0 - hx) >> shift;
+            }
+            i += 2;
+        }
+        if oddx == 1 {
+            let s00 = ny * i;
+            let mut j = 0;
+            while j + oddy < nytop {
+                let s00 = s00 + j;
+                let hy = round(a[s00 + 1], prnd1, nrnd1, mask1);
+                let lowbit1 = hy & bit1;
+                let h0 = if a[s00] >= 0 {
+                    a[s00] - lowbit1
+                } else {
+                    a[s00] + lowbit1
+                };
+                a[s00 + 1] = (h0 + hy) >> shift;
+                a[s00] = (h0 - hy) >> shift;
+                j += 2;
+            }
+            if oddy == 1 {
+                a[s00 + j] >>= shift;
+            }
+        }
+        bit1 = bit0;
+        bit0 >>= 1;
+        mask1 = mask0;
+        mask0 >>= 1;
+        prnd1 = prnd0;
+        prnd0 >>= 1;
+        nrnd1 = nrnd0;
+        nrnd0 = prnd0 - 1;
+    }
+}
+
+/// The H-transform of an nx × ny array, in place, the inverse of [`inverse`]. Each pass
+/// replaces 2 × 2 blocks by their sum and differences and moves the sums to the upper left
+/// quarter, until only the sum of all pixels is left.
+fn transform(a: &mut [i64], nx: usize, ny: usize) {
+    let mut tmp = Vec::with_capacity(nx.max(ny).div_ceil(2));
+    let mut shift = 0;
+    let mut mask = -2i64;
+    let mut mask2 = mask << 1;
+    let mut prnd = 1i64;
+    let mut prnd2 = prnd << 1;
+    let mut nrnd2 = prnd2 - 1;
+    // differences are rounded up to a multiple of the mask, sums to the nearest one
+    let difference = |h: i64, prnd: i64, mask: i64| match h >= 0 {
+        true => (h + prnd) & mask,
+        false => h & mask,
+    };
+    let sum = |h: i64, prnd2: i64, nrnd2: i64, mask2: i64| match h >= 0 {
+        true => (h + prnd2) & mask2,
+        false => (h + nrnd2) & mask2,
+    };
+    let (mut nxtop, mut nytop) = (nx, ny);
+    for _ in 0..log2(nx.max(ny)) {
+        let (oddx, oddy) = (nxtop % 2, nytop % 2);
+        let mut i = 0;
+        while i + oddx < nxtop {
+            let s00 = ny * i;
+            let s10 = s00 + ny;
+            let mut j = 0;
+            while j + oddy < nytop {
+                let (s00, s10) = (s00 + j, s10 + j);
+                let h0 = (a[s10 + 1] + a[s10] + a[s00 + 1] + a[s00]) >> shift;
+                let hx = (a[s10 + 1] + a[s10] - a[s00 + 1] - a[s00]) >> shift;
+                let hy = (a[s10 + 1] - a[s10] + a[s00 + 1] - a[s00]) >> shift;
+                let hc = (a[s10 + 1] - a[s10] - a[s00 + 1] + a[s00]) >> shift;
+                a[s10 + 1] = hc;
+                a[s10] = difference(hx, prnd, mask);
+                a[s00 + 1] = difference(hy, prnd, mask);
+                a[s00] = sum(h0, prnd2, nrnd2, mask2);
+                j += 2;
+            }
+            if oddy == 1 {
+                let (s00, s10) = (s00 + j, s10 + j);
+                let h0 = (a[s10] + a[s00]) << (1 - shift);
+                let hx = (a[s10] - a[s00]) << (1 - shift);
+                a[s10] = difference(hx, prnd, mask);
+                a[s00] = sum(h0, prnd2, nrnd2, mask2);
+            }
+            i += 2;
+        }
+        if oddx == 1 {
+            let s00 = ny * i;
+            let mut j = 0;
+            while j + oddy < nytop {
+                let s00 = s00 + j;
+                let h0 = (a[s00 + 1] + a[s00]) << (1 - shift);
+                let hy = (a[s00 + 1] - a[s00]) << (1 - shift);
+                a[s00 + 1] = difference(hy, prnd, mask);
+                a[s00] = sum(h0, prnd2, nrnd2, mask2);
+                j += 2;
+            }
+            if oddy == 1 {
+                a[s00 + j] <<= 2 - shift;
+            }
+        }
+        for i in 0..nxtop {
+            shuffle(a, ny * i, nytop, 1, &mut tmp);
+        }
+        for j in 0..nytop {
+            shuffle(a, j, nxtop, ny, &mut tmp);
+        }
+        nxtop = nxtop.div_ceil(2);
+        nytop = nytop.div_ceil(2);
+        shift = 1;
+        mask = mask2;
+        prnd = prnd2;
+        mask2 <<= 1;
+        prnd2 <<= 1;
+        nrnd2 = prnd2 - 1;
+    }
+}
+
+/// Moves the elements at even positions of the `n` elements at `start`, `start + stride`, ...
+/// to the first half and those at odd positions to the second half, the inverse of
+/// [`unshuffle`].
+fn shuffle(a: &mut [i64], start: usize, n: usize, stride: usize, tmp: &mut Vec<i64>) {
+    tmp.clear();
+    tmp.extend((1..n).step_by(2).map(|i| a[start + i * stride]));
+    for i in (2..n).step_by(2) {
+        a[start + i / 2 * stride] = a[start + i * stride];
+    }
+    let half = n.div_ceil(2);
+    for (k, v) in tmp.iter().enumerate() {
+        a[start + (half + k) * stride] = *v;
+    }
+}
+
+/// Moves the first half of the `n` elements at `start`, `start + stride`, ... to the even
+/// positions and the second half to the odd positions.
+fn unshuffle(a: &mut [i64], start: usize, n: usize, stride: usize, tmp: &mut Vec<i64>) {
+    let half = n.div_ceil(2);
+    tmp.clear();
+    tmp.extend((half..n).map(|i| a[start + i * stride]));
+    for i in (0..half).rev() {
+        a[start + 2 * i * stride] = a[start + i * stride];
+    }
+    for (v, i) in tmp.iter().zip((1..n).step_by(2)) {
+        a[start + i * stride] = *v;
+    }
+}
+
+/// Adjusts the differences of each 2 × 2 block of the nxtop × nytop corner of `a` towards the
+/// slopes of the neighbouring blocks, by at most half the scale factor and without creating new
+/// extrema. The blocks at the edges are not changed.
+fn smooth(a: &mut [i64], nxtop: usize, nytop: usize, ny: usize, scale: i64) {
+    let smax = scale >> 1;
+    if smax <= 0 {
+        return;
+    }
+    let ny2 = ny << 1;
+    let (nxtop, nytop) = (nxtop as i64, nytop as i64);
+    let limit = |diff: i64, shift: u32, current: i64| {
+        let s = diff - (current << shift);
+        let s = match s >= 0 {
+            true => s >> shift,
+            false => (s + (1 << shift) - 1) >> shift,
+        };
+        s.clamp(-smax, smax)
+    };
+    // differences in x
+    for i in (2..nxtop - 2).step_by(2) {
+        for j in (0..nytop).step_by(2) {
+            let s00 = ny * i as usize + j as usize;
+            let s10 = s00 + ny;
+            let (hm, h0, hp) = (a[s00 - ny2], a[s00], a[s00 + ny2]);
+            let dmax = (hp - h0).min(h0 - hm).max(0) << 2;
+            let dmin = (hp - h0).max(h0 - hm).min(0) << 2;
+            if dmin < dmax {
+                let diff = (hp - hm).min(dmax).max(dmin);
+                a[s10] += limit(diff, 3, a[s10]);
+            }
+        }
+    }
+    // differences in y
+    for i in (0..nxtop).step_by(2) {
+        for j in (2..nytop - 2).step_by(2) {
+            let s00 = ny * i as usize + j as usize;
+            let (hm, h0, hp) = (a[s00 - 2], a[s00], a[s00 + 2]);
+            let dmax = (hp - h0).min(h0 - hm).max(0) << 2;
+            let dmin = (hp - h0).max(h0 - hm).min(0) << 2;
+            if dmin < dmax {
+                let diff = (hp - hm).min(dmax).max(dmin);
+                a[s00 + 1] += limit(diff, 3, a[s00 + 1]);
+            }
+        }
+    }
+    // curvature
+    for i in (2..nxtop - 2).step_by(2) {
+        for j in (2..nytop - 2).step_by(2) {
+            let s00 = ny * i as usize + j as usize;
+            let s10 = s00 + ny;
+            let hmm = a[s00 - ny2 - 2];
+            let hpm = a[s00 + ny2 - 2];
+            let hmp = a[s00 - ny2 + 2];
+            let hpp = a[s00 + ny2 + 2];
+            let h0 = a[s00];
+            let hx2 = a[s10] << 1;
+            let hy2 = a[s00 + 1] << 1;
+            let m1 = ((hpp - h0).max(0) - hx2 - hy2).min((h0 - hpm).max(0) + hx2 - hy2);
+            let m2 = ((h0 - hmp).max(0) - hx2 + hy2).min((hmm - h0).max(0) + hx2 + hy2);
+            let dmax = m1.min(m2) << 4;
+            let m1 = ((hpp - h0).min(0) - hx2 - hy2).max((h0 - hpm).min(0) + hx2 - hy2);
+            let m2 = ((h0 - hmp).min(0) - hx2 + hy2).max((hmm - h0).min(0) + hx2 + hy2);
+            let dmin = m1.max(m2) << 4;
+            if dmin < dmax {
+                let diff = (hpp + hmm - hmp - hpm).min(dmax).max(dmin);
+                a[s10 + 1] += limit(diff, 6, a[s10 + 1]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// A tile of 2 × 2 pixels with the sum of 12 and the coefficients 2 and 4 in 3 bit planes.
+    fn tile(signs: u8) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        for v in [2, 2, 0] {
+            bytes.extend(i32::to_be_bytes(v));
+        }
+        bytes.extend(12i64.to_be_bytes());
+        bytes.extend([0, 3, 0]);
+        bytes.extend([0xff, 0xbd, 0xff, 0xde, 0xff, 0xef, 0xf8, 0x00, signs]);
+        bytes
+    }
+
+    #[test]
+    fn decompress() {
+        assert_eq!(super::decompress(&tile(0), 4, false), Ok(vec![1, 2, 3, 4]));
+        assert_eq!(
+            super::decompress(&tile(0xc0), 4, true),
+            Ok(vec![4, 3, 2, 1])
+        );
+        assert!(super::decompress(&tile(0), 6, false).is_err());
+        assert!(super::decompress(&tile(0)[..20], 4, false).is_err());
+        let mut constant = MAGIC.to_vec();
+        for v in [2, 2, 0] {
+            constant.extend(i32::to_be_bytes(v));
+        }
+        constant.extend(28i64.to_be_bytes());
+        constant.extend([0, 0, 0, 0]);
+        assert_eq!(super::decompress(&constant, 4, false), Ok(vec![7; 4]));
+    }
+
+    #[test]
+    fn unshuffle() {
+        let mut a = vec![0, 1, 2, 3, 4, 10, 11, 12, 13];
+        let mut tmp = Vec::new();
+        super::unshuffle(&mut a, 0, 5, 1, &mut tmp);
+        assert_eq!(a[..5], [0, 3, 1, 4, 2]);
+        super::unshuffle(&mut a, 5, 2, 2, &mut tmp);
+        assert_eq!(a[5..], [10, 11, 12, 13]);
+        super::shuffle(&mut a, 5, 2, 2, &mut tmp);
+        super::shuffle(&mut a, 0, 5, 1, &mut tmp);
+        assert_eq!(a, vec![0, 1, 2, 3, 4, 10, 11, 12, 13]);
+    }
+
+    #[test]
+    fn compress() {
+        assert_eq!(super::compress(&[1, 2, 3, 4], 2, 2), tile(0));
+        assert_eq!(super::compress(&[4, 3, 2, 1], 2, 2), tile(0xc0));
+    }
+
+    proptest! {
+        #[test]
+        fn round_trips(
+            (nx, ny, pixels) in (1usize..20, 1usize..20).prop_flat_map(|(nx, ny)| {
+                (Just(nx), Just(ny), prop::collection::vec(
+                    prop_oneof![-10i64..10, i32::MIN as i64..=i32::MAX as i64],
+                    nx * ny,
+                ))
+            }),
+        ) {
+            let bytes = super::compress(&pixels, nx, ny);
+            prop_assert_eq!(super::decompress(&bytes, nx * ny, false), Ok(pixels));
+        }
+    }
+}
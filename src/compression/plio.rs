@@ -0,0 +1,168 @@
+/// Decodes the line list of one tile into `count` pixels. The instructions of the list set the
+/// current value and write runs of zeros or of that value, the remaining pixels are zero.
+/// FITS standard section 10.4.3
+pub(crate) fn decompress(words: &[i16], count: usize) -> Result<Vec<i64>, String> {
+    let word = |i: usize| {
+        words
+            .get(i)
+            .map(|&w| w as i64)
+            .ok_or_else(|| String::from("line list ends unexpectedly"))
+    };
+    // the header of the list gives its length and the first instruction
+    let (length, first) = match word(2)? {
+        length if length > 0 => (length as usize, 3),
+        _ => (((word(4)? << 15) + word(3)?) as usize, word(1)? as usize),
+    };
+    let mut pixels = vec![0; count];
+    // the next pixel in the line, counting from 1, and the current value
+    let (mut x1, mut pv) = (1, 1);
+    let end = count as i64;
+    let mut ip = first;
+    while ip < length && x1 <= end {
+        let w = word(ip)?;
+        let data = w & 4095;
+        match w >> 12 {
+            // a run of zeros, of the current value or of zeros that ends in the current value
+            opcode @ (0 | 4 | 5) => {
+                let x2 = x1 + data - 1;
+                let last = x2.min(end);
+                if last >= x1 {
+                    let run = &mut pixels[x1 as usize - 1..last as usize];
+                    if opcode == 4 {
+                        run.fill(pv);
+                    }
+                    if opcode == 5 && last == x2 {
+                        run[run.len() - 1] = pv;
+                    }
+                }
+                x1 = x2 + 1;
+            }
+            1 => {
+                pv = (word(ip + 1)? << 12) + data;
+                ip += 1;
+            }
+            2 => pv += data,
+            3 => pv -= data,
+            opcode @ (6 | 7) => {
+                pv += if opcode == 6 { data } else { -data };
+                pixels[x1 as usize - 1] = pv;
+                x1 += 1;
+            }
+            _ => return Err(format!("bad line list instruction {:#06x}", w)),
+        }
+        ip += 1;
+    }
+    Ok(pixels)
+}
+
+/// Encodes `pixels`, which must lie between 0 and 2^24, as a line list, the inverse of
+/// [`decompress`]. Runs of equal values are single instructions and trailing zeros are left
+/// out. FITS standard section 10.4.3
+pub(crate) fn compress(pixels: &[i64]) -> Result<Vec<i16>, String> {
+    if let Some(p) = pixels.iter().find(|p| !(0..1 << 24).contains(*p)) {
+        return Err(format!("PLIO_1 cannot store the pixel value {}", p));
+    }
+    // the header with the length in words 3 and 4 and the first instruction at word 7
+    let mut words = vec![0, 7, -100, 0, 0, 0, 0];
+    let end = pixels.iter().rposition(|&p| p != 0).map_or(0, |i| i + 1);
+    let mut pv = 1;
+    let mut x = 0;
+    while x < end {
+        let v = pixels[x];
+        let length = pixels[x..end].iter().take_while(|&&p| p == v).count();
+        x += length;
+        if v == 0 {
+            runs(&mut words, 0, length);
+            continue;
+        }
+        let difference = v - pv;
+        pv = v;
+        match difference {
+            // a single pixel that differs a little from the previous value
+            d if length == 1 && d != 0 && d.abs() < 4096 => {
+                words.push(((if d > 0 { 6 } else { 7 }) << 12 | d.abs()) as i16);
+                continue;
+            }
+            0 => {}
+            d if d.abs() < 4096 => words.push(((if d > 0 { 2 } else { 3 }) << 12 | d.abs()) as i16),
+            _ => {
+                words.push((1 << 12 | (v & 4095)) as i16);
+                words.push((v >> 12) as i16);
+            }
+        }
+        runs(&mut words, 4, length);
+    }
+    let length = words.len();
+    words[3] = (length & 0x7fff) as i16;
+    words[4] = (length >> 15) as i16;
+    Ok(words)
+}
+
+/// Appends instructions with `opcode` for a run of `length` pixels, at most 4095 each.
+fn runs(words: &mut Vec<i16>, opcode: i16, mut length: usize) {
+    while length > 0 {
+        let n = length.min(4095);
+        words.push(opcode << 12 | n as i16);
+        length -= n;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    #[test]
+    fn decompress() {
+        let header = [0, 7, -100, 0, 0, 0, 0];
+        let words = [0x0002, 0x2004, 0x4003, 0x3004, 0x5002, 0x6002];
+        let list = |words: &[i16]| {
+            let mut list = header.to_vec();
+            list[3] = (header.len() + words.len()) as i16;
+            list.extend(words);
+            list
+        };
+        // 2 zeros, add 4 to 1, 3 × 5, subtract 4, a zero and 1, add 2 and write it
+        assert_eq!(
+            super::decompress(&list(&words), 9),
+            Ok(vec![0, 0, 5, 5, 5, 0, 1, 3, 0])
+        );
+        assert_eq!(super::decompress(&list(&words), 4), Ok(vec![0, 0, 5, 5]));
+        // a value of more than 12 bits
+        assert_eq!(
+            super::decompress(&list(&[0x1001, 0x0002, 0x4002]), 2),
+            Ok(vec![0x2001, 0x2001])
+        );
+        assert!(super::decompress(&list(&[0x1001]), 2).is_err());
+        assert!(super::decompress(&[0, 7], 2).is_err());
+    }
+
+    #[test]
+    fn compress() {
+        assert_eq!(
+            super::compress(&[0, 0, 5, 5, 5, 0, 1, 3, 0, 0]),
+            Ok(vec![
+                0, 7, -100, 13, 0, 0, 0, 0x0002, 0x2004, 0x4003, 0x0001, 0x7004, 0x6002
+            ])
+        );
+        assert_eq!(
+            super::compress(&[0x2001; 5000]),
+            Ok(vec![
+                0, 7, -100, 11, 0, 0, 0, 0x1001, 0x0002, 0x4fff, 0x4389
+            ])
+        );
+        assert_eq!(super::compress(&[0; 3]), Ok(vec![0, 7, -100, 7, 0, 0, 0]));
+        assert!(super::compress(&[-1]).is_err());
+        assert!(super::compress(&[1 << 24]).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn round_trips(pixels in prop::collection::vec(
+            prop_oneof![Just(0i64), 0i64..20, 0i64..1 << 24],
+            0..300,
+        )) {
+            let words = super::compress(&pixels).unwrap();
+            prop_assert_eq!(super::decompress(&words, pixels.len()), Ok(pixels));
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use std::sync::OnceLock;
+
+use super::Quantization;
+
+/// Length of the sequence of dither offsets.
+const N_RANDOM: usize = 10000;
+
+/// The stored value of zero with SUBTRACTIVE_DITHER_2.
+pub(crate) const ZERO_VALUE: i64 = -2147483646;
+
+/// The stored value of undefined pixels, ZBLANK of quantized tiles.
+pub(crate) const NULL_VALUE: i64 = -2147483647;
+
+/// Number of stored values below the quantized pixels that are reserved for NULL_VALUE and
+/// ZERO_VALUE.
+const N_RESERVED_VALUES: f64 = 10.0;
+
+/// The dither offsets between 0 and 1, from a Park-Miller generator with seed 1 in single
+/// precision. FITS standard section 10.2.1
+fn randoms() -> &'static [f32] {
+    static RANDOMS: OnceLock<Vec<f32>> = OnceLock::new();
+    RANDOMS.get_or_init(|| {
+        let (a, m) = (16807.0, 2147483647.0);
+        let mut seed = 1.0f64;
+        (0..N_RANDOM)
+            .map(|_| {
+                let temp = a * seed;
+                seed = temp - m * (temp / m).trunc();
+                (seed / m) as f32
+            })
+            .collect()
+    })
+}
+
+/// The dither offsets of the pixels of one tile, a run through the sequence that starts at an
+/// offset determined by the tile number.
+pub(crate) struct Dither {
+    seed: usize,
+    next: usize,
+}
+
+impl Dither {
+    /// The offsets of tile `tile`, counted from 1, with ZDITHER0 `seed`.
+    pub(crate) fn new(tile: usize, seed: i64) -> Self {
+        let seed = (tile as i64 - 1 + seed - 1).rem_euclid(N_RANDOM as i64) as usize;
+        Self {
+            seed,
+            next: Self::start(seed),
+        }
+    }
+
+    fn start(seed: usize) -> usize {
+        (randoms()[seed] * 500.0) as usize
+    }
+
+    pub(crate) fn next(&mut self) -> f64 {
+        let r = randoms()[self.next] as f64;
+        self.next += 1;
+        if self.next == N_RANDOM {
+            self.seed = (self.seed + 1) % N_RANDOM;
+            self.next = Self::start(self.seed);
+        }
+        r
+    }
+}
+
+/// Restores the floating point pixels of tile `tile` from the quantized integers, with NaN for
+/// `blank`. physical value = (stored value - dither offset + 0.5) × ZSCALE + ZZERO.
+/// FITS standard section 10.2
+pub(crate) fn dequantize(
+    values: &[i64],
+    quantization: Quantization,
+    tile: usize,
+    seed: i64,
+    scale: f64,
+    zero: f64,
+    blank: Option<i64>,
+) -> Vec<f64> {
+    let mut dither = Dither::new(tile, seed);
+    values
+        .iter()
+        .map(|&v| {
+            let r = match quantization {
+                Quantization::NoDither | Quantization::Lossless => 0.5,
+                _ => dither.next(),
+            };
+            match v {
+                v if Some(v) == blank => f64::NAN,
+                ZERO_VALUE if quantization == Quantization::SubtractiveDither2 => 0.0,
+                v => (v as f64 - r + 0.5) * scale + zero,
+            }
+        })
+        .collect()
+}
+
+/// A tile of quantized pixels with ZSCALE and ZZERO.
+pub(crate) struct Quantized {
+    pub(crate) values: Vec<i64>,
+    pub(crate) scale: f64,
+    pub(crate) zero: f64,
+}
+
+/// Quantizes the floating point pixels of tile `tile`, which has rows of `width` pixels, the
+/// inverse of [`dequantize`]. ZSCALE is the noise of the tile divided by `level`, or -`level` if
+/// it is negative, and NaN is stored as [`NULL_VALUE`]. `None` if the tile cannot be quantized,
+/// e.g. because it has no noise. FITS standard section 10.2
+pub(crate) fn quantize(
+    values: &[f64],
+    width: usize,
+    quantization: Quantization,
+    level: f64,
+    tile: usize,
+    seed: i64,
+) -> Option<Quantized> {
+    let defined = values.iter().filter(|v| !v.is_nan());
+    let (min, max) = defined
+        .clone()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &v| {
+            (min.min(v), max.max(v))
+        });
+    if !min.is_finite() || !max.is_finite() {
+        return None;
+    }
+    let scale = match level < 0.0 {
+        true => -level,
+        false => noise(values, width) / level,
+    };
+    let range = (max - min) / scale;
+    if !(scale > 0.0 && range <= 2.0 * i32::MAX as f64 - N_RESERVED_VALUES) {
+        return None;
+    }
+    // the stored values are kept above the reserved ones and positive where possible, which
+    // suits the compression algorithms
+    let zero = if defined.count() < values.len() || quantization == Quantization::SubtractiveDither2
+    {
+        min - scale * (NULL_VALUE as f64 + N_RESERVED_VALUES)
+    } else if range < i32::MAX as f64 - N_RESERVED_VALUES {
+        // a multiple of ZSCALE, so that quantizing again gives the same values
+        (min / scale + 0.5).floor() * scale
+    } else {
+        (min + max) / 2.0
+    };
+    let mut dither = Dither::new(tile, seed);
+    let values = values
+        .iter()
+        .map(|&v| {
+            let r = match quantization {
+                Quantization::NoDither | Quantization::Lossless => 0.5,
+                _ => dither.next(),
+            };
+            match v {
+                v if v.is_nan() => NULL_VALUE,
+                0.0 if quantization == Quantization::SubtractiveDither2 => ZERO_VALUE,
+                v => ((v - zero) / scale + r - 0.5).round() as i64,
+            }
+        })
+        .collect();
+    Some(Quantized {
+        values,
+        scale,
+        zero,
+    })
+}
+
+/// Estimates the standard deviation of the noise from the median absolute differences of
+/// pixels 2 apart, and of the second and fourth order differences, in each row. The smallest
+/// estimate that is not zero is used, unless the one from the second order is zero.
+fn noise(values: &[f64], width: usize) -> f64 {
+    // rows of less than 9 pixels are joined
+    let width = if width < 9 { values.len() } else { width };
+    let mut medians = [Vec::new(), Vec::new(), Vec::new()];
+    for row in values.chunks(width.max(1)) {
+        let v = row
+            .iter()
+            .copied()
+            .filter(|v| !v.is_nan())
+            .collect::<Vec<_>>();
+        if v.len() < 9 {
+            continue;
+        }
+        let differences = |f: &dyn Fn(usize) -> f64, margin| {
+            let mut d = (margin..v.len() - margin)
+                .map(|i| f(i).abs())
+                .collect::<Vec<_>>();
+            median(&mut d)
+        };
+        medians[0].push(differences(&|i| v[i - 1] - v[i + 1], 1));
+        medians[1].push(differences(&|i| 2.0 * v[i] - v[i - 2] - v[i + 2], 2));
+        medians[2].push(differences(
+            &|i| 6.0 * v[i] - 4.0 * (v[i - 2] + v[i + 2]) + v[i - 4] + v[i + 4],
+            4,
+        ));
+    }
+    // the factors scale the medians to the standard deviation of normally distributed noise
+    let [noise2, noise3, noise5] = medians.map(|mut m| median(&mut m));
+    [1.0483579 * noise2, 0.1772048 * noise5]
+        .into_iter()
+        .filter(|&n| n > 0.0)
+        .fold(0.6052614 * noise3, f64::min)
+}
+
+/// The median of `values`, 0 if there are none.
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(f64::total_cmp);
+    match values.len() {
+        0 => 0.0,
+        n if n % 2 == 0 => (values[n / 2 - 1] + values[n / 2]) / 2.0,
+        n => values[n / 2],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn randoms() {
+        let randoms = super::randoms();
+        assert_eq!(randoms.len(), N_RANDOM);
+        assert_eq!(randoms[0], (16807.0 / 2147483647.0) as f32);
+        // the generator reaches this seed after 10000 steps
+        assert_eq!(randoms[N_RANDOM - 1], (1043618065.0 / 2147483647.0) as f32);
+    }
+
+    #[test]
+    fn dither() {
+        let mut dither = Dither::new(1, 1);
+        let start = (super::randoms()[0] * 500.0) as usize;
+        assert_eq!(dither.next(), super::randoms()[start] as f64);
+        // the sequence wraps around to a new start
+        let mut dither = Dither::new(N_RANDOM + 1, 1);
+        for _ in start..N_RANDOM {
+            dither.next();
+        }
+        assert_eq!(dither.next(), super::randoms()[Dither::start(1)] as f64);
+    }
+
+    #[test]
+    fn dequantize() {
+        assert_eq!(
+            super::dequantize(&[0, 3, -1], Quantization::NoDither, 1, 1, 0.5, 10.0, None),
+            vec![10.0, 11.5, 9.5]
+        );
+        let values = super::dequantize(
+            &[2, ZERO_VALUE, -5],
+            Quantization::SubtractiveDither2,
+            3,
+            7,
+            2.0,
+            0.0,
+            Some(-5),
+        );
+        let mut dither = Dither::new(3, 7);
+        assert_eq!(values[0], (2.0 - dither.next() + 0.5) * 2.0);
+        assert_eq!(values[1], 0.0);
+        assert!(values[2].is_nan());
+    }
+
+    /// Uniformly distributed noise with a standard deviation of 1 around `mean`.
+    fn noisy(mean: f64, count: usize) -> Vec<f64> {
+        super::randoms()[..count]
+            .iter()
+            .map(|&r| mean + (r as f64 - 0.5) * 12f64.sqrt())
+            .collect()
+    }
+
+    #[test]
+    fn noise() {
+        let values = noisy(100.0, 1000);
+        let noise = super::noise(&values, 100);
+        assert!((0.7..1.3).contains(&noise), "{}", noise);
+        assert_eq!(super::noise(&[3.0; 100], 10), 0.0);
+        assert_eq!(super::noise(&values[..8], 8), 0.0);
+    }
+
+    #[test]
+    fn quantize() {
+        let mut values = noisy(100.0, 400);
+        values[7] = f64::NAN;
+        values[8] = 0.0;
+        for quantization in [
+            Quantization::NoDither,
+            Quantization::SubtractiveDither1,
+            Quantization::SubtractiveDither2,
+        ] {
+            let quantized = super::quantize(&values, 20, quantization, 4.0, 5, 9).unwrap();
+            assert!((0.15..0.35).contains(&quantized.scale));
+            assert_eq!(quantized.values[7], NULL_VALUE);
+            let restored = super::dequantize(
+                &quantized.values,
+                quantization,
+                5,
+                9,
+                quantized.scale,
+                quantized.zero,
+                Some(NULL_VALUE),
+            );
+            assert!(restored[7].is_nan());
+            for (v, r) in values.iter().zip(&restored).filter(|(v, _)| !v.is_nan()) {
+                assert!((v - r).abs() <= quantized.scale / 2.0 + 1e-9, "{} {}", v, r);
+            }
+            if quantization == Quantization::SubtractiveDither2 {
+                assert_eq!(quantized.values[8], ZERO_VALUE);
+                assert_eq!(restored[8], 0.0);
+            }
+        }
+        // an absolute ZSCALE, with the zero point at a multiple of it
+        let quantized = super::quantize(&values[10..], 20, Quantization::NoDither, -0.5, 1, 1);
+        let quantized = quantized.unwrap();
+        assert_eq!(quantized.scale, 0.5);
+        assert_eq!(quantized.zero % 0.5, 0.0);
+        assert!(quantized.values.iter().all(|&v| v >= 0));
+        assert!(super::quantize(&[1.0; 100], 10, Quantization::NoDither, 4.0, 1, 1).is_none());
+        assert!(super::quantize(&[f64::NAN; 4], 2, Quantization::NoDither, -1.0, 1, 1).is_none());
+        assert!(super::quantize(&[0.0, 1e20], 2, Quantization::NoDither, -1.0, 1, 1).is_none());
+    }
+}
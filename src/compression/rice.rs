@@ -0,0 +1,166 @@
+use super::bits::{BitReader, BitWriter};
+
+/// Bits of the split count, the largest split and bits of a pixel of `bytepix` bytes.
+fn parameters(bytepix: usize) -> Result<(u32, i64, u32), String> {
+    match bytepix {
+        1 => Ok((3, 6, 8)),
+        2 => Ok((4, 14, 16)),
+        4 => Ok((5, 25, 32)),
+        _ => Err(format!("BYTEPIX {} is not supported", bytepix)),
+    }
+}
+
+/// Decodes `count` pixels of `bytepix` bytes that were Rice coded in blocks of `block_size`.
+/// The first pixel is stored as is, the differences between neighbours follow, block by block
+/// with the number of bits that are split off given at the start of each block.
+/// FITS standard section 10.4.1
+pub(crate) fn decompress(
+    bytes: &[u8],
+    count: usize,
+    bytepix: usize,
+    block_size: usize,
+) -> Result<Vec<i64>, String> {
+    let (fsbits, fsmax, bbits) = parameters(bytepix)?;
+    if block_size == 0 {
+        return Err(String::from("BLOCKSIZE must be positive"));
+    }
+    let mask = (1u64 << bbits) - 1;
+    let mut reader = BitReader::new(bytes);
+    let mut last = reader.read(bbits)?;
+    let mut pixels = Vec::with_capacity(count);
+    while pixels.len() < count {
+        let block = block_size.min(count - pixels.len());
+        let fs = reader.read(fsbits)? as i64 - 1;
+        for _ in 0..block {
+            let difference = match fs {
+                // all differences are zero
+                -1 => 0,
+                fs if fs == fsmax => reader.read(bbits)?,
+                fs => (reader.zeros()? as u64) << fs | reader.read(fs as u32)?,
+            };
+            // differences are mapped to non-negative numbers, 0, -1, 1, -2, 2, ...
+            let difference = match difference & 1 {
+                0 => difference >> 1,
+                _ => !(difference >> 1),
+            };
+            last = last.wrapping_add(difference) & mask;
+            pixels.push(match bytepix {
+                1 => last as i64,
+                2 => last as u16 as i16 as i64,
+                _ => last as u32 as i32 as i64,
+            });
+        }
+    }
+    Ok(pixels)
+}
+
+/// Rice codes `pixels` of `bytepix` bytes in blocks of `block_size`, the inverse of
+/// [`decompress`]. The split of each block is chosen from the mean of its differences, blocks
+/// of zero differences are a single split count and blocks of large differences are stored as
+/// is. FITS standard section 10.4.1
+pub(crate) fn compress(
+    pixels: &[i64],
+    bytepix: usize,
+    block_size: usize,
+) -> Result<Vec<u8>, String> {
+    let (fsbits, fsmax, bbits) = parameters(bytepix)?;
+    if block_size == 0 {
+        return Err(String::from("BLOCKSIZE must be positive"));
+    }
+    let mask = (1u64 << bbits) - 1;
+    let mut writer = BitWriter::new();
+    let mut last = pixels.first().map_or(0, |&p| p as u64 & mask);
+    writer.write(last, bbits);
+    for block in pixels.chunks(block_size) {
+        // differences are mapped to non-negative numbers, 0, -1, 1, -2, 2, ...
+        let differences = block
+            .iter()
+            .map(|&p| {
+                let p = p as u64 & mask;
+                let difference = p.wrapping_sub(last) & mask;
+                last = p;
+                // sign extended from the width of a pixel
+                let difference = ((difference << (64 - bbits)) as i64) >> (64 - bbits);
+                match difference < 0 {
+                    true => !(difference << 1) as u64,
+                    false => (difference << 1) as u64,
+                }
+            })
+            .collect::<Vec<_>>();
+        let sum = differences.iter().map(|&d| d as f64).sum::<f64>();
+        let n = differences.len() as f64;
+        let mean = (((sum - (n / 2.0).floor() - 1.0) / n).max(0.0) as u64) >> 1;
+        let fs = (u64::BITS - mean.leading_zeros()) as i64;
+        if fs >= fsmax {
+            writer.write(fsmax as u64 + 1, fsbits);
+            differences.iter().for_each(|&d| writer.write(d, bbits));
+        } else if fs == 0 && sum == 0.0 {
+            writer.write(0, fsbits);
+        } else {
+            writer.write(fs as u64 + 1, fsbits);
+            for &d in &differences {
+                writer.zeros((d >> fs) as u32);
+                writer.write(d & ((1 << fs) - 1), fs as u32);
+            }
+        }
+    }
+    Ok(writer.into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    #[test]
+    fn decompress() {
+        // 100, then split 0 with the differences 0, 1, -2, 0
+        assert_eq!(
+            super::decompress(&[0x00, 0x64, 0x19, 0x18], 4, 2, 32),
+            Ok(vec![100, 101, 99, 99])
+        );
+        // a block of zero differences is a single split count of 0
+        assert_eq!(
+            super::decompress(&[0x00, 0x07, 0x00], 3, 2, 32),
+            Ok(vec![7, 7, 7])
+        );
+        assert!(super::decompress(&[0x00, 0x64, 0x19], 4, 2, 32).is_err());
+        assert!(super::decompress(&[0x00], 1, 3, 32).is_err());
+    }
+
+    #[test]
+    fn compress() {
+        assert_eq!(
+            super::compress(&[100, 101, 99, 99], 2, 32),
+            Ok(vec![0x00, 0x64, 0x19, 0x18])
+        );
+        assert_eq!(
+            super::compress(&[7, 7, 7], 2, 32),
+            Ok(vec![0x00, 0x07, 0x00])
+        );
+        assert!(super::compress(&[0], 8, 32).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn round_trips(
+            pixels in prop::collection::vec(any::<i32>(), 1..200),
+            bytepix in prop::sample::select(vec![1usize, 2, 4]),
+            block_size in 1usize..40,
+        ) {
+            // the pixels as stored with `bytepix` bytes
+            let pixels = pixels
+                .iter()
+                .map(|&p| match bytepix {
+                    1 => p as u8 as i64,
+                    2 => p as i16 as i64,
+                    _ => p as i64,
+                })
+                .collect::<Vec<_>>();
+            let bytes = super::compress(&pixels, bytepix, block_size).unwrap();
+            prop_assert_eq!(
+                super::decompress(&bytes, pixels.len(), bytepix, block_size),
+                Ok(pixels)
+            );
+        }
+    }
+}
@@ -1,49 +1,536 @@
+// files are mapped into memory by the fitsio-mmap crate, see mmap::MappedFile::open
 #![forbid(unsafe_code)]
+pub mod builder;
+pub mod checksum;
+pub mod compression;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+mod parallel;
 pub mod parser;
+pub mod reader;
 pub mod types;
+pub mod writer;
 
-use std::io::Write;
+use std::io::{Cursor, Write};
 
+use checksum::Verification;
 use types::{Fits, FitsError};
 /// Reads a fits structure from a byte stream.
-/// returns the fits structure or an error.
+/// returns the fits structure or an error that points to the HDU and keyword record
+/// that could not be read.
 ///
 /// # Examples
 ///
 /// ```
 /// use fitsio::parse_fits;
-/// use fitsio::types::Fits;
+/// use fitsio::types::{Fits, FitsError, Position};
 /// let mut bytes = b"SIMPLE  =                    T / file does conform to FITS standard".to_vec();
 ///
 /// let res = parse_fits(&bytes);
-/// assert!(res.is_ok());
+/// assert_eq!(res, Err(FitsError::Truncated(Position::new(0, 0, Some(1)))));
 ///
+/// let mut bytes = format!("{:80}{:80}{:80}{:80}", "SIMPLE  =                    T", "BITPIX  =                    8", "NAXIS   =                    0", "END").into_bytes();
+/// bytes.resize(2880, b' ');
+/// let res = parse_fits(&bytes);
+/// assert!(res.is_ok());
 /// ```
-pub fn parse_fits(bytes: &[u8]) -> Result<Fits, FitsError> {
+pub fn parse_fits(bytes: &[u8]) -> Result<Fits<'_>, FitsError> {
     // Todo: check if perhaps using Arc instead of the slice is better?
     // Todo: check that we are not copying shit around too much, and perhaps use Arc<str> in a few places
     // Todo: inspect all the structs so they appropriately hold references instead of copies
     // Toto: write documentation and design the library innterface, which types to re-export
-    match parser::fits(bytes) {
-        Ok((_, fits)) => Ok(fits),
-        Err(e) => Err(FitsError::ParseError(e.to_string())),
-    }
+    parser::fits(bytes)
+}
+
+/// Reads a fits structure like [`parse_fits`], but without decoding the data arrays. They are
+/// decoded when [`types::HDU::data_array`] is first called, and the rows of tables when
+/// [`types::HDU::table`] or [`types::HDU::binary_table`] is first called, so reading only the
+/// headers of a large file is cheap. The HDUs borrow the data units from `bytes`.
+///
+/// # Examples
+///
+/// ```
+/// use fitsio::parse_fits_lazy;
+/// let mut bytes = format!("{:80}{:80}{:80}{:80}{:80}", "SIMPLE  =                    T", "BITPIX  =                    8", "NAXIS   =                    1", "NAXIS1  =                    3", "END").into_bytes();
+/// bytes.resize(2880, b' ');
+/// bytes.extend([1, 2, 3]);
+///
+/// let fits = parse_fits_lazy(&bytes).unwrap();
+/// let hdu = fits.primary_hdu().unwrap();
+/// assert!(!hdu.is_decoded());
+/// assert_eq!(hdu.data_array().as_ref().unwrap().len(), 3);
+/// ```
+pub fn parse_fits_lazy(bytes: &[u8]) -> Result<Fits<'_>, FitsError> {
+    parser::fits_view(bytes)
+}
+
+/// Reads a fits structure like [`parse_fits`] and decompresses every tile-compressed image,
+/// see [`compression::decompress_fits`]. Files written by fpack are read as the original file.
+///
+/// # Examples
+///
+/// ```
+/// use fitsio::parse_fits_decompressed;
+/// let mut bytes = format!("{:80}{:80}{:80}{:80}", "SIMPLE  =                    T", "BITPIX  =                    8", "NAXIS   =                    0", "END").into_bytes();
+/// bytes.resize(2880, b' ');
+///
+/// let fits = parse_fits_decompressed(&bytes).unwrap();
+/// assert_eq!(fits.hdus().len(), 1);
+/// ```
+pub fn parse_fits_decompressed(bytes: &[u8]) -> Result<Fits<'_>, FitsError> {
+    compression::decompress_fits(parser::fits(bytes)?)
 }
 
 /// Writes a fits structure to a file.
 /// returns the number of bytes written.
 ///
-pub fn write_fits(_w: impl Write, _fits: &Fits) -> Result<u64, FitsError> {
-    todo!()
+/// Every header is padded with spaces and every data array with zeros to a multiple of
+/// 2880 bytes, data arrays are written in big-endian byte order.
+/// Keyword records that were read by [`parse_fits`] keep their original card image and data
+/// units their original fill, so a file that is parsed and written back unchanged is reproduced
+/// byte for byte.
+///
+/// # Examples
+///
+/// ```
+/// use fitsio::{parse_fits, write_fits};
+/// use fitsio::types::Fits;
+/// let mut bytes = format!("{:80}{:80}{:80}{:80}", "SIMPLE  =                    T", "BITPIX  =                    8", "NAXIS   =                    0", "END").into_bytes();
+/// bytes.resize(2880, b' ');
+///
+/// let fits = parse_fits(&bytes).unwrap();
+/// let mut out = Vec::new();
+/// assert_eq!(write_fits(&mut out, &fits).unwrap(), 2880);
+/// assert_eq!(out, bytes);
+/// ```
+pub fn write_fits(mut w: impl Write, fits: &Fits) -> Result<u64, FitsError> {
+    let written = writer::fits(&mut w, fits)?;
+    w.flush()?;
+    Ok(written)
+}
+
+/// Verifies CHECKSUM and DATASUM of every HDU in a byte stream, in the order of the HDUs.
+/// Use [`writer::update_checksum`] to set them before writing.
+///
+/// # Examples
+///
+/// ```
+/// use fitsio::{parse_fits, verify_checksums, write_fits, writer};
+/// use fitsio::checksum::Status;
+/// let mut bytes = format!("{:80}{:80}{:80}{:80}", "SIMPLE  =                    T", "BITPIX  =                    8", "NAXIS   =                    0", "END").into_bytes();
+/// bytes.resize(2880, b' ');
+/// assert_eq!(verify_checksums(&bytes).unwrap()[0].checksum, Status::Missing);
+///
+/// let mut fits = parse_fits(&bytes).unwrap();
+/// writer::update_checksum(&mut fits.hdus_mut()[0]).unwrap();
+/// let mut out = Vec::new();
+/// write_fits(&mut out, &fits).unwrap();
+/// let verification = verify_checksums(&out).unwrap();
+/// assert_eq!(verification[0].checksum, Status::Valid);
+/// assert_eq!(verification[0].datasum, Status::Valid);
+/// ```
+pub fn verify_checksums(bytes: &[u8]) -> Result<Vec<Verification>, FitsError> {
+    let mut reader = reader::FitsReader::new(Cursor::new(bytes))?;
+    (0..reader.len()).map(|hdu| reader.verify(hdu)).collect()
 }
 
-//todo: add functions to create a fits structure from scratch and save it to a file
-//todo: complete parsing of tables and bintables, so it does not crash
-//todo: complete parsing of random groups
 //todo: complete parsing of other, conforming extensions
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use time::macros::datetime;
+    use types::header::{FitsHeader, HeaderKind};
+    use types::{binary_table::Cell, keyword::Keyword, table::Field, value::Value};
+
+    fn example(name: &str) -> Vec<u8> {
+        std::fs::read(format!(
+            "{}/FITS-EXAMPLES/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        ))
+        .unwrap()
+    }
+
+    fn assert_round_trip(name: &str) {
+        let bytes = example(name);
+        let fits = parse_fits(&bytes).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(write_fits(&mut out, &fits).unwrap(), bytes.len() as u64);
+        assert!(out == bytes, "{} was not written back byte for byte", name);
+    }
+
     #[test]
     fn lib() {}
+
+    #[test]
+    fn round_trip() {
+        assert_round_trip("UITfuv2582gc.fits");
+        assert_round_trip("WFPC2ASSNu5780205bx.fits");
+        assert_round_trip("NICMOSn4hk12010_mos.fits");
+        assert_round_trip("FOSy19g0309t_c2f.fits");
+        assert_round_trip("HRSz0yd020fm_c2f.fits");
+        assert_round_trip("FGSf64y0106m_a1f.fits");
+        assert_round_trip("IUElwp25637mxlo.fits");
+        assert_round_trip("WFPC2u5780205r_c0fx.fits");
+        // the fill after the last HDU is not zeroed
+        assert_round_trip("DDTSUVDATA.fits");
+    }
+
+    #[test]
+    fn data_view() {
+        for name in [
+            "UITfuv2582gc.fits",
+            "NICMOSn4hk12010_mos.fits",
+            "IUElwp25637mxlo.fits",
+            "FGSf64y0106m_a1f.fits",
+        ] {
+            let bytes = example(name);
+            let fits = parse_fits(&bytes).unwrap();
+            let view = parse_fits_lazy(&bytes).unwrap();
+            let mut out = Vec::new();
+            write_fits(&mut out, &view).unwrap();
+            assert!(out == bytes, "{} was not written back byte for byte", name);
+            assert!(view
+                .hdus()
+                .iter()
+                .filter(|hdu| hdu.data_view().is_some())
+                .all(|hdu| !hdu.is_decoded()));
+            for (hdu, view) in fits.hdus().iter().zip(view.hdus()) {
+                assert_eq!(
+                    hdu.data_array(),
+                    &view.data_view().map(|v| v.to_data_array()),
+                    "{}",
+                    name
+                );
+                assert_eq!(hdu.binary_table(), view.binary_table());
+                assert_eq!(hdu.table(), view.table());
+            }
+            assert!(view == fits);
+        }
+    }
+
+    #[test]
+    fn random_groups() {
+        let bytes = example("DDTSUVDATA.fits");
+        let fits = parse_fits(&bytes).unwrap();
+        assert_eq!(fits.hdus().len(), 2);
+        let primary = fits.primary_hdu().unwrap();
+        assert!(primary.header().groups());
+        let groups = primary.random_groups().unwrap();
+        assert_eq!(groups.groups().len(), 7956);
+        assert_eq!(groups.parameters().len(), 6);
+        assert_eq!(groups.parameters()[3].name(), Some("BASELINE"));
+        let date = groups.parameter(0, "DATE").unwrap();
+        assert!(date > 2445728.5 && date < 2445729.5);
+        assert_eq!(
+            groups.group(0).unwrap().data_array().dimensions(),
+            &[3, 4, 1, 1, 1]
+        );
+        assert_eq!(
+            fits.extensions()[0].header().header_kind(),
+            &HeaderKind::BinaryTable
+        );
+
+        // the fill of the binary table is not zeroed, but the groups are written back unchanged
+        let mut out = Vec::new();
+        let written = writer::hdu(&mut out, primary).unwrap() as usize;
+        assert!(written > primary.header().data_size().unwrap());
+        assert!(out == bytes[..written]);
+
+        // the groups of a truncated HDU parsed without checks are reported on access
+        let (_, primary) = parser::hdu(&bytes[..written - 2880]).unwrap();
+        assert!(matches!(
+            primary.try_random_groups(),
+            Err(FitsError::BadDataLength { .. })
+        ));
+        assert_eq!(primary.random_groups(), None);
+    }
+
+    #[test]
+    fn multi_extension() {
+        let bytes = example("NICMOSn4hk12010_mos.fits");
+        let fits = parse_fits(&bytes).unwrap();
+        assert_eq!(fits.hdus().len(), 6);
+        assert_eq!(fits.extensions().len(), 5);
+        for hdu in fits.extensions() {
+            assert_eq!(hdu.header().header_kind(), &HeaderKind::Image);
+            assert_eq!(hdu.header().dimensions(), &vec![270, 263]);
+        }
+
+        for (name, kind) in [
+            ("FOSy19g0309t_c2f.fits", HeaderKind::Table),
+            ("HRSz0yd020fm_c2f.fits", HeaderKind::Table),
+            ("FGSf64y0106m_a1f.fits", HeaderKind::Table),
+            ("WFPC2u5780205r_c0fx.fits", HeaderKind::Table),
+            ("IUElwp25637mxlo.fits", HeaderKind::BinaryTable),
+        ] {
+            let bytes = example(name);
+            let fits = parse_fits(&bytes).unwrap();
+            assert_eq!(fits.hdus().len(), 2, "{}", name);
+            assert_eq!(fits.extensions()[0].header().header_kind(), &kind);
+        }
+    }
+
+    #[test]
+    fn binary_table() {
+        let bytes = example("IUElwp25637mxlo.fits");
+        let fits = parse_fits(&bytes).unwrap();
+        let table = fits.extensions()[0].binary_table().unwrap();
+        assert_eq!(table.columns().len(), 9);
+        assert_eq!(table.rows().len(), 1);
+        let npoints = table.values::<i16>("NPOINTS").unwrap()[0][0];
+        assert!(npoints > 0 && npoints <= 640);
+        assert_eq!(table.values::<f32>("FLUX").unwrap()[0].len(), 640);
+        assert_eq!(table.values::<i16>("QUALITY").unwrap()[0].len(), 640);
+        assert!(table.cell(0, 0).and_then(Cell::as_str).is_some());
+        assert_eq!(table.columns()[2].unit(), Some("ANGSTROM"));
+    }
+
+    #[test]
+    fn header_lookup() {
+        let bytes = example("DDTSUVDATA.fits");
+        let fits = parse_fits(&bytes).unwrap();
+        let header = fits.primary_hdu().unwrap().header();
+        assert_eq!(header.get_date("DATE-OBS"), Ok(datetime!(1984-01-29 0:00)));
+        let history = header.history().collect::<Vec<_>>();
+        assert!(history.contains(&"BLOCKED =                    T /Tape may be blocked"));
+        assert!(history.contains(&" / Where baseline = 256*ant1 + ant2 + (array#-1)/100"));
+
+        let bytes = example("IUElwp25637mxlo.fits");
+        let fits = parse_fits(&bytes).unwrap();
+        let header = fits.extensions()[0].header();
+        let names = header
+            .indexed(Keyword::TTypen)
+            .map(|(n, _)| header.get_str(Keyword::TTypen(n)).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names.len(), header.get_i64("TFIELDS").unwrap() as usize);
+        assert_eq!(names[2], "WAVELENGTH");
+    }
+
+    #[test]
+    fn edit_header() {
+        let bytes = example("UITfuv2582gc.fits");
+        let mut fits = parse_fits(&bytes).unwrap();
+        let header = fits.hdus_mut()[0].header_mut();
+        header.set(
+            Keyword::Object,
+            Value::CharacterString("NGC 4472".into()),
+            None,
+        );
+        header.append_history("calibrated");
+        let mut out = Vec::new();
+        writer::fits(&mut out, &fits).unwrap();
+
+        let edited = parse_fits(&out).unwrap();
+        let header = edited.hdus()[0].header();
+        assert_eq!(header.get_str("OBJECT").unwrap(), "NGC 4472");
+        assert_eq!(header.history().last(), Some("calibrated"));
+        assert_eq!(edited.hdus()[0].data_array(), fits.hdus()[0].data_array());
+        // records that were not edited are written back unchanged
+        let records = fits.hdus()[0].header().keyword_records();
+        for (record, written) in records.iter().zip(header.keyword_records()) {
+            if let Some(card) = record.card() {
+                assert_eq!(written.card(), Some(card));
+            }
+        }
+    }
+
+    #[test]
+    fn checksums() {
+        use checksum::Status;
+        let bytes = example("NICMOSn4hk12010_mos.fits");
+        let verification = verify_checksums(&bytes).unwrap();
+        assert_eq!(verification.len(), 6);
+        assert!(verification
+            .iter()
+            .all(|v| v.checksum == Status::Missing && v.datasum == Status::Missing));
+
+        let mut fits = parse_fits(&bytes).unwrap();
+        for hdu in fits.hdus_mut() {
+            writer::update_checksum(hdu).unwrap();
+        }
+        let mut out = Vec::new();
+        write_fits(&mut out, &fits).unwrap();
+        let valid = Verification {
+            checksum: Status::Valid,
+            datasum: Status::Valid,
+        };
+        assert_eq!(verify_checksums(&out).unwrap(), vec![valid; 6]);
+        let written = parse_fits(&out).unwrap();
+        let header = written.hdus()[1].header();
+        assert_eq!(header.get_str("CHECKSUM").unwrap().len(), 16);
+
+        // a changed header byte breaks CHECKSUM, a changed data byte both keywords
+        let location = reader::FitsReader::new(Cursor::new(&out))
+            .unwrap()
+            .location(1)
+            .unwrap();
+        let mut corrupted = out.clone();
+        corrupted[location.header_offset as usize + 15] ^= 1;
+        let verification = verify_checksums(&corrupted).unwrap();
+        assert_eq!(verification[1].checksum, Status::Invalid);
+        assert_eq!(verification[1].datasum, Status::Valid);
+        assert!(verification[0].is_valid());
+        let mut corrupted = out.clone();
+        corrupted[location.data_offset as usize + 100] ^= 0x80;
+        let verification = verify_checksums(&corrupted).unwrap();
+        assert_eq!(verification[1].checksum, Status::Invalid);
+        assert_eq!(verification[1].datasum, Status::Invalid);
+        assert!(!verification[1].is_valid());
+    }
+
+    #[test]
+    fn reference_checksums() {
+        use checksum::Status;
+        // written by FITS-EXAMPLES/checksum/checksum.py, which shares no code with the crate
+        let bytes = example("checksum/FOSy19g0309t_c2f.fits");
+        let valid = Verification {
+            checksum: Status::Valid,
+            datasum: Status::Valid,
+        };
+        assert_eq!(verify_checksums(&bytes).unwrap(), vec![valid; 2]);
+
+        let fits = parse_fits(&bytes).unwrap();
+        assert_eq!(
+            fits.extensions()[0].header().get_str("DATASUM").unwrap(),
+            "2173609164"
+        );
+        let location = reader::FitsReader::new(Cursor::new(&bytes))
+            .unwrap()
+            .location(1)
+            .unwrap();
+        let mut corrupted = bytes.clone();
+        corrupted[location.data_offset as usize] ^= 1;
+        let verification = verify_checksums(&corrupted).unwrap();
+        assert_eq!(verification[0], valid);
+        assert_eq!(verification[1].checksum, Status::Invalid);
+        assert_eq!(verification[1].datasum, Status::Invalid);
+    }
+
+    #[test]
+    fn ascii_table() {
+        let bytes = example("FGSf64y0106m_a1f.fits");
+        let fits = parse_fits(&bytes).unwrap();
+        let table = fits.extensions()[0].table().unwrap();
+        assert_eq!(table.columns().len(), 6);
+        assert_eq!(table.rows().len(), 7);
+        assert_eq!(
+            table.field(0, table.column_index("DATAMAX").unwrap()),
+            Some(&Field::Real(479.0))
+        );
+        assert_eq!(
+            table.column("CTYPE1").unwrap()[0],
+            &Field::Character(String::from("SECONDS"))
+        );
+
+        for name in [
+            "FOSy19g0309t_c2f.fits",
+            "HRSz0yd020fm_c2f.fits",
+            "WFPC2u5780205r_c0fx.fits",
+        ] {
+            let bytes = example(name);
+            let fits = parse_fits(&bytes).unwrap();
+            let hdu = fits.extensions()[0];
+            let table = hdu.table().unwrap();
+            assert_eq!(table.rows().len(), hdu.header().dimensions()[1] as usize);
+            assert_eq!(table.column("CRPIX1").unwrap().len(), table.rows().len());
+        }
+    }
+
+    #[test]
+    fn decompressed() {
+        use builder::{FitsBuilder, HduBuilder};
+        use flate2::{write::GzEncoder, Compression};
+        use types::binary_table::{
+            ArrayDescriptor, BinaryColumn, BinaryFormat, BinaryTable, BinaryType,
+        };
+        use types::data_array::DataArray;
+
+        // a primary array of 3 × 2 bytes, compressed by rows with GZIP_1
+        let rows = [[1u8, 2, 3], [4, 5, 6]]
+            .iter()
+            .map(|row| {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(row).unwrap();
+                vec![Cell::U8(encoder.finish().unwrap())]
+            })
+            .collect();
+        let column = BinaryColumn::new(
+            0,
+            BinaryFormat::variable_length(BinaryType::Byte, ArrayDescriptor::P(None)),
+        )
+        .with_name(Some(String::from("COMPRESSED_DATA")));
+        let compressed = [
+            ("ZIMAGE", Value::Logical(true)),
+            ("ZSIMPLE", Value::Logical(true)),
+            ("ZBITPIX", Value::Integer(8)),
+            ("ZNAXIS", Value::Integer(2)),
+            ("ZNAXIS1", Value::Integer(3)),
+            ("ZNAXIS2", Value::Integer(2)),
+            ("ZEXTEND", Value::Logical(true)),
+            ("ZCMPTYPE", Value::CharacterString(String::from("GZIP_1"))),
+            ("TELESCOP", Value::CharacterString(String::from("HST"))),
+        ]
+        .into_iter()
+        .fold(
+            HduBuilder::binary_table(BinaryTable::new(vec![column], rows)),
+            |hdu, (name, value)| hdu.keyword(Keyword::from(name), value, None),
+        );
+        let fits = FitsBuilder::new().extension(compressed).build().unwrap();
+        let mut bytes = Vec::new();
+        write_fits(&mut bytes, &fits).unwrap();
+
+        assert_eq!(parse_fits(&bytes).unwrap().hdus().len(), 2);
+        let fits = parse_fits_decompressed(&bytes).unwrap();
+        assert_eq!(fits.hdus().len(), 1);
+        let header = fits.hdus()[0].header();
+        assert_eq!(*header.header_kind(), HeaderKind::Primary);
+        assert_eq!(header.get_bool(Keyword::Extend), Ok(true));
+        assert_eq!(header.get_str(Keyword::Telescop).as_deref(), Ok("HST"));
+        assert_eq!(
+            *fits.hdus()[0].data_array(),
+            Some(DataArray::from_u8(
+                vec![1, 2, 3, 4, 5, 6],
+                vec![3, 2],
+                None,
+                None
+            ))
+        );
+        let mut out = Vec::new();
+        write_fits(&mut out, &fits).unwrap();
+        assert_eq!(out.len(), 2 * types::BLOCK_SIZE);
+    }
+
+    #[test]
+    fn compressed() {
+        use builder::{FitsBuilder, HduBuilder};
+        use compression::{Algorithm, Compressor};
+        use types::data_array::DataArray;
+
+        let data = || {
+            DataArray::from_i16(
+                (0..600).map(|i| i % 37 - 18).collect(),
+                vec![30, 20],
+                None,
+                None,
+            )
+        };
+        for algorithm in [Algorithm::Rice, Algorithm::Gzip2, Algorithm::Hcompress] {
+            let fits = FitsBuilder::new()
+                .primary(HduBuilder::new(None))
+                .extension(HduBuilder::new(Some(data())))
+                .build()
+                .unwrap();
+            let compressed = Compressor::new(algorithm).compress_fits(fits).unwrap();
+            let mut bytes = Vec::new();
+            write_fits(&mut bytes, &compressed).unwrap();
+
+            let fits = parse_fits_decompressed(&bytes).unwrap();
+            assert_eq!(fits.hdus().len(), 2);
+            let header = fits.hdus()[1].header();
+            assert_eq!(*header.header_kind(), HeaderKind::Image);
+            assert_eq!(*fits.hdus()[1].data_array(), Some(data()));
+        }
+    }
 }
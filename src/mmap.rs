@@ -0,0 +1,69 @@
+use std::{fs::File, path::Path};
+
+use fitsio_mmap::Mmap;
+
+use crate::{
+    parser,
+    types::{Fits, FitsError},
+};
+
+/// A FITS file mapped into memory. [`MappedFile::fits`] parses it without copying the data
+/// arrays, they are read from the mapped pages when accessed.
+///
+/// # Examples
+///
+/// ```no_run
+/// use fitsio::mmap::MappedFile;
+/// let file = MappedFile::open("cube.fits").unwrap();
+/// let fits = file.fits().unwrap();
+/// let view = fits.primary_hdu().unwrap().data_view().unwrap();
+/// let value: f64 = view.get(&[100, 200, 3]).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct MappedFile {
+    map: Mmap,
+}
+
+impl MappedFile {
+    /// Maps the file at `path` read-only. Modifying or truncating the file while it is mapped
+    /// is undefined behaviour, which the caller has to rule out like for any memory map.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, FitsError> {
+        let file = File::open(path)?;
+        let map = fitsio_mmap::map(&file)?;
+        Ok(Self { map })
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.map
+    }
+
+    /// Parses the mapped file like [`crate::parser::fits_view`].
+    pub fn fits(&self) -> Result<Fits<'_>, FitsError> {
+        parser::fits_view(&self.map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_fits;
+
+    #[test]
+    fn mapped_file() {
+        let path = format!(
+            "{}/FITS-EXAMPLES/NICMOSn4hk12010_mos.fits",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let file = MappedFile::open(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(file.bytes(), &bytes[..]);
+
+        let fits = file.fits().unwrap();
+        let decoded = parse_fits(&bytes).unwrap();
+        assert_eq!(fits.hdus().len(), 6);
+        let view = fits.hdus()[1].data_view().unwrap();
+        // the view points into the mapped pages
+        assert!(file.bytes().as_ptr_range().contains(&view.bytes().as_ptr()));
+        assert_eq!(&Some(view.to_data_array()), decoded.hdus()[1].data_array());
+    }
+}
@@ -0,0 +1,99 @@
+//! Splits work on large arrays across threads if the `rayon` feature is enabled. Every chunk is
+//! processed like the whole array would be, so the result does not depend on the feature.
+
+/// Number of elements that are processed by one task.
+#[cfg(feature = "rayon")]
+const CHUNK: usize = 1 << 20;
+
+/// Calls `f` with matching parts of `out` and `input`, where every `out_size` elements of `out`
+/// correspond to `input_size` elements of `input`. Without the `rayon` feature, or for small
+/// arrays, `f` is called once with both slices.
+pub(crate) fn zip_chunks<O, I>(
+    out: &mut [O],
+    out_size: usize,
+    input: &[I],
+    input_size: usize,
+    f: impl Fn(&mut [O], &[I]) + Send + Sync,
+) where
+    O: Send,
+    I: Sync,
+{
+    #[cfg(feature = "rayon")]
+    if out.len() > CHUNK * out_size {
+        use rayon::prelude::*;
+        return out
+            .par_chunks_mut(CHUNK * out_size)
+            .zip(input.par_chunks(CHUNK * input_size))
+            .for_each(|(out, input)| f(out, input));
+    }
+    let _ = (out_size, input_size);
+    f(out, input)
+}
+
+/// Collects `f(0)`, …, `f(len - 1)`, in parallel with the `rayon` feature.
+pub(crate) fn collect<T: Send>(len: usize, f: impl Fn(usize) -> T + Send + Sync) -> Vec<T> {
+    #[cfg(feature = "rayon")]
+    if len > CHUNK {
+        use rayon::prelude::*;
+        return (0..len).into_par_iter().map(f).collect();
+    }
+    (0..len).map(f).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        builder::{FitsBuilder, HduBuilder},
+        parse_fits,
+        types::data_array::DataArray,
+        write_fits,
+    };
+
+    #[test]
+    fn round_trip() {
+        let values = (0..3_000_007u32)
+            .map(|v| v.wrapping_mul(7919) as u16)
+            .collect::<Vec<_>>();
+        let data_array = DataArray::from_u16(values.clone(), vec![3_000_007]);
+        let fits = FitsBuilder::new()
+            .primary(HduBuilder::new(Some(data_array)))
+            .build()
+            .unwrap();
+        let mut bytes = Vec::new();
+        write_fits(&mut bytes, &fits).unwrap();
+        let data = &bytes[2880..2880 + values.len() * 2];
+        assert!(data
+            .chunks_exact(2)
+            .zip(&values)
+            .all(|(b, &v)| b == (v ^ 0x8000).to_be_bytes()));
+
+        let parsed = parse_fits(&bytes).unwrap();
+        let data_array = parsed.hdus()[0].data_array().as_ref().unwrap();
+        assert_eq!(data_array.as_slice::<u16>(), Some(&values[..]));
+        let physical = data_array.physical_values();
+        assert!(physical.iter().zip(&values).all(|(&p, &v)| p == v as f64));
+    }
+
+    #[test]
+    fn zip_chunks() {
+        let input = (0..3_000_001u32).collect::<Vec<_>>();
+        let mut out = vec![0u8; input.len() * 4];
+        super::zip_chunks(&mut out, 4, &input, 1, |out, input| {
+            for (out, value) in out.chunks_exact_mut(4).zip(input) {
+                out.copy_from_slice(&value.to_be_bytes());
+            }
+        });
+        let expected = input
+            .iter()
+            .flat_map(|v| v.to_be_bytes())
+            .collect::<Vec<u8>>();
+        assert!(out == expected);
+    }
+
+    #[test]
+    fn collect() {
+        let values = super::collect(2_000_003, |i| i * 2);
+        assert_eq!(values.len(), 2_000_003);
+        assert!(values.iter().enumerate().all(|(i, &v)| v == i * 2));
+    }
+}
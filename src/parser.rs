@@ -1,52 +1,490 @@
-use std::u8;
-
 use nom::{
-    error::{context, VerboseError},
-    multi::many0,
+    bytes::complete::take,
+    error::{context, ErrorKind, ParseError, VerboseError},
     IResult,
 };
 use tracing::trace;
 
-use crate::types::{header::FitsHeader, Fits, HDU};
+use crate::types::{
+    binary_table::BinaryTable,
+    data_view::DataView,
+    header::{FitsHeader, Header, HeaderKind},
+    keyword::Keyword,
+    random_groups::RandomGroups,
+    table::Table,
+    DataUnit, Fits, FitsError, Position, BLOCK_SIZE, CARD_SIZE, HDU,
+};
 
+mod binary_table;
 mod data_array;
 mod header;
 mod keyword_record;
-mod value;
+mod random_groups;
+mod table;
+pub(crate) mod value;
 
 fn is_allowed_ascii(c: u8) -> bool {
     (32u8..=126u8).contains(&c)
 }
 
-pub fn hdu(i: &[u8]) -> IResult<&[u8], HDU, VerboseError<&[u8]>> {
-    match context("header", header::header)(i) {
-        Ok((i, header)) => match header.has_data_array() {
-            true => {
-                let (i, data_array) = data_array::data_array(i, &header)?;
-                let hdu = HDU::new(header, Some(data_array));
-                trace!("{:?}", hdu);
-                Ok((i, hdu))
-            }
-            false => {
-                let hdu = HDU::new(header, None);
-                trace!("{:?}", hdu);
-                Ok((i, hdu))
-            }
-        },
-        Err(e) => Err(e),
+/// How the data array of an HDU is read.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DataMode {
+    /// The elements are decoded into a [`crate::types::data_array::DataArray`].
+    Decode,
+    /// The HDU keeps a [`DataView`] of the input, elements are converted on access and the
+    /// data array is decoded the first time it is requested.
+    View,
+}
+
+pub fn hdu(i: &[u8]) -> IResult<&[u8], HDU<'_>, VerboseError<&[u8]>> {
+    hdu_with(i, DataMode::Decode)
+}
+
+/// Like [`hdu`], but the data array is not decoded until it is requested.
+pub fn hdu_view(i: &[u8]) -> IResult<&[u8], HDU<'_>, VerboseError<&[u8]>> {
+    hdu_with(i, DataMode::View)
+}
+
+fn hdu_with(i: &[u8], mode: DataMode) -> IResult<&[u8], HDU<'_>, VerboseError<&[u8]>> {
+    let (data_unit, header) = context("header", header::header)(i)?;
+    let position = Position::new(0, 0, None);
+    let data_position = Position::new(i.len() - data_unit.len(), 0, None);
+    data(data_unit, header, mode, position, data_position)
+}
+
+/// Parses the data unit described by `header` and skips its padding. The rest of the data unit,
+/// e.g. the rows of a table, is decoded on first access.
+fn data<'a>(
+    data_unit: &'a [u8],
+    header: Header<'a>,
+    mode: DataMode,
+    position: Position,
+    data_position: Position,
+) -> IResult<&'a [u8], HDU<'a>, VerboseError<&'a [u8]>> {
+    let data_size = header.data_size().map_err(|_| {
+        nom::Err::Error(VerboseError::from_error_kind(
+            data_unit,
+            ErrorKind::TooLarge,
+        ))
+    })?;
+    let (i, hdu) = match (header.has_data_array(), mode) {
+        (true, DataMode::Decode) => {
+            let (i, data_array) = data_array::data_array(data_unit, &header)?;
+            let data_array = data_array.with_blank(header.blank()).into_unsigned();
+            (i, HDU::new(header, Some(data_array)))
+        }
+        (true, DataMode::View) => {
+            let view = DataView::new(&header, data_unit).ok_or_else(|| {
+                nom::Err::Error(VerboseError::from_error_kind(data_unit, ErrorKind::Eof))
+            })?;
+            let i = &data_unit[view.bytes().len()..];
+            (i, HDU::new(header, None).with_data_view(view))
+        }
+        (false, _) => (data_unit, HDU::new(header, None)),
+    };
+    let padded_size = data_size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+    let hdu = hdu.with_data_unit(DataUnit {
+        bytes: &data_unit[..data_size.min(data_unit.len())],
+        fill: data_unit
+            .get(data_size..padded_size.min(data_unit.len()))
+            .unwrap_or_default(),
+        data_array_size: data_unit.len() - i.len(),
+        position,
+        data_position,
+    });
+    let (i, _) = padding(data_unit, i, data_size)?;
+    trace!("{:?}", hdu);
+    Ok((i, hdu))
+}
+
+/// Skips the rest of the data unit after the data array, i.e. a heap and the fill up to the
+/// next block boundary. A missing fill at the end of the file is tolerated.
+/// FITS standard section 3.3.2
+fn padding<'a>(
+    data_unit: &'a [u8],
+    i: &'a [u8],
+    data_size: usize,
+) -> IResult<&'a [u8], &'a [u8], VerboseError<&'a [u8]>> {
+    let consumed = data_unit.len() - i.len();
+    let padded_size = data_size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+    take(padded_size.saturating_sub(consumed).min(i.len()))(i)
+}
+
+/// Reads the primary HDU and all extensions that follow it. Anything after the last HDU that
+/// does not start with `XTENSION` is treated as special records and ignored.
+/// FITS standard section 3.5
+pub fn fits(i: &[u8]) -> Result<Fits<'_>, FitsError> {
+    fits_with(i, DataMode::Decode)
+}
+
+/// Like [`fits`], but the data arrays are not decoded. Every HDU with a data array keeps a
+/// [`DataView`] that borrows from `i` and decodes the data array on first access, see
+/// [`HDU::data_view`] and [`HDU::data_array`]. Tables and random groups are likewise decoded
+/// on first access, see [`HDU::try_table`] and [`HDU::try_binary_table`].
+pub fn fits_view(i: &[u8]) -> Result<Fits<'_>, FitsError> {
+    fits_with(i, DataMode::View)
+}
+
+fn fits_with(i: &[u8], mode: DataMode) -> Result<Fits<'_>, FitsError> {
+    let mut hdus = Vec::new();
+    let mut input = i;
+    loop {
+        let position = Position::new(i.len() - input.len(), hdus.len(), None);
+        let (rest, hdu) = checked_hdu(input, position, mode)?;
+        hdus.push(hdu);
+        input = rest;
+        if !input.starts_with(Keyword::Xtension.name().as_bytes()) {
+            break;
+        }
+    }
+    Ok(Fits::from(hdus))
+}
+
+/// Parses an HDU that starts at `position`, validating the header and the length of the data
+/// unit, so that errors point to the offending HDU and keyword record.
+fn checked_hdu(
+    i: &[u8],
+    position: Position,
+    mode: DataMode,
+) -> Result<(&[u8], HDU<'_>), FitsError> {
+    let (data_unit, header) = checked_header(i, position)?;
+    let data_position = Position::new(
+        position.offset + i.len() - data_unit.len(),
+        position.hdu,
+        None,
+    );
+    let padded_size = header.data_size()?.div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+    let hdu = checked_data(header, data_unit, position, data_position, mode)?;
+    Ok((&data_unit[padded_size.min(data_unit.len())..], hdu))
+}
+
+/// Parses and validates the header that starts at `position`. Returns the input that follows
+/// the header.
+pub(crate) fn checked_header(
+    i: &[u8],
+    position: Position,
+) -> Result<(&[u8], Header<'_>), FitsError> {
+    let (data_unit, header) = header::header(i)
+        .map_err(|e| FitsError::Truncated(position.card(failure_offset(i, e) / CARD_SIZE + 1)))?;
+    header::validate(&header, position)?;
+    Ok((data_unit, header))
+}
+
+/// Decodes the data unit of an HDU with the header at `position` and the data unit at
+/// `data_position`. `data_unit` may extend beyond the data unit. With [`DataMode::View`] the
+/// rows of a table are not decoded, so errors in them are reported on first access.
+pub(crate) fn checked_data<'a>(
+    header: Header<'a>,
+    data_unit: &'a [u8],
+    position: Position,
+    data_position: Position,
+    mode: DataMode,
+) -> Result<HDU<'a>, FitsError> {
+    let data_size = header.data_size()?;
+    let bad_data_length = FitsError::BadDataLength {
+        position: data_position,
+        expected: data_size,
+        actual: data_unit.len(),
+    };
+    if data_unit.len() < data_size {
+        return Err(bad_data_length);
+    }
+    let (_, hdu) =
+        data(data_unit, header, mode, position, data_position).map_err(|_| bad_data_length)?;
+    if mode == DataMode::Decode {
+        hdu.try_table()?;
+        hdu.try_binary_table()?;
+        hdu.try_random_groups()?;
+    }
+    Ok(hdu)
+}
+
+/// Decodes the rows of an ASCII table extension, `None` for other HDUs.
+pub(crate) fn decode_table(header: &Header, unit: &DataUnit) -> Result<Option<Table>, FitsError> {
+    match header.header_kind() {
+        HeaderKind::Table => {
+            table::table(header, unit.bytes, unit.position, unit.data_position).map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Decodes the rows of a binary table extension, `None` for other HDUs.
+pub(crate) fn decode_binary_table(
+    header: &Header,
+    unit: &DataUnit,
+) -> Result<Option<BinaryTable>, FitsError> {
+    match header.header_kind() {
+        HeaderKind::BinaryTable => {
+            binary_table::binary_table(header, unit.bytes, unit.position, unit.data_position)
+                .map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Decodes the groups of a random groups primary HDU, `None` for other HDUs.
+pub(crate) fn decode_random_groups(
+    header: &Header,
+    unit: &DataUnit,
+) -> Result<Option<RandomGroups>, FitsError> {
+    match header.header_kind() {
+        HeaderKind::Primary if header.groups() => {
+            random_groups::random_groups(header, unit.bytes, unit.data_position).map(Some)
+        }
+        _ => Ok(None),
     }
 }
 
-pub fn fits(i: &[u8]) -> IResult<&[u8], Fits, VerboseError<&[u8]>> {
-    match context("fits", many0(hdu))(i) {
-        Ok((i, hdus)) => Ok((i, Fits::from(hdus))),
-        Err(e) => Err(e),
+/// Offset into `i` at which a parser failed.
+fn failure_offset(i: &[u8], e: nom::Err<VerboseError<&[u8]>>) -> usize {
+    match e {
+        nom::Err::Error(e) | nom::Err::Failure(e) => e
+            .errors
+            .first()
+            .map_or(i.len(), |(rest, _)| i.len() - rest.len()),
+        nom::Err::Incomplete(_) => i.len(),
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::types::{
+        data_array::DataArray,
+        header::{FitsHeader, HeaderKind},
+        FitsError, Position,
+    };
+
+    fn header_block(cards: &[&str]) -> Vec<u8> {
+        let mut block = cards
+            .iter()
+            .flat_map(|card| format!("{:80}", card).into_bytes())
+            .collect::<Vec<u8>>();
+        block.resize(2880, b' ');
+        block
+    }
+
+    #[test]
+    fn hdu() {
+        let mut input = header_block(&[
+            "SIMPLE  =                    T",
+            "BITPIX  =                   16",
+            "NAXIS   =                    1",
+            "NAXIS1  =                    3",
+            "END",
+        ]);
+        input.extend([0, 1, 0, 2, 0, 3]);
+        input.resize(2 * 2880, 0);
+        input.extend(header_block(&[
+            "XTENSION= 'IMAGE   '",
+            "BITPIX  =                    8",
+            "NAXIS   =                    0",
+            "PCOUNT  =                    0",
+            "GCOUNT  =                    1",
+            "END",
+        ]));
+        let (i, hdu) = super::hdu(&input).unwrap();
+        assert_eq!(i.len(), 2880);
+        assert_eq!(
+            hdu.data_array(),
+            &Some(DataArray::from_i16(vec![1, 2, 3], vec![3], None, None))
+        );
+        let (i, hdu) = super::hdu(i).unwrap();
+        assert_eq!(i, b"");
+        assert_eq!(hdu.header().header_kind(), &HeaderKind::Image);
+    }
+
+    #[test]
+    fn hdu_view() {
+        let mut input = header_block(&[
+            "SIMPLE  =                    T",
+            "BITPIX  =                   16",
+            "NAXIS   =                    1",
+            "NAXIS1  =                    3",
+            "END",
+        ]);
+        input.extend([0, 1, 0, 2, 0, 3]);
+        input.resize(2 * 2880, 0);
+        let (i, hdu) = super::hdu_view(&input).unwrap();
+        assert_eq!(i, b"");
+        assert!(!hdu.is_decoded());
+        assert_eq!(hdu.data_view().unwrap().get::<i16>(&[2]), Ok(3));
+        assert!(!hdu.is_decoded());
+        assert_eq!(
+            hdu.data_array(),
+            &Some(DataArray::from_i16(vec![1, 2, 3], vec![3], None, None))
+        );
+        assert!(hdu.is_decoded());
+        assert_eq!(hdu, super::hdu(&input).unwrap().1);
+    }
+
+    #[test]
+    fn hdu_skips_heap() {
+        let mut input = header_block(&[
+            "XTENSION= 'BINTABLE'",
+            "BITPIX  =                    8",
+            "NAXIS   =                    2",
+            "NAXIS1  =                    2",
+            "NAXIS2  =                    2",
+            "PCOUNT  =                 2880",
+            "GCOUNT  =                    1",
+            "END",
+        ]);
+        input.resize(4 * 2880, 0);
+        input[2884] = 1;
+        let (i, hdu) = super::hdu(&input).unwrap();
+        assert_eq!(i.len(), 2880);
+        assert_eq!(hdu.heap().len(), 2880);
+        assert_eq!(hdu.heap()[0], 1);
+    }
 
     #[test]
-    fn hdu() {}
+    fn hdu_without_final_padding() {
+        let mut input = header_block(&[
+            "SIMPLE  =                    T",
+            "BITPIX  =                    8",
+            "NAXIS   =                    1",
+            "NAXIS1  =                    3",
+            "END",
+        ]);
+        input.extend([1, 2, 3]);
+        let (i, hdu) = super::hdu(&input).unwrap();
+        assert_eq!(i, b"");
+        assert_eq!(hdu.header().data_size(), Ok(3));
+    }
+
+    fn image(bitpix: &str, naxis1: &str) -> Vec<u8> {
+        header_block(&[
+            "SIMPLE  =                    T",
+            &format!("BITPIX  = {:>20}", bitpix),
+            "NAXIS   =                    1",
+            &format!("NAXIS1  = {:>20}", naxis1),
+            "END",
+        ])
+    }
+
+    #[test]
+    fn fits() {
+        let mut input = image("8", "3");
+        input.resize(2 * 2880, 0);
+        input.extend(header_block(&[
+            "XTENSION= 'IMAGE   '",
+            "BITPIX  =                    8",
+            "NAXIS   =                    0",
+            "PCOUNT  =                    0",
+            "GCOUNT  =                    1",
+            "END",
+        ]));
+        let fits = super::fits(&input).unwrap();
+        assert_eq!(fits.hdus().len(), 2);
+
+        // special records after the last HDU are ignored
+        input.extend([b'X'; 2880]);
+        assert_eq!(super::fits(&input).unwrap().hdus().len(), 2);
+    }
+
+    #[test]
+    fn fits_view_table() {
+        let mut input = image("8", "0");
+        input.extend(header_block(&[
+            "XTENSION= 'BINTABLE'",
+            "BITPIX  =                    8",
+            "NAXIS   =                    2",
+            "NAXIS1  =                    4",
+            "NAXIS2  =                    2",
+            "PCOUNT  =                    3",
+            "GCOUNT  =                    1",
+            "TFIELDS =                    1",
+            "TFORM1  = '1J      '",
+            "END",
+        ]));
+        input.extend([0, 0, 0, 1, 0, 0, 0, 2, 7, 8, 9]);
+        input.resize(3 * 2880, 0);
+
+        let fits = super::fits_view(&input).unwrap();
+        let hdu = &fits.hdus()[1];
+        assert!(!hdu.is_decoded());
+        // the heap is borrowed from the input
+        assert_eq!(hdu.heap(), &[7, 8, 9]);
+        assert_eq!(hdu.heap().as_ptr(), input[2 * 2880 + 8..].as_ptr());
+        assert!(!hdu.is_decoded());
+        assert_eq!(
+            hdu.binary_table().unwrap().values_at::<i32>(0),
+            Some(vec![&[1][..], &[2][..]])
+        );
+        hdu.data_array();
+        assert!(hdu.is_decoded());
+        assert_eq!(*hdu, super::fits(&input).unwrap().hdus()[1]);
+
+        // errors in the rows are reported on first access
+        let input = String::from_utf8(input)
+            .unwrap()
+            .replace("'1J      '", "'1Q      '")
+            .into_bytes();
+        assert!(matches!(
+            super::fits(&input),
+            Err(FitsError::BadKeywordCard { .. })
+        ));
+        let fits = super::fits_view(&input).unwrap();
+        assert!(matches!(
+            fits.hdus()[1].try_binary_table(),
+            Err(FitsError::BadKeywordCard { .. })
+        ));
+        assert_eq!(fits.hdus()[1].binary_table(), None);
+    }
+
+    #[test]
+    fn fits_truncated() {
+        assert_eq!(
+            super::fits(b""),
+            Err(FitsError::Truncated(Position::new(0, 0, Some(1))))
+        );
+        let input = image("8", "3");
+        assert_eq!(
+            super::fits(&input[..1000]),
+            Err(FitsError::Truncated(Position::new(960, 0, Some(13))))
+        );
+        let mut input = image("8", "3");
+        input.resize(2 * 2880, 0);
+        input.extend(&header_block(&["XTENSION= 'IMAGE   '"])[..200]);
+        assert_eq!(
+            super::fits(&input),
+            Err(FitsError::Truncated(Position::new(5760 + 160, 1, Some(3))))
+        );
+    }
+
+    #[test]
+    fn fits_bad_data_length() {
+        let mut input = image("16", "3");
+        input.extend([0, 1, 0, 2]);
+        assert_eq!(
+            super::fits(&input),
+            Err(FitsError::BadDataLength {
+                position: Position::new(2880, 0, None),
+                expected: 6,
+                actual: 4
+            })
+        );
+    }
+
+    #[test]
+    fn fits_unsupported_bitpix() {
+        assert_eq!(
+            super::fits(&image("12", "3")),
+            Err(FitsError::UnsupportedBitpix {
+                position: Position::new(80, 0, Some(2)),
+                bitpix: 12
+            })
+        );
+    }
+
+    #[test]
+    fn fits_bad_keyword_card() {
+        let err = super::fits(&image("8", "'three'")).unwrap_err();
+        assert!(matches!(
+            err,
+            FitsError::BadKeywordCard { position, .. } if position == Position::new(240, 0, Some(4))
+        ));
+    }
 }
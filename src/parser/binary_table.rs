@@ -0,0 +1,634 @@
+use nom::{
+    branch::alt,
+    character::complete::{char, digit1, one_of, space0},
+    combinator::{all_consuming, map, map_res, opt},
+    error::{context, VerboseError},
+    multi::separated_list1,
+    sequence::{delimited, pair, terminated, tuple},
+    IResult,
+};
+
+use crate::types::{
+    binary_table::{ArrayDescriptor, BinaryColumn, BinaryFormat, BinaryTable, BinaryType, Cell},
+    header::{FitsHeader, Header},
+    keyword::Keyword,
+    value::Value,
+    FitsError, Position,
+};
+
+use super::header::{end_card, find, real, string, tfields};
+
+/// Decodes a binary table extension from its data unit without the block padding.
+/// Variable length arrays are read from the heap that starts at THEAP. `position` is the start
+/// of the header and `data_position` the start of the data unit. FITS standard section 7.3
+pub fn binary_table(
+    header: &Header,
+    data: &[u8],
+    position: Position,
+    data_position: Position,
+) -> Result<BinaryTable, FitsError> {
+    let columns = columns(header, position)?;
+    let (row_length, number_of_rows) = match header.dimensions().as_slice() {
+        [row_length, number_of_rows] => (*row_length as usize, *number_of_rows as usize),
+        _ => {
+            return Err(FitsError::BadKeywordCard {
+                position: position.card(3),
+                reason: String::from("a binary table must have NAXIS = 2"),
+            })
+        }
+    };
+    let table_size = row_length * number_of_rows;
+    let heap = match find(header.keyword_records(), Keyword::THeap) {
+        Some((card, Value::Integer(theap))) => {
+            match usize::try_from(*theap).ok().filter(|&t| t >= table_size) {
+                Some(theap) => data.get(theap..).unwrap_or_default(),
+                None => {
+                    return Err(FitsError::BadKeywordCard {
+                        position: position.card(card),
+                        reason: format!("THEAP must be at least {}, found {}", table_size, theap),
+                    })
+                }
+            }
+        }
+        _ => data.get(table_size..).unwrap_or_default(),
+    };
+    let mut rows = Vec::with_capacity(number_of_rows);
+    for (r, row) in data
+        .chunks_exact(row_length.max(1))
+        .take(number_of_rows)
+        .enumerate()
+    {
+        let mut cells = Vec::with_capacity(columns.len());
+        for (c, column) in columns.iter().enumerate() {
+            let bytes = &row[column.start()..][..column.format().width()];
+            cells.push(
+                cell(column, bytes, heap).map_err(|reason| FitsError::BadTableField {
+                    position: Position::new(
+                        data_position.offset + r * row_length + column.start(),
+                        data_position.hdu,
+                        None,
+                    ),
+                    row: r + 1,
+                    column: c + 1,
+                    reason,
+                })?,
+            );
+        }
+        rows.push(cells);
+    }
+    Ok(BinaryTable::new(columns, rows))
+}
+
+/// Reads the field descriptions from TFIELDS, TFORMn, TTYPEn, TUNITn, TDIMn, TNULLn, TSCALn
+/// and TZEROn. The fields must fit into NAXIS1 bytes.
+fn columns(header: &Header, position: Position) -> Result<Vec<BinaryColumn>, FitsError> {
+    let records = header.keyword_records();
+    let row_length = header.dimensions().first().copied().unwrap_or(0) as usize;
+    let end = end_card(records);
+    let mut columns = Vec::new();
+    let mut start = 0;
+    for n in 1..=tfields(records, position)? {
+        let (card, format) = match find(records, Keyword::TFormn(n)) {
+            Some((card, Value::CharacterString(s))) => match tform(s.trim()) {
+                Ok((_, format)) => (card, format),
+                Err(_) => {
+                    return Err(FitsError::BadKeywordCard {
+                        position: position.card(card),
+                        reason: format!("TFORM{} has an invalid format '{}'", n, s.trim()),
+                    })
+                }
+            },
+            Some((card, value)) => {
+                return Err(FitsError::BadKeywordCard {
+                    position: position.card(card),
+                    reason: format!("TFORM{} must be a string, found {}", n, value),
+                })
+            }
+            None => {
+                return Err(FitsError::MissingKeyword {
+                    position: position.card(end),
+                    keyword: Keyword::TFormn(n),
+                })
+            }
+        };
+        if start + format.width() > row_length {
+            return Err(FitsError::BadKeywordCard {
+                position: position.card(card),
+                reason: format!(
+                    "field {} does not fit into a row of {} bytes",
+                    n, row_length
+                ),
+            });
+        }
+        let dimensions = match find(records, Keyword::TDimn(n)) {
+            Some((card, Value::CharacterString(s))) => match tdim(s.trim()) {
+                Ok((_, dimensions)) if dimensions.iter().product::<usize>() <= format.repeat() => {
+                    Some(dimensions)
+                }
+                _ => {
+                    return Err(FitsError::BadKeywordCard {
+                        position: position.card(card),
+                        reason: format!(
+                            "TDIM{} '{}' does not match a repeat count of {}",
+                            n,
+                            s.trim(),
+                            format.repeat()
+                        ),
+                    })
+                }
+            },
+            _ => None,
+        };
+        let null = match find(records, Keyword::TNulln(n)) {
+            Some((_, Value::Integer(null))) => Some(*null),
+            _ => None,
+        };
+        columns.push(
+            BinaryColumn::new(start, format)
+                .with_name(string(records, Keyword::TTypen(n)))
+                .with_unit(string(records, Keyword::TUnitn(n)))
+                .with_dimensions(dimensions)
+                .with_null(null)
+                .with_scaling(
+                    real(records, Keyword::TScaln(n)).unwrap_or(1.0),
+                    real(records, Keyword::TZeron(n)).unwrap_or(0.0),
+                ),
+        );
+        start += format.width();
+    }
+    Ok(columns)
+}
+
+/// Parses a TFORMn value `rTa`, or `rPt(max)` and `rQt(max)` for variable length arrays, of a
+/// binary table. Characters after the type code are reserved and ignored.
+/// FITS standard sections 7.3.3.1 and 7.3.5
+fn tform(i: &str) -> IResult<&str, BinaryFormat, VerboseError<&str>> {
+    context(
+        "tform",
+        alt((
+            map(
+                tuple((
+                    opt(number),
+                    one_of("PQ"),
+                    data_type,
+                    opt(delimited(char('('), number, char(')'))),
+                )),
+                |(_, descriptor, data_type, max)| {
+                    let descriptor = match descriptor {
+                        'P' => ArrayDescriptor::P(max),
+                        _ => ArrayDescriptor::Q(max),
+                    };
+                    BinaryFormat::variable_length(data_type, descriptor)
+                },
+            ),
+            map(pair(opt(number), data_type), |(repeat, data_type)| {
+                BinaryFormat::new(repeat.unwrap_or(1), data_type)
+            }),
+        )),
+    )(i)
+}
+
+fn data_type(i: &str) -> IResult<&str, BinaryType, VerboseError<&str>> {
+    map(one_of("LXBIJKAEDCM"), |code| match code {
+        'L' => BinaryType::Logical,
+        'X' => BinaryType::Bit,
+        'B' => BinaryType::Byte,
+        'I' => BinaryType::I16,
+        'J' => BinaryType::I32,
+        'K' => BinaryType::I64,
+        'A' => BinaryType::Character,
+        'E' => BinaryType::F32,
+        'D' => BinaryType::F64,
+        'C' => BinaryType::ComplexF32,
+        _ => BinaryType::ComplexF64,
+    })(i)
+}
+
+/// Parses a TDIMn value `(l,m,n...)`. FITS standard section 7.3.2
+fn tdim(i: &str) -> IResult<&str, Vec<usize>, VerboseError<&str>> {
+    context(
+        "tdim",
+        all_consuming(delimited(
+            char('('),
+            separated_list1(char(','), delimited(space0, number, space0)),
+            terminated(char(')'), space0),
+        )),
+    )(i)
+}
+
+fn number(i: &str) -> IResult<&str, usize, VerboseError<&str>> {
+    map_res(digit1, str::parse::<usize>)(i)
+}
+
+/// Decodes a field, following the array descriptor of a variable length array into the heap.
+/// FITS standard section 7.3.5
+fn cell(column: &BinaryColumn, bytes: &[u8], heap: &[u8]) -> Result<Cell, String> {
+    let (count, offset) = match column.format().descriptor() {
+        None => return Ok(values(column, column.format().repeat(), bytes)),
+        Some(ArrayDescriptor::P(_)) => (
+            u32::from_be_bytes(bytes[..4].try_into().unwrap()) as usize,
+            u32::from_be_bytes(bytes[4..8].try_into().unwrap()) as usize,
+        ),
+        Some(ArrayDescriptor::Q(_)) => (
+            u64::from_be_bytes(bytes[..8].try_into().unwrap()) as usize,
+            u64::from_be_bytes(bytes[8..16].try_into().unwrap()) as usize,
+        ),
+    };
+    let size = column.format().data_type().bytes(count);
+    offset
+        .checked_add(size)
+        .and_then(|end| heap.get(offset..end))
+        .map(|bytes| values(column, count, bytes))
+        .ok_or_else(|| {
+            format!(
+                "array of {} bytes at offset {} exceeds the heap of {} bytes",
+                size,
+                offset,
+                heap.len()
+            )
+        })
+}
+
+/// Decodes `count` big-endian values of a field. TSCALn and TZEROn are applied to integers and
+/// floating point numbers, complex numbers are returned as stored. FITS standard section 7.3.3
+fn values(column: &BinaryColumn, count: usize, bytes: &[u8]) -> Cell {
+    macro_rules! values {
+        ($t:ty) => {
+            bytes
+                .chunks_exact(std::mem::size_of::<$t>())
+                .map(|b| <$t>::from_be_bytes(b.try_into().unwrap()))
+                .collect::<Vec<$t>>()
+        };
+    }
+    let scaled = |values: Vec<f64>| {
+        Cell::Real(
+            values
+                .into_iter()
+                .map(|v| column.zero() + column.scale() * v)
+                .collect(),
+        )
+    };
+    let scaled_integers = |values: Vec<i64>| {
+        Cell::Real(
+            values
+                .into_iter()
+                .map(|v| match column.null() == Some(v) {
+                    true => f64::NAN,
+                    false => column.zero() + column.scale() * v as f64,
+                })
+                .collect(),
+        )
+    };
+    let offset = column.is_offset_integer();
+    match column.format().data_type() {
+        BinaryType::Logical => Cell::Logical(
+            bytes
+                .iter()
+                .map(|b| match b {
+                    b'T' => Some(true),
+                    b'F' => Some(false),
+                    _ => None,
+                })
+                .collect(),
+        ),
+        BinaryType::Bit => Cell::Bit(
+            (0..count)
+                .map(|n| bytes[n / 8] & (0x80 >> (n % 8)) != 0)
+                .collect(),
+        ),
+        BinaryType::Character => {
+            let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+            Cell::Character(
+                String::from_utf8_lossy(&bytes[..end])
+                    .trim_end()
+                    .to_string(),
+            )
+        }
+        BinaryType::Byte if offset => Cell::I8(bytes.iter().map(|&b| (b ^ 0x80) as i8).collect()),
+        BinaryType::Byte if column.is_scaled() => {
+            scaled_integers(bytes.iter().map(|&b| b as i64).collect())
+        }
+        BinaryType::Byte => Cell::U8(bytes.to_vec()),
+        BinaryType::I16 if offset => Cell::U16(
+            values!(i16)
+                .into_iter()
+                .map(|v| v as u16 ^ 0x8000)
+                .collect(),
+        ),
+        BinaryType::I16 if column.is_scaled() => {
+            scaled_integers(values!(i16).into_iter().map(i64::from).collect())
+        }
+        BinaryType::I16 => Cell::I16(values!(i16)),
+        BinaryType::I32 if offset => Cell::U32(
+            values!(i32)
+                .into_iter()
+                .map(|v| v as u32 ^ 0x8000_0000)
+                .collect(),
+        ),
+        BinaryType::I32 if column.is_scaled() => {
+            scaled_integers(values!(i32).into_iter().map(i64::from).collect())
+        }
+        BinaryType::I32 => Cell::I32(values!(i32)),
+        BinaryType::I64 if offset => Cell::U64(
+            values!(i64)
+                .into_iter()
+                .map(|v| v as u64 ^ 0x8000_0000_0000_0000)
+                .collect(),
+        ),
+        BinaryType::I64 if column.is_scaled() => scaled_integers(values!(i64)),
+        BinaryType::I64 => Cell::I64(values!(i64)),
+        BinaryType::F32 if column.is_scaled() => {
+            scaled(values!(f32).into_iter().map(f64::from).collect())
+        }
+        BinaryType::F32 => Cell::F32(values!(f32)),
+        BinaryType::F64 if column.is_scaled() => scaled(values!(f64)),
+        BinaryType::F64 => Cell::F64(values!(f64)),
+        BinaryType::ComplexF32 => {
+            Cell::ComplexF32(values!(f32).chunks_exact(2).map(|c| (c[0], c[1])).collect())
+        }
+        BinaryType::ComplexF64 => {
+            Cell::ComplexF64(values!(f64).chunks_exact(2).map(|c| (c[0], c[1])).collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table_header(row_length: usize, rows: usize, cards: &[&str]) -> Vec<u8> {
+        let mut bytes = [
+            String::from("XTENSION= 'BINTABLE'"),
+            String::from("BITPIX  =                    8"),
+            String::from("NAXIS   =                    2"),
+            format!("NAXIS1  = {:>20}", row_length),
+            format!("NAXIS2  = {:>20}", rows),
+            String::from("PCOUNT  =                    0"),
+            String::from("GCOUNT  =                    1"),
+        ]
+        .into_iter()
+        .chain(cards.iter().map(|card| card.to_string()))
+        .chain([String::from("END")])
+        .flat_map(|card| format!("{:80}", card).into_bytes())
+        .collect::<Vec<u8>>();
+        bytes.resize(2880, b' ');
+        bytes
+    }
+
+    fn decode(header: &[u8], data: &[u8]) -> Result<BinaryTable, FitsError> {
+        let (_, header) = super::super::header::header(header).unwrap();
+        super::binary_table(
+            &header,
+            data,
+            Position::new(0, 1, None),
+            Position::new(2880, 1, None),
+        )
+    }
+
+    #[test]
+    fn tform() {
+        assert_eq!(
+            super::tform("1J"),
+            Ok(("", BinaryFormat::new(1, BinaryType::I32)))
+        );
+        assert_eq!(
+            super::tform("E"),
+            Ok(("", BinaryFormat::new(1, BinaryType::F32)))
+        );
+        assert_eq!(
+            super::tform("640E"),
+            Ok(("", BinaryFormat::new(640, BinaryType::F32)))
+        );
+        assert_eq!(
+            super::tform("0D"),
+            Ok(("", BinaryFormat::new(0, BinaryType::F64)))
+        );
+        assert_eq!(
+            super::tform("20A10"),
+            Ok(("10", BinaryFormat::new(20, BinaryType::Character)))
+        );
+        assert_eq!(
+            super::tform("1PE(640)"),
+            Ok((
+                "",
+                BinaryFormat::variable_length(BinaryType::F32, ArrayDescriptor::P(Some(640)))
+            ))
+        );
+        assert_eq!(
+            super::tform("QJ"),
+            Ok((
+                "",
+                BinaryFormat::variable_length(BinaryType::I32, ArrayDescriptor::Q(None))
+            ))
+        );
+        assert!(super::tform("3Z").is_err());
+        assert!(super::tform("1P").is_err());
+        assert!(super::tform("").is_err());
+    }
+
+    #[test]
+    fn tdim() {
+        assert_eq!(super::tdim("(2,3)"), Ok(("", vec![2, 3])));
+        assert_eq!(super::tdim("( 4 , 5 ,6)"), Ok(("", vec![4, 5, 6])));
+        assert!(super::tdim("(2,3").is_err());
+        assert!(super::tdim("2,3").is_err());
+    }
+
+    #[test]
+    fn binary_table() {
+        let header = table_header(
+            22,
+            2,
+            &[
+                "TFIELDS =                    8",
+                "TTYPE1  = 'FLAG    '",
+                "TFORM1  = '2L      '",
+                "TTYPE2  = 'MASK    '",
+                "TFORM2  = '10X     '",
+                "TTYPE3  = 'NAME    '",
+                "TFORM3  = '4A      '",
+                "TTYPE4  = 'COUNTS  '",
+                "TFORM4  = '1I      '",
+                "TNULL4  =                   -1",
+                "TTYPE5  = 'SCALED  '",
+                "TFORM5  = '1I      '",
+                "TSCAL5  =                  0.5",
+                "TNULL5  =                   -1",
+                "TTYPE6  = 'UNSIGNED'",
+                "TFORM6  = '1I      '",
+                "TZERO6  =                32768",
+                "TTYPE7  = 'IMAGE   '",
+                "TFORM7  = '4B      '",
+                "TDIM7   = '(2,2)   '",
+                "TTYPE8  = 'FLUX    '",
+                "TFORM8  = '1E      '",
+            ],
+        );
+        let mut data = Vec::new();
+        data.extend(b"TF");
+        data.extend([0b1010_0000, 0b1100_0000]);
+        data.extend(b"ab\0x");
+        data.extend((-1i16).to_be_bytes());
+        data.extend(5i16.to_be_bytes());
+        data.extend(i16::MIN.to_be_bytes());
+        data.extend([1, 2, 3, 4]);
+        data.extend(1.5f32.to_be_bytes());
+        data.extend(b"\0T");
+        data.extend([0, 0]);
+        data.extend(b"cd  ");
+        data.extend(7i16.to_be_bytes());
+        data.extend((-1i16).to_be_bytes());
+        data.extend(i16::MAX.to_be_bytes());
+        data.extend([5, 6, 7, 8]);
+        data.extend((-2.0f32).to_be_bytes());
+
+        let table = decode(&header, &data).unwrap();
+        assert_eq!(table.rows().len(), 2);
+        assert_eq!(table.columns()[6].dimensions(), vec![2, 2]);
+        assert_eq!(
+            table.row(0).unwrap(),
+            &[
+                Cell::Logical(vec![Some(true), Some(false)]),
+                Cell::Bit(vec![
+                    true, false, true, false, false, false, false, false, true, true
+                ]),
+                Cell::Character(String::from("ab")),
+                Cell::I16(vec![-1]),
+                Cell::Real(vec![2.5]),
+                Cell::U16(vec![0]),
+                Cell::U8(vec![1, 2, 3, 4]),
+                Cell::F32(vec![1.5]),
+            ]
+        );
+        assert_eq!(
+            table.cell(1, 0),
+            Some(&Cell::Logical(vec![None, Some(true)]))
+        );
+        assert_eq!(table.cell(1, 2).and_then(Cell::as_str), Some("cd"));
+        assert!(table.values::<f64>("SCALED").unwrap()[1][0].is_nan());
+        assert_eq!(
+            table.values::<u16>("unsigned"),
+            Some(vec![&[0][..], &[65535][..]])
+        );
+        assert_eq!(table.values_at::<i16>(3), Some(vec![&[-1][..], &[7][..]]));
+        assert_eq!(table.columns()[3].null(), Some(-1));
+    }
+
+    #[test]
+    fn binary_table_complex() {
+        let header = table_header(
+            24,
+            1,
+            &[
+                "TFIELDS =                    3",
+                "TFORM1  = '1C      '",
+                "TFORM2  = '1M      '",
+                "TFORM3  = '0D      '",
+            ],
+        );
+        let mut data = Vec::new();
+        data.extend(1.0f32.to_be_bytes());
+        data.extend((-1.0f32).to_be_bytes());
+        data.extend(2.0f64.to_be_bytes());
+        data.extend(0.5f64.to_be_bytes());
+        let table = decode(&header, &data).unwrap();
+        assert_eq!(
+            table.row(0).unwrap(),
+            &[
+                Cell::ComplexF32(vec![(1.0, -1.0)]),
+                Cell::ComplexF64(vec![(2.0, 0.5)]),
+                Cell::F64(vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn binary_table_bad_columns() {
+        let header = table_header(
+            4,
+            1,
+            &["TFIELDS =                    1", "TFORM1  = '2J      '"],
+        );
+        assert!(matches!(
+            decode(&header, &[0; 4]),
+            Err(FitsError::BadKeywordCard { position, .. }) if position.card == Some(9)
+        ));
+        let header = table_header(
+            8,
+            1,
+            &[
+                "TFIELDS =                    1",
+                "TFORM1  = '2J      '",
+                "TDIM1   = '(2,2)   '",
+            ],
+        );
+        assert!(matches!(
+            decode(&header, &[0; 8]),
+            Err(FitsError::BadKeywordCard { position, .. }) if position.card == Some(10)
+        ));
+        let header = table_header(
+            8,
+            1,
+            &["TFIELDS =                    1", "TFORM1  = '2Z      '"],
+        );
+        assert!(matches!(
+            decode(&header, &[0; 8]),
+            Err(FitsError::BadKeywordCard { position, .. }) if position.card == Some(9)
+        ));
+        let header = table_header(0, 1, &["TFIELDS =                65537"]);
+        assert!(matches!(
+            decode(&header, &[]),
+            Err(FitsError::BadKeywordCard { position, .. }) if position.card == Some(8)
+        ));
+    }
+
+    #[test]
+    fn binary_table_variable_length() {
+        let header = table_header(
+            24,
+            2,
+            &[
+                "TFIELDS =                    2",
+                "TTYPE1  = 'SPECTRUM'",
+                "TFORM1  = '1PE(3)  '",
+                "TTYPE2  = 'EVENTS  '",
+                "TFORM2  = '1QJ(1)  '",
+                "THEAP   =                   64",
+            ],
+        );
+        let mut data = Vec::new();
+        for (count, offset, events) in [(3u32, 0u32, 12u64), (0, 12, 16)] {
+            data.extend(count.to_be_bytes());
+            data.extend(offset.to_be_bytes());
+            data.extend(1u64.to_be_bytes());
+            data.extend(events.to_be_bytes());
+        }
+        data.resize(64, 0);
+        for value in [1.0f32, 2.0, 3.0] {
+            data.extend(value.to_be_bytes());
+        }
+        data.extend(7i32.to_be_bytes());
+        data.extend(8i32.to_be_bytes());
+
+        let table = decode(&header, &data).unwrap();
+        assert_eq!(
+            table.values::<f32>("SPECTRUM"),
+            Some(vec![&[1.0, 2.0, 3.0][..], &[][..]])
+        );
+        assert_eq!(
+            table.values::<i32>("EVENTS"),
+            Some(vec![&[7][..], &[8][..]])
+        );
+
+        data.truncate(data.len() - 1);
+        assert_eq!(
+            decode(&header, &data),
+            Err(FitsError::BadTableField {
+                position: Position::new(2880 + 24 + 8, 1, None),
+                row: 2,
+                column: 2,
+                reason: String::from("array of 4 bytes at offset 16 exceeds the heap of 19 bytes")
+            })
+        );
+    }
+}
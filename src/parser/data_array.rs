@@ -1,10 +1,10 @@
 use nom::{
+    bytes::complete::take,
     error::{ParseError, VerboseError},
-    multi::count,
-    number, IResult,
+    IResult,
 };
 
-use crate::types::{data_array::DataArray, header::FitsHeader};
+use crate::types::{data_array::DataArray, data_view::decode, header::FitsHeader};
 
 pub fn data_array<'a>(
     i: &'a [u8],
@@ -16,85 +16,38 @@ pub fn data_array<'a>(
             nom::error::ErrorKind::Tag,
         )));
     }
-    let number_of_elements = header.dimensions().iter().product::<u32>() as usize;
-    match header.bitpix().unwrap() {
-        8 => {
-            let (i, data_array) = count(number::complete::u8, number_of_elements)(i)?;
-            Ok((
+    let bitpix = header.bitpix().unwrap();
+    let length = header
+        .dimensions()
+        .iter()
+        .try_fold(bitpix.unsigned_abs() as usize / 8, |product, &d| {
+            product.checked_mul(d as usize)
+        })
+        .ok_or_else(|| {
+            nom::Err::Error(ParseError::from_error_kind(
                 i,
-                DataArray::from_u8(
-                    data_array,
-                    header.dimensions().clone(), // todo: should this be a &header.dimensions?
-                    header.bzero(),
-                    header.bscale(),
-                ),
+                nom::error::ErrorKind::TooLarge,
             ))
-        }
-        16 => {
-            let (i, data_array) = count(number::complete::be_i16, number_of_elements)(i)?;
-            Ok((
+        })?;
+    let (i, bytes) = take(length)(i)?;
+    // todo: should this be a &header.dimensions?
+    let dimensions = header.dimensions().clone();
+    let (bzero, bscale) = (header.bzero(), header.bscale());
+    let data_array = match bitpix {
+        8 => DataArray::from_u8(bytes.to_vec(), dimensions, bzero, bscale),
+        16 => DataArray::from_i16(decode(bytes), dimensions, bzero, bscale),
+        32 => DataArray::from_i32(decode(bytes), dimensions, bzero, bscale),
+        64 => DataArray::from_i64(decode(bytes), dimensions, bzero, bscale),
+        -32 => DataArray::from_f32(decode(bytes), dimensions, bzero, bscale),
+        -64 => DataArray::from_f64(decode(bytes), dimensions, bzero, bscale),
+        _ => {
+            return Err(nom::Err::Error(ParseError::from_error_kind(
                 i,
-                DataArray::from_i16(
-                    data_array,
-                    header.dimensions().clone(),
-                    header.bzero(),
-                    header.bscale(),
-                ),
-            ))
-        }
-        32 => {
-            let (i, data_array) = count(number::complete::be_i32, number_of_elements)(i)?;
-            Ok((
-                i,
-                DataArray::from_i32(
-                    data_array,
-                    header.dimensions().clone(),
-                    header.bzero(),
-                    header.bscale(),
-                ),
-            ))
+                nom::error::ErrorKind::Tag,
+            )))
         }
-        64 => {
-            let (i, data_array) = count(number::complete::be_i64, number_of_elements)(i)?;
-            Ok((
-                i,
-                DataArray::from_i64(
-                    data_array,
-                    header.dimensions().clone(),
-                    header.bzero(),
-                    header.bscale(),
-                ),
-            ))
-        }
-        -32 => {
-            let (i, data_array) = count(number::complete::be_f32, number_of_elements)(i)?;
-            Ok((
-                i,
-                DataArray::from_f32(
-                    data_array,
-                    header.dimensions().clone(),
-                    header.bzero(),
-                    header.bscale(),
-                ),
-            ))
-        }
-        -64 => {
-            let (i, data_array) = count(number::complete::be_f64, number_of_elements)(i)?;
-            Ok((
-                i,
-                DataArray::from_f64(
-                    data_array,
-                    header.dimensions().clone(),
-                    header.bzero(),
-                    header.bscale(),
-                ),
-            ))
-        }
-        _ => Err(nom::Err::Error(ParseError::from_error_kind(
-            i,
-            nom::error::ErrorKind::Tag,
-        ))),
-    }
+    };
+    Ok((i, data_array))
 }
 
 #[cfg(test)]
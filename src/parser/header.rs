@@ -1,11 +1,19 @@
+use std::borrow::Cow;
+
 use nom::error::VerboseError;
 use nom::IResult;
 
-use crate::types::{header::Header, keyword::Keyword, keyword_record::KeywordRecord};
+use crate::types::{
+    header::{FitsHeader, Header},
+    keyword::Keyword,
+    keyword_record::KeywordRecord,
+    value::Value,
+    FitsError, Position, CARD_SIZE,
+};
 
 use super::keyword_record::{self};
 
-pub fn header(i: &[u8]) -> IResult<&[u8], Header, VerboseError<&[u8]>> {
+pub fn header(i: &[u8]) -> IResult<&[u8], Header<'_>, VerboseError<&[u8]>> {
     let mut input = i;
     let mut last_block = false;
     let mut acc: Vec<KeywordRecord> = Vec::new();
@@ -21,14 +29,256 @@ pub fn header(i: &[u8]) -> IResult<&[u8], Header, VerboseError<&[u8]>> {
             }
         }
     }
-    Ok((input, Header::from(acc)))
+    Ok((input, Header::from(continued_strings(i, acc))))
+}
+
+/// Joins a string value ending in `&` with the string values of the CONTINUE records that follow
+/// it. The joined record keeps the card images and the comments of all its records, so it is
+/// written back unchanged. `records` are the cards at the start of `i`.
+/// FITS standard section 4.2.1.2
+fn continued_strings<'a>(i: &'a [u8], records: Vec<KeywordRecord<'a>>) -> Vec<KeywordRecord<'a>> {
+    let is_continue = |record: &KeywordRecord| {
+        *record.keyword() == Keyword::Continue && string_part(record).is_some()
+    };
+    let mut acc = Vec::with_capacity(records.len());
+    let mut records = records.into_iter().enumerate().peekable();
+    while let Some((n, record)) = records.next() {
+        match string_part(&record) {
+            Some((first, true)) if records.peek().is_some_and(|(_, next)| is_continue(next)) => {
+                let mut parts = vec![first];
+                let mut comments = vec![*record.comment()];
+                let mut last = n;
+                let mut continued = true;
+                while let Some((m, next)) =
+                    records.next_if(|(_, next)| continued && is_continue(next))
+                {
+                    let (part, more) = string_part(&next).unwrap();
+                    parts.push(part);
+                    comments.push(*next.comment());
+                    last = m;
+                    continued = more;
+                }
+                acc.push(
+                    KeywordRecord::new(
+                        record.keyword().clone(),
+                        Value::ContinuedString(parts),
+                        None,
+                    )
+                    .with_part_comments(comments)
+                    .with_card(&i[n * CARD_SIZE..(last + 1) * CARD_SIZE]),
+                );
+            }
+            _ => acc.push(record),
+        }
+    }
+    acc
+}
+
+/// A string value without trailing blanks and without the `&` that marks it as continued, and
+/// whether it was marked. The part is borrowed from the card image unless it contains escaped
+/// quotes.
+fn string_part<'a>(record: &KeywordRecord<'a>) -> Option<(Cow<'a, str>, bool)> {
+    let Value::CharacterString(s) = record.value() else {
+        return None;
+    };
+    let s = s.trim_end();
+    let (s, continued) = match s.strip_suffix('&') {
+        Some(s) => (s, true),
+        None => (s, false),
+    };
+    let borrowed = record.card().and_then(|card| {
+        let start = card.iter().skip(8).position(|&c| c == b'\'')? + 9;
+        std::str::from_utf8(card.get(start..start + s.len())?).ok()
+    });
+    let part = match borrowed {
+        Some(borrowed) if borrowed == s => Cow::Borrowed(borrowed),
+        _ => Cow::Owned(s.to_string()),
+    };
+    Some((part, continued))
+}
+
+/// Checks the keyword records of a header that starts at `position`: all cards must be
+/// printable ASCII and the mandatory keywords must be present with valid values.
+/// FITS standard sections 4.1.1 and 4.4.1
+pub fn validate(header: &Header, position: Position) -> Result<(), FitsError> {
+    let records = header.keyword_records();
+    if let Some((n, offset)) = records.iter().enumerate().find_map(|(n, record)| {
+        record
+            .card()
+            .and_then(|card| card.iter().position(|&c| !super::is_allowed_ascii(c)))
+            .map(|offset| (n, offset))
+    }) {
+        return Err(FitsError::BadKeywordCard {
+            position: position.card(card_number(records, n) + offset / CARD_SIZE),
+            reason: String::from("card contains characters outside of 0x20 to 0x7E"),
+        });
+    }
+
+    let is_primary = position.hdu == 0;
+    let first = match is_primary {
+        true => Keyword::Simple,
+        false => Keyword::Xtension,
+    };
+    match records.first() {
+        Some(record) if *record.keyword() == first => match record.value() {
+            Value::Logical(_) if is_primary => {}
+            Value::CharacterString(_) if !is_primary => {}
+            value => {
+                return Err(FitsError::BadKeywordCard {
+                    position: position.card(1),
+                    reason: format!("{} has an invalid value {}", first.name(), value),
+                })
+            }
+        },
+        _ => {
+            return Err(FitsError::MissingKeyword {
+                position: position.card(1),
+                keyword: first,
+            })
+        }
+    }
+
+    let (card, bitpix) = mandatory_integer(records, Keyword::BitPix, 2, position)?;
+    if ![8, 16, 32, 64, -32, -64].contains(&bitpix) {
+        return Err(FitsError::UnsupportedBitpix {
+            position: position.card(card),
+            bitpix,
+        });
+    }
+    let (naxis_card, naxis) = mandatory_integer(records, Keyword::NAxis, 3, position)?;
+    if !(0..=999).contains(&naxis) {
+        return Err(FitsError::BadKeywordCard {
+            position: position.card(naxis_card),
+            reason: format!("NAXIS must be between 0 and 999, found {}", naxis),
+        });
+    }
+    let mut mandatory = (1..=naxis)
+        .map(|n| Keyword::NAxisn(n as u16))
+        .collect::<Vec<Keyword>>();
+    if !is_primary {
+        mandatory.extend([Keyword::PCount, Keyword::GCount]);
+    }
+    for (n, keyword) in mandatory.into_iter().enumerate() {
+        let (card, value) = mandatory_integer(records, keyword.clone(), n + 4, position)?;
+        if value < 0 {
+            return Err(FitsError::BadKeywordCard {
+                position: position.card(card),
+                reason: format!("{} must not be negative, found {}", keyword.name(), value),
+            });
+        }
+        if value > u32::MAX as i64 {
+            return Err(FitsError::BadKeywordCard {
+                position: position.card(card),
+                reason: format!(
+                    "{} must be at most {}, found {}",
+                    keyword.name(),
+                    u32::MAX,
+                    value
+                ),
+            });
+        }
+    }
+    header.data_size().map_err(|_| FitsError::BadKeywordCard {
+        position: position.card(naxis_card),
+        reason: String::from("the size of the data unit overflows"),
+    })?;
+    Ok(())
+}
+
+/// Finds a mandatory integer keyword, returning its card number and value.
+/// `expected_card` is reported if the keyword is missing.
+pub fn mandatory_integer(
+    records: &[KeywordRecord],
+    keyword: Keyword,
+    expected_card: usize,
+    position: Position,
+) -> Result<(usize, i64), FitsError> {
+    match records
+        .iter()
+        .position(|record| *record.keyword() == keyword)
+    {
+        Some(n) => match records[n].value() {
+            Value::Integer(value) => Ok((card_number(records, n), *value)),
+            value => Err(FitsError::BadKeywordCard {
+                position: position.card(card_number(records, n)),
+                reason: format!("{} must be an integer, found {}", keyword.name(), value),
+            }),
+        },
+        None => Err(FitsError::MissingKeyword {
+            position: position.card(expected_card),
+            keyword,
+        }),
+    }
+}
+
+/// Reads TFIELDS, the number of fields in a row of a table, which is between 0 and 999.
+/// FITS standard sections 7.2.1 and 7.3.1
+pub fn tfields(records: &[KeywordRecord], position: Position) -> Result<u16, FitsError> {
+    let (card, tfields) = mandatory_integer(records, Keyword::Tfields, 8, position)?;
+    match u16::try_from(tfields) {
+        Ok(tfields) if tfields <= 999 => Ok(tfields),
+        _ => Err(FitsError::BadKeywordCard {
+            position: position.card(card),
+            reason: format!("TFIELDS must be between 0 and 999, found {}", tfields),
+        }),
+    }
+}
+
+/// Returns the card number and value of a keyword.
+pub fn find<'a>(records: &'a [KeywordRecord], keyword: Keyword) -> Option<(usize, &'a Value<'a>)> {
+    records
+        .iter()
+        .position(|record| *record.keyword() == keyword)
+        .map(|n| (card_number(records, n), records[n].value()))
+}
+
+/// Card number of record `n`, counting from 1. The record of a continued string may span
+/// several cards.
+pub fn card_number(records: &[KeywordRecord], n: usize) -> usize {
+    records[..n]
+        .iter()
+        .map(|record| record.card().map_or(1, |card| card.len() / CARD_SIZE))
+        .sum::<usize>()
+        + 1
+}
+
+/// A string value without trailing blanks, `None` if it is missing or blank.
+pub fn string(records: &[KeywordRecord], keyword: Keyword) -> Option<String> {
+    match find(records, keyword) {
+        Some((_, Value::CharacterString(s))) if !s.trim_end().is_empty() => {
+            Some(s.trim_end().to_string())
+        }
+        _ => None,
+    }
+}
+
+/// A real value, integers are converted.
+pub fn real(records: &[KeywordRecord], keyword: Keyword) -> Option<f64> {
+    match find(records, keyword) {
+        Some((_, Value::Real(r))) => Some(*r),
+        Some((_, Value::Integer(i))) => Some(*i as f64),
+        _ => None,
+    }
+}
+
+/// Card number of the END keyword, or of the last card if END is missing. Missing indexed
+/// keywords are reported there.
+pub fn end_card(records: &[KeywordRecord]) -> usize {
+    records
+        .iter()
+        .position(|record| *record.keyword() == Keyword::End)
+        .map_or(card_number(records, records.len()) - 1, |n| {
+            card_number(records, n)
+        })
 }
 
 #[cfg(test)]
 mod tests {
+    use std::borrow::Cow;
+
     use proptest::prelude::*;
 
-    use crate::types::header::FitsHeader;
+    use crate::types::{header::FitsHeader, keyword::Keyword, value::Value, FitsError, Position};
 
     fn primary_header_string() -> String {
         let mut input = String::from(
@@ -608,6 +858,182 @@ mod tests {
         assert_eq!(res.1.bscale(), Some(1.0));
     }
 
+    #[test]
+    fn validate() {
+        let s = primary_header_string();
+        let (_, header) = super::header(s.as_bytes()).unwrap();
+        assert_eq!(super::validate(&header, Position::new(0, 0, None)), Ok(()));
+        assert_eq!(
+            super::validate(&header, Position::new(0, 1, None)),
+            Err(FitsError::MissingKeyword {
+                position: Position::new(0, 1, Some(1)),
+                keyword: Keyword::Xtension
+            })
+        );
+
+        let s = extension_header_string();
+        let (_, header) = super::header(s.as_bytes()).unwrap();
+        assert_eq!(
+            super::validate(&header, Position::new(2880, 1, None)),
+            Ok(())
+        );
+
+        let s = s.replacen("GCOUNT  =", "GCOUNTX =", 1);
+        let (_, header) = super::header(s.as_bytes()).unwrap();
+        assert_eq!(
+            super::validate(&header, Position::new(2880, 1, None)),
+            Err(FitsError::MissingKeyword {
+                position: Position::new(2880 + 6 * 80, 1, Some(7)),
+                keyword: Keyword::GCount
+            })
+        );
+    }
+
+    #[test]
+    fn validate_bad_characters() {
+        let mut s = primary_header_string().into_bytes();
+        s[4 * 80 + 20] = 0xe9;
+        let (_, header) = super::header(&s).unwrap();
+        assert_eq!(
+            super::validate(&header, Position::new(0, 0, None)),
+            Err(FitsError::BadKeywordCard {
+                position: Position::new(320, 0, Some(5)),
+                reason: String::from("card contains characters outside of 0x20 to 0x7E")
+            })
+        );
+    }
+
+    #[test]
+    fn continued_strings() {
+        let mut s = [
+            "SIMPLE  =                    T",
+            "BITPIX  =                    8",
+            "NAXIS   =                    0",
+            "LONGSTR = 'first &'           / first comment",
+            "CONTINUE  'it''s &'",
+            "CONTINUE  'last'              / last comment",
+            "CONTINUE  'orphan'",
+            "END",
+        ]
+        .iter()
+        .flat_map(|card| format!("{:80}", card).into_bytes())
+        .collect::<Vec<u8>>();
+        s.resize(2880, b' ');
+        let (_, header) = super::header(&s).unwrap();
+        let records = header.keyword_records();
+        assert_eq!(*records[5].keyword(), Keyword::End);
+        assert_eq!(
+            records[3].value(),
+            &Value::ContinuedString(vec!["first ".into(), "it's ".into(), "last".into()])
+        );
+        assert!(matches!(
+            records[3].value(),
+            Value::ContinuedString(parts) if matches!(parts[0], Cow::Borrowed(_))
+        ));
+        assert_eq!(records[3].comment(), &Some(" first comment"));
+        assert_eq!(
+            records[3].part_comments(),
+            [Some(" first comment"), None, Some(" last comment")]
+        );
+        assert_eq!(records[3].card(), Some(&s[240..480]));
+        assert_eq!(*records[4].keyword(), Keyword::Continue);
+        assert_eq!(header.get_str("LONGSTR").unwrap(), "first it's last");
+        assert_eq!(super::card_number(records, 4), 7);
+        assert_eq!(super::end_card(records), 8);
+
+        s[5 * 80 + 20] = 0xe9;
+        let (_, header) = super::header(&s).unwrap();
+        assert_eq!(
+            super::validate(&header, Position::new(0, 0, None)),
+            Err(FitsError::BadKeywordCard {
+                position: Position::new(400, 0, Some(6)),
+                reason: String::from("card contains characters outside of 0x20 to 0x7E")
+            })
+        );
+    }
+
+    #[test]
+    fn continued_string_comments() {
+        let cards = [
+            "LONGSTR = 'first &'           / first comment",
+            "CONTINUE  'second &'          / second comment",
+            "CONTINUE  'last'              / last comment",
+        ];
+        let mut s = [
+            "SIMPLE  =                    T",
+            "BITPIX  =                    8",
+        ]
+        .iter()
+        .chain(&["NAXIS   =                    0"])
+        .chain(&cards)
+        .chain(&["END"])
+        .flat_map(|card| format!("{:80}", card).into_bytes())
+        .collect::<Vec<u8>>();
+        s.resize(2880, b' ');
+        let (_, header) = super::header(&s).unwrap();
+        let record = &header.keyword_records()[3];
+        assert_eq!(
+            record.part_comments(),
+            [
+                Some(" first comment"),
+                Some(" second comment"),
+                Some(" last comment")
+            ]
+        );
+        assert_eq!(record.comment(), &Some(" first comment"));
+    }
+
+    #[test]
+    fn validate_naxis() {
+        let s = primary_header_string().replacen(
+            "NAXIS   =                    0",
+            "NAXIS   =                 1000",
+            1,
+        );
+        let (_, header) = super::header(s.as_bytes()).unwrap();
+        assert!(matches!(
+            super::validate(&header, Position::new(0, 0, None)),
+            Err(FitsError::BadKeywordCard { position, .. }) if position.card == Some(3)
+        ));
+    }
+
+    #[test]
+    fn validate_huge_axes() {
+        let mut s = [
+            "SIMPLE  =                    T",
+            "BITPIX  =                  -64",
+            "NAXIS   =                    3",
+            "NAXIS1  =           4294967295",
+            "NAXIS2  =           4294967295",
+            "NAXIS3  =           4294967295",
+            "END",
+        ]
+        .iter()
+        .flat_map(|card| format!("{:80}", card).into_bytes())
+        .collect::<Vec<u8>>();
+        s.resize(2880, b' ');
+        let (_, header) = super::header(&s).unwrap();
+        assert_eq!(
+            super::validate(&header, Position::new(0, 0, None)),
+            Err(FitsError::BadKeywordCard {
+                position: Position::new(160, 0, Some(3)),
+                reason: String::from("the size of the data unit overflows")
+            })
+        );
+        assert!(crate::parser::hdu(&s).is_err());
+
+        s[3 * 80..4 * 80]
+            .copy_from_slice(format!("{:80}", "NAXIS1  =        1099511627776").as_bytes());
+        let (_, header) = super::header(&s).unwrap();
+        assert_eq!(
+            super::validate(&header, Position::new(0, 0, None)),
+            Err(FitsError::BadKeywordCard {
+                position: Position::new(240, 0, Some(4)),
+                reason: String::from("NAXIS1 must be at most 4294967295, found 1099511627776")
+            })
+        );
+    }
+
     proptest! {
         #[test]
         fn doesnt_crash(s in "\\PC*") {
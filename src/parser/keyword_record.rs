@@ -1,10 +1,10 @@
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take, take_while},
+    bytes::complete::{tag, take, take_till1, take_while},
     character::complete::space0,
-    combinator::{all_consuming, complete, map, map_parser, opt, success},
+    combinator::{all_consuming, complete, consumed, map, map_parser, opt, peek, rest, success},
     error::{context, VerboseError},
-    sequence::{pair, preceded},
+    sequence::{pair, preceded, terminated},
     IResult,
 };
 
@@ -17,38 +17,70 @@ use crate::parser::value::{
 
 use crate::types::keyword::Keyword;
 use crate::types::keyword_record::KeywordRecord;
+use crate::types::value::Value;
 
 fn keyword(i: &[u8]) -> IResult<&[u8], Keyword, VerboseError<&[u8]>> {
     context("keyword", map(complete(take(8u8)), Keyword::from))(i)
 }
 
-pub fn keyword_record(i: &[u8]) -> IResult<&[u8], KeywordRecord, VerboseError<&[u8]>> {
-    map_parser(
-        take(80u8),
+pub fn keyword_record(i: &[u8]) -> IResult<&[u8], KeywordRecord<'_>, VerboseError<&[u8]>> {
+    map(
+        consumed(map_parser(
+            take(80u8),
+            alt((
+                pair(
+                    hierarch,
+                    alt((value, map(rest, |s| (unknown_value(s), None)))),
+                ),
+                pair(
+                    keyword,
+                    alt((value, all_consuming(pair(unknown, success(None))))),
+                ),
+            )),
+        )),
+        |(card, (key, (value, comment)))| {
+            let record = KeywordRecord::new(key, value, comment).with_card(card);
+            trace!("keyword_record: {}", record);
+            record
+        },
+    )(i)
+}
+
+/// The name of a keyword of the ESO HIERARCH convention, up to the value indicator.
+fn hierarch(i: &[u8]) -> IResult<&[u8], Keyword, VerboseError<&[u8]>> {
+    context(
+        "hierarch",
         map(
-            pair(
-                keyword,
-                alt((
-                    all_consuming(pair(character_string, opt(comment))),
-                    all_consuming(pair(complex_float, opt(comment))),
-                    all_consuming(pair(complex_integer, opt(comment))),
-                    all_consuming(pair(continued_string, opt(comment))),
-                    all_consuming(pair(date, opt(comment))),
-                    all_consuming(pair(integer, opt(comment))),
-                    all_consuming(pair(logical, opt(comment))),
-                    all_consuming(pair(real, opt(comment))),
-                    all_consuming(pair(unknown, success(None))),
-                )),
+            preceded(
+                tag("HIERARCH "),
+                terminated(take_till1(|c| c == b'='), peek(tag("="))),
             ),
-            |(key, (value, comment))| {
-                let record = KeywordRecord::new(key, value, comment);
-                trace!("keyword_record: {}", record);
-                record
-            },
+            |name| Keyword::hierarch(&String::from_utf8_lossy(name)),
         ),
     )(i)
 }
 
+/// A value with its optional comment.
+type ValueAndComment<'a> = (Value<'a>, Option<&'a str>);
+
+fn value(i: &[u8]) -> IResult<&[u8], ValueAndComment<'_>, VerboseError<&[u8]>> {
+    alt((
+        all_consuming(pair(character_string, opt(comment))),
+        all_consuming(pair(complex_float, opt(comment))),
+        all_consuming(pair(complex_integer, opt(comment))),
+        all_consuming(pair(continued_string, opt(comment))),
+        all_consuming(pair(date, opt(comment))),
+        all_consuming(pair(integer, opt(comment))),
+        all_consuming(pair(logical, opt(comment))),
+        all_consuming(pair(real, opt(comment))),
+    ))(i)
+}
+
+/// The rest of a HIERARCH card that holds no valid value, from the value indicator on.
+fn unknown_value(i: &[u8]) -> Value<'_> {
+    Value::Unknown(String::from_utf8_lossy(i).trim_end().to_string())
+}
+
 fn comment(i: &[u8]) -> IResult<&[u8], &str, VerboseError<&[u8]>> {
     context(
         "comment",
@@ -65,7 +97,6 @@ fn comment(i: &[u8]) -> IResult<&[u8], &str, VerboseError<&[u8]>> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::{keyword::Keyword, value::Value};
 
     #[test]
     fn keyword() {
@@ -150,6 +181,57 @@ mod tests {
         );
     }
 
+    #[test]
+    fn hierarch() {
+        let cards = [
+            "HIERARCH ESO DET CHIP TEMP = 120.5 / Chip temperature",
+            "HIERARCH ESO OBS NAME = 'M31 field'",
+            "HIERARCH ESO DET  WIN1 BINX= 1",
+            "HIERARCH ESO TPL ID = bad value",
+            "HIERARCH text without a value",
+        ]
+        .map(|card| format!("{:80}", card));
+        let record = |n: usize| super::keyword_record(cards[n].as_bytes()).unwrap().1;
+        let name = |name: &str| Keyword::Hierarch(String::from(name));
+        assert_eq!(
+            record(0),
+            KeywordRecord::new(
+                name("ESO DET CHIP TEMP"),
+                Value::Real(120.5),
+                Some(" Chip temperature")
+            )
+        );
+        assert_eq!(
+            record(1),
+            KeywordRecord::new(
+                name("ESO OBS NAME"),
+                Value::CharacterString(String::from("M31 field")),
+                None
+            )
+        );
+        assert_eq!(
+            record(2),
+            KeywordRecord::new(name("ESO DET WIN1 BINX"), Value::Integer(1), None)
+        );
+        assert_eq!(
+            record(3),
+            KeywordRecord::new(
+                name("ESO TPL ID"),
+                Value::Unknown(String::from("= bad value")),
+                None
+            )
+        );
+        assert_eq!(*record(4).keyword(), Keyword::Unknown(*b"HIERARCH"));
+    }
+
+    #[test]
+    fn keyword_record_keeps_card() {
+        let card =
+            b"DATE    = '31/10/97'           / Date file was written (dd/mm/yy) 19yy          ";
+        let (_, record) = super::keyword_record(card).unwrap();
+        assert_eq!(record.card(), Some(&card[..]));
+    }
+
     #[test]
     fn keyword_record_display() {
         let record = KeywordRecord::new(
@@ -0,0 +1,173 @@
+use crate::types::{
+    data_array::DataArray,
+    data_view::decode,
+    header::{FitsHeader, Header},
+    keyword::Keyword,
+    random_groups::{Group, Parameter, RandomGroups},
+    FitsError, Position,
+};
+
+use super::header::{real, string};
+
+/// Decodes the data of a random groups primary HDU. Every group holds PCOUNT parameters
+/// followed by an array of NAXIS2 × … × NAXISm elements, all of type BITPIX.
+/// Fails if `data`, which starts at `data_position`, is shorter than the groups described by
+/// the header. FITS standard section 6
+pub fn random_groups(
+    header: &Header,
+    data: &[u8],
+    data_position: Position,
+) -> Result<RandomGroups, FitsError> {
+    let data_size = header.data_size()?;
+    if data.len() < data_size {
+        return Err(FitsError::BadDataLength {
+            position: data_position,
+            expected: data_size,
+            actual: data.len(),
+        });
+    }
+    let records = header.keyword_records();
+    let number_of_parameters = header.pcount().unwrap_or(0) as usize;
+    let parameters = (1..=number_of_parameters as u16)
+        .map(|n| {
+            Parameter::new(
+                string(records, Keyword::PTypen(n)),
+                real(records, Keyword::PScaln(n)).unwrap_or(1.0),
+                real(records, Keyword::PZeron(n)).unwrap_or(0.0),
+            )
+        })
+        .collect::<Vec<_>>();
+    let bitpix = header.bitpix().unwrap_or(8);
+    let size = bitpix.unsigned_abs() as usize / 8;
+    let dimensions = header
+        .dimensions()
+        .iter()
+        .skip(1)
+        .copied()
+        .collect::<Vec<_>>();
+    let number_of_elements = dimensions.iter().map(|&d| d as usize).product::<usize>();
+    let parameter_length = number_of_parameters * size;
+    let group_length = parameter_length + number_of_elements * size;
+    let groups = data
+        .chunks_exact(group_length.max(1))
+        .take(header.gcount().unwrap_or(1) as usize)
+        .map(|group| {
+            let values = numbers(bitpix, &group[..parameter_length])
+                .into_iter()
+                .zip(&parameters)
+                .map(|(value, parameter)| parameter.zero() + parameter.scale() * value)
+                .collect();
+            Group::new(
+                values,
+                data_array(
+                    bitpix,
+                    &group[parameter_length..],
+                    dimensions.clone(),
+                    header.bzero(),
+                    header.bscale(),
+                    header.blank(),
+                ),
+            )
+        })
+        .collect();
+    Ok(RandomGroups::new(parameters, groups))
+}
+
+/// Reads big-endian numbers of type `bitpix` as f64.
+fn numbers(bitpix: i8, bytes: &[u8]) -> Vec<f64> {
+    macro_rules! read {
+        ($t:ty) => {
+            bytes
+                .chunks_exact(std::mem::size_of::<$t>())
+                .map(|b| <$t>::from_be_bytes(b.try_into().unwrap()) as f64)
+                .collect()
+        };
+    }
+    match bitpix {
+        8 => read!(u8),
+        16 => read!(i16),
+        32 => read!(i32),
+        64 => read!(i64),
+        -32 => read!(f32),
+        _ => read!(f64),
+    }
+}
+
+fn data_array(
+    bitpix: i8,
+    bytes: &[u8],
+    dimensions: Vec<u32>,
+    bzero: Option<f64>,
+    bscale: Option<f64>,
+    blank: Option<i64>,
+) -> DataArray {
+    let data_array = match bitpix {
+        8 => DataArray::from_u8(bytes.to_vec(), dimensions, bzero, bscale),
+        16 => DataArray::from_i16(decode(bytes), dimensions, bzero, bscale),
+        32 => DataArray::from_i32(decode(bytes), dimensions, bzero, bscale),
+        64 => DataArray::from_i64(decode(bytes), dimensions, bzero, bscale),
+        -32 => DataArray::from_f32(decode(bytes), dimensions, bzero, bscale),
+        _ => DataArray::from_f64(decode(bytes), dimensions, bzero, bscale),
+    };
+    data_array.with_blank(blank).into_unsigned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn random_groups() {
+        let mut header = [
+            "SIMPLE  =                    T",
+            "BITPIX  =                   16",
+            "NAXIS   =                    3",
+            "NAXIS1  =                    0",
+            "NAXIS2  =                    2",
+            "NAXIS3  =                    1",
+            "GROUPS  =                    T",
+            "PCOUNT  =                    3",
+            "GCOUNT  =                    2",
+            "PTYPE1  = 'UU      '",
+            "PSCAL1  =                  0.5",
+            "PTYPE2  = 'DATE    '",
+            "PZERO2  =                 1000",
+            "PTYPE3  = 'DATE    '",
+            "BZERO   =                 10.0",
+            "END",
+        ]
+        .iter()
+        .flat_map(|card| format!("{:80}", card).into_bytes())
+        .collect::<Vec<u8>>();
+        header.resize(2880, b' ');
+        let (_, header) = super::super::header::header(&header).unwrap();
+        assert!(header.groups());
+        let data = [4i16, 1, 2, 7, 8, -4, 3, 4, 9, 10]
+            .iter()
+            .flat_map(|v| v.to_be_bytes())
+            .collect::<Vec<u8>>();
+        assert_eq!(Ok(data.len()), header.data_size());
+
+        let position = Position::new(2880, 0, None);
+        assert_eq!(
+            super::random_groups(&header, &data[..18], position),
+            Err(FitsError::BadDataLength {
+                position,
+                expected: 20,
+                actual: 18
+            })
+        );
+
+        let groups = super::random_groups(&header, &data, position).unwrap();
+        assert_eq!(groups.parameters().len(), 3);
+        assert_eq!(groups.parameters()[0].name(), Some("UU"));
+        assert_eq!(groups.groups().len(), 2);
+        assert_eq!(groups.group(0).unwrap().parameters(), &[2.0, 1001.0, 2.0]);
+        assert_eq!(groups.parameter(1, "UU"), Some(-2.0));
+        assert_eq!(groups.parameter(1, "DATE"), Some(1007.0));
+        assert_eq!(
+            groups.group(1).unwrap().data_array(),
+            &DataArray::from_i16(vec![9, 10], vec![2, 1], Some(10.0), None)
+        );
+    }
+}
@@ -0,0 +1,343 @@
+use nom::{
+    branch::alt,
+    character::complete::{char, digit1, one_of},
+    combinator::{all_consuming, map, map_res},
+    error::{context, VerboseError},
+    sequence::{pair, preceded, separated_pair},
+    IResult,
+};
+
+use crate::types::{
+    header::{FitsHeader, Header},
+    keyword::Keyword,
+    table::{Column, Field, Table, TableFormat},
+    value::Value,
+    FitsError, Position,
+};
+
+use super::header::{end_card, find, mandatory_integer, real, string, tfields};
+
+/// Decodes the data of an ASCII table extension. `position` is the start of the header and
+/// `data_position` the start of the data unit. FITS standard section 7.2
+pub fn table(
+    header: &Header,
+    data: &[u8],
+    position: Position,
+    data_position: Position,
+) -> Result<Table, FitsError> {
+    let columns = columns(header, position)?;
+    let (row_length, number_of_rows) = match header.dimensions().as_slice() {
+        [row_length, number_of_rows] => (*row_length as usize, *number_of_rows as usize),
+        _ => {
+            return Err(FitsError::BadKeywordCard {
+                position: position.card(3),
+                reason: String::from("an ASCII table must have NAXIS = 2"),
+            })
+        }
+    };
+    let mut rows = Vec::with_capacity(number_of_rows);
+    for (r, row) in data
+        .chunks_exact(row_length.max(1))
+        .take(number_of_rows)
+        .enumerate()
+    {
+        let mut fields = Vec::with_capacity(columns.len());
+        for (c, column) in columns.iter().enumerate() {
+            let bytes = &row[column.start()..column.start() + column.format().width()];
+            fields.push(
+                field(column, bytes).map_err(|reason| FitsError::BadTableField {
+                    position: Position::new(
+                        data_position.offset + r * row_length + column.start(),
+                        data_position.hdu,
+                        None,
+                    ),
+                    row: r + 1,
+                    column: c + 1,
+                    reason,
+                })?,
+            );
+        }
+        rows.push(fields);
+    }
+    Ok(Table::new(columns, rows))
+}
+
+/// Reads the field descriptions from TFIELDS, TBCOLn, TFORMn, TTYPEn, TUNITn, TNULLn, TSCALn
+/// and TZEROn. A missing TBCOLn or TFORMn is reported at the END card.
+fn columns(header: &Header, position: Position) -> Result<Vec<Column>, FitsError> {
+    let records = header.keyword_records();
+    let row_length = header.dimensions().first().copied().unwrap_or(0) as usize;
+    let end = end_card(records);
+    let mut columns = Vec::new();
+    for n in 1..=tfields(records, position)? {
+        let (card, tbcol) = mandatory_integer(records, Keyword::TBcoln(n), end, position)?;
+        let format = match find(records, Keyword::TFormn(n)) {
+            Some((card, Value::CharacterString(s))) => match tform(s.trim()) {
+                Ok((_, format)) => format,
+                Err(_) => {
+                    return Err(FitsError::BadKeywordCard {
+                        position: position.card(card),
+                        reason: format!("TFORM{} has an invalid format '{}'", n, s.trim()),
+                    })
+                }
+            },
+            Some((card, value)) => {
+                return Err(FitsError::BadKeywordCard {
+                    position: position.card(card),
+                    reason: format!("TFORM{} must be a string, found {}", n, value),
+                })
+            }
+            None => {
+                return Err(FitsError::MissingKeyword {
+                    position: position.card(end),
+                    keyword: Keyword::TFormn(n),
+                })
+            }
+        };
+        if tbcol < 1 || tbcol as usize - 1 + format.width() > row_length {
+            return Err(FitsError::BadKeywordCard {
+                position: position.card(card),
+                reason: format!(
+                    "field {} does not fit into a row of {} bytes",
+                    n, row_length
+                ),
+            });
+        }
+        columns.push(
+            Column::new(tbcol as usize - 1, format)
+                .with_name(string(records, Keyword::TTypen(n)))
+                .with_unit(string(records, Keyword::TUnitn(n)))
+                .with_null(string(records, Keyword::TNulln(n)))
+                .with_scaling(
+                    real(records, Keyword::TScaln(n)).unwrap_or(1.0),
+                    real(records, Keyword::TZeron(n)).unwrap_or(0.0),
+                ),
+        );
+    }
+    Ok(columns)
+}
+
+/// Parses a TFORMn value of an ASCII table. FITS standard section 7.2.5
+fn tform(i: &str) -> IResult<&str, TableFormat, VerboseError<&str>> {
+    context(
+        "tform",
+        all_consuming(alt((
+            map(preceded(char('A'), width), TableFormat::Character),
+            map(preceded(char('I'), width), TableFormat::Integer),
+            map(
+                pair(one_of("FED"), separated_pair(width, char('.'), width)),
+                |(code, (w, d))| match code {
+                    'F' => TableFormat::FixedPoint(w, d),
+                    'E' => TableFormat::Exponential(w, d),
+                    _ => TableFormat::DoubleExponential(w, d),
+                },
+            ),
+        ))),
+    )(i)
+}
+
+fn width(i: &str) -> IResult<&str, usize, VerboseError<&str>> {
+    map_res(digit1, str::parse::<usize>)(i)
+}
+
+/// Decodes a single field. Leading and trailing blanks of numbers are ignored, a field that is
+/// blank or equal to TNULLn is `Field::Null`. FITS standard section 7.2.5
+fn field(column: &Column, bytes: &[u8]) -> Result<Field, String> {
+    let text =
+        std::str::from_utf8(bytes).map_err(|_| String::from("field is not an ASCII string"))?;
+    if column.null().is_some_and(|null| null == text.trim()) {
+        return Ok(Field::Null);
+    }
+    let trimmed = text.trim();
+    let decimals = match column.format() {
+        TableFormat::Character(_) => return Ok(Field::Character(text.trim_end().to_string())),
+        _ if trimmed.is_empty() => return Ok(Field::Null),
+        TableFormat::Integer(_) => {
+            let value = trimmed
+                .parse::<i64>()
+                .map_err(|_| format!("'{}' is not an integer", trimmed))?;
+            return Ok(match column.is_scaled() {
+                true => Field::Real(column.zero() + column.scale() * value as f64),
+                false => Field::Integer(value),
+            });
+        }
+        TableFormat::FixedPoint(_, d)
+        | TableFormat::Exponential(_, d)
+        | TableFormat::DoubleExponential(_, d) => *d,
+    };
+    let value = fortran_real(trimmed, decimals)
+        .ok_or_else(|| format!("'{}' is not a real number", trimmed))?;
+    Ok(Field::Real(column.zero() + column.scale() * value))
+}
+
+/// Reads a real number written by a Fortran `Fw.d`, `Ew.d` or `Dw.d` edit descriptor. `D` is
+/// accepted as exponent letter and a number without decimal point has `d` implied decimals.
+fn fortran_real(s: &str, decimals: usize) -> Option<f64> {
+    let s = s.replace(['D', 'd'], "E");
+    let mantissa = s.split(['E', 'e']).next().unwrap_or_default();
+    let value = s.parse::<f64>().ok()?;
+    match mantissa.contains('.') {
+        true => Some(value),
+        false => Some(value / 10f64.powi(decimals as i32)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table_header(cards: &[&str]) -> Vec<u8> {
+        let mut bytes = [
+            "XTENSION= 'TABLE   '",
+            "BITPIX  =                    8",
+            "NAXIS   =                    2",
+            "NAXIS1  =                   24",
+            "NAXIS2  =                    3",
+            "PCOUNT  =                    0",
+            "GCOUNT  =                    1",
+        ]
+        .iter()
+        .chain(cards)
+        .chain(&["END"])
+        .flat_map(|card| format!("{:80}", card).into_bytes())
+        .collect::<Vec<u8>>();
+        bytes.resize(2880, b' ');
+        bytes
+    }
+
+    fn decode(header: &[u8], data: &[u8]) -> Result<Table, FitsError> {
+        let (_, header) = super::super::header::header(header).unwrap();
+        super::table(
+            &header,
+            data,
+            Position::new(0, 1, None),
+            Position::new(2880, 1, None),
+        )
+    }
+
+    #[test]
+    fn tform() {
+        assert_eq!(super::tform("A8"), Ok(("", TableFormat::Character(8))));
+        assert_eq!(super::tform("I11"), Ok(("", TableFormat::Integer(11))));
+        assert_eq!(
+            super::tform("F8.3"),
+            Ok(("", TableFormat::FixedPoint(8, 3)))
+        );
+        assert_eq!(
+            super::tform("E15.7"),
+            Ok(("", TableFormat::Exponential(15, 7)))
+        );
+        assert_eq!(
+            super::tform("D25.17"),
+            Ok(("", TableFormat::DoubleExponential(25, 17)))
+        );
+        assert!(super::tform("E15").is_err());
+        assert!(super::tform("X8").is_err());
+        assert!(super::tform("I8 ").is_err());
+    }
+
+    #[test]
+    fn fortran_real() {
+        assert_eq!(super::fortran_real("1.5", 0), Some(1.5));
+        assert_eq!(super::fortran_real("-4.79E+02", 2), Some(-479.0));
+        assert_eq!(
+            super::fortran_real("4.994713710625928D+08", 17),
+            Some(4.994713710625928e8)
+        );
+        assert_eq!(super::fortran_real("1234", 2), Some(12.34));
+        assert_eq!(super::fortran_real("15E1", 1), Some(15.0));
+        assert_eq!(super::fortran_real("1.2.3", 1), None);
+    }
+
+    #[test]
+    fn table() {
+        let header = table_header(&[
+            "TFIELDS =                    3",
+            "TTYPE1  = 'NAME    '",
+            "TBCOL1  =                    1",
+            "TFORM1  = 'A6      '",
+            "TTYPE2  = 'COUNT   '",
+            "TBCOL2  =                    8",
+            "TFORM2  = 'I5      '",
+            "TNULL2  = '-       '",
+            "TTYPE3  = 'FLUX    '",
+            "TBCOL3  =                   14",
+            "TFORM3  = 'F8.2    '",
+            "TSCAL3  =                  2.0",
+            "TZERO3  =                    1",
+            "TUNIT3  = 'Jy      '",
+        ]);
+        let data = [
+            ("alpha", "12", "1.50E+00"),
+            ("beta", "-", "1200"),
+            ("gamma", "3", ""),
+        ]
+        .iter()
+        .map(|(name, count, flux)| format!("{:6} {:>5} {:>8}   ", name, count, flux))
+        .collect::<String>();
+        let table = decode(&header, data.as_bytes()).unwrap();
+        assert_eq!(table.columns().len(), 3);
+        assert_eq!(table.columns()[0].name(), Some("NAME"));
+        assert_eq!(table.columns()[2].unit(), Some("Jy"));
+        assert_eq!(
+            table.row(0),
+            Some(
+                &[
+                    Field::Character(String::from("alpha")),
+                    Field::Integer(12),
+                    Field::Real(4.0)
+                ][..]
+            )
+        );
+        assert_eq!(table.field(1, 1), Some(&Field::Null));
+        assert_eq!(table.field(1, 2), Some(&Field::Real(25.0)));
+        assert_eq!(table.field(2, 2), Some(&Field::Null));
+    }
+
+    #[test]
+    fn table_bad_field() {
+        let header = table_header(&[
+            "TFIELDS =                    1",
+            "TBCOL1  =                    3",
+            "TFORM1  = 'I4      '",
+        ]);
+        let data = format!("{:24}{:24}{:24}", "  12", "  xx", "");
+        assert_eq!(
+            decode(&header, data.as_bytes()),
+            Err(FitsError::BadTableField {
+                position: Position::new(2880 + 24 + 2, 1, None),
+                row: 2,
+                column: 1,
+                reason: String::from("'xx' is not an integer")
+            })
+        );
+    }
+
+    #[test]
+    fn table_bad_column() {
+        let header = table_header(&[
+            "TFIELDS =                    1",
+            "TBCOL1  =                   20",
+            "TFORM1  = 'E15.7   '",
+        ]);
+        assert!(matches!(
+            decode(&header, &[b' '; 72]),
+            Err(FitsError::BadKeywordCard { position, .. }) if position.card == Some(9)
+        ));
+
+        let header = table_header(&["TFIELDS =                70000"]);
+        assert!(matches!(
+            decode(&header, &[b' '; 72]),
+            Err(FitsError::BadKeywordCard { position, .. }) if position.card == Some(8)
+        ));
+
+        let header = table_header(&["TFIELDS =                    1", "TBCOL1  =    1"]);
+        assert_eq!(
+            decode(&header, &[b' '; 72]),
+            Err(FitsError::MissingKeyword {
+                position: Position::new(720, 1, Some(10)),
+                keyword: Keyword::TFormn(1)
+            })
+        );
+    }
+}
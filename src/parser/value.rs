@@ -16,7 +16,7 @@ use time::macros::format_description;
 use time::PrimitiveDateTime;
 use tracing::{error, instrument};
 
-pub fn character_string(i: &[u8]) -> IResult<&[u8], Value, VerboseError<&[u8]>> {
+pub fn character_string(i: &[u8]) -> IResult<&[u8], Value<'_>, VerboseError<&[u8]>> {
     context(
         "character_string",
         map(
@@ -44,9 +44,8 @@ fn u8vec_to_string(v: Vec<&[u8]>) -> String {
     let mut acc = String::new();
     while let Some(part) = it.next() {
         acc.push_str(std::str::from_utf8(part).unwrap_or(""));
-        match it.peek().is_some() {
-            true => acc.push('\''),
-            false => (),
+        if it.peek().is_some() {
+            acc.push('\'');
         }
     }
     acc
@@ -59,18 +58,17 @@ fn u8vec_to_string_eating_last_ampersand(v: Vec<&[u8]>) -> String {
         acc.push_str(std::str::from_utf8(part).unwrap_or(""));
         match it.peek().is_some() {
             true => acc.push('\''),
-            false => match acc.ends_with('&') {
-                true => {
+            false => {
+                if acc.ends_with('&') {
                     _ = acc.pop();
                 }
-                false => (),
-            },
+            }
         }
     }
     acc
 }
 
-pub fn complex_float(i: &[u8]) -> IResult<&[u8], Value, VerboseError<&[u8]>> {
+pub fn complex_float(i: &[u8]) -> IResult<&[u8], Value<'_>, VerboseError<&[u8]>> {
     context(
         "complex integer",
         map(
@@ -93,7 +91,7 @@ pub fn complex_float(i: &[u8]) -> IResult<&[u8], Value, VerboseError<&[u8]>> {
     )(i)
 }
 
-pub fn complex_integer(i: &[u8]) -> IResult<&[u8], Value, VerboseError<&[u8]>> {
+pub fn complex_integer(i: &[u8]) -> IResult<&[u8], Value<'_>, VerboseError<&[u8]>> {
     context(
         "complex integer",
         map(
@@ -116,7 +114,7 @@ pub fn complex_integer(i: &[u8]) -> IResult<&[u8], Value, VerboseError<&[u8]>> {
     )(i)
 }
 
-pub fn continued_string(i: &[u8]) -> IResult<&[u8], Value, VerboseError<&[u8]>> {
+pub fn continued_string(i: &[u8]) -> IResult<&[u8], Value<'_>, VerboseError<&[u8]>> {
     context(
         "continued string",
         map(
@@ -137,11 +135,11 @@ pub fn continued_string(i: &[u8]) -> IResult<&[u8], Value, VerboseError<&[u8]>>
     )(i)
 }
 
-const DATE_FORMAT: &[time::format_description::FormatItem<'_>] = format_description!(
+pub(crate) const DATE_FORMAT: &[time::format_description::FormatItem<'_>] = format_description!(
     version = 2,
     "[year]-[month]-[day][ optional [T[hour]:[minute]:[second][ optional [.[subsecond]]]]]"
 );
-pub fn date(i: &[u8]) -> IResult<&[u8], Value, VerboseError<&[u8]>> {
+pub fn date(i: &[u8]) -> IResult<&[u8], Value<'_>, VerboseError<&[u8]>> {
     //CCYY-MM-DD[Thh:mm:ss[.s...]]
     context(
         "date",
@@ -182,7 +180,7 @@ pub fn date(i: &[u8]) -> IResult<&[u8], Value, VerboseError<&[u8]>> {
     )(i)
 }
 
-pub fn integer(i: &[u8]) -> IResult<&[u8], Value, VerboseError<&[u8]>> {
+pub fn integer(i: &[u8]) -> IResult<&[u8], Value<'_>, VerboseError<&[u8]>> {
     context(
         "integer",
         map(
@@ -192,7 +190,7 @@ pub fn integer(i: &[u8]) -> IResult<&[u8], Value, VerboseError<&[u8]>> {
     )(i)
 }
 
-pub fn logical(i: &[u8]) -> IResult<&[u8], Value, VerboseError<&[u8]>> {
+pub fn logical(i: &[u8]) -> IResult<&[u8], Value<'_>, VerboseError<&[u8]>> {
     context(
         "logical",
         map(
@@ -208,7 +206,7 @@ pub fn logical(i: &[u8]) -> IResult<&[u8], Value, VerboseError<&[u8]>> {
     )(i)
 }
 
-pub fn real(i: &[u8]) -> IResult<&[u8], Value, VerboseError<&[u8]>> {
+pub fn real(i: &[u8]) -> IResult<&[u8], Value<'_>, VerboseError<&[u8]>> {
     context(
         "real",
         map(
@@ -218,7 +216,7 @@ pub fn real(i: &[u8]) -> IResult<&[u8], Value, VerboseError<&[u8]>> {
     )(i)
 }
 #[instrument]
-pub fn unknown(i: &[u8]) -> IResult<&[u8], Value, VerboseError<&[u8]>> {
+pub fn unknown(i: &[u8]) -> IResult<&[u8], Value<'_>, VerboseError<&[u8]>> {
     context(
         "unknown",
         map(take(72u8), |value: &[u8]| {
@@ -0,0 +1,358 @@
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+
+use crate::{
+    checksum::{self, Verification},
+    parser::{self, DataMode},
+    types::{
+        data_array::DataArray,
+        header::{FitsHeader, Header},
+        keyword::Keyword,
+        FitsError, Position, BLOCK_SIZE, CARD_SIZE, HDU,
+    },
+};
+
+/// Where an HDU is located in the file.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct HduLocation {
+    /// Byte offset of the first header block.
+    pub header_offset: u64,
+    /// Byte offset of the data unit, right after the header blocks.
+    pub data_offset: u64,
+    /// Number of bytes in the data unit, without the fill.
+    pub data_size: usize,
+}
+
+#[derive(Debug)]
+struct Entry {
+    location: HduLocation,
+    header: Vec<u8>,
+}
+
+/// Reads a FITS file incrementally from a stream. [`FitsReader::new`] scans the headers block by
+/// block and seeks over the data units, so only the header blocks are read. Data units are read
+/// when an HDU is requested.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use fitsio::reader::FitsReader;
+/// use fitsio::types::header::FitsHeader;
+/// let mut bytes = format!("{:80}{:80}{:80}{:80}{:80}", "SIMPLE  =                    T", "BITPIX  =                    8", "NAXIS   =                    1", "NAXIS1  =                    3", "END").into_bytes();
+/// bytes.resize(2880, b' ');
+/// bytes.extend([1, 2, 3]);
+///
+/// let mut reader = FitsReader::new(Cursor::new(bytes)).unwrap();
+/// assert_eq!(reader.len(), 1);
+/// assert_eq!(reader.header(0).unwrap().dimensions(), &vec![3]);
+/// let data_array = reader.read_data_array(0).unwrap().unwrap();
+/// assert_eq!(data_array.as_slice::<u8>(), Some(&[1u8, 2, 3][..]));
+/// ```
+#[derive(Debug)]
+pub struct FitsReader<R> {
+    reader: R,
+    entries: Vec<Entry>,
+    /// The data unit that was read last.
+    data_unit: Vec<u8>,
+}
+
+impl<R: Read + Seek> FitsReader<R> {
+    /// Scans the headers of the primary HDU and all extensions that follow it. Anything after
+    /// the last HDU that does not start with `XTENSION` is treated as special records and
+    /// ignored. FITS standard section 3.5
+    pub fn new(mut reader: R) -> Result<Self, FitsError> {
+        let length = reader.seek(SeekFrom::End(0))?;
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        loop {
+            let position = Position::new(offset as usize, entries.len(), None);
+            reader.seek(SeekFrom::Start(offset))?;
+            let first = read_block(&mut reader, position)?;
+            if !entries.is_empty() && !first.starts_with(Keyword::Xtension.name().as_bytes()) {
+                break;
+            }
+            let entry = scan_header(&mut reader, first, position, length)?;
+            let padded_size = entry.location.data_size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+            offset = entry.location.data_offset + padded_size as u64;
+            entries.push(entry);
+            if offset >= length {
+                break;
+            }
+        }
+        Ok(Self {
+            reader,
+            entries,
+            data_unit: Vec::new(),
+        })
+    }
+
+    /// Number of HDUs in the file.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// A file always has a primary HDU, so this is never true.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn location(&self, hdu: usize) -> Option<HduLocation> {
+        self.entries.get(hdu).map(|entry| entry.location)
+    }
+
+    /// The header of HDU number `hdu`, the primary HDU has number 0.
+    pub fn header(&self, hdu: usize) -> Option<Header<'_>> {
+        self.entries.get(hdu).map(|entry| {
+            let position = Position::new(entry.location.header_offset as usize, hdu, None);
+            let (_, header) = parser::checked_header(&entry.header, position)
+                .expect("the header was validated while scanning");
+            header
+        })
+    }
+
+    /// Reads the data unit of HDU number `hdu` and decodes it like [`crate::parse_fits`].
+    pub fn read_hdu(&mut self, hdu: usize) -> Result<HDU<'_>, FitsError> {
+        self.read_data_unit(hdu)?;
+        let entry = &self.entries[hdu];
+        let position = Position::new(entry.location.header_offset as usize, hdu, None);
+        let data_position = Position::new(entry.location.data_offset as usize, hdu, None);
+        let (_, header) = parser::checked_header(&entry.header, position)?;
+        parser::checked_data(
+            header,
+            &self.data_unit,
+            position,
+            data_position,
+            DataMode::Decode,
+        )
+    }
+
+    /// Reads only the data array of HDU number `hdu`.
+    pub fn read_data_array(&mut self, hdu: usize) -> Result<Option<DataArray>, FitsError> {
+        self.read_hdu(hdu).map(HDU::into_data_array)
+    }
+
+    /// Verifies CHECKSUM and DATASUM of HDU number `hdu`. The data unit is read with its fill,
+    /// fill that is missing at the end of the file counts as zeros. FITS standard section 4.4.2.7
+    pub fn verify(&mut self, hdu: usize) -> Result<Verification, FitsError> {
+        let location = self
+            .location(hdu)
+            .ok_or_else(|| FitsError::InvalidHdu(format!("there is no HDU number {}", hdu)))?;
+        let padded_size = location.data_size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+        let position = Position::new(location.data_offset as usize, hdu, None);
+        let io_error = |source| FitsError::Io {
+            position: Some(position),
+            source,
+        };
+        self.reader
+            .seek(SeekFrom::Start(location.data_offset))
+            .map_err(io_error)?;
+        self.data_unit.clear();
+        (&mut self.reader)
+            .take(padded_size as u64)
+            .read_to_end(&mut self.data_unit)
+            .map_err(io_error)?;
+        let header_sum = checksum::checksum(&self.entries[hdu].header, 0);
+        let data_sum = checksum::checksum(&self.data_unit, 0);
+        let header = self.header(hdu).unwrap();
+        Ok(checksum::verify(&header, header_sum, data_sum))
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_data_unit(&mut self, hdu: usize) -> Result<(), FitsError> {
+        let location = self
+            .location(hdu)
+            .ok_or_else(|| FitsError::InvalidHdu(format!("there is no HDU number {}", hdu)))?;
+        let position = Position::new(location.data_offset as usize, hdu, None);
+        let io_error = |source| FitsError::Io {
+            position: Some(position),
+            source,
+        };
+        self.reader
+            .seek(SeekFrom::Start(location.data_offset))
+            .map_err(io_error)?;
+        self.data_unit.resize(location.data_size, 0);
+        self.reader
+            .read_exact(&mut self.data_unit)
+            .map_err(io_error)
+    }
+}
+
+/// Reads header blocks until the one containing the END keyword and validates the header.
+fn scan_header<R: Read>(
+    reader: &mut R,
+    first: Vec<u8>,
+    position: Position,
+    length: u64,
+) -> Result<Entry, FitsError> {
+    let mut header = first;
+    loop {
+        let block = &header[header.len() - header.len().min(BLOCK_SIZE)..];
+        let end = block
+            .chunks(CARD_SIZE)
+            .any(|card| card.starts_with(b"END     "));
+        if end || !header.len().is_multiple_of(BLOCK_SIZE) {
+            break;
+        }
+        let block_position = Position::new(position.offset + header.len(), position.hdu, None);
+        let block = read_block(reader, block_position)?;
+        if block.is_empty() {
+            break;
+        }
+        header.extend(block);
+    }
+    let (data_unit, parsed) = parser::checked_header(&header, position)?;
+    let header_length = header.len() - data_unit.len();
+    let data_size = parsed.data_size()?;
+    let data_offset = (position.offset + header_length) as u64;
+    let available = length.saturating_sub(data_offset) as usize;
+    if available < data_size {
+        return Err(FitsError::BadDataLength {
+            position: Position::new(data_offset as usize, position.hdu, None),
+            expected: data_size,
+            actual: available,
+        });
+    }
+    header.truncate(header_length);
+    Ok(Entry {
+        location: HduLocation {
+            header_offset: position.offset as u64,
+            data_offset,
+            data_size,
+        },
+        header,
+    })
+}
+
+/// Reads up to one block, less only at the end of the stream.
+fn read_block<R: Read>(reader: &mut R, position: Position) -> Result<Vec<u8>, FitsError> {
+    let mut block = vec![0; BLOCK_SIZE];
+    let mut filled = 0;
+    while filled < BLOCK_SIZE {
+        match reader.read(&mut block[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(source) => {
+                return Err(FitsError::Io {
+                    position: Some(position),
+                    source,
+                })
+            }
+        }
+    }
+    block.truncate(filled);
+    Ok(block)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::parse_fits;
+
+    /// Counts the bytes read from the inner reader.
+    struct Counting<R> {
+        inner: R,
+        read: usize,
+    }
+
+    impl<R: Read> Read for Counting<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            self.read += n;
+            Ok(n)
+        }
+    }
+
+    impl<R: Seek> Seek for Counting<R> {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    fn example(name: &str) -> Vec<u8> {
+        std::fs::read(format!(
+            "{}/FITS-EXAMPLES/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn fits_reader() {
+        for name in [
+            "NICMOSn4hk12010_mos.fits",
+            "IUElwp25637mxlo.fits",
+            "FGSf64y0106m_a1f.fits",
+            "DDTSUVDATA.fits",
+        ] {
+            let bytes = example(name);
+            let fits = parse_fits(&bytes).unwrap();
+            let mut reader = FitsReader::new(Cursor::new(bytes.clone())).unwrap();
+            assert_eq!(reader.len(), fits.hdus().len(), "{}", name);
+            for (n, hdu) in fits.hdus().iter().enumerate() {
+                assert_eq!(reader.header(n).as_ref(), Some(hdu.header()), "{}", name);
+                assert_eq!(&reader.read_hdu(n).unwrap(), hdu, "{}", name);
+            }
+            assert_eq!(reader.header(fits.hdus().len()), None);
+        }
+    }
+
+    #[test]
+    fn fits_reader_reads_only_headers() {
+        let bytes = example("NICMOSn4hk12010_mos.fits");
+        let mut reader = FitsReader::new(Counting {
+            inner: Cursor::new(bytes),
+            read: 0,
+        })
+        .unwrap();
+        assert_eq!(reader.len(), 6);
+        let headers = reader
+            .entries
+            .iter()
+            .map(|entry| entry.header.len())
+            .sum::<usize>();
+        assert_eq!(reader.reader.read, headers);
+
+        let location = reader.location(1).unwrap();
+        assert_eq!(location.data_size, 270 * 263 * 4);
+        reader.read_data_array(1).unwrap();
+        assert_eq!(reader.into_inner().read, headers + location.data_size);
+    }
+
+    #[test]
+    fn fits_reader_errors() {
+        let mut bytes = format!(
+            "{:80}{:80}{:80}{:80}{:80}",
+            "SIMPLE  =                    T",
+            "BITPIX  =                   16",
+            "NAXIS   =                    1",
+            "NAXIS1  =                    3",
+            "END"
+        )
+        .into_bytes();
+        bytes.resize(2880, b' ');
+        assert_eq!(
+            FitsReader::new(Cursor::new(&bytes[..1000])).unwrap_err(),
+            FitsError::Truncated(Position::new(960, 0, Some(13)))
+        );
+        bytes.extend([0, 1, 0, 2]);
+        assert_eq!(
+            FitsReader::new(Cursor::new(&bytes)).unwrap_err(),
+            FitsError::BadDataLength {
+                position: Position::new(2880, 0, None),
+                expected: 6,
+                actual: 4
+            }
+        );
+        assert_eq!(
+            FitsReader::new(Cursor::new(b"")).unwrap_err(),
+            FitsError::Truncated(Position::new(0, 0, Some(1)))
+        );
+    }
+}
@@ -1,16 +1,33 @@
+pub mod binary_table;
 pub mod data_array;
+pub mod data_view;
 pub mod header;
 pub mod keyword;
 pub mod keyword_record;
+pub mod random_groups;
+pub mod table;
 pub mod value;
 
+use std::{fmt, sync::OnceLock};
+
+use crate::parser;
+
+use binary_table::BinaryTable;
 use data_array::DataArray;
+use data_view::DataView;
 use header::Header;
-use nom::error;
+use keyword::Keyword;
+use random_groups::RandomGroups;
+use table::Table;
 use thiserror::Error;
 
 use self::header::{FitsHeader, HeaderKind};
 
+/// Length of a FITS block in bytes. FITS standard section 3.1
+pub const BLOCK_SIZE: usize = 2880;
+/// Length of a keyword record in bytes. FITS standard section 4.1.1
+pub const CARD_SIZE: usize = 80;
+
 #[derive(PartialEq, Debug)]
 pub struct Fits<'a> {
     hdus: Vec<HDU<'a>>,
@@ -21,13 +38,21 @@ impl<'a> Fits<'a> {
         Self { hdus: Vec::new() }
     }
 
-    pub fn primary_hdu(&self) -> Option<&HDU> {
+    pub fn primary_hdu(&self) -> Option<&HDU<'a>> {
         self.hdus
             .iter()
             .find(|hdu| *hdu.header().header_kind() == HeaderKind::Primary)
     }
 
-    pub fn extensions(&self) -> Vec<&HDU> {
+    pub fn hdus(&self) -> &[HDU<'a>] {
+        &self.hdus
+    }
+
+    pub fn hdus_mut(&mut self) -> &mut [HDU<'a>] {
+        &mut self.hdus
+    }
+
+    pub fn extensions(&self) -> Vec<&HDU<'a>> {
         self.hdus
             .iter()
             .filter(|hdu| *hdu.header().header_kind() != HeaderKind::Primary)
@@ -37,6 +62,10 @@ impl<'a> Fits<'a> {
     pub fn from(hdus: Vec<HDU<'a>>) -> Self {
         Self { hdus }
     }
+
+    pub fn into_hdus(self) -> Vec<HDU<'a>> {
+        self.hdus
+    }
 }
 
 impl<'a> Default for Fits<'a> {
@@ -45,38 +74,486 @@ impl<'a> Default for Fits<'a> {
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Debug)]
 pub struct HDU<'a> {
     header: Header<'a>,
-    data: Option<DataArray>,
+    /// The decoded data array, set on first access if the HDU has a data view.
+    data: OnceLock<Option<DataArray>>,
+    /// The decoded rows and groups, set on first access if the HDU has a data unit.
+    table: OnceLock<Option<Table>>,
+    binary_table: OnceLock<Option<BinaryTable>>,
+    random_groups: OnceLock<Option<RandomGroups>>,
+    data_view: Option<DataView<'a>>,
+    data_unit: Option<DataUnit<'a>>,
+    heap: Vec<u8>,
+}
+
+/// The undecoded data unit of a parsed HDU, without the fill. The heap is borrowed from it and
+/// tables and random groups are decoded from it on first access.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct DataUnit<'a> {
+    pub(crate) bytes: &'a [u8],
+    /// The bytes after the data unit up to the next block boundary, which are not always zeros.
+    pub(crate) fill: &'a [u8],
+    /// Length of the data array, the heap follows it.
+    pub(crate) data_array_size: usize,
+    /// Start of the header, for errors in the keywords that describe a table.
+    pub(crate) position: Position,
+    pub(crate) data_position: Position,
+}
+
+impl<'a> DataUnit<'a> {
+    fn heap(&self) -> &'a [u8] {
+        self.bytes.get(self.data_array_size..).unwrap_or_default()
+    }
 }
 
 impl<'a> HDU<'a> {
-    pub fn header(&self) -> &Header {
+    pub fn header(&self) -> &Header<'a> {
         &self.header
     }
+
+    /// The header for editing. Keywords that describe the data unit, such as BITPIX and NAXISn,
+    /// must still match the data when the HDU is written.
+    pub fn header_mut(&mut self) -> &mut Header<'a> {
+        &mut self.header
+    }
+    /// The data array. If the HDU was read with a [`DataView`], the elements are decoded on the
+    /// first call.
     pub fn data_array(&self) -> &Option<DataArray> {
-        &self.data
+        self.data
+            .get_or_init(|| self.data_view.as_ref().map(DataView::to_data_array))
+    }
+
+    pub fn into_data_array(mut self) -> Option<DataArray> {
+        self.data_array();
+        self.data.take().flatten()
+    }
+
+    /// False while the data array of an HDU with a data view, or the rows of a table or the
+    /// groups of an HDU read by [`crate::parser::fits_view`], have not been decoded.
+    pub fn is_decoded(&self) -> bool {
+        let data = self.data_view.is_none() || self.data.get().is_some();
+        let rows = match self.header.header_kind() {
+            _ if self.data_unit.is_none() => true,
+            HeaderKind::Table => self.table.get().is_some(),
+            HeaderKind::BinaryTable => self.binary_table.get().is_some(),
+            HeaderKind::Primary if self.header.groups() => self.random_groups.get().is_some(),
+            _ => true,
+        };
+        data && rows
+    }
+
+    /// The decoded rows of an ASCII table extension. The data array keeps the raw bytes.
+    /// An HDU read by [`crate::parser::fits_view`] decodes them on the first call, `None` if
+    /// they are invalid, see [`HDU::try_table`].
+    pub fn table(&self) -> Option<&Table> {
+        self.try_table().ok().flatten()
+    }
+
+    /// Like [`HDU::table`], but reports why the rows cannot be decoded.
+    pub fn try_table(&self) -> Result<Option<&Table>, FitsError> {
+        decoded(&self.table, || match &self.data_unit {
+            Some(unit) => parser::decode_table(&self.header, unit),
+            None => Ok(None),
+        })
+    }
+
+    /// The decoded rows of a binary table extension. The data array keeps the raw bytes.
+    /// An HDU read by [`crate::parser::fits_view`] decodes them on the first call, `None` if
+    /// they are invalid, see [`HDU::try_binary_table`].
+    pub fn binary_table(&self) -> Option<&BinaryTable> {
+        self.try_binary_table().ok().flatten()
+    }
+
+    /// Like [`HDU::binary_table`], but reports why the rows cannot be decoded.
+    pub fn try_binary_table(&self) -> Result<Option<&BinaryTable>, FitsError> {
+        decoded(&self.binary_table, || match &self.data_unit {
+            Some(unit) => parser::decode_binary_table(&self.header, unit),
+            None => Ok(None),
+        })
+    }
+
+    /// The decoded groups of a random groups primary HDU. The raw bytes are kept in
+    /// [`HDU::heap`], since the data array is empty with NAXIS1 = 0. `None` if they are
+    /// invalid, see [`HDU::try_random_groups`].
+    pub fn random_groups(&self) -> Option<&RandomGroups> {
+        self.try_random_groups().ok().flatten()
+    }
+
+    /// Like [`HDU::random_groups`], but reports why the groups cannot be decoded.
+    pub fn try_random_groups(&self) -> Result<Option<&RandomGroups>, FitsError> {
+        decoded(&self.random_groups, || match &self.data_unit {
+            Some(unit) => parser::decode_random_groups(&self.header, unit),
+            None => Ok(None),
+        })
+    }
+
+    /// The undecoded data array of an HDU read by [`crate::parser::fits_view`], which borrows
+    /// from the input. Accessing elements through the view does not decode the data array.
+    pub fn data_view(&self) -> Option<&DataView<'a>> {
+        self.data_view.as_ref()
+    }
+
+    /// The bytes that follow the data array in the data unit, i.e. the gap and the heap of a
+    /// binary table or the groups of a random groups HDU. A parsed HDU borrows them from the
+    /// input. FITS standard section 7.3.5
+    pub fn heap(&self) -> &[u8] {
+        self.data_unit.as_ref().map_or(&self.heap, DataUnit::heap)
+    }
+
+    /// The fill of the last block of the data unit of a parsed HDU, empty for other HDUs.
+    pub(crate) fn fill(&self) -> &[u8] {
+        self.data_unit.as_ref().map_or(&[], |unit| unit.fill)
     }
 
     pub fn new(header: Header<'a>, data: Option<DataArray>) -> Self {
-        Self { header, data }
+        let decoded = OnceLock::new();
+        if data.is_some() {
+            decoded.get_or_init(|| data);
+        }
+        Self {
+            header,
+            data: decoded,
+            table: OnceLock::new(),
+            binary_table: OnceLock::new(),
+            random_groups: OnceLock::new(),
+            data_view: None,
+            data_unit: None,
+            heap: Vec::new(),
+        }
     }
+
+    pub fn with_table(self, table: Table) -> Self {
+        Self {
+            table: OnceLock::from(Some(table)),
+            ..self
+        }
+    }
+
+    pub fn with_heap(self, heap: Vec<u8>) -> Self {
+        Self { heap, ..self }
+    }
+
+    pub fn with_binary_table(self, binary_table: BinaryTable) -> Self {
+        Self {
+            binary_table: OnceLock::from(Some(binary_table)),
+            ..self
+        }
+    }
+
+    pub fn with_random_groups(self, random_groups: RandomGroups) -> Self {
+        Self {
+            random_groups: OnceLock::from(Some(random_groups)),
+            ..self
+        }
+    }
+
+    pub fn with_data_view(self, data_view: DataView<'a>) -> Self {
+        Self {
+            data_view: Some(data_view),
+            ..self
+        }
+    }
+
+    pub(crate) fn with_data_unit(self, data_unit: DataUnit<'a>) -> Self {
+        Self {
+            data_unit: Some(data_unit),
+            ..self
+        }
+    }
+}
+
+/// The value in `cell`, decoded on first access. A value that cannot be decoded is not kept,
+/// so the error is reported again on the next access.
+fn decoded<T>(
+    cell: &OnceLock<Option<T>>,
+    decode: impl FnOnce() -> Result<Option<T>, FitsError>,
+) -> Result<Option<&T>, FitsError> {
+    if let Some(value) = cell.get() {
+        return Ok(value.as_ref());
+    }
+    let value = decode()?;
+    Ok(cell.get_or_init(|| value).as_ref())
+}
+
+/// HDUs compare equal if they hold the same header and data, whether decoded or not.
+impl PartialEq for HDU<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.header == other.header
+            && self.data_array() == other.data_array()
+            && self.table() == other.table()
+            && self.binary_table() == other.binary_table()
+            && self.random_groups() == other.random_groups()
+            && self.heap() == other.heap()
+    }
+}
+
+/// Location of an error in a FITS file.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Position {
+    /// Absolute byte offset from the start of the file.
+    pub offset: usize,
+    /// Index of the HDU, the primary HDU has index 0.
+    pub hdu: usize,
+    /// Number of the keyword record in the header, starting at 1.
+    /// `None` if the error is not located in a header.
+    pub card: Option<usize>,
 }
-#[derive(Error, Debug, PartialEq)]
-#[error("could not pars fits file")]
+
+impl Position {
+    pub fn new(offset: usize, hdu: usize, card: Option<usize>) -> Self {
+        Self { offset, hdu, card }
+    }
+
+    /// Position of the `card`th keyword record of a header that starts at this position.
+    pub fn card(&self, card: usize) -> Self {
+        Self::new(self.offset + (card - 1) * CARD_SIZE, self.hdu, Some(card))
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "byte {} (HDU {}", self.offset, self.hdu)?;
+        if let Some(card) = self.card {
+            write!(f, ", card {}", card)?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[derive(Error, Debug)]
 pub enum FitsError {
-    ParseError(String),
+    #[error("file is truncated at {0}")]
+    Truncated(Position),
+    #[error("bad keyword record at {position}: {reason}")]
+    BadKeywordCard { position: Position, reason: String },
+    #[error("mandatory keyword {} is missing at {position}", .keyword.name())]
+    MissingKeyword {
+        position: Position,
+        keyword: Keyword,
+    },
+    #[error("unsupported BITPIX {bitpix} at {position}")]
+    UnsupportedBitpix { position: Position, bitpix: i64 },
+    #[error("data unit at {position} has {actual} bytes, expected {expected}")]
+    BadDataLength {
+        position: Position,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("bad value in row {row}, field {column} of the table at {position}: {reason}")]
+    BadTableField {
+        position: Position,
+        /// Row number, starting at 1.
+        row: usize,
+        /// Field number n of TFORMn.
+        column: usize,
+        reason: String,
+    },
+    #[error("I/O error{}: {source}", .position.map(|p| format!(" at {}", p)).unwrap_or_default())]
+    Io {
+        position: Option<Position>,
+        source: std::io::Error,
+    },
+    #[error("could not write fits file: {0}")]
+    WriteError(String),
+    #[error("invalid HDU: {0}")]
+    InvalidHdu(String),
+    #[error("index {position:?} is out of bounds for dimensions {dimensions:?}")]
+    IndexOutOfBounds {
+        position: Vec<usize>,
+        dimensions: Vec<u32>,
+    },
+    #[error("type mismatch: {0}")]
+    TypeMismatch(String),
+    #[error("keyword {} is not in the header", .0.name())]
+    KeywordNotFound(Keyword),
+    #[error("mandatory keyword {} cannot be removed", .0.name())]
+    MandatoryKeyword(Keyword),
+    #[error("value {value} of keyword {} is not {expected}", .keyword.name())]
+    WrongValueType {
+        keyword: Keyword,
+        /// The kind of value that was requested, e.g. "an integer".
+        expected: &'static str,
+        value: String,
+    },
+    #[error("cannot decompress tile {tile} of the compressed image: {reason}")]
+    BadCompressedTile {
+        /// Tile number, starting at 1.
+        tile: usize,
+        reason: String,
+    },
+    #[error("unsupported compression: {0}")]
+    UnsupportedCompression(String),
 }
 
-impl From<error::VerboseError<&[u8]>> for FitsError {
-    fn from(_: error::VerboseError<&[u8]>) -> Self {
-        Self::ParseError("".to_string())
+impl FitsError {
+    /// The location in the file the error refers to, if known.
+    pub fn position(&self) -> Option<Position> {
+        match self {
+            Self::Truncated(position)
+            | Self::BadKeywordCard { position, .. }
+            | Self::MissingKeyword { position, .. }
+            | Self::UnsupportedBitpix { position, .. }
+            | Self::BadDataLength { position, .. }
+            | Self::BadTableField { position, .. } => Some(*position),
+            Self::Io { position, .. } => *position,
+            Self::WriteError(_)
+            | Self::InvalidHdu(_)
+            | Self::IndexOutOfBounds { .. }
+            | Self::TypeMismatch(_)
+            | Self::KeywordNotFound(_)
+            | Self::MandatoryKeyword(_)
+            | Self::WrongValueType { .. }
+            | Self::BadCompressedTile { .. }
+            | Self::UnsupportedCompression(_) => None,
+        }
+    }
+}
+
+/// I/O errors compare equal if they have the same position and kind.
+impl PartialEq for FitsError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Truncated(a), Self::Truncated(b)) => a == b,
+            (
+                Self::BadKeywordCard {
+                    position: a,
+                    reason: r,
+                },
+                Self::BadKeywordCard {
+                    position: b,
+                    reason: s,
+                },
+            ) => a == b && r == s,
+            (
+                Self::MissingKeyword {
+                    position: a,
+                    keyword: k,
+                },
+                Self::MissingKeyword {
+                    position: b,
+                    keyword: l,
+                },
+            ) => a == b && k == l,
+            (
+                Self::UnsupportedBitpix {
+                    position: a,
+                    bitpix: x,
+                },
+                Self::UnsupportedBitpix {
+                    position: b,
+                    bitpix: y,
+                },
+            ) => a == b && x == y,
+            (
+                Self::BadDataLength {
+                    position: a,
+                    expected: e,
+                    actual: x,
+                },
+                Self::BadDataLength {
+                    position: b,
+                    expected: f,
+                    actual: y,
+                },
+            ) => a == b && e == f && x == y,
+            (
+                Self::BadTableField {
+                    position: a,
+                    row: r,
+                    column: c,
+                    reason: x,
+                },
+                Self::BadTableField {
+                    position: b,
+                    row: s,
+                    column: d,
+                    reason: y,
+                },
+            ) => a == b && r == s && c == d && x == y,
+            (
+                Self::Io {
+                    position: a,
+                    source: s,
+                },
+                Self::Io {
+                    position: b,
+                    source: t,
+                },
+            ) => a == b && s.kind() == t.kind(),
+            (Self::WriteError(a), Self::WriteError(b)) => a == b,
+            (Self::InvalidHdu(a), Self::InvalidHdu(b)) => a == b,
+            (
+                Self::IndexOutOfBounds {
+                    position: a,
+                    dimensions: d,
+                },
+                Self::IndexOutOfBounds {
+                    position: b,
+                    dimensions: e,
+                },
+            ) => a == b && d == e,
+            (Self::TypeMismatch(a), Self::TypeMismatch(b)) => a == b,
+            (Self::KeywordNotFound(a), Self::KeywordNotFound(b)) => a == b,
+            (Self::MandatoryKeyword(a), Self::MandatoryKeyword(b)) => a == b,
+            (
+                Self::WrongValueType {
+                    keyword: k,
+                    expected: e,
+                    value: v,
+                },
+                Self::WrongValueType {
+                    keyword: l,
+                    expected: f,
+                    value: w,
+                },
+            ) => k == l && e == f && v == w,
+            (
+                Self::BadCompressedTile { tile: a, reason: r },
+                Self::BadCompressedTile { tile: b, reason: s },
+            ) => a == b && r == s,
+            (Self::UnsupportedCompression(a), Self::UnsupportedCompression(b)) => a == b,
+            _ => false,
+        }
     }
 }
 
 impl From<std::io::Error> for FitsError {
-    fn from(e: std::io::Error) -> Self {
-        Self::ParseError(e.to_string())
+    fn from(source: std::io::Error) -> Self {
+        Self::Io {
+            position: None,
+            source,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn position() {
+        let position = Position::new(5760, 1, None);
+        assert_eq!(position.to_string(), "byte 5760 (HDU 1)");
+        assert_eq!(position.card(3), Position::new(5920, 1, Some(3)));
+        assert_eq!(position.card(3).to_string(), "byte 5920 (HDU 1, card 3)");
+    }
+
+    #[test]
+    fn fits_error() {
+        let error = FitsError::MissingKeyword {
+            position: Position::new(80, 0, Some(2)),
+            keyword: Keyword::BitPix,
+        };
+        assert_eq!(
+            error.to_string(),
+            "mandatory keyword BITPIX is missing at byte 80 (HDU 0, card 2)"
+        );
+        assert_eq!(error.position(), Some(Position::new(80, 0, Some(2))));
+
+        let error = FitsError::from(std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
+        assert_eq!(error.position(), None);
+        assert_eq!(error.to_string(), "I/O error: unexpected end of file");
     }
 }
@@ -0,0 +1,493 @@
+use std::fmt;
+
+/// Data type of a field in a binary table, the `a` in a TFORMn value `rTa`.
+/// FITS standard section 7.3.3.1
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum BinaryType {
+    /// `L`, logical values `T` and `F`.
+    Logical,
+    /// `X`, an array of bits.
+    Bit,
+    /// `B`, unsigned 8 bit integers.
+    Byte,
+    /// `I`, 16 bit integers.
+    I16,
+    /// `J`, 32 bit integers.
+    I32,
+    /// `K`, 64 bit integers.
+    I64,
+    /// `A`, characters.
+    Character,
+    /// `E`, single precision floating point numbers.
+    F32,
+    /// `D`, double precision floating point numbers.
+    F64,
+    /// `C`, single precision complex numbers.
+    ComplexF32,
+    /// `M`, double precision complex numbers.
+    ComplexF64,
+}
+
+impl BinaryType {
+    /// Number of bytes of a single element. Bits are packed, so `X` returns 0.
+    pub fn size(&self) -> usize {
+        match self {
+            Self::Logical | Self::Byte | Self::Character => 1,
+            Self::Bit => 0,
+            Self::I16 => 2,
+            Self::I32 | Self::F32 => 4,
+            Self::I64 | Self::F64 | Self::ComplexF32 => 8,
+            Self::ComplexF64 => 16,
+        }
+    }
+}
+
+impl BinaryType {
+    fn code(&self) -> char {
+        match self {
+            Self::Logical => 'L',
+            Self::Bit => 'X',
+            Self::Byte => 'B',
+            Self::I16 => 'I',
+            Self::I32 => 'J',
+            Self::I64 => 'K',
+            Self::Character => 'A',
+            Self::F32 => 'E',
+            Self::F64 => 'D',
+            Self::ComplexF32 => 'C',
+            Self::ComplexF64 => 'M',
+        }
+    }
+
+    /// Number of bytes needed for `count` elements.
+    pub fn bytes(&self, count: usize) -> usize {
+        match self {
+            Self::Bit => count.div_ceil(8),
+            data_type => count * data_type.size(),
+        }
+    }
+}
+
+/// Descriptor of a variable length array field, pointing to the elements in the heap.
+/// The value is the maximum number of elements in a row, if known. FITS standard section 7.3.5
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ArrayDescriptor {
+    /// `P`, a 32 bit element count and offset.
+    P(Option<usize>),
+    /// `Q`, a 64 bit element count and offset.
+    Q(Option<usize>),
+}
+
+impl ArrayDescriptor {
+    /// Number of bytes of the descriptor in the row.
+    pub fn size(&self) -> usize {
+        match self {
+            Self::P(_) => 8,
+            Self::Q(_) => 16,
+        }
+    }
+}
+
+/// The TFORMn value of a binary table field, a repeat count and a data type. For a variable
+/// length array the data type is the type of the elements in the heap.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct BinaryFormat {
+    repeat: usize,
+    data_type: BinaryType,
+    descriptor: Option<ArrayDescriptor>,
+}
+
+impl BinaryFormat {
+    pub fn new(repeat: usize, data_type: BinaryType) -> Self {
+        Self {
+            repeat,
+            data_type,
+            descriptor: None,
+        }
+    }
+
+    /// A variable length array of elements of `data_type`.
+    pub fn variable_length(data_type: BinaryType, descriptor: ArrayDescriptor) -> Self {
+        Self {
+            repeat: 1,
+            data_type,
+            descriptor: Some(descriptor),
+        }
+    }
+
+    pub fn repeat(&self) -> usize {
+        self.repeat
+    }
+
+    pub fn data_type(&self) -> &BinaryType {
+        &self.data_type
+    }
+
+    pub fn descriptor(&self) -> Option<&ArrayDescriptor> {
+        self.descriptor.as_ref()
+    }
+
+    /// Number of bytes the field takes in a row.
+    pub fn width(&self) -> usize {
+        match self.descriptor {
+            Some(descriptor) => self.repeat * descriptor.size(),
+            None => self.data_type.bytes(self.repeat),
+        }
+    }
+}
+
+/// Formats the TFORMn value, e.g. `640E` or `1PE(320)`. FITS standard section 7.3.1
+impl fmt::Display for BinaryFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.repeat)?;
+        let max = match self.descriptor {
+            Some(ArrayDescriptor::P(max)) => {
+                write!(f, "P")?;
+                max
+            }
+            Some(ArrayDescriptor::Q(max)) => {
+                write!(f, "Q")?;
+                max
+            }
+            None => None,
+        };
+        write!(f, "{}", self.data_type.code())?;
+        match max {
+            Some(max) => write!(f, "({})", max),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Describes a field of a binary table, as given by TTYPEn, TFORMn, TUNITn, TDIMn, TNULLn,
+/// TSCALn and TZEROn. FITS standard section 7.3.2
+#[derive(PartialEq, Debug, Clone)]
+pub struct BinaryColumn {
+    name: Option<String>,
+    unit: Option<String>,
+    start: usize,
+    format: BinaryFormat,
+    dimensions: Option<Vec<usize>>,
+    null: Option<i64>,
+    scale: f64,
+    zero: f64,
+}
+
+impl BinaryColumn {
+    pub fn new(start: usize, format: BinaryFormat) -> Self {
+        Self {
+            name: None,
+            unit: None,
+            start,
+            format,
+            dimensions: None,
+            null: None,
+            scale: 1.0,
+            zero: 0.0,
+        }
+    }
+
+    pub fn with_name(self, name: Option<String>) -> Self {
+        Self { name, ..self }
+    }
+
+    pub fn with_unit(self, unit: Option<String>) -> Self {
+        Self { unit, ..self }
+    }
+
+    pub fn with_dimensions(self, dimensions: Option<Vec<usize>>) -> Self {
+        Self { dimensions, ..self }
+    }
+
+    pub fn with_null(self, null: Option<i64>) -> Self {
+        Self { null, ..self }
+    }
+
+    pub fn with_scaling(self, scale: f64, zero: f64) -> Self {
+        Self {
+            scale,
+            zero,
+            ..self
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn unit(&self) -> Option<&str> {
+        self.unit.as_deref()
+    }
+
+    /// Offset of the field from the start of the row, starting at 0.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn format(&self) -> &BinaryFormat {
+        &self.format
+    }
+
+    /// Dimensions of a cell from TDIMn, the repeat count if TDIMn is missing. For variable
+    /// length arrays TDIMn gives the maximum dimensions.
+    /// FITS standard section 7.3.2
+    pub fn dimensions(&self) -> Vec<usize> {
+        self.dimensions
+            .clone()
+            .unwrap_or_else(|| vec![self.format.repeat()])
+    }
+
+    /// The TNULLn value that marks undefined integers.
+    pub fn null(&self) -> Option<i64> {
+        self.null
+    }
+
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    pub fn zero(&self) -> f64 {
+        self.zero
+    }
+
+    /// Whether TZEROn shifts signed integers into the range of an unsigned type or vice versa,
+    /// e.g. TZEROn = 32768 for 16 bit unsigned integers. FITS standard section 7.3.2
+    pub fn is_offset_integer(&self) -> bool {
+        self.scale == 1.0
+            && match self.format.data_type() {
+                BinaryType::Byte => self.zero == -128.0,
+                BinaryType::I16 => self.zero == 32768.0,
+                BinaryType::I32 => self.zero == 2147483648.0,
+                BinaryType::I64 => self.zero == 9223372036854775808.0,
+                _ => false,
+            }
+    }
+
+    /// Whether TSCALn or TZEROn turn the stored numbers into real physical values.
+    pub fn is_scaled(&self) -> bool {
+        (self.scale != 1.0 || self.zero != 0.0) && !self.is_offset_integer()
+    }
+}
+
+/// The decoded values of a field in one row. A variable length array is resolved from the heap
+/// into the cell of its element type.
+/// physical value = TZEROn + TSCALn × field value, so scaled numbers become `Real`, with NaN
+/// for integers equal to TNULLn. Offset integers are returned as the corresponding signed or
+/// unsigned type.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Cell {
+    /// `None` for a NUL byte, which marks an undefined logical value.
+    Logical(Vec<Option<bool>>),
+    Bit(Vec<bool>),
+    U8(Vec<u8>),
+    I8(Vec<i8>),
+    I16(Vec<i16>),
+    U16(Vec<u16>),
+    I32(Vec<i32>),
+    U32(Vec<u32>),
+    I64(Vec<i64>),
+    U64(Vec<u64>),
+    /// Characters up to the first NUL, without trailing blanks.
+    Character(String),
+    F32(Vec<f32>),
+    F64(Vec<f64>),
+    ComplexF32(Vec<(f32, f32)>),
+    ComplexF64(Vec<(f64, f64)>),
+    Real(Vec<f64>),
+}
+
+/// Element types that can be read from a `Cell`.
+pub trait CellType: Sized {
+    fn values(cell: &Cell) -> Option<&[Self]>;
+}
+
+macro_rules! cell_type {
+    ($t:ty, $variant:ident) => {
+        impl CellType for $t {
+            fn values(cell: &Cell) -> Option<&[Self]> {
+                match cell {
+                    Cell::$variant(values) => Some(values),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+cell_type!(Option<bool>, Logical);
+cell_type!(bool, Bit);
+cell_type!(u8, U8);
+cell_type!(i8, I8);
+cell_type!(i16, I16);
+cell_type!(u16, U16);
+cell_type!(i32, I32);
+cell_type!(u32, U32);
+cell_type!(i64, I64);
+cell_type!(u64, U64);
+cell_type!(f32, F32);
+cell_type!((f32, f32), ComplexF32);
+cell_type!((f64, f64), ComplexF64);
+
+/// `f64` reads both double precision fields and scaled fields.
+impl CellType for f64 {
+    fn values(cell: &Cell) -> Option<&[Self]> {
+        match cell {
+            Cell::F64(values) | Cell::Real(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+impl Cell {
+    /// The values of the cell, if they are of type `T`.
+    pub fn values<T: CellType>(&self) -> Option<&[T]> {
+        T::values(self)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Cell::Character(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// The rows of a binary table extension, decoded cell by cell.
+#[derive(PartialEq, Debug, Default)]
+pub struct BinaryTable {
+    columns: Vec<BinaryColumn>,
+    rows: Vec<Vec<Cell>>,
+}
+
+impl BinaryTable {
+    pub fn new(columns: Vec<BinaryColumn>, rows: Vec<Vec<Cell>>) -> Self {
+        Self { columns, rows }
+    }
+
+    pub fn columns(&self) -> &[BinaryColumn] {
+        &self.columns
+    }
+
+    /// Index of the column named `name`. Names are compared case-insensitively, as recommended
+    /// for TTYPEn. FITS standard section 7.3.2
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns
+            .iter()
+            .position(|column| column.name().is_some_and(|n| n.eq_ignore_ascii_case(name)))
+    }
+
+    pub fn rows(&self) -> &[Vec<Cell>] {
+        &self.rows
+    }
+
+    pub fn row(&self, row: usize) -> Option<&[Cell]> {
+        self.rows.get(row).map(|cells| cells.as_slice())
+    }
+
+    pub fn cell(&self, row: usize, column: usize) -> Option<&Cell> {
+        self.rows.get(row).and_then(|cells| cells.get(column))
+    }
+
+    /// All cells of the column named `name`, from the first to the last row, or `None` if a
+    /// row has no cell for it.
+    pub fn column(&self, name: &str) -> Option<Vec<&Cell>> {
+        let index = self.column_index(name)?;
+        self.rows.iter().map(|cells| cells.get(index)).collect()
+    }
+
+    /// The values of column `index` in every row, if the column holds values of type `T`.
+    pub fn values_at<T: CellType>(&self, index: usize) -> Option<Vec<&[T]>> {
+        self.columns.get(index)?;
+        self.rows
+            .iter()
+            .map(|cells| cells.get(index)?.values())
+            .collect()
+    }
+
+    /// The values of the column named `name` in every row, if the column holds values of
+    /// type `T`.
+    pub fn values<T: CellType>(&self, name: &str) -> Option<Vec<&[T]>> {
+        self.values_at(self.column_index(name)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_format() {
+        assert_eq!(BinaryFormat::new(3, BinaryType::F64).width(), 24);
+        assert_eq!(BinaryFormat::new(0, BinaryType::F64).width(), 0);
+        assert_eq!(BinaryFormat::new(9, BinaryType::Bit).width(), 2);
+        assert_eq!(BinaryFormat::new(2, BinaryType::ComplexF64).width(), 32);
+        assert_eq!(
+            BinaryFormat::variable_length(BinaryType::F32, ArrayDescriptor::P(None)).width(),
+            8
+        );
+        assert_eq!(
+            BinaryFormat::variable_length(BinaryType::I32, ArrayDescriptor::Q(None)).width(),
+            16
+        );
+    }
+
+    #[test]
+    fn binary_format_display() {
+        assert_eq!(BinaryFormat::new(640, BinaryType::F32).to_string(), "640E");
+        assert_eq!(BinaryFormat::new(1, BinaryType::Bit).to_string(), "1X");
+        assert_eq!(
+            BinaryFormat::variable_length(BinaryType::F32, ArrayDescriptor::P(Some(320)))
+                .to_string(),
+            "1PE(320)"
+        );
+        assert_eq!(
+            BinaryFormat::variable_length(BinaryType::I32, ArrayDescriptor::Q(None)).to_string(),
+            "1QJ"
+        );
+    }
+
+    #[test]
+    fn binary_column() {
+        let column = BinaryColumn::new(0, BinaryFormat::new(4, BinaryType::I16));
+        assert_eq!(column.dimensions(), vec![4]);
+        assert!(!column.is_scaled());
+        let column = column.with_scaling(1.0, 32768.0);
+        assert!(column.is_offset_integer());
+        assert!(!column.is_scaled());
+        let column = column
+            .with_scaling(0.5, 0.0)
+            .with_dimensions(Some(vec![2, 2]));
+        assert!(column.is_scaled());
+        assert_eq!(column.dimensions(), vec![2, 2]);
+    }
+
+    #[test]
+    fn binary_table() {
+        let table = BinaryTable::new(
+            vec![
+                BinaryColumn::new(0, BinaryFormat::new(2, BinaryType::I16))
+                    .with_name(Some(String::from("COUNTS"))),
+                BinaryColumn::new(4, BinaryFormat::new(3, BinaryType::Character))
+                    .with_name(Some(String::from("name"))),
+            ],
+            vec![
+                vec![Cell::I16(vec![1, 2]), Cell::Character(String::from("abc"))],
+                vec![Cell::I16(vec![3, 4]), Cell::Character(String::from("d"))],
+            ],
+        );
+        assert_eq!(table.column_index("NAME"), Some(1));
+        assert_eq!(
+            table.values::<i16>("counts"),
+            Some(vec![&[1, 2][..], &[3, 4][..]])
+        );
+        assert_eq!(table.values::<f32>("counts"), None);
+        assert_eq!(table.values_at::<i16>(2), None);
+        assert_eq!(table.cell(1, 1).and_then(Cell::as_str), Some("d"));
+        assert_eq!(table.row(2), None);
+        assert_eq!(table.column("NAME").unwrap().len(), 2);
+
+        let table = BinaryTable::new(table.columns().to_vec(), vec![vec![Cell::I16(vec![5, 6])]]);
+        assert_eq!(table.column("NAME"), None);
+        assert_eq!(table.values::<u8>("NAME"), None);
+    }
+}
@@ -1,59 +1,78 @@
+use std::collections::HashSet;
+
+use super::FitsError;
+use crate::parallel;
+
+#[cfg(feature = "ndarray")]
+mod ndarray;
+
+/// The elements of a data array with their dimensions, BZERO and BSCALE. Integer arrays also
+/// hold BLANK, the stored value that marks undefined pixels. FITS standard section 4.4.2.5
 #[derive(PartialEq, Debug)]
 pub enum DataArray {
-    U8(Vec<u8>, Vec<u32>, f32, f64),
-    I16(Vec<i16>, Vec<u32>, f32, f64),
-    I32(Vec<i32>, Vec<u32>, f32, f64),
-    I64(Vec<i64>, Vec<u32>, f32, f64),
-    F32(Vec<f32>, Vec<u32>, f32, f64),
-    F64(Vec<f64>, Vec<u32>, f32, f64),
+    U8(Vec<u8>, Vec<u32>, f64, f64, Option<i64>),
+    I16(Vec<i16>, Vec<u32>, f64, f64, Option<i64>),
+    I32(Vec<i32>, Vec<u32>, f64, f64, Option<i64>),
+    I64(Vec<i64>, Vec<u32>, f64, f64, Option<i64>),
+    F32(Vec<f32>, Vec<u32>, f64, f64),
+    F64(Vec<f64>, Vec<u32>, f64, f64),
+    /// Signed bytes, stored with BITPIX 8 and BZERO = -128.
+    I8(Vec<i8>, Vec<u32>, Option<i64>),
+    /// Unsigned integers, stored with BITPIX 16 and BZERO = 32768.
+    U16(Vec<u16>, Vec<u32>, Option<i64>),
+    /// Unsigned integers, stored with BITPIX 32 and BZERO = 2147483648.
+    U32(Vec<u32>, Vec<u32>, Option<i64>),
+    /// Unsigned integers, stored with BITPIX 64 and BZERO = 9223372036854775808.
+    U64(Vec<u64>, Vec<u32>, Option<i64>),
 }
 
 //physical value = BZERO + BSCALE × array value
+//the signed and unsigned integer variants without BZERO and BSCALE hold physical values
 impl DataArray {
     pub fn from_u8(
         data: Vec<u8>,
         dimensions: Vec<u32>,
-        bzero: Option<f32>,
+        bzero: Option<f64>,
         bscale: Option<f64>,
     ) -> Self {
         let bzero = bzero.unwrap_or(0.0);
         let bscale = bscale.unwrap_or(1.0);
-        Self::U8(data, dimensions, bzero, bscale)
+        Self::U8(data, dimensions, bzero, bscale, None)
     }
     pub fn from_i16(
         data: Vec<i16>,
         dimensions: Vec<u32>,
-        bzero: Option<f32>,
+        bzero: Option<f64>,
         bscale: Option<f64>,
     ) -> Self {
         let bzero = bzero.unwrap_or(0.0);
         let bscale = bscale.unwrap_or(1.0);
-        Self::I16(data, dimensions, bzero, bscale)
+        Self::I16(data, dimensions, bzero, bscale, None)
     }
     pub fn from_i32(
         data: Vec<i32>,
         dimensions: Vec<u32>,
-        bzero: Option<f32>,
+        bzero: Option<f64>,
         bscale: Option<f64>,
     ) -> Self {
         let bzero = bzero.unwrap_or(0.0);
         let bscale = bscale.unwrap_or(1.0);
-        Self::I32(data, dimensions, bzero, bscale)
+        Self::I32(data, dimensions, bzero, bscale, None)
     }
     pub fn from_i64(
         data: Vec<i64>,
         dimensions: Vec<u32>,
-        bzero: Option<f32>,
+        bzero: Option<f64>,
         bscale: Option<f64>,
     ) -> Self {
         let bzero = bzero.unwrap_or(0.0);
         let bscale = bscale.unwrap_or(1.0);
-        Self::I64(data, dimensions, bzero, bscale)
+        Self::I64(data, dimensions, bzero, bscale, None)
     }
     pub fn from_f32(
         data: Vec<f32>,
         dimensions: Vec<u32>,
-        bzero: Option<f32>,
+        bzero: Option<f64>,
         bscale: Option<f64>,
     ) -> Self {
         let bzero = bzero.unwrap_or(0.0);
@@ -63,7 +82,7 @@ impl DataArray {
     pub fn from_f64(
         data: Vec<f64>,
         dimensions: Vec<u32>,
-        bzero: Option<f32>,
+        bzero: Option<f64>,
         bscale: Option<f64>,
     ) -> Self {
         let bzero = bzero.unwrap_or(0.0);
@@ -71,43 +90,623 @@ impl DataArray {
         Self::F64(data, dimensions, bzero, bscale)
     }
 
-    pub fn get_u8_value(&self, position: Vec<u32>) -> Option<u8> {
+    pub fn from_i8(data: Vec<i8>, dimensions: Vec<u32>) -> Self {
+        Self::I8(data, dimensions, None)
+    }
+    pub fn from_u16(data: Vec<u16>, dimensions: Vec<u32>) -> Self {
+        Self::U16(data, dimensions, None)
+    }
+    pub fn from_u32(data: Vec<u32>, dimensions: Vec<u32>) -> Self {
+        Self::U32(data, dimensions, None)
+    }
+    pub fn from_u64(data: Vec<u64>, dimensions: Vec<u32>) -> Self {
+        Self::U64(data, dimensions, None)
+    }
+
+    /// Sets BLANK, the stored value of undefined pixels. Ignored for floating point arrays,
+    /// which use NaN instead. FITS standard section 4.4.2.5
+    pub fn with_blank(self, blank: Option<i64>) -> Self {
         match self {
-            Self::U8(data, dimensions, bzero, bscale) => {
-                let index = calculate_index(dimensions, position);
-                Some((*bzero as f64 + *bscale * data[index] as f64) as u8)
+            Self::U8(data, dimensions, bzero, bscale, _) => {
+                Self::U8(data, dimensions, bzero, bscale, blank)
+            }
+            Self::I16(data, dimensions, bzero, bscale, _) => {
+                Self::I16(data, dimensions, bzero, bscale, blank)
+            }
+            Self::I32(data, dimensions, bzero, bscale, _) => {
+                Self::I32(data, dimensions, bzero, bscale, blank)
+            }
+            Self::I64(data, dimensions, bzero, bscale, _) => {
+                Self::I64(data, dimensions, bzero, bscale, blank)
             }
+            Self::I8(data, dimensions, _) => Self::I8(data, dimensions, blank),
+            Self::U16(data, dimensions, _) => Self::U16(data, dimensions, blank),
+            Self::U32(data, dimensions, _) => Self::U32(data, dimensions, blank),
+            Self::U64(data, dimensions, _) => Self::U64(data, dimensions, blank),
+            data_array => data_array,
+        }
+    }
+
+    /// Converts integers that follow the convention for signed bytes and unsigned integers,
+    /// BSCALE = 1 and BZERO = -2^7, 2^15, 2^31 or 2^63, into the corresponding type.
+    /// Any other array is returned unchanged. FITS standard section 5.2.5
+    pub fn into_unsigned(self) -> Self {
+        match self {
+            Self::U8(data, dimensions, -128.0, 1.0, blank) => Self::I8(
+                flip_sign_bit(&data, |v| (v ^ 0x80) as i8),
+                dimensions,
+                blank,
+            ),
+            Self::I16(data, dimensions, 32768.0, 1.0, blank) => Self::U16(
+                flip_sign_bit(&data, |v| v as u16 ^ 0x8000),
+                dimensions,
+                blank,
+            ),
+            Self::I32(data, dimensions, 2147483648.0, 1.0, blank) => Self::U32(
+                flip_sign_bit(&data, |v| v as u32 ^ 0x8000_0000),
+                dimensions,
+                blank,
+            ),
+            Self::I64(data, dimensions, 9223372036854775808.0, 1.0, blank) => Self::U64(
+                flip_sign_bit(&data, |v| v as u64 ^ 0x8000_0000_0000_0000),
+                dimensions,
+                blank,
+            ),
+            data_array => data_array,
+        }
+    }
+
+    /// The BITPIX value that corresponds to the type of the elements. FITS standard section 4.4.1.1
+    pub fn bitpix(&self) -> i8 {
+        match self {
+            Self::U8(..) | Self::I8(..) => 8,
+            Self::I16(..) | Self::U16(..) => 16,
+            Self::I32(..) | Self::U32(..) => 32,
+            Self::I64(..) | Self::U64(..) => 64,
+            Self::F32(..) => -32,
+            Self::F64(..) => -64,
+        }
+    }
+
+    pub fn dimensions(&self) -> &[u32] {
+        match self {
+            Self::U8(_, dimensions, ..)
+            | Self::I16(_, dimensions, ..)
+            | Self::I32(_, dimensions, ..)
+            | Self::I64(_, dimensions, ..)
+            | Self::F32(_, dimensions, ..)
+            | Self::F64(_, dimensions, ..)
+            | Self::I8(_, dimensions, _)
+            | Self::U16(_, dimensions, _)
+            | Self::U32(_, dimensions, _)
+            | Self::U64(_, dimensions, _) => dimensions,
+        }
+    }
+
+    pub fn bzero(&self) -> f64 {
+        match self {
+            Self::U8(_, _, bzero, ..)
+            | Self::I16(_, _, bzero, ..)
+            | Self::I32(_, _, bzero, ..)
+            | Self::I64(_, _, bzero, ..)
+            | Self::F32(_, _, bzero, _)
+            | Self::F64(_, _, bzero, _) => *bzero,
+            Self::I8(..) => -128.0,
+            Self::U16(..) => 32768.0,
+            Self::U32(..) => 2147483648.0,
+            Self::U64(..) => 9223372036854775808.0,
+        }
+    }
+
+    pub fn bscale(&self) -> f64 {
+        match self {
+            Self::U8(_, _, _, bscale, _)
+            | Self::I16(_, _, _, bscale, _)
+            | Self::I32(_, _, _, bscale, _)
+            | Self::I64(_, _, _, bscale, _)
+            | Self::F32(_, _, _, bscale)
+            | Self::F64(_, _, _, bscale) => *bscale,
+            Self::I8(..) | Self::U16(..) | Self::U32(..) | Self::U64(..) => 1.0,
+        }
+    }
+
+    /// Number of elements in the array.
+    pub fn len(&self) -> usize {
+        match self {
+            Self::U8(data, ..) => data.len(),
+            Self::I16(data, ..) => data.len(),
+            Self::I32(data, ..) => data.len(),
+            Self::I64(data, ..) => data.len(),
+            Self::F32(data, ..) => data.len(),
+            Self::F64(data, ..) => data.len(),
+            Self::I8(data, ..) => data.len(),
+            Self::U16(data, ..) => data.len(),
+            Self::U32(data, ..) => data.len(),
+            Self::U64(data, ..) => data.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// BLANK, the stored value of undefined pixels of an integer array.
+    pub fn blank(&self) -> Option<i64> {
+        match self {
+            Self::U8(.., blank)
+            | Self::I16(.., blank)
+            | Self::I32(.., blank)
+            | Self::I64(.., blank)
+            | Self::I8(.., blank)
+            | Self::U16(.., blank)
+            | Self::U32(.., blank)
+            | Self::U64(.., blank) => *blank,
+            Self::F32(..) | Self::F64(..) => None,
+        }
+    }
+
+    /// Whether the element at `index` of the stored values is undefined, i.e. equal to BLANK
+    /// or NaN. The signed and unsigned conventions are reverted before comparing to BLANK.
+    fn is_undefined(&self, index: usize) -> bool {
+        let blank = self.blank();
+        match self {
+            Self::U8(data, ..) => blank == Some(data[index] as i64),
+            Self::I16(data, ..) => blank == Some(data[index] as i64),
+            Self::I32(data, ..) => blank == Some(data[index] as i64),
+            Self::I64(data, ..) => blank == Some(data[index]),
+            Self::I8(data, ..) => blank == Some((data[index] as u8 ^ 0x80) as i64),
+            Self::U16(data, ..) => blank == Some((data[index] ^ 0x8000) as i16 as i64),
+            Self::U32(data, ..) => blank == Some((data[index] ^ 0x8000_0000) as i32 as i64),
+            Self::U64(data, ..) => blank == Some((data[index] ^ 0x8000_0000_0000_0000) as i64),
+            Self::F32(data, ..) => data[index].is_nan(),
+            Self::F64(data, ..) => data[index].is_nan(),
+        }
+    }
+
+    /// One flag per element that is `false` for undefined pixels, in the order of the stored
+    /// values.
+    pub fn mask(&self) -> Vec<bool> {
+        (0..self.len()).map(|i| !self.is_undefined(i)).collect()
+    }
+
+    /// The stored values, if the elements are of type `T`.
+    pub fn as_slice<T: ArrayType>(&self) -> Option<&[T]> {
+        T::values(self)
+    }
+
+    /// Takes the stored values out of the array, if the elements are of type `T`.
+    pub fn into_vec<T: ArrayType>(self) -> Result<Vec<T>, Self> {
+        T::into_values(self)
+    }
+
+    /// An array of stored values of type `T` without BZERO, BSCALE and BLANK.
+    pub fn from_vec<T: ArrayType>(values: Vec<T>, dimensions: Vec<u32>) -> Self {
+        T::data_array(values, dimensions)
+    }
+
+    /// Offset of the element at `position` in the stored values. NAXIS1 is the axis that varies
+    /// fastest. FITS standard section 3.3.2
+    pub fn index(&self, position: &[usize]) -> Result<usize, FitsError> {
+        index(self.dimensions(), position)
+    }
+
+    /// The stored value at `position`, without applying BZERO and BSCALE.
+    pub fn raw<T: ArrayType>(&self, position: &[usize]) -> Result<T, FitsError> {
+        let index = self.index(position)?;
+        match T::values(self) {
+            Some(values) => Ok(values[index]),
+            None => Err(FitsError::TypeMismatch(format!(
+                "data array with BITPIX {} has no elements of type {}",
+                self.bitpix(),
+                std::any::type_name::<T>()
+            ))),
+        }
+    }
+
+    /// The physical value at `position`, BZERO + BSCALE × stored value, converted to `T`.
+    /// Undefined pixels equal to BLANK are NaN. Fails if the value cannot be represented by `T`
+    /// exactly, e.g. 1.5 or NaN as `i16`. Integers are scaled without rounding to f64 if BZERO
+    /// and BSCALE are integers. FITS standard section 4.4.2.5
+    pub fn get<T: PhysicalType>(&self, position: &[usize]) -> Result<T, FitsError> {
+        let index = self.index(position)?;
+        if self.is_undefined(index) {
+            return T::from_physical(f64::NAN).ok_or_else(|| {
+                FitsError::TypeMismatch(format!(
+                    "undefined pixel {:?} cannot be represented as {}",
+                    position,
+                    std::any::type_name::<T>()
+                ))
+            });
+        }
+        let (bzero, bscale) = self.scaling();
+        self.stored(index)
+            .physical(bzero, bscale)
+            .map_err(|physical| {
+                FitsError::TypeMismatch(format!(
+                    "physical value {} cannot be represented as {}",
+                    physical,
+                    std::any::type_name::<T>()
+                ))
+            })
+    }
+
+    /// BZERO and BSCALE that convert the elements to physical values. The signed and unsigned
+    /// integer variants already hold physical values.
+    fn scaling(&self) -> (f64, f64) {
+        match self {
+            Self::I8(..) | Self::U16(..) | Self::U32(..) | Self::U64(..) => (0.0, 1.0),
+            _ => (self.bzero(), self.bscale()),
+        }
+    }
+
+    /// The element at `index` of the stored values.
+    fn stored(&self, index: usize) -> Stored {
+        match self {
+            Self::U8(data, ..) => Stored::Integer(data[index] as i128),
+            Self::I16(data, ..) => Stored::Integer(data[index] as i128),
+            Self::I32(data, ..) => Stored::Integer(data[index] as i128),
+            Self::I64(data, ..) => Stored::Integer(data[index] as i128),
+            Self::F32(data, ..) => Stored::Real(data[index] as f64),
+            Self::F64(data, ..) => Stored::Real(data[index]),
+            Self::I8(data, ..) => Stored::Integer(data[index] as i128),
+            Self::U16(data, ..) => Stored::Integer(data[index] as i128),
+            Self::U32(data, ..) => Stored::Integer(data[index] as i128),
+            Self::U64(data, ..) => Stored::Integer(data[index] as i128),
+        }
+    }
+
+    /// The physical value of the element at `index` of the stored values.
+    fn physical(&self, index: usize) -> f64 {
+        if self.is_undefined(index) {
+            return f64::NAN;
+        }
+        let scaled = |stored: f64| self.bzero() + self.bscale() * stored;
+        match self {
+            Self::U8(data, ..) => scaled(data[index] as f64),
+            Self::I16(data, ..) => scaled(data[index] as f64),
+            Self::I32(data, ..) => scaled(data[index] as f64),
+            Self::I64(data, ..) => scaled(data[index] as f64),
+            Self::F32(data, ..) => scaled(data[index] as f64),
+            Self::F64(data, ..) => scaled(data[index]),
+            Self::I8(data, ..) => data[index] as f64,
+            Self::U16(data, ..) => data[index] as f64,
+            Self::U32(data, ..) => data[index] as f64,
+            Self::U64(data, ..) => data[index] as f64,
+        }
+    }
+
+    /// The physical values of all elements in the order of the stored values, NaN for
+    /// undefined pixels.
+    pub fn physical_values(&self) -> Vec<f64> {
+        parallel::collect(self.len(), |i| self.physical(i))
+    }
+
+    /// The physical value at `position`, `None` for undefined pixels.
+    pub fn get_defined<T: PhysicalType>(&self, position: &[usize]) -> Result<Option<T>, FitsError> {
+        match self.is_undefined(self.index(position)?) {
+            true => Ok(None),
+            false => self.get(position).map(Some),
+        }
+    }
+
+    /// Converts physical values to the stored integers of BITPIX `bitpix`,
+    /// stored value = round((physical value - BZERO) / BSCALE). NaN is stored as `blank`, which
+    /// defaults to a value no defined pixel is stored as, searched from the largest value for
+    /// BITPIX 8 and from the smallest otherwise, and sets BLANK of the result.
+    /// Fails for values out of the range of the type or a floating point `bitpix`, for a `blank`
+    /// out of the range of the type and for values that would be stored as BLANK.
+    /// FITS standard section 4.4.2.5
+    pub fn quantize(
+        values: &[f64],
+        dimensions: Vec<u32>,
+        bitpix: i8,
+        bzero: f64,
+        bscale: f64,
+        blank: Option<i64>,
+    ) -> Result<Self, FitsError> {
+        // the end of the range is exclusive, as i64::MAX is not representable as f64
+        let (min, end) = match bitpix {
+            8 => (0.0, 2f64.powi(8)),
+            16 => (-(2f64.powi(15)), 2f64.powi(15)),
+            32 => (-(2f64.powi(31)), 2f64.powi(31)),
+            64 => (-(2f64.powi(63)), 2f64.powi(63)),
+            _ => {
+                return Err(FitsError::TypeMismatch(format!(
+                    "cannot quantize to BITPIX {}",
+                    bitpix
+                )))
+            }
+        };
+        let in_range = |v: i64| match bitpix {
+            8 => u8::try_from(v).is_ok(),
+            16 => i16::try_from(v).is_ok(),
+            32 => i32::try_from(v).is_ok(),
+            _ => true,
+        };
+        if let Some(blank) = blank.filter(|&b| !in_range(b)) {
+            return Err(FitsError::TypeMismatch(format!(
+                "BLANK {} is out of range for BITPIX {}",
+                blank, bitpix
+            )));
+        }
+        // NaN is kept as None until BLANK is known
+        let stored = values
+            .iter()
+            .map(|&v| {
+                if v.is_nan() {
+                    return Ok(None);
+                }
+                let stored = ((v - bzero) / bscale).round();
+                match (min..end).contains(&stored) {
+                    true => Ok(Some(stored as i64)),
+                    false => Err(FitsError::TypeMismatch(format!(
+                        "physical value {} is out of range for BITPIX {}",
+                        v, bitpix
+                    ))),
+                }
+            })
+            .collect::<Result<Vec<Option<i64>>, FitsError>>()?;
+        // a defined pixel must not be stored as BLANK, or it would become undefined
+        let used = stored.iter().flatten().collect::<HashSet<_>>();
+        let blank = match blank {
+            Some(blank) if used.contains(&blank) => {
+                return Err(FitsError::TypeMismatch(format!(
+                    "a physical value would be stored as BLANK {}",
+                    blank
+                )))
+            }
+            Some(blank) => Some(blank),
+            None if stored.iter().any(Option::is_none) => {
+                let unused = |v: &i64| !used.contains(v);
+                let blank = match bitpix {
+                    8 => (0..=u8::MAX as i64).rev().find(unused),
+                    _ => (min as i64..).find(unused),
+                };
+                Some(blank.ok_or_else(|| {
+                    FitsError::TypeMismatch(format!(
+                        "no stored value of BITPIX {} is free for BLANK",
+                        bitpix
+                    ))
+                })?)
+            }
+            None => None,
+        };
+        let stored = stored
+            .iter()
+            .map(|v| v.or(blank).unwrap_or_default())
+            .collect::<Vec<i64>>();
+        let (bzero, bscale) = (Some(bzero), Some(bscale));
+        let data_array = match bitpix {
+            8 => Self::from_u8(
+                stored.iter().map(|&v| v as u8).collect(),
+                dimensions,
+                bzero,
+                bscale,
+            ),
+            16 => Self::from_i16(
+                stored.iter().map(|&v| v as i16).collect(),
+                dimensions,
+                bzero,
+                bscale,
+            ),
+            32 => Self::from_i32(
+                stored.iter().map(|&v| v as i32).collect(),
+                dimensions,
+                bzero,
+                bscale,
+            ),
+            _ => Self::from_i64(stored, dimensions, bzero, bscale),
+        };
+        Ok(data_array.with_blank(blank))
+    }
+
+    /// The physical value at `position` of an array with BITPIX 8. Use [`DataArray::get`] for
+    /// the other types.
+    pub fn get_u8_value(&self, position: Vec<u32>) -> Option<u8> {
+        match self {
+            Self::U8(..) => self.get(&usize_position(&position)).ok(),
             _ => None,
         }
     }
 
+    /// The physical value at `position` of an array with BITPIX 16. Use [`DataArray::get`] for
+    /// the other types.
     pub fn get_i16_value(&self, position: Vec<u32>) -> Option<i16> {
         match self {
-            Self::I16(data, dimensions, bzero, bscale) => {
-                let index = calculate_index(dimensions, position);
-                Some((*bzero as f64 + *bscale * data[index] as f64) as i16)
-            }
+            Self::I16(..) => self.get(&usize_position(&position)).ok(),
             _ => None,
         }
     }
 }
 
-fn calculate_index(dimensions: &[u32], position: Vec<u32>) -> usize {
-    let (_, index) = dimensions.iter().zip(position.iter()).fold(
-        (None, None),
-        |mut acc: (Option<u32>, Option<u64>), (&d, &p)| {
-            acc.1 = match acc.1 {
-                Some(_) => Some(acc.1.unwrap() + p as u64 * acc.0.unwrap() as u64),
-                None => Some(p as u64),
-            };
-            acc.0 = match acc.0 {
-                Some(dimension) => Some(d * dimension),
-                None => Some(d),
-            };
-            acc
-        },
-    );
-    index.unwrap_or(0) as usize
+fn usize_position(position: &[u32]) -> Vec<usize> {
+    position.iter().map(|&p| p as usize).collect()
+}
+
+/// Converts stored values to the type of the unsigned convention, which adds BZERO by flipping
+/// the sign bit. Large arrays are converted in parallel with the `rayon` feature.
+fn flip_sign_bit<T: Copy + Sync, U: Copy + Default + Send>(
+    data: &[T],
+    flip: impl Fn(T) -> U + Send + Sync,
+) -> Vec<U> {
+    let mut values = vec![U::default(); data.len()];
+    parallel::zip_chunks(&mut values, 1, data, 1, |values, data| {
+        for (value, &v) in values.iter_mut().zip(data) {
+            *value = flip(v);
+        }
+    });
+    values
+}
+
+/// Offset of the element at `position` in an array with `dimensions`, see [`DataArray::index`].
+pub(crate) fn index(dimensions: &[u32], position: &[usize]) -> Result<usize, FitsError> {
+    let out_of_bounds = || FitsError::IndexOutOfBounds {
+        position: position.to_vec(),
+        dimensions: dimensions.to_vec(),
+    };
+    if position.len() != dimensions.len() {
+        return Err(out_of_bounds());
+    }
+    let mut index = 0;
+    for (&p, &d) in position.iter().zip(dimensions).rev() {
+        if p >= d as usize {
+            return Err(out_of_bounds());
+        }
+        index = index * d as usize + p;
+    }
+    Ok(index)
+}
+
+/// Element types that can be read from a `DataArray`, one for each BITPIX.
+pub trait ArrayType: Copy {
+    fn values(data: &DataArray) -> Option<&[Self]>;
+    fn into_values(data: DataArray) -> Result<Vec<Self>, DataArray>;
+    fn data_array(values: Vec<Self>, dimensions: Vec<u32>) -> DataArray;
+}
+
+macro_rules! array_type {
+    ($t:ty, $variant:ident, $data_array:expr) => {
+        impl ArrayType for $t {
+            fn values(data: &DataArray) -> Option<&[Self]> {
+                match data {
+                    DataArray::$variant(values, ..) => Some(values),
+                    _ => None,
+                }
+            }
+
+            fn into_values(data: DataArray) -> Result<Vec<Self>, DataArray> {
+                match data {
+                    DataArray::$variant(values, ..) => Ok(values),
+                    data => Err(data),
+                }
+            }
+
+            fn data_array(values: Vec<Self>, dimensions: Vec<u32>) -> DataArray {
+                $data_array(values, dimensions)
+            }
+        }
+    };
+}
+
+array_type!(u8, U8, |v, d| DataArray::from_u8(v, d, None, None));
+array_type!(i16, I16, |v, d| DataArray::from_i16(v, d, None, None));
+array_type!(i32, I32, |v, d| DataArray::from_i32(v, d, None, None));
+array_type!(i64, I64, |v, d| DataArray::from_i64(v, d, None, None));
+array_type!(f32, F32, |v, d| DataArray::from_f32(v, d, None, None));
+array_type!(f64, F64, |v, d| DataArray::from_f64(v, d, None, None));
+array_type!(i8, I8, DataArray::from_i8);
+array_type!(u16, U16, DataArray::from_u16);
+array_type!(u32, U32, DataArray::from_u32);
+array_type!(u64, U64, DataArray::from_u64);
+
+/// A stored value. Integers are kept apart from floating point values so that they can be
+/// scaled without rounding to f64.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Stored {
+    Integer(i128),
+    Real(f64),
+}
+
+impl Stored {
+    /// The physical value BZERO + BSCALE × stored value as `T`. Integers are scaled exactly if
+    /// BZERO and BSCALE are integers, otherwise as f64, which is only exact for integers below
+    /// 2^53. Fails with the physical value as f64 if `T` cannot represent it exactly.
+    pub(crate) fn physical<T: PhysicalType>(self, bzero: f64, bscale: f64) -> Result<T, f64> {
+        match self {
+            Self::Integer(stored) => {
+                let exact = integer(bzero)
+                    .zip(integer(bscale))
+                    .and_then(|(bzero, bscale)| bscale.checked_mul(stored)?.checked_add(bzero));
+                if let Some(physical) = exact {
+                    return T::from_integer(physical).ok_or(physical as f64);
+                }
+                let physical = bzero + bscale * stored as f64;
+                match stored.unsigned_abs() < 1 << 53 && physical.abs() < 2f64.powi(53) {
+                    true => T::from_physical(physical).ok_or(physical),
+                    false => T::from_rounded(physical).ok_or(physical),
+                }
+            }
+            Self::Real(stored) => {
+                let physical = bzero + bscale * stored;
+                T::from_physical(physical).ok_or(physical)
+            }
+        }
+    }
+}
+
+/// `value` as an integer, if it is one and small enough to be scaled without overflow.
+fn integer(value: f64) -> Option<i128> {
+    (value.fract() == 0.0 && value.abs() <= 2f64.powi(64)).then_some(value as i128)
+}
+
+/// Types that physical values can be converted to.
+pub trait PhysicalType: Sized {
+    /// `None` if `value` is not exactly representable.
+    fn from_physical(value: f64) -> Option<Self>;
+    /// `None` if `value` is not exactly representable.
+    fn from_integer(value: i128) -> Option<Self>;
+    /// A value that may have been rounded when it was computed, `None` for integer types.
+    fn from_rounded(value: f64) -> Option<Self>;
+}
+
+macro_rules! integer_physical_type {
+    ($t:ty) => {
+        impl PhysicalType for $t {
+            fn from_physical(value: f64) -> Option<Self> {
+                // the upper bound of i64 and u64 is not representable as f64, hence the `<`
+                (value.fract() == 0.0
+                    && value >= <$t>::MIN as f64
+                    && value < <$t>::MAX as f64 + 1.0)
+                    .then_some(value as $t)
+            }
+
+            fn from_integer(value: i128) -> Option<Self> {
+                <$t>::try_from(value).ok()
+            }
+
+            fn from_rounded(_: f64) -> Option<Self> {
+                None
+            }
+        }
+    };
+}
+
+integer_physical_type!(u8);
+integer_physical_type!(i16);
+integer_physical_type!(i32);
+integer_physical_type!(i64);
+integer_physical_type!(i8);
+integer_physical_type!(u16);
+integer_physical_type!(u32);
+integer_physical_type!(u64);
+
+/// Single precision values are rounded, NaN and infinity are preserved.
+impl PhysicalType for f32 {
+    fn from_physical(value: f64) -> Option<Self> {
+        Some(value as f32)
+    }
+
+    fn from_integer(value: i128) -> Option<Self> {
+        Some(value as f32)
+    }
+
+    fn from_rounded(value: f64) -> Option<Self> {
+        Some(value as f32)
+    }
+}
+
+impl PhysicalType for f64 {
+    fn from_physical(value: f64) -> Option<Self> {
+        Some(value)
+    }
+
+    fn from_integer(value: i128) -> Option<Self> {
+        Some(value as f64)
+    }
+
+    fn from_rounded(value: f64) -> Option<Self> {
+        Some(value)
+    }
 }
 
 #[cfg(test)]
@@ -151,4 +750,217 @@ mod tests {
         assert_eq!(data_array.get_i16_value(vec![0, 1]), Some(3));
         assert_eq!(data_array.get_i16_value(vec![1, 1]), Some(4));
     }
+
+    #[test]
+    fn data_array_properties() {
+        let data_array = DataArray::from_f32(vec![1.0; 6], vec![3, 2], Some(32768.0), None);
+        assert_eq!(data_array.bitpix(), -32);
+        assert_eq!(data_array.dimensions(), &[3, 2]);
+        assert_eq!(data_array.bzero(), 32768.0);
+        assert_eq!(data_array.bscale(), 1.0);
+        assert_eq!(data_array.len(), 6);
+        assert!(!data_array.is_empty());
+        assert_eq!(
+            DataArray::from_i64(vec![], vec![0], None, None).bitpix(),
+            64
+        );
+    }
+
+    #[test]
+    fn data_array_get() {
+        let data_array =
+            DataArray::from_i16(vec![1, 2, 3, 4, 5, 6], vec![3, 2], Some(0.5), Some(2.0));
+        assert_eq!(data_array.index(&[2, 1]), Ok(5));
+        assert_eq!(data_array.get::<f64>(&[1, 0]), Ok(4.5));
+        assert_eq!(data_array.get::<f32>(&[0, 1]), Ok(8.5));
+        assert_eq!(data_array.raw::<i16>(&[0, 1]), Ok(4));
+        assert_eq!(data_array.as_slice::<i16>(), Some(&[1, 2, 3, 4, 5, 6][..]));
+        assert_eq!(data_array.as_slice::<u8>(), None);
+        assert_eq!(
+            data_array.get::<f64>(&[3, 0]),
+            Err(FitsError::IndexOutOfBounds {
+                position: vec![3, 0],
+                dimensions: vec![3, 2]
+            })
+        );
+        assert!(data_array.get::<f64>(&[0]).is_err());
+        assert_eq!(
+            data_array.get::<i16>(&[0, 0]),
+            Err(FitsError::TypeMismatch(String::from(
+                "physical value 2.5 cannot be represented as i16"
+            )))
+        );
+        assert!(matches!(
+            data_array.raw::<f32>(&[0, 0]),
+            Err(FitsError::TypeMismatch(_))
+        ));
+
+        let data_array = DataArray::from_i16(vec![-32768, 32767], vec![2], Some(32768.0), None);
+        assert_eq!(data_array.get::<i32>(&[0]), Ok(0));
+        assert_eq!(data_array.get::<i32>(&[1]), Ok(65535));
+        assert!(data_array.get::<i16>(&[1]).is_err());
+        assert!(data_array.get::<u8>(&[1]).is_err());
+
+        let data_array = DataArray::from_f64(vec![f64::NAN, 1e300], vec![2], None, None);
+        assert!(data_array.get::<f64>(&[0]).unwrap().is_nan());
+        assert!(data_array.get::<i64>(&[0]).is_err());
+        assert_eq!(data_array.get::<f32>(&[1]), Ok(f32::INFINITY));
+        assert!(data_array.get::<i64>(&[1]).is_err());
+
+        // integers beyond 2^53 are not rounded to f64
+        let large = (1i64 << 53) + 1;
+        let data_array = DataArray::from_i64(vec![large, -large], vec![2], None, None);
+        assert_eq!(data_array.get::<i64>(&[0]), Ok(large));
+        assert_eq!(data_array.get::<i64>(&[1]), Ok(-large));
+        assert_eq!(data_array.get::<f64>(&[0]), Ok(2f64.powi(53)));
+        let data_array = DataArray::from_i64(vec![large], vec![1], Some(-1.0), Some(2.0));
+        assert_eq!(data_array.get::<i64>(&[0]), Ok(2 * large - 1));
+        assert!(data_array.get::<i32>(&[0]).is_err());
+        let data_array = DataArray::from_i64(vec![large], vec![1], Some(0.5), None);
+        assert!(data_array.get::<i64>(&[0]).is_err());
+        assert_eq!(data_array.get::<f64>(&[0]), Ok(2f64.powi(53)));
+    }
+
+    #[test]
+    fn data_array_values() {
+        let data_array = DataArray::from_vec(vec![1u16, 2, 3], vec![3]);
+        assert_eq!(data_array, DataArray::from_u16(vec![1, 2, 3], vec![3]));
+        let data_array =
+            DataArray::from_i16(vec![1, -1, 3], vec![3], Some(1.0), Some(2.0)).with_blank(Some(-1));
+        let physical = data_array.physical_values();
+        assert_eq!(physical[0], 3.0);
+        assert!(physical[1].is_nan());
+        let data_array = data_array.into_vec::<f32>().unwrap_err();
+        assert_eq!(data_array.into_vec::<i16>(), Ok(vec![1, -1, 3]));
+    }
+
+    #[test]
+    fn data_array_blank() {
+        let data_array =
+            DataArray::from_i16(vec![1, -1, 3], vec![3], None, Some(2.0)).with_blank(Some(-1));
+        assert_eq!(data_array.blank(), Some(-1));
+        assert_eq!(data_array.mask(), vec![true, false, true]);
+        assert!(data_array.get::<f64>(&[1]).unwrap().is_nan());
+        assert!(data_array.get::<f32>(&[1]).unwrap().is_nan());
+        assert!(matches!(
+            data_array.get::<i32>(&[1]),
+            Err(FitsError::TypeMismatch(_))
+        ));
+        assert_eq!(data_array.get_defined::<i32>(&[1]), Ok(None));
+        assert_eq!(data_array.get_defined::<i32>(&[2]), Ok(Some(6)));
+        assert_eq!(data_array.raw::<i16>(&[1]), Ok(-1));
+
+        // BLANK refers to the stored value, before the unsigned convention is applied
+        let data_array = DataArray::from_i16(vec![-32768, 0], vec![2], Some(32768.0), None)
+            .with_blank(Some(-32768))
+            .into_unsigned();
+        assert_eq!(data_array.mask(), vec![false, true]);
+        assert_eq!(data_array.get_defined::<u16>(&[0]), Ok(None));
+        assert_eq!(data_array.get_defined::<u16>(&[1]), Ok(Some(32768)));
+
+        let data_array = DataArray::from_f32(vec![f32::NAN, 1.0], vec![2], None, None);
+        assert_eq!(data_array.with_blank(Some(0)).blank(), None);
+        assert_eq!(
+            DataArray::from_f32(vec![f32::NAN, 1.0], vec![2], None, None).mask(),
+            vec![false, true]
+        );
+    }
+
+    #[test]
+    fn data_array_quantize() {
+        let values = [0.0, 1.25, f64::NAN, -2.5];
+        let data_array = DataArray::quantize(&values, vec![4], 16, 0.0, 0.25, None).unwrap();
+        assert_eq!(
+            data_array,
+            DataArray::from_i16(vec![0, 5, i16::MIN, -10], vec![4], Some(0.0), Some(0.25))
+                .with_blank(Some(-32768))
+        );
+        assert_eq!(data_array.get_defined::<f64>(&[1]), Ok(Some(1.25)));
+        assert_eq!(data_array.get_defined::<f64>(&[2]), Ok(None));
+
+        let data_array = DataArray::quantize(&[1.0], vec![1], 8, 0.0, 1.0, None).unwrap();
+        assert_eq!(data_array.blank(), None);
+        let data_array = DataArray::quantize(&[f64::NAN], vec![1], 32, 0.0, 1.0, Some(7)).unwrap();
+        assert_eq!(data_array.raw::<i32>(&[0]), Ok(7));
+        assert!(DataArray::quantize(&[300.0], vec![1], 8, 0.0, 1.0, None).is_err());
+        assert!(DataArray::quantize(&[255.4], vec![1], 8, 0.0, 1.0, None).is_ok());
+        assert!(DataArray::quantize(&[-32768.0], vec![1], 16, 0.0, 1.0, None).is_ok());
+        assert!(DataArray::quantize(&[32767.6], vec![1], 16, 0.0, 1.0, None).is_err());
+        assert!(DataArray::quantize(&[i64::MAX as f64], vec![1], 64, 0.0, 1.0, None).is_err());
+        assert!(DataArray::quantize(&[i64::MIN as f64], vec![1], 64, 0.0, 1.0, None).is_ok());
+        assert!(DataArray::quantize(&[1.0], vec![1], -32, 0.0, 1.0, None).is_err());
+
+        // a defined pixel is not stored as BLANK
+        let values = [-16384.0, f64::NAN, 1.0];
+        let data_array = DataArray::quantize(&values, vec![3], 16, 0.0, 0.5, None).unwrap();
+        assert_eq!(data_array.blank(), Some(-32767));
+        assert_eq!(data_array.mask(), vec![true, false, true]);
+        assert!(DataArray::quantize(&[0.0, 1.0], vec![2], 8, 0.0, 1.0, Some(1)).is_err());
+        let data_array = DataArray::quantize(&values, vec![3], 16, 0.0, 0.5, Some(7)).unwrap();
+        assert_eq!(data_array.get_defined::<f64>(&[0]), Ok(Some(-16384.0)));
+        assert_eq!(data_array.get_defined::<f64>(&[1]), Ok(None));
+        let data_array = DataArray::quantize(&[0.0, f64::NAN], vec![2], 8, 0.0, 1.0, None).unwrap();
+        assert_eq!(data_array.blank(), Some(255));
+        assert_eq!(data_array.get_defined::<f64>(&[0]), Ok(Some(0.0)));
+        assert_eq!(data_array.get_defined::<f64>(&[1]), Ok(None));
+        let mut values = (1..=255).map(|v| v as f64).collect::<Vec<_>>();
+        values.push(f64::NAN);
+        let data_array = DataArray::quantize(&values, vec![256], 8, 0.0, 1.0, None).unwrap();
+        assert_eq!(data_array.blank(), Some(0));
+        let mut values = (0..256).map(|v| v as f64).collect::<Vec<_>>();
+        values.push(f64::NAN);
+        assert!(DataArray::quantize(&values, vec![257], 8, 0.0, 1.0, None).is_err());
+
+        // BLANK must be a stored value of the type
+        assert!(DataArray::quantize(&[f64::NAN], vec![1], 16, 0.0, 1.0, Some(100000)).is_err());
+        assert!(DataArray::quantize(&[f64::NAN], vec![1], 8, 0.0, 1.0, Some(-1)).is_err());
+        let data_array =
+            DataArray::quantize(&[f64::NAN], vec![1], 64, 0.0, 1.0, Some(i64::MAX)).unwrap();
+        assert_eq!(data_array.blank(), Some(i64::MAX));
+    }
+
+    #[test]
+    fn data_array_unsigned() {
+        let data_array = DataArray::from_i16(vec![-32768, 0, 32767], vec![3], Some(32768.0), None)
+            .into_unsigned();
+        assert_eq!(
+            data_array,
+            DataArray::from_u16(vec![0, 32768, 65535], vec![3])
+        );
+        assert_eq!(data_array.bitpix(), 16);
+        assert_eq!(data_array.bzero(), 32768.0);
+        assert_eq!(data_array.get::<u16>(&[2]), Ok(65535));
+        assert_eq!(data_array.raw::<u16>(&[1]), Ok(32768));
+
+        let data_array =
+            DataArray::from_i32(vec![i32::MIN, -1], vec![2], Some(2147483648.0), Some(1.0))
+                .into_unsigned();
+        assert_eq!(data_array.as_slice::<u32>(), Some(&[0, u32::MAX >> 1][..]));
+
+        let data_array = DataArray::from_i64(
+            vec![-1, i64::MAX],
+            vec![2],
+            Some(9223372036854775808.0),
+            None,
+        )
+        .into_unsigned();
+        assert_eq!(data_array.raw::<u64>(&[0]), Ok(i64::MAX as u64));
+        assert_eq!(data_array.raw::<u64>(&[1]), Ok(u64::MAX));
+        assert_eq!(data_array.bzero(), 9223372036854775808.0);
+        assert_eq!(data_array.get::<u64>(&[1]), Ok(u64::MAX));
+        assert!(data_array.get::<i64>(&[1]).is_err());
+        let data_array = DataArray::from_u64(vec![(1 << 60) + 1], vec![1]);
+        assert_eq!(data_array.get::<u64>(&[0]), Ok((1 << 60) + 1));
+
+        let data_array =
+            DataArray::from_u8(vec![0, 255], vec![2], Some(-128.0), None).into_unsigned();
+        assert_eq!(data_array, DataArray::from_i8(vec![-128, 127], vec![2]));
+
+        // only BSCALE = 1 with exactly the offset of the type is converted
+        let data_array = DataArray::from_i16(vec![1], vec![1], Some(32768.0), Some(2.0));
+        assert_eq!(data_array.into_unsigned().bitpix(), 16);
+        let data_array = DataArray::from_i32(vec![1], vec![1], Some(32768.0), None).into_unsigned();
+        assert_eq!(data_array.get::<i64>(&[0]), Ok(32769));
+        assert_eq!(data_array.as_slice::<i32>(), Some(&[1][..]));
+    }
 }
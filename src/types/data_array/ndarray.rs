@@ -0,0 +1,196 @@
+use ::ndarray::{ArrayD, ArrayViewD, IxDyn, ShapeBuilder};
+
+use super::{ArrayType, DataArray};
+use crate::types::FitsError;
+
+/// Conversions between data arrays and `ndarray` arrays.
+///
+/// # Axis order
+///
+/// The axes are in FITS order: axis n of the `ndarray` array is NAXIS(n+1), so the shape is
+/// `[NAXIS1, NAXIS2, …]` and `array[[x, y]]` is the element at `get(&[x, y])`. This is the
+/// reverse of the `[…, NAXIS2, NAXIS1]` order of C-order libraries such as numpy. The stored
+/// values are in column-major order, NAXIS1 varying fastest, so the arrays have Fortran strides
+/// and [`DataArray::view`] does not copy them. `array.t()` is a C-order view of the same memory
+/// with the axes reversed. FITS standard section 3.3.2
+///
+/// ```
+/// use fitsio::types::data_array::DataArray;
+///
+/// // NAXIS1 = 3 columns, NAXIS2 = 2 rows
+/// let data_array = DataArray::from_u16(vec![1, 2, 3, 4, 5, 6], vec![3, 2]);
+/// let view = data_array.view::<u16>().unwrap();
+/// assert_eq!(view.shape(), &[3, 2]);
+/// assert_eq!(view[[2, 0]], data_array.get::<u16>(&[2, 0]).unwrap());
+/// assert_eq!(view.t()[[0, 2]], 3);
+/// assert!(view.t().is_standard_layout());
+/// ```
+impl DataArray {
+    /// The shape of the array in `ndarray` axis order, a single axis with all elements for an
+    /// array without axes.
+    fn shape(&self) -> Vec<usize> {
+        match self.dimensions() {
+            [] => vec![self.len()],
+            dimensions => dimensions.iter().map(|&d| d as usize).collect(),
+        }
+    }
+
+    /// A view of the stored values, if the elements are of type `T`, indexed as `[x, y, …]` in
+    /// FITS axis order. Does not copy the data.
+    pub fn view<T: ArrayType>(&self) -> Option<ArrayViewD<'_, T>> {
+        let shape = IxDyn(&self.shape()).f();
+        T::values(self).and_then(|values| ArrayViewD::from_shape(shape, values).ok())
+    }
+
+    /// The stored values as an owned array, if the elements are of type `T`.
+    pub fn to_ndarray<T: ArrayType>(&self) -> Option<ArrayD<T>> {
+        self.view().map(|view| view.to_owned())
+    }
+
+    /// Moves the stored values into an owned array without copying them. Fails if the
+    /// elements are not of type `T` or the dimensions do not match the number of values.
+    pub fn into_ndarray<T: ArrayType>(self) -> Result<ArrayD<T>, FitsError> {
+        let (shape, bitpix) = (self.shape(), self.bitpix());
+        let values = self.into_vec::<T>().map_err(|_| {
+            FitsError::TypeMismatch(format!(
+                "data array with BITPIX {} has no elements of type {}",
+                bitpix,
+                std::any::type_name::<T>()
+            ))
+        })?;
+        from_shape(shape, values)
+    }
+
+    /// The physical values, BZERO + BSCALE × stored value, NaN for undefined pixels. Fails if
+    /// the dimensions do not match the number of values.
+    pub fn to_physical_ndarray(&self) -> Result<ArrayD<f64>, FitsError> {
+        from_shape(self.shape(), self.physical_values())
+    }
+
+    /// A data array of type `T` holding the elements of `array`, NAXISn is the length of
+    /// axis n-1. The memory layout of `array` does not matter.
+    pub fn from_ndarray<T: ArrayType>(array: ArrayD<T>) -> Self {
+        let dimensions = array.shape().iter().map(|&d| d as u32).collect();
+        let values = array.t().iter().copied().collect();
+        T::data_array(values, dimensions)
+    }
+}
+
+/// An array of the values in column-major order.
+fn from_shape<T>(shape: Vec<usize>, values: Vec<T>) -> Result<ArrayD<T>, FitsError> {
+    let len = values.len();
+    ArrayD::from_shape_vec(IxDyn(&shape).f(), values).map_err(|_| {
+        FitsError::InvalidHdu(format!(
+            "data array has {} elements, but dimensions {:?} require {}",
+            len,
+            shape,
+            shape.iter().product::<usize>()
+        ))
+    })
+}
+
+impl<T: ArrayType> From<ArrayD<T>> for DataArray {
+    fn from(array: ArrayD<T>) -> Self {
+        Self::from_ndarray(array)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::ndarray::{array, Array2};
+
+    use super::*;
+    use crate::{
+        builder::{FitsBuilder, HduBuilder},
+        types::header::FitsHeader,
+    };
+
+    #[test]
+    fn ndarray() {
+        let data_array = DataArray::from_i16(vec![1, 2, 3, 4, 5, -1], vec![3, 2], Some(10.0), None)
+            .with_blank(Some(-1));
+        let view = data_array.view::<i16>().unwrap();
+        assert_eq!(view.shape(), &[3, 2]);
+        assert_eq!(view[[2, 0]], 3);
+        assert_eq!(view[[0, 1]], data_array.raw::<i16>(&[0, 1]).unwrap());
+        assert_eq!(
+            view.as_ptr(),
+            data_array.as_slice::<i16>().unwrap().as_ptr()
+        );
+        assert!(data_array.view::<u16>().is_none());
+        assert_eq!(
+            data_array.to_ndarray::<i16>().unwrap(),
+            array![[1, 4], [2, 5], [3, -1]].into_dyn()
+        );
+        let physical = data_array.to_physical_ndarray().unwrap();
+        assert_eq!(physical[[1, 1]], 15.0);
+        assert!(physical[[2, 1]].is_nan());
+
+        let array = data_array.into_ndarray::<i16>().unwrap();
+        assert_eq!(array[[1, 0]], 2);
+        assert!(matches!(
+            DataArray::from_u16(vec![1], vec![1]).into_ndarray::<f32>(),
+            Err(FitsError::TypeMismatch(_))
+        ));
+
+        // the constructors do not check that the dimensions match the values
+        let data_array = DataArray::from_u16(vec![1, 2, 3], vec![2, 2]);
+        assert!(data_array.view::<u16>().is_none());
+        assert!(matches!(
+            data_array.to_physical_ndarray(),
+            Err(FitsError::InvalidHdu(_))
+        ));
+        assert!(matches!(
+            data_array.into_ndarray::<u16>(),
+            Err(FitsError::InvalidHdu(_))
+        ));
+    }
+
+    #[test]
+    fn axis_order() {
+        let values = (0..24).collect::<Vec<i16>>();
+        let data_array = DataArray::from_i16(values, vec![4, 3, 2], Some(100.0), Some(2.0));
+        let view = data_array.view::<i16>().unwrap();
+        let physical = data_array.to_physical_ndarray().unwrap();
+        assert_eq!(view.shape(), &[4, 3, 2]);
+        for z in 0..2 {
+            for y in 0..3 {
+                for x in 0..4 {
+                    let raw = data_array.raw::<i16>(&[x, y, z]).unwrap();
+                    assert_eq!(view[[x, y, z]], raw);
+                    assert_eq!(view[[x, y, z]], (x + 4 * y + 12 * z) as i16);
+                    assert_eq!(view.t()[[z, y, x]], raw);
+                    assert_eq!(
+                        physical[[x, y, z]],
+                        data_array.get::<f64>(&[x, y, z]).unwrap()
+                    );
+                }
+            }
+        }
+        assert!(view.t().is_standard_layout());
+        assert_eq!(
+            view.t().as_ptr(),
+            data_array.as_slice::<i16>().unwrap().as_ptr()
+        );
+    }
+
+    #[test]
+    fn from_ndarray() {
+        let array = Array2::from_shape_fn((3, 2), |(x, y)| (x + 10 * y) as u16).into_dyn();
+        let data_array = DataArray::from(array.clone());
+        assert_eq!(
+            data_array,
+            DataArray::from_u16(vec![0, 1, 2, 10, 11, 12], vec![3, 2])
+        );
+        assert_eq!(data_array.get::<u16>(&[1, 1]), Ok(11));
+        assert_eq!(data_array.view::<u16>().unwrap(), array.view());
+
+        let fits = FitsBuilder::new()
+            .primary(HduBuilder::new(Some(array.into())))
+            .build()
+            .unwrap();
+        let hdu = fits.primary_hdu().unwrap();
+        assert_eq!(hdu.header().dimensions(), &vec![3, 2]);
+        assert_eq!(hdu.header().bzero(), Some(32768.0));
+    }
+}
@@ -0,0 +1,310 @@
+use std::marker::PhantomData;
+
+use crate::parallel;
+
+use super::{
+    data_array::{self, DataArray, PhysicalType, Stored},
+    header::FitsHeader,
+    FitsError,
+};
+
+/// Element types that are stored big-endian in a data unit, one for each BITPIX.
+/// FITS standard section 5.2
+pub trait BigEndian: Copy + Default + Send + Sync + 'static {
+    const BITPIX: i8;
+    /// Converts the first `BITPIX / 8` bytes of `bytes`.
+    fn from_be_slice(bytes: &[u8]) -> Self;
+    fn to_f64(self) -> f64;
+    /// The value as i64 for comparison with BLANK and exact scaling, `None` for floating point
+    /// types.
+    fn to_blank(self) -> Option<i64>;
+}
+
+macro_rules! big_endian {
+    ($t:ty, $bitpix:expr, $to_blank:expr) => {
+        impl BigEndian for $t {
+            const BITPIX: i8 = $bitpix;
+
+            #[inline]
+            fn from_be_slice(bytes: &[u8]) -> Self {
+                const SIZE: usize = std::mem::size_of::<$t>();
+                <$t>::from_be_bytes(bytes[..SIZE].try_into().unwrap())
+            }
+
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            fn to_blank(self) -> Option<i64> {
+                $to_blank(self)
+            }
+        }
+    };
+}
+
+big_endian!(u8, 8, |v| Some(v as i64));
+big_endian!(i16, 16, |v| Some(v as i64));
+big_endian!(i32, 32, |v| Some(v as i64));
+big_endian!(i64, 64, Some);
+big_endian!(f32, -32, |_| None);
+big_endian!(f64, -64, |_| None);
+
+/// Converts all big-endian values in `bytes` at once, a trailing partial value is ignored.
+/// The fixed-size chunks let the compiler swap whole vectors of values at a time.
+/// Large arrays are decoded in parallel with the `rayon` feature.
+pub fn decode<T: BigEndian>(bytes: &[u8]) -> Vec<T> {
+    let size = std::mem::size_of::<T>();
+    let mut values = vec![T::default(); bytes.len() / size];
+    parallel::zip_chunks(&mut values, 1, bytes, size, |values, bytes| {
+        for (value, chunk) in values.iter_mut().zip(bytes.chunks_exact(size)) {
+            *value = T::from_be_slice(chunk);
+        }
+    });
+    values
+}
+
+/// Big-endian values of type `T` in a byte slice, converted to the native byte order on access.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct BigEndianSlice<'a, T> {
+    bytes: &'a [u8],
+    values: PhantomData<T>,
+}
+
+impl<'a, T: BigEndian> BigEndianSlice<'a, T> {
+    const SIZE: usize = std::mem::size_of::<T>();
+
+    /// Fails if the length of `bytes` is not a multiple of the size of `T`.
+    pub fn new(bytes: &'a [u8]) -> Option<Self> {
+        match bytes.len() % Self::SIZE {
+            0 => Some(Self {
+                bytes,
+                values: PhantomData,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len() / Self::SIZE
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<T> {
+        self.bytes
+            .get(index * Self::SIZE..(index + 1) * Self::SIZE)
+            .map(T::from_be_slice)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = T> + 'a {
+        self.bytes.chunks_exact(Self::SIZE).map(T::from_be_slice)
+    }
+
+    pub fn to_vec(&self) -> Vec<T> {
+        decode(self.bytes)
+    }
+}
+
+/// A data array that has not been decoded, i.e. the big-endian bytes of a data unit with the
+/// keywords needed to interpret them. Elements are converted when they are accessed.
+#[derive(PartialEq, Debug, Clone)]
+pub struct DataView<'a> {
+    bytes: &'a [u8],
+    bitpix: i8,
+    dimensions: Vec<u32>,
+    bzero: f64,
+    bscale: f64,
+    blank: Option<i64>,
+}
+
+impl<'a> DataView<'a> {
+    /// The data array described by `header` at the start of `data_unit`, `None` if the header
+    /// has no data array or `data_unit` is too short.
+    pub fn new(header: &impl FitsHeader, data_unit: &'a [u8]) -> Option<Self> {
+        let bitpix = header.bitpix().filter(|_| header.has_data_array())?;
+        let dimensions = header.dimensions().clone();
+        let length = dimensions
+            .iter()
+            .try_fold(bitpix.unsigned_abs() as usize / 8, |product, &d| {
+                product.checked_mul(d as usize)
+            })?;
+        Some(Self {
+            bytes: data_unit.get(..length)?,
+            bitpix,
+            dimensions,
+            bzero: header.bzero().unwrap_or(0.0),
+            bscale: header.bscale().unwrap_or(1.0),
+            blank: header.blank(),
+        })
+    }
+
+    /// The undecoded bytes of the data array.
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    pub fn bitpix(&self) -> i8 {
+        self.bitpix
+    }
+
+    pub fn dimensions(&self) -> &[u32] {
+        &self.dimensions
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len() / (self.bitpix.unsigned_abs() as usize / 8)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// The stored values, if BITPIX matches `T`.
+    pub fn values<T: BigEndian>(&self) -> Option<BigEndianSlice<'a, T>> {
+        match T::BITPIX == self.bitpix {
+            true => BigEndianSlice::new(self.bytes),
+            false => None,
+        }
+    }
+
+    /// The stored value at `position`, without applying BZERO and BSCALE.
+    pub fn raw<T: BigEndian>(&self, position: &[usize]) -> Result<T, FitsError> {
+        let index = data_array::index(&self.dimensions, position)?;
+        let values = self.values::<T>().ok_or_else(|| {
+            FitsError::TypeMismatch(format!(
+                "data array with BITPIX {} has no elements of type {}",
+                self.bitpix,
+                std::any::type_name::<T>()
+            ))
+        })?;
+        Ok(values.get(index).unwrap())
+    }
+
+    /// The physical value at `position`, see [`DataArray::get`]. FITS standard section 4.4.2.5
+    pub fn get<T: PhysicalType>(&self, position: &[usize]) -> Result<T, FitsError> {
+        let index = data_array::index(&self.dimensions, position)?;
+        let stored = match self.bitpix {
+            8 => self.stored::<u8>(index),
+            16 => self.stored::<i16>(index),
+            32 => self.stored::<i32>(index),
+            64 => self.stored::<i64>(index),
+            -32 => self.stored::<f32>(index),
+            _ => self.stored::<f64>(index),
+        };
+        stored
+            .physical(self.bzero, self.bscale)
+            .map_err(|physical| {
+                FitsError::TypeMismatch(format!(
+                    "physical value {} at {:?} cannot be represented as {}",
+                    physical,
+                    position,
+                    std::any::type_name::<T>()
+                ))
+            })
+    }
+
+    /// The stored value at `index`, NaN if it equals BLANK.
+    fn stored<T: BigEndian>(&self, index: usize) -> Stored {
+        let value = self.values::<T>().and_then(|v| v.get(index)).unwrap();
+        match (value.to_blank(), self.blank) {
+            (Some(value), Some(blank)) if value == blank => Stored::Real(f64::NAN),
+            (Some(value), _) => Stored::Integer(value as i128),
+            (None, _) => Stored::Real(value.to_f64()),
+        }
+    }
+
+    /// Decodes all elements, as if the data unit had been parsed.
+    pub fn to_data_array(&self) -> DataArray {
+        let dimensions = self.dimensions.clone();
+        let (bzero, bscale) = (Some(self.bzero), Some(self.bscale));
+        let data_array = match self.bitpix {
+            8 => DataArray::from_u8(self.bytes.to_vec(), dimensions, bzero, bscale),
+            16 => DataArray::from_i16(self.decode(), dimensions, bzero, bscale),
+            32 => DataArray::from_i32(self.decode(), dimensions, bzero, bscale),
+            64 => DataArray::from_i64(self.decode(), dimensions, bzero, bscale),
+            -32 => DataArray::from_f32(self.decode(), dimensions, bzero, bscale),
+            _ => DataArray::from_f64(self.decode(), dimensions, bzero, bscale),
+        };
+        data_array.with_blank(self.blank).into_unsigned()
+    }
+
+    fn decode<T: BigEndian>(&self) -> Vec<T> {
+        self.values().map(|v| v.to_vec()).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        header::Header, keyword::Keyword, keyword_record::KeywordRecord, value::Value,
+    };
+
+    #[test]
+    fn big_endian_slice() {
+        let bytes = [0x01, 0x02, 0xff, 0xfe];
+        let values = BigEndianSlice::<i16>::new(&bytes).unwrap();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values.get(0), Some(0x0102));
+        assert_eq!(values.get(2), None);
+        assert_eq!(values.to_vec(), vec![0x0102, -2]);
+        assert!(BigEndianSlice::<i32>::new(&bytes[..3]).is_none());
+    }
+
+    #[test]
+    fn decode() {
+        let values = [1.5f64, -0.25, f64::MAX];
+        let bytes = values
+            .iter()
+            .flat_map(|v| v.to_be_bytes())
+            .collect::<Vec<u8>>();
+        assert_eq!(super::decode::<f64>(&bytes), values);
+        assert_eq!(super::decode::<i64>(&bytes[..20]).len(), 2);
+        assert_eq!(super::decode::<f32>(&[0x3f, 0x80, 0, 0]), vec![1.0]);
+    }
+
+    #[test]
+    fn data_view() {
+        let header = Header::from(vec![
+            KeywordRecord::new(Keyword::Simple, Value::Logical(true), None),
+            KeywordRecord::new(Keyword::BitPix, Value::Integer(16), None),
+            KeywordRecord::new(Keyword::NAxis, Value::Integer(2), None),
+            KeywordRecord::new(Keyword::NAxisn(1), Value::Integer(2), None),
+            KeywordRecord::new(Keyword::NAxisn(2), Value::Integer(2), None),
+            KeywordRecord::new(Keyword::BZero, Value::Integer(32768), None),
+            KeywordRecord::new(Keyword::Blank, Value::Integer(-1), None),
+        ]);
+        let bytes = [1i16, 2, -1, i16::MIN, 99]
+            .iter()
+            .flat_map(|v| v.to_be_bytes())
+            .collect::<Vec<u8>>();
+        assert!(DataView::new(&header, &bytes[..6]).is_none());
+        let view = DataView::new(&header, &bytes).unwrap();
+        assert_eq!(view.len(), 4);
+        assert_eq!(view.bytes().len(), 8);
+        assert!(view.values::<i32>().is_none());
+        assert_eq!(view.raw::<i16>(&[1, 1]), Ok(i16::MIN));
+        assert_eq!(view.get::<u16>(&[1, 0]), Ok(32770));
+        assert_eq!(view.get::<u16>(&[1, 1]), Ok(0));
+        assert!(view.get::<f64>(&[0, 1]).unwrap().is_nan());
+        assert!(view.get::<u16>(&[0, 1]).is_err());
+        assert!(view.get::<u16>(&[2, 0]).is_err());
+        assert_eq!(
+            view.to_data_array(),
+            DataArray::from_u16(vec![32769, 32770, 32767, 0], vec![2, 2]).with_blank(Some(-1))
+        );
+
+        let header = Header::from(vec![
+            KeywordRecord::new(Keyword::Simple, Value::Logical(true), None),
+            KeywordRecord::new(Keyword::BitPix, Value::Integer(64), None),
+            KeywordRecord::new(Keyword::NAxis, Value::Integer(1), None),
+            KeywordRecord::new(Keyword::NAxisn(1), Value::Integer(1), None),
+        ]);
+        let large = (1i64 << 53) + 1;
+        let bytes = large.to_be_bytes();
+        let view = DataView::new(&header, &bytes).unwrap();
+        assert_eq!(view.get::<i64>(&[0]), Ok(large));
+    }
+}
@@ -1,7 +1,14 @@
 #[cfg(test)]
 use mockall::{automock, predicate::*};
 
-use super::{keyword::Keyword, keyword_record::KeywordRecord, value::Value};
+use std::borrow::Cow;
+
+use time::{Date, Month, PrimitiveDateTime};
+
+use super::{
+    keyword::Keyword, keyword_record::KeywordRecord, value::Value, FitsError, BLOCK_SIZE, CARD_SIZE,
+};
+use crate::parser::value::DATE_FORMAT;
 
 #[derive(PartialEq, Debug, Default)]
 pub enum HeaderKind {
@@ -22,10 +29,40 @@ pub trait FitsHeader {
     fn naxis(&self) -> u16;
     fn bitpix(&self) -> Option<i8>;
     fn dimensions(&self) -> &Vec<u32>;
-    fn bzero(&self) -> Option<f32>;
+    fn bzero(&self) -> Option<f64>;
     fn bscale(&self) -> Option<f64>;
+    /// BLANK, the stored value of undefined pixels in integer arrays. FITS standard section 4.4.2.5
+    fn blank(&self) -> Option<i64>;
     fn pcount(&self) -> Option<u32>;
     fn gcount(&self) -> Option<u32>;
+    /// Whether the primary data array uses the random groups structure, i.e. GROUPS = T and
+    /// NAXIS1 = 0. FITS standard section 6
+    fn groups(&self) -> bool;
+
+    /// Number of bytes in the data unit following the header, including a heap but without
+    /// the padding to a full block. FITS standard section 4.4.1.1
+    ///
+    /// Fails if the data unit, padded to a full block, has more bytes than fit in a `usize`.
+    fn data_size(&self) -> Result<usize, FitsError> {
+        let overflow =
+            || FitsError::InvalidHdu(String::from("the size of the data unit overflows"));
+        let size = match (self.naxis(), self.bitpix()) {
+            (0, _) | (_, None) => 0,
+            (_, Some(bitpix)) => self
+                .dimensions()
+                .iter()
+                // NAXIS1 = 0 does not count for random groups
+                .skip(self.groups() as usize)
+                .try_fold(1usize, |product, &d| product.checked_mul(d as usize))
+                .and_then(|elements| elements.checked_add(self.pcount().unwrap_or(0) as usize))
+                .and_then(|elements| elements.checked_mul(self.gcount().unwrap_or(1) as usize))
+                .and_then(|elements| elements.checked_mul(bitpix.unsigned_abs() as usize / 8))
+                .ok_or_else(overflow)?,
+        };
+        size.checked_next_multiple_of(BLOCK_SIZE)
+            .map(|_| size)
+            .ok_or_else(overflow)
+    }
 }
 
 #[derive(PartialEq, Debug, Default)]
@@ -36,10 +73,12 @@ pub struct Header<'a> {
     naxis: u16,
     bitpix: Option<i8>,
     dimensions: Vec<u32>,
-    bzero: Option<f32>,
+    bzero: Option<f64>,
     bscale: Option<f64>,
+    blank: Option<i64>,
     pcount: Option<u32>,
     gcount: Option<u32>,
+    groups: bool,
 }
 
 impl<'a> FitsHeader for Header<'a> {
@@ -71,7 +110,7 @@ impl<'a> FitsHeader for Header<'a> {
         self.dimensions.as_ref()
     }
 
-    fn bzero(&self) -> Option<f32> {
+    fn bzero(&self) -> Option<f64> {
         self.bzero
     }
 
@@ -79,6 +118,10 @@ impl<'a> FitsHeader for Header<'a> {
         self.bscale
     }
 
+    fn blank(&self) -> Option<i64> {
+        self.blank
+    }
+
     fn pcount(&self) -> Option<u32> {
         self.pcount
     }
@@ -86,6 +129,10 @@ impl<'a> FitsHeader for Header<'a> {
     fn gcount(&self) -> Option<u32> {
         self.gcount
     }
+
+    fn groups(&self) -> bool {
+        self.groups
+    }
 }
 
 impl<'a> Header<'a> {
@@ -97,154 +144,406 @@ impl<'a> Header<'a> {
     }
 
     pub fn from(keyword_records: Vec<KeywordRecord<'a>>) -> Self {
-        let number_of_axes = match keyword_records
-            .iter()
-            .find(|keyword_record| *keyword_record.keyword() == Keyword::NAxis)
-        {
-            Some(record) => {
-                if let Value::Integer(n) = record.value() {
-                    *n as u16
-                } else {
-                    0
-                }
-            }
-            None => 0,
+        let integer = |keyword| match find(&keyword_records, keyword) {
+            Some(Value::Integer(n)) => Some(*n),
+            _ => None,
+        };
+        // integer values such as BZERO = 32768 are common
+        let real = |keyword| match find(&keyword_records, keyword) {
+            Some(Value::Real(n)) => Some(*n),
+            Some(Value::Integer(n)) => Some(*n as f64),
+            _ => None,
         };
+        let number_of_axes = integer(Keyword::NAxis)
+            .and_then(|n| u16::try_from(n).ok())
+            .unwrap_or(0);
+        let is_primary = find(&keyword_records, Keyword::Simple).is_some();
         Self {
-            has_data_array: {
-                keyword_records.iter().any(|keyword_record| {
-                    *keyword_record.keyword() == Keyword::NAxis
-                        && *keyword_record.value() != Value::Integer(0)
-                })
-            },
-            header_kind: {
-                match keyword_records
-                    .iter()
-                    .find(|keyword_record| *keyword_record.keyword() == Keyword::Simple)
-                {
-                    Some(_) => HeaderKind::Primary,
-                    None => {
-                        match keyword_records
-                            .iter()
-                            .find(|keyword_record| *keyword_record.keyword() == Keyword::Xtension)
-                        {
-                            Some(record) => {
-                                if let Value::CharacterString(s) = record.value() {
-                                    match s.as_str() {
-                                        "IMAGE   " => HeaderKind::Image,
-                                        "TABLE   " => HeaderKind::Table,
-                                        "BINTABLE" => HeaderKind::BinaryTable,
-                                        _ => HeaderKind::Other(s.clone()),
-                                    }
-                                } else {
-                                    HeaderKind::Other(String::from("Unknown"))
-                                }
-                            }
-                            None => HeaderKind::Other(String::from("Unknown")),
-                        }
-                    }
-                }
+            has_data_array: find(&keyword_records, Keyword::NAxis)
+                .is_some_and(|value| *value != Value::Integer(0)),
+            header_kind: match (is_primary, find(&keyword_records, Keyword::Xtension)) {
+                (true, _) => HeaderKind::Primary,
+                // trailing spaces are not significant. FITS standard section 4.2.1.1
+                (false, Some(Value::CharacterString(s))) => match s.trim_end() {
+                    "IMAGE" => HeaderKind::Image,
+                    "TABLE" => HeaderKind::Table,
+                    "BINTABLE" => HeaderKind::BinaryTable,
+                    _ => HeaderKind::Other(s.clone()),
+                },
+                (false, _) => HeaderKind::Other(String::from("Unknown")),
             },
             naxis: number_of_axes,
-            bitpix: {
-                match keyword_records
-                    .iter()
-                    .find(|keyword_record| *keyword_record.keyword() == Keyword::BitPix)
-                {
-                    Some(record) => {
-                        if let Value::Integer(n) = record.value() {
-                            Some(*n as i8)
-                        } else {
-                            None
-                        }
-                    }
-                    None => None,
-                }
-            },
-            dimensions: {
-                let mut dimensions = Vec::new();
-                for i in 1..=number_of_axes {
-                    let keyword = Keyword::from(format!("NAXIS{}", i).as_bytes());
-                    if let Some(record) = keyword_records
-                        .iter()
-                        .find(|keyword_record| *keyword_record.keyword() == keyword)
-                    {
-                        if let Value::Integer(n) = record.value() {
-                            dimensions.push(*n as u32);
-                        }
-                    }
-                }
-                dimensions
-            },
-            bzero: {
-                match keyword_records
-                    .iter()
-                    .find(|keyword_record| *keyword_record.keyword() == Keyword::BZero)
-                {
-                    Some(record) => {
-                        if let Value::Real(n) = record.value() {
-                            Some(*n as f32)
-                        } else {
-                            None
-                        }
-                    }
-                    None => None,
-                }
-            },
-            bscale: {
-                match keyword_records
-                    .iter()
-                    .find(|keyword_record| *keyword_record.keyword() == Keyword::BScale)
-                {
-                    Some(record) => {
-                        if let Value::Real(n) = record.value() {
-                            Some(*n)
-                        } else {
-                            None
-                        }
-                    }
-                    None => None,
-                }
-            },
-            pcount: {
-                match keyword_records
-                    .iter()
-                    .find(|keyword_record| *keyword_record.keyword() == Keyword::PCount)
-                {
-                    Some(record) => {
-                        if let Value::Integer(n) = record.value() {
-                            Some(*n as u32)
-                        } else {
-                            None
-                        }
-                    }
-                    None => None,
-                }
-            },
-            gcount: {
-                match keyword_records
-                    .iter()
-                    .find(|keyword_record| *keyword_record.keyword() == Keyword::GCount)
-                {
-                    Some(record) => {
-                        if let Value::Integer(n) = record.value() {
-                            Some(*n as u32)
-                        } else {
-                            None
-                        }
-                    }
-                    None => None,
-                }
-            },
+            bitpix: integer(Keyword::BitPix).and_then(|n| i8::try_from(n).ok()),
+            dimensions: (1..=number_of_axes)
+                .filter_map(|n| integer(Keyword::NAxisn(n)))
+                .filter_map(|n| u32::try_from(n).ok())
+                .collect(),
+            bzero: real(Keyword::BZero),
+            bscale: real(Keyword::BScale),
+            blank: integer(Keyword::Blank),
+            pcount: integer(Keyword::PCount).and_then(|n| u32::try_from(n).ok()),
+            gcount: integer(Keyword::GCount).and_then(|n| u32::try_from(n).ok()),
+            groups: is_primary
+                && find(&keyword_records, Keyword::Groups) == Some(&Value::Logical(true))
+                && number_of_axes > 0
+                && find(&keyword_records, Keyword::NAxisn(1)) == Some(&Value::Integer(0)),
             keyword_records,
         }
     }
+
+    /// The first record with `keyword`.
+    pub fn record(&self, keyword: impl Into<Keyword>) -> Option<&KeywordRecord<'a>> {
+        let keyword = keyword.into();
+        self.keyword_records
+            .iter()
+            .find(|record| *record.keyword() == keyword)
+    }
+
+    /// The value of the first record with `keyword`, e.g. `header.get("OBJECT")`.
+    pub fn get(&self, keyword: impl Into<Keyword>) -> Option<&Value<'a>> {
+        self.record(keyword).map(KeywordRecord::value)
+    }
+
+    pub fn contains(&self, keyword: impl Into<Keyword>) -> bool {
+        self.record(keyword).is_some()
+    }
+
+    /// A character string without trailing blanks, the parts of a continued string are joined.
+    /// FITS standard section 4.2.1
+    pub fn get_str(&self, keyword: impl Into<Keyword>) -> Result<Cow<'_, str>, FitsError> {
+        let (keyword, value) = self.value(keyword)?;
+        match value {
+            Value::CharacterString(s) => Ok(Cow::Borrowed(s.trim_end())),
+            Value::ContinuedString(parts) => Ok(Cow::Owned(parts.concat().trim_end().to_string())),
+            _ => Err(wrong_value_type(keyword, "a character string", value)),
+        }
+    }
+
+    /// A real or integer value as f64. FITS standard sections 4.2.3 and 4.2.4
+    pub fn get_f64(&self, keyword: impl Into<Keyword>) -> Result<f64, FitsError> {
+        let (keyword, value) = self.value(keyword)?;
+        match value {
+            Value::Real(r) => Ok(*r),
+            Value::Integer(i) => Ok(*i as f64),
+            _ => Err(wrong_value_type(keyword, "a number", value)),
+        }
+    }
+
+    /// An integer value, real values are accepted if they have no fractional part.
+    /// FITS standard section 4.2.3
+    pub fn get_i64(&self, keyword: impl Into<Keyword>) -> Result<i64, FitsError> {
+        let (keyword, value) = self.value(keyword)?;
+        match value {
+            Value::Integer(i) => Ok(*i),
+            Value::Real(r) if r.fract() == 0.0 && r.abs() < i64::MAX as f64 => Ok(*r as i64),
+            _ => Err(wrong_value_type(keyword, "an integer", value)),
+        }
+    }
+
+    /// A logical value. FITS standard section 4.2.2
+    pub fn get_bool(&self, keyword: impl Into<Keyword>) -> Result<bool, FitsError> {
+        let (keyword, value) = self.value(keyword)?;
+        match value {
+            Value::Logical(b) => Ok(*b),
+            _ => Err(wrong_value_type(keyword, "a logical value", value)),
+        }
+    }
+
+    /// A date in ISO-8601 format `CCYY-MM-DD[Thh:mm:ss[.s...]]`, or in the deprecated format
+    /// `DD/MM/YY` that refers to the years 1900 - 1999. FITS standard section 4.4.2.1
+    pub fn get_date(&self, keyword: impl Into<Keyword>) -> Result<PrimitiveDateTime, FitsError> {
+        let (keyword, value) = self.value(keyword)?;
+        match value {
+            Value::Date(date) => Ok(*date),
+            Value::CharacterString(s) => {
+                parse_date(s.trim()).ok_or_else(|| wrong_value_type(keyword, "a date", value))
+            }
+            _ => Err(wrong_value_type(keyword, "a date", value)),
+        }
+    }
+
+    /// The text of all COMMENT records, in order. FITS standard section 4.4.2.4
+    pub fn comments(&self) -> impl Iterator<Item = &str> {
+        self.commentary(Keyword::Comment)
+    }
+
+    /// The text of all HISTORY records, in order. FITS standard section 4.4.2.4
+    pub fn history(&self) -> impl Iterator<Item = &str> {
+        self.commentary(Keyword::History)
+    }
+
+    /// All records of an indexed keyword with their index n, in the order of the header, e.g.
+    /// `header.indexed(Keyword::TTypen)` for the names of all table fields.
+    pub fn indexed(
+        &self,
+        keyword: fn(u16) -> Keyword,
+    ) -> impl Iterator<Item = (u16, &KeywordRecord<'a>)> {
+        self.keyword_records.iter().filter_map(move |record| {
+            let n = record.keyword().index()?;
+            (*record.keyword() == keyword(n)).then_some((n, record))
+        })
+    }
+
+    /// Sets the value and comment of `keyword`. An existing record is replaced in place, a new
+    /// one is added before END. Commentary keywords are appended with [`Header::append_comment`]
+    /// and [`Header::append_history`] instead.
+    pub fn set(&mut self, keyword: impl Into<Keyword>, value: Value<'a>, comment: Option<&'a str>) {
+        let record = KeywordRecord::new(keyword.into(), value, comment);
+        match self.position(record.keyword()) {
+            Some(n) => self.keyword_records[n] = record,
+            None => self.keyword_records.insert(self.end(), record),
+        }
+        self.update();
+    }
+
+    /// Sets the value of `keyword` and keeps its comment.
+    pub fn set_value(&mut self, keyword: impl Into<Keyword>, value: Value<'a>) {
+        let keyword = keyword.into();
+        let comment = self.record(&keyword).and_then(|record| *record.comment());
+        self.set(keyword, value, comment);
+    }
+
+    /// Inserts `record` at `index`, i.e. before the record that is there now. A record with the
+    /// same keyword is moved, unless it is a commentary keyword that may appear more than once.
+    /// Reserved keywords are placed where the standard requires them regardless of `index`.
+    pub fn insert(&mut self, index: usize, record: KeywordRecord<'a>) {
+        let mut index = index.min(self.keyword_records.len());
+        if let Some(n) = self
+            .position(record.keyword())
+            .filter(|_| !is_commentary(record.keyword()))
+        {
+            self.keyword_records.remove(n);
+            if n < index {
+                index -= 1;
+            }
+        }
+        self.keyword_records.insert(index, record);
+        self.update();
+    }
+
+    /// Inserts `record` before the first record with `keyword`, see [`Header::insert`].
+    pub fn insert_before(
+        &mut self,
+        keyword: impl Into<Keyword>,
+        record: KeywordRecord<'a>,
+    ) -> Result<(), FitsError> {
+        let keyword = keyword.into();
+        let n = self
+            .position(&keyword)
+            .ok_or(FitsError::KeywordNotFound(keyword))?;
+        self.insert(n, record);
+        Ok(())
+    }
+
+    /// Inserts `record` after the first record with `keyword`, see [`Header::insert`].
+    pub fn insert_after(
+        &mut self,
+        keyword: impl Into<Keyword>,
+        record: KeywordRecord<'a>,
+    ) -> Result<(), FitsError> {
+        let keyword = keyword.into();
+        let n = self
+            .position(&keyword)
+            .ok_or(FitsError::KeywordNotFound(keyword))?;
+        self.insert(n + 1, record);
+        Ok(())
+    }
+
+    /// Removes the first record with `keyword`. Mandatory keywords cannot be removed.
+    pub fn remove(
+        &mut self,
+        keyword: impl Into<Keyword>,
+    ) -> Result<Option<KeywordRecord<'a>>, FitsError> {
+        let keyword = keyword.into();
+        if self.is_mandatory(&keyword) {
+            return Err(FitsError::MandatoryKeyword(keyword));
+        }
+        let record = self
+            .position(&keyword)
+            .map(|n| self.keyword_records.remove(n));
+        self.update();
+        Ok(record)
+    }
+
+    /// Adds COMMENT records before END, long text is continued on further records.
+    pub fn append_comment(&mut self, text: &str) {
+        self.append_commentary(Keyword::Comment, text);
+    }
+
+    /// Adds HISTORY records before END, long text is continued on further records.
+    pub fn append_history(&mut self, text: &str) {
+        self.append_commentary(Keyword::History, text);
+    }
+
+    /// Whether `keyword` is required for this kind of HDU. FITS standard sections 4.4.1, 6.1
+    /// and 7
+    pub fn is_mandatory(&self, keyword: impl Into<Keyword>) -> bool {
+        let is_table = matches!(
+            self.header_kind,
+            HeaderKind::Table | HeaderKind::BinaryTable
+        );
+        let fields = || self.get_i64(Keyword::Tfields).unwrap_or(0);
+        match keyword.into() {
+            Keyword::Simple
+            | Keyword::Xtension
+            | Keyword::BitPix
+            | Keyword::NAxis
+            | Keyword::End => true,
+            Keyword::NAxisn(n) => n <= self.naxis,
+            Keyword::PCount | Keyword::GCount => {
+                self.header_kind != HeaderKind::Primary || self.groups
+            }
+            Keyword::Groups => self.groups,
+            Keyword::Tfields => is_table,
+            Keyword::TFormn(n) => is_table && i64::from(n) <= fields(),
+            Keyword::TBcoln(n) => self.header_kind == HeaderKind::Table && i64::from(n) <= fields(),
+            _ => false,
+        }
+    }
+
+    fn append_commentary(&mut self, keyword: Keyword, text: &str) {
+        let text = text.chars().collect::<Vec<_>>();
+        let end = self.end();
+        let records = text
+            .chunks(CARD_SIZE - 8)
+            .map(|chunk| chunk.iter().collect::<String>())
+            .chain(text.is_empty().then(String::new))
+            .map(|text| KeywordRecord::new(keyword.clone(), Value::Unknown(text), None));
+        self.keyword_records.splice(end..end, records);
+    }
+
+    fn position(&self, keyword: &Keyword) -> Option<usize> {
+        self.keyword_records
+            .iter()
+            .position(|record| record.keyword() == keyword)
+    }
+
+    /// The index of END, or the number of records if there is none.
+    fn end(&self) -> usize {
+        self.position(&Keyword::End)
+            .unwrap_or(self.keyword_records.len())
+    }
+
+    /// Moves the reserved keywords to the start and END to the end of the header, and derives
+    /// the mandatory values again. FITS standard section 4.4.1
+    fn update(&mut self) {
+        let mut records = std::mem::take(&mut self.keyword_records);
+        let layout = match (
+            find(&records, Keyword::Simple),
+            find(&records, Keyword::Xtension),
+        ) {
+            (Some(_), _) => match find(&records, Keyword::Groups) {
+                Some(Value::Logical(true)) => Layout::RandomGroups,
+                _ => Layout::Primary,
+            },
+            (None, Some(Value::CharacterString(s)))
+                if matches!(s.trim_end(), "TABLE" | "BINTABLE") =>
+            {
+                Layout::Table
+            }
+            (None, _) => Layout::Extension,
+        };
+        records.sort_by_key(|record| order(record.keyword(), layout));
+        *self = Header::from(records);
+    }
+
+    fn value(&self, keyword: impl Into<Keyword>) -> Result<(Keyword, &Value<'a>), FitsError> {
+        let keyword = keyword.into();
+        match self.get(&keyword) {
+            Some(value) => Ok((keyword, value)),
+            None => Err(FitsError::KeywordNotFound(keyword)),
+        }
+    }
+
+    /// The text of commentary keywords is bytes 9 to 80 of the card, which may have been parsed
+    /// as any kind of value. FITS standard section 4.4.2.4
+    fn commentary(&self, keyword: Keyword) -> impl Iterator<Item = &str> {
+        self.keyword_records
+            .iter()
+            .filter(move |record| *record.keyword() == keyword)
+            .map(|record| match (record.card(), record.value()) {
+                (Some(card), _) => std::str::from_utf8(&card[8..]).unwrap_or("").trim_end(),
+                (None, Value::CharacterString(s) | Value::Unknown(s)) => s.trim_end(),
+                _ => "",
+            })
+    }
+}
+
+fn find<'r, 'a>(records: &'r [KeywordRecord<'a>], keyword: Keyword) -> Option<&'r Value<'a>> {
+    records
+        .iter()
+        .find(|record| *record.keyword() == keyword)
+        .map(KeywordRecord::value)
+}
+
+/// The mandatory keywords at the start of a header differ between primary HDUs, random groups
+/// and extensions. FITS standard sections 4.4.1, 6.1.1, 7.2.1 and 7.3.1
+#[derive(Clone, Copy, PartialEq)]
+enum Layout {
+    Primary,
+    RandomGroups,
+    Table,
+    Extension,
+}
+
+/// Records sort as the reserved keywords in their required order, all other records in their
+/// current order and END last.
+fn order(keyword: &Keyword, layout: Layout) -> (u8, u8, u16) {
+    let is_extension = matches!(layout, Layout::Table | Layout::Extension);
+    match keyword {
+        Keyword::Simple | Keyword::Xtension => (0, 0, 0),
+        Keyword::BitPix => (0, 1, 0),
+        Keyword::NAxis => (0, 2, 0),
+        Keyword::NAxisn(n) => (0, 3, *n),
+        Keyword::Extend if layout == Layout::Primary => (0, 4, 0),
+        Keyword::Groups if layout == Layout::RandomGroups => (0, 4, 0),
+        Keyword::PCount if is_extension || layout == Layout::RandomGroups => (0, 5, 0),
+        Keyword::GCount if is_extension || layout == Layout::RandomGroups => (0, 6, 0),
+        Keyword::Tfields if layout == Layout::Table => (0, 7, 0),
+        Keyword::End => (2, 0, 0),
+        _ => (1, 0, 0),
+    }
+}
+
+/// Keywords that may appear more than once. FITS standard section 4.4.2.4
+fn is_commentary(keyword: &Keyword) -> bool {
+    matches!(
+        keyword,
+        Keyword::Comment | Keyword::History | Keyword::Empty
+    )
+}
+
+fn wrong_value_type(keyword: Keyword, expected: &'static str, value: &Value) -> FitsError {
+    FitsError::WrongValueType {
+        keyword,
+        expected,
+        value: value.to_string(),
+    }
+}
+
+fn parse_date(s: &str) -> Option<PrimitiveDateTime> {
+    if let Ok(date) = PrimitiveDateTime::parse(s, &DATE_FORMAT) {
+        return Some(date);
+    }
+    let mut parts = s.split('/').map(|part| part.parse::<u8>().ok());
+    match (parts.next()?, parts.next()?, parts.next()?, parts.next()) {
+        (Some(day), Some(month), Some(year), None) if year < 100 => {
+            let month = Month::try_from(month).ok()?;
+            Date::from_calendar_date(1900 + year as i32, month, day)
+                .ok()
+                .map(|date| date.midnight())
+        }
+        _ => None,
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::vec;
 
+    use proptest::prelude::*;
+    use time::macros::datetime;
+
     use super::*;
 
     #[test]
@@ -260,6 +559,251 @@ mod tests {
         assert_eq!(header.bscale(), None);
     }
 
+    #[test]
+    fn keyword_lookup() {
+        let header = Header::from(vec![
+            KeywordRecord::new(Keyword::Simple, Value::Logical(true), None),
+            KeywordRecord::new(Keyword::BitPix, Value::Integer(8), None),
+            KeywordRecord::new(Keyword::NAxis, Value::Real(0.0), None),
+            KeywordRecord::new(
+                Keyword::Object,
+                Value::CharacterString("M31  ".into()),
+                None,
+            ),
+            KeywordRecord::new(
+                Keyword::Unknown(*b"LONGSTR "),
+                Value::ContinuedString(vec!["first ".into(), "second".into()]),
+                None,
+            ),
+            KeywordRecord::new(Keyword::Equinox, Value::Integer(2000), None),
+            KeywordRecord::new(Keyword::from("ESO DET CHIP TEMP"), Value::Real(120.5), None),
+            KeywordRecord::new(
+                Keyword::Date,
+                Value::CharacterString("29/01/84".into()),
+                None,
+            ),
+            KeywordRecord::new(
+                Keyword::DateObs,
+                Value::CharacterString("2019-01-01T12:30:00".into()),
+                None,
+            ),
+            KeywordRecord::new(Keyword::Comment, Value::Unknown("first  ".into()), None),
+            KeywordRecord::new(Keyword::TTypen(2), Value::CharacterString("B".into()), None),
+            KeywordRecord::new(Keyword::TFormn(1), Value::CharacterString("I".into()), None),
+            KeywordRecord::new(Keyword::TTypen(1), Value::CharacterString("A".into()), None),
+            KeywordRecord::new(
+                Keyword::Comment,
+                Value::CharacterString("second".into()),
+                None,
+            ),
+        ]);
+        assert!(header.contains("OBJECT"));
+        assert!(header.contains(Keyword::Simple));
+        assert!(!header.contains(Keyword::Telescop));
+        assert_eq!(header.get(Keyword::BitPix), Some(&Value::Integer(8)));
+        assert_eq!(header.get_str("OBJECT").unwrap(), "M31");
+        assert_eq!(header.get_str("LONGSTR").unwrap(), "first second");
+        assert_eq!(header.get_f64("EQUINOX"), Ok(2000.0));
+        assert_eq!(header.get_f64("HIERARCH ESO DET CHIP TEMP"), Ok(120.5));
+        assert!(header.contains("ESO DET CHIP TEMP"));
+        assert_eq!(header.get_i64(Keyword::NAxis), Ok(0));
+        assert_eq!(header.get_bool("SIMPLE"), Ok(true));
+        assert_eq!(header.get_date("DATE"), Ok(datetime!(1984-01-29 0:00)));
+        assert_eq!(header.get_date("DATE-OBS"), Ok(datetime!(2019-01-01 12:30)));
+        assert_eq!(
+            header.get_i64("TELESCOP"),
+            Err(FitsError::KeywordNotFound(Keyword::Telescop))
+        );
+        assert_eq!(
+            header.get_bool("BITPIX"),
+            Err(FitsError::WrongValueType {
+                keyword: Keyword::BitPix,
+                expected: "a logical value",
+                value: String::from("8"),
+            })
+        );
+        assert!(header.get_date("OBJECT").is_err());
+        assert!(header.get_str("SIMPLE").is_err());
+        assert_eq!(header.comments().collect::<Vec<_>>(), ["first", "second"]);
+        assert_eq!(header.history().count(), 0);
+        let names = header
+            .indexed(Keyword::TTypen)
+            .map(|(n, record)| (n, record.value()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                (2, &Value::CharacterString("B".into())),
+                (1, &Value::CharacterString("A".into()))
+            ]
+        );
+    }
+
+    #[test]
+    fn edit_header() {
+        let mut header = Header::from(vec![
+            KeywordRecord::new(Keyword::Simple, Value::Logical(true), None),
+            KeywordRecord::new(Keyword::BitPix, Value::Integer(16), None),
+            KeywordRecord::new(Keyword::NAxis, Value::Integer(0), Some("number of axes")),
+            KeywordRecord::new(Keyword::End, Value::CharacterString(String::new()), None),
+        ]);
+        header.set(Keyword::Object, Value::CharacterString("M31".into()), None);
+        header.set_value("NAXIS", Value::Integer(2));
+        header.set(Keyword::NAxisn(2), Value::Integer(3), None);
+        header.set(Keyword::NAxisn(1), Value::Integer(5), None);
+        header.set(Keyword::Extend, Value::Logical(true), None);
+        header.set(Keyword::BZero, Value::Integer(32768), None);
+        let names = |header: &Header| {
+            header
+                .keyword_records()
+                .iter()
+                .map(|record| record.keyword().name())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(&header),
+            ["SIMPLE", "BITPIX", "NAXIS", "NAXIS1", "NAXIS2", "EXTEND", "OBJECT", "BZERO", "END"]
+        );
+        assert_eq!(
+            header.record("NAXIS").unwrap().comment(),
+            &Some("number of axes")
+        );
+        assert!(header.has_data_array());
+        assert_eq!(header.dimensions(), &vec![5, 3]);
+        assert_eq!(header.bzero(), Some(32768.0));
+
+        header
+            .insert_before(
+                "OBJECT",
+                KeywordRecord::new(Keyword::Telescop, Value::Integer(1), None),
+            )
+            .unwrap();
+        header
+            .insert_after(
+                "BZERO",
+                KeywordRecord::new(Keyword::Object, Value::Integer(2), None),
+            )
+            .unwrap();
+        header.insert(
+            0,
+            KeywordRecord::new(Keyword::Origin, Value::Integer(3), None),
+        );
+        assert_eq!(
+            names(&header)[5..],
+            ["EXTEND", "ORIGIN", "TELESCOP", "BZERO", "OBJECT", "END"]
+        );
+        assert_eq!(
+            header.insert_after(
+                "AUTHOR",
+                KeywordRecord::new(Keyword::Date, Value::Integer(4), None)
+            ),
+            Err(FitsError::KeywordNotFound(Keyword::Author))
+        );
+
+        assert_eq!(
+            header.remove("BITPIX"),
+            Err(FitsError::MandatoryKeyword(Keyword::BitPix))
+        );
+        assert!(header.remove(Keyword::NAxisn(2)).is_err());
+        assert!(header.remove("BZERO").unwrap().is_some());
+        assert_eq!(header.remove("BZERO"), Ok(None));
+        assert_eq!(header.bzero(), None);
+        header.set_value("NAXIS", Value::Integer(1));
+        assert!(header.remove(Keyword::NAxisn(2)).unwrap().is_some());
+        assert_eq!(header.dimensions(), &vec![5]);
+
+        header.append_history(&"x".repeat(100));
+        header.append_comment("");
+        header.append_comment("done");
+        assert_eq!(header.history().map(str::len).collect::<Vec<_>>(), [72, 28]);
+        assert_eq!(header.comments().collect::<Vec<_>>(), ["", "done"]);
+        assert_eq!(
+            header.keyword_records().last().unwrap().keyword(),
+            &Keyword::End
+        );
+    }
+
+    #[test]
+    fn edit_table_header() {
+        let mut header = Header::from(vec![
+            KeywordRecord::new(
+                Keyword::Xtension,
+                Value::CharacterString("BINTABLE".into()),
+                None,
+            ),
+            KeywordRecord::new(Keyword::BitPix, Value::Integer(8), None),
+            KeywordRecord::new(Keyword::NAxis, Value::Integer(2), None),
+            KeywordRecord::new(Keyword::NAxisn(1), Value::Integer(4), None),
+            KeywordRecord::new(Keyword::NAxisn(2), Value::Integer(1), None),
+            KeywordRecord::new(Keyword::PCount, Value::Integer(0), None),
+            KeywordRecord::new(Keyword::GCount, Value::Integer(1), None),
+            KeywordRecord::new(Keyword::Tfields, Value::Integer(1), None),
+            KeywordRecord::new(
+                Keyword::TFormn(1),
+                Value::CharacterString("1J".into()),
+                None,
+            ),
+            KeywordRecord::new(Keyword::End, Value::CharacterString(String::new()), None),
+        ]);
+        header.insert(
+            0,
+            KeywordRecord::new(Keyword::Origin, Value::Integer(1), None),
+        );
+        header
+            .insert_before(
+                "TFIELDS",
+                KeywordRecord::new(Keyword::Object, Value::Integer(2), None),
+            )
+            .unwrap();
+        let names = header
+            .keyword_records()
+            .iter()
+            .map(|record| record.keyword().name())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names[5..],
+            ["PCOUNT", "GCOUNT", "TFIELDS", "OBJECT", "ORIGIN", "TFORM1", "END"]
+        );
+        assert_eq!(header.header_kind(), &HeaderKind::BinaryTable);
+
+        // GROUPS, PCOUNT and GCOUNT follow NAXISn in a random groups primary header
+        let mut header = Header::from(vec![
+            KeywordRecord::new(Keyword::Simple, Value::Logical(true), None),
+            KeywordRecord::new(Keyword::BitPix, Value::Integer(8), None),
+            KeywordRecord::new(Keyword::NAxis, Value::Integer(2), None),
+            KeywordRecord::new(Keyword::NAxisn(1), Value::Integer(0), None),
+            KeywordRecord::new(Keyword::NAxisn(2), Value::Integer(2), None),
+            KeywordRecord::new(Keyword::Groups, Value::Logical(true), None),
+            KeywordRecord::new(Keyword::PCount, Value::Integer(0), None),
+            KeywordRecord::new(Keyword::GCount, Value::Integer(1), None),
+        ]);
+        header.insert(
+            0,
+            KeywordRecord::new(Keyword::Origin, Value::Integer(1), None),
+        );
+        assert_eq!(header.keyword_records()[5].keyword(), &Keyword::Groups);
+        assert_eq!(header.keyword_records()[8].keyword(), &Keyword::Origin);
+        assert!(header.groups());
+    }
+
+    #[test]
+    fn mandatory_keywords() {
+        let header = Header::from(vec![
+            KeywordRecord::new(
+                Keyword::Xtension,
+                Value::CharacterString("TABLE   ".into()),
+                None,
+            ),
+            KeywordRecord::new(Keyword::NAxis, Value::Integer(2), None),
+            KeywordRecord::new(Keyword::Tfields, Value::Integer(2), None),
+        ]);
+        assert!(header.is_mandatory(Keyword::PCount));
+        assert!(header.is_mandatory(Keyword::TBcoln(2)));
+        assert!(!header.is_mandatory(Keyword::TBcoln(3)));
+        assert!(!header.is_mandatory(Keyword::Groups));
+        assert!(!header.is_mandatory(Keyword::Extend));
+    }
+
     #[test]
     fn header_from_keyword_records() {
         let keyword_records = vec![
@@ -292,6 +836,26 @@ mod tests {
         assert_eq!(header.dimensions(), &Vec::new());
         assert_eq!(header.bzero(), Some(0.0));
         assert_eq!(header.bscale(), Some(1.0));
+
+        let header = Header::from(vec![
+            KeywordRecord::new(Keyword::BZero, Value::Integer(2147483648), None),
+            KeywordRecord::new(Keyword::BScale, Value::Integer(1), None),
+        ]);
+        assert_eq!(header.bzero(), Some(2147483648.0));
+        assert_eq!(header.bscale(), Some(1.0));
+        let header = Header::from(vec![KeywordRecord::new(
+            Keyword::BZero,
+            Value::Real(9223372036854775808.0),
+            None,
+        )]);
+        assert_eq!(header.bzero(), Some(9223372036854775808.0));
+        assert_eq!(header.blank(), None);
+        let header = Header::from(vec![KeywordRecord::new(
+            Keyword::Blank,
+            Value::Integer(-32768),
+            None,
+        )]);
+        assert_eq!(header.blank(), Some(-32768));
     }
     #[test]
     fn image_extension_from_keyword_records() {
@@ -368,5 +932,137 @@ mod tests {
         assert_eq!(header.gcount(), Some(1));
         assert_eq!(header.bzero(), None);
         assert_eq!(header.bscale(), None);
+        assert_eq!(header.data_size(), Ok(4));
+    }
+
+    #[test]
+    fn extension_kind_ignores_trailing_spaces() {
+        // trailing spaces of XTENSION are not significant. FITS standard section 4.2.1.1
+        for (xtension, kind) in [
+            ("IMAGE", HeaderKind::Image),
+            ("IMAGE   ", HeaderKind::Image),
+            ("TABLE", HeaderKind::Table),
+            ("TABLE   ", HeaderKind::Table),
+            ("BINTABLE", HeaderKind::BinaryTable),
+            ("A3DTABLE", HeaderKind::Other(String::from("A3DTABLE"))),
+        ] {
+            let header = Header::from(vec![KeywordRecord::new(
+                Keyword::Xtension,
+                Value::CharacterString(String::from(xtension)),
+                None,
+            )]);
+            assert_eq!(header.header_kind(), &kind, "{}", xtension);
+        }
+    }
+
+    #[test]
+    fn data_size() {
+        let header = Header::from(vec![
+            KeywordRecord::new(
+                Keyword::Xtension,
+                Value::CharacterString(String::from("BINTABLE")),
+                None,
+            ),
+            KeywordRecord::new(Keyword::BitPix, Value::Integer(8), None),
+            KeywordRecord::new(Keyword::NAxis, Value::Integer(2), None),
+            KeywordRecord::new(Keyword::NAxisn(1), Value::Integer(12), None),
+            KeywordRecord::new(Keyword::NAxisn(2), Value::Integer(10), None),
+            KeywordRecord::new(Keyword::PCount, Value::Integer(100), None),
+            KeywordRecord::new(Keyword::GCount, Value::Integer(1), None),
+        ]);
+        assert_eq!(header.data_size(), Ok(220));
+
+        let header = Header::from(vec![
+            KeywordRecord::new(Keyword::Simple, Value::Logical(true), None),
+            KeywordRecord::new(Keyword::BitPix, Value::Integer(-64), None),
+            KeywordRecord::new(Keyword::NAxis, Value::Integer(2), None),
+            KeywordRecord::new(Keyword::NAxisn(1), Value::Integer(3), None),
+            KeywordRecord::new(Keyword::NAxisn(2), Value::Integer(5), None),
+        ]);
+        assert_eq!(header.data_size(), Ok(120));
+        assert!(!header.groups());
+        assert_eq!(Header::new().data_size(), Ok(0));
+
+        let header = Header::from(vec![
+            KeywordRecord::new(Keyword::Simple, Value::Logical(true), None),
+            KeywordRecord::new(Keyword::BitPix, Value::Integer(-32), None),
+            KeywordRecord::new(Keyword::NAxis, Value::Integer(3), None),
+            KeywordRecord::new(Keyword::NAxisn(1), Value::Integer(0), None),
+            KeywordRecord::new(Keyword::NAxisn(2), Value::Integer(3), None),
+            KeywordRecord::new(Keyword::NAxisn(3), Value::Integer(4), None),
+            KeywordRecord::new(Keyword::Groups, Value::Logical(true), None),
+            KeywordRecord::new(Keyword::PCount, Value::Integer(6), None),
+            KeywordRecord::new(Keyword::GCount, Value::Integer(10), None),
+        ]);
+        assert!(header.groups());
+        assert_eq!(header.data_size(), Ok(4 * 10 * (6 + 12)));
+    }
+
+    #[test]
+    fn out_of_range_integers() {
+        let header = Header::from(vec![
+            KeywordRecord::new(Keyword::Simple, Value::Logical(true), None),
+            KeywordRecord::new(Keyword::BitPix, Value::Integer(264), None),
+            KeywordRecord::new(Keyword::NAxis, Value::Integer(-1), None),
+        ]);
+        assert_eq!(header.bitpix(), None);
+        assert_eq!(header.naxis(), 0);
+
+        let header = Header::from(vec![
+            KeywordRecord::new(
+                Keyword::Xtension,
+                Value::CharacterString(String::from("BINTABLE")),
+                None,
+            ),
+            KeywordRecord::new(Keyword::BitPix, Value::Integer(8), None),
+            KeywordRecord::new(Keyword::NAxis, Value::Integer(1), None),
+            KeywordRecord::new(Keyword::NAxisn(1), Value::Integer(1 << 32), None),
+            KeywordRecord::new(Keyword::PCount, Value::Integer(-1), None),
+            KeywordRecord::new(Keyword::GCount, Value::Integer(1), None),
+        ]);
+        assert!(header.dimensions().is_empty());
+        assert_eq!(header.pcount(), None);
+    }
+
+    proptest! {
+        #[test]
+        fn data_size_does_not_overflow(
+            bitpix in prop::sample::select(vec![8i64, 16, 32, 64, -32, -64]),
+            dimensions in prop::collection::vec(any::<u32>(), 1..=4),
+            pcount in any::<u32>(),
+            gcount in any::<u32>(),
+        ) {
+            let mut records = vec![
+                KeywordRecord::new(
+                    Keyword::Xtension,
+                    Value::CharacterString(String::from("BINTABLE")),
+                    None,
+                ),
+                KeywordRecord::new(Keyword::BitPix, Value::Integer(bitpix), None),
+                KeywordRecord::new(Keyword::NAxis, Value::Integer(dimensions.len() as i64), None),
+            ];
+            for (n, &d) in dimensions.iter().enumerate() {
+                records.push(KeywordRecord::new(
+                    Keyword::NAxisn(n as u16 + 1),
+                    Value::Integer(d as i64),
+                    None,
+                ));
+            }
+            records.push(KeywordRecord::new(Keyword::PCount, Value::Integer(pcount as i64), None));
+            records.push(KeywordRecord::new(Keyword::GCount, Value::Integer(gcount as i64), None));
+            let header = Header::from(records);
+
+            // the same size without overflow, or None if it does not fit in 128 bits
+            let expected = dimensions
+                .iter()
+                .try_fold(1u128, |product, &d| product.checked_mul(d as u128))
+                .and_then(|elements| elements.checked_add(pcount as u128))
+                .and_then(|elements| elements.checked_mul(gcount as u128))
+                .and_then(|elements| elements.checked_mul(bitpix.unsigned_abs() as u128 / 8));
+            match expected.filter(|&size| size.div_ceil(2880) * 2880 <= usize::MAX as u128) {
+                Some(size) => prop_assert_eq!(header.data_size(), Ok(size as usize)),
+                None => prop_assert!(header.data_size().is_err()),
+            }
+        }
     }
 }
@@ -2,7 +2,7 @@ use std::fmt;
 
 use tracing::{error, instrument};
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Keyword {
     Author,
     BScale,
@@ -32,6 +32,9 @@ pub enum Keyword {
     FZTileLn,
     GCount,
     Groups,
+    /// A keyword of the ESO HIERARCH convention, the name without `HIERARCH` with words separated
+    /// by single blanks, e.g. `ESO DET CHIP TEMP`.
+    Hierarch(String),
     History,
     Inherit,
     Instrume,
@@ -124,6 +127,134 @@ impl Keyword {
         }
     }
 
+    /// The keyword name as it appears in bytes 1 to 8 of a keyword record, without trailing spaces.
+    pub fn name(&self) -> String {
+        match self {
+            Keyword::Author => String::from("AUTHOR"),
+            Keyword::BScale => String::from("BSCALE"),
+            Keyword::BUnit => String::from("BUNIT"),
+            Keyword::BZero => String::from("BZERO"),
+            Keyword::BitPix => String::from("BITPIX"),
+            Keyword::Blank => String::from("BLANK"),
+            Keyword::Blocked => String::from("BLOCKED"),
+            Keyword::CheckSum => String::from("CHECKSUM"),
+            Keyword::Comment => String::from("COMMENT"),
+            Keyword::Continue => String::from("CONTINUE"),
+            Keyword::DataMax => String::from("DATAMAX"),
+            Keyword::DataMin => String::from("DATAMIN"),
+            Keyword::DataSum => String::from("DATASUM"),
+            Keyword::Date => String::from("DATE"),
+            Keyword::DateObs => String::from("DATE-OBS"),
+            Keyword::Empty => String::from(""),
+            Keyword::End => String::from("END"),
+            Keyword::Epoch => String::from("EPOCH"),
+            Keyword::Equinox => String::from("EQUINOX"),
+            Keyword::ExtLevel => String::from("EXTLEVEL"),
+            Keyword::ExtName => String::from("EXTNAME"),
+            Keyword::ExtVer => String::from("EXTVER"),
+            Keyword::Extend => String::from("EXTEND"),
+            Keyword::FZALGn(n) => format!("FZALG{}", n),
+            Keyword::FZAlgor => String::from("FZALGOR"),
+            Keyword::FZTileLn => String::from("FZTILELN"),
+            Keyword::GCount => String::from("GCOUNT"),
+            Keyword::Groups => String::from("GROUPS"),
+            Keyword::Hierarch(name) => format!("HIERARCH {}", name),
+            Keyword::History => String::from("HISTORY"),
+            Keyword::Inherit => String::from("INHERIT"),
+            Keyword::Instrume => String::from("INSTRUME"),
+            Keyword::NAxis => String::from("NAXIS"),
+            Keyword::NAxisn(n) => format!("NAXIS{}", n),
+            Keyword::Object => String::from("OBJECT"),
+            Keyword::Obs => String::from("OBS"),
+            Keyword::Observer => String::from("OBSERVER"),
+            Keyword::Origin => String::from("ORIGIN"),
+            Keyword::PCount => String::from("PCOUNT"),
+            Keyword::PScaln(n) => format!("PSCAL{}", n),
+            Keyword::PTypen(n) => format!("PTYPE{}", n),
+            Keyword::PZeron(n) => format!("PZERO{}", n),
+            Keyword::Referenc => String::from("REFERENC"),
+            Keyword::Simple => String::from("SIMPLE"),
+            Keyword::TBcoln(n) => format!("TBCOL{}", n),
+            Keyword::TDMaxn(n) => format!("TDMAX{}", n),
+            Keyword::TDMinn(n) => format!("TDMIN{}", n),
+            Keyword::TDimn(n) => format!("TDIM{}", n),
+            Keyword::TDispn(n) => format!("TDISP{}", n),
+            Keyword::TFormn(n) => format!("TFORM{}", n),
+            Keyword::THeap => String::from("THEAP"),
+            Keyword::TLMaxn(n) => format!("TLMAX{}", n),
+            Keyword::TLMinn(n) => format!("TLMIN{}", n),
+            Keyword::TNulln(n) => format!("TNULL{}", n),
+            Keyword::TScaln(n) => format!("TSCAL{}", n),
+            Keyword::TTypen(n) => format!("TTYPE{}", n),
+            Keyword::TUnitn(n) => format!("TUNIT{}", n),
+            Keyword::TZeron(n) => format!("TZERO{}", n),
+            Keyword::Telescop => String::from("TELESCOP"),
+            Keyword::Tfields => String::from("TFIELDS"),
+            Keyword::Unknown(s) => String::from_utf8_lossy(s).trim_end().to_string(),
+            Keyword::Xtension => String::from("XTENSION"),
+            Keyword::ZBitPix => String::from("ZBITPIX"),
+            Keyword::ZBlocked => String::from("ZBLOCKED"),
+            Keyword::ZCTypn(n) => format!("ZCTYP{}", n),
+            Keyword::ZCmpType => String::from("ZCMPTYPE"),
+            Keyword::ZDataSum => String::from("ZDATASUM"),
+            Keyword::ZDither0 => String::from("ZDITHER0"),
+            Keyword::ZExtend => String::from("ZEXTEND"),
+            Keyword::ZFormn(n) => format!("ZFORM{}", n),
+            Keyword::ZGCount => String::from("ZGCOUNT"),
+            Keyword::ZImage => String::from("ZIMAGE"),
+            Keyword::ZMaskCmp => String::from("ZMASKCMP"),
+            Keyword::ZNAMEi(n) => format!("ZNAME{}", n),
+            Keyword::ZNaxis => String::from("ZNAXIS"),
+            Keyword::ZNaxis1 => String::from("ZNAXIS1"),
+            Keyword::ZNaxis2 => String::from("ZNAXIS2"),
+            Keyword::ZPCount => String::from("ZPCOUNT"),
+            Keyword::ZQuantiz => String::from("ZQUANTIZ"),
+            Keyword::ZSimple => String::from("ZSIMPLE"),
+            Keyword::ZTHeap => String::from("ZTHEAP"),
+            Keyword::ZTable => String::from("ZTABLE"),
+            Keyword::ZTension => String::from("ZTENSION"),
+            Keyword::ZTileLen => String::from("ZTILELEN"),
+            Keyword::ZTilen(n) => format!("ZTILE{}", n),
+            Keyword::ZVALi(n) => format!("ZVAL{}", n),
+            Keyword::ZheckSum => String::from("ZHECKSUM"),
+        }
+    }
+
+    /// The number n of an indexed keyword such as NAXISn or TTYPEn.
+    pub fn index(&self) -> Option<u16> {
+        match self {
+            Keyword::FZALGn(n)
+            | Keyword::NAxisn(n)
+            | Keyword::PScaln(n)
+            | Keyword::PTypen(n)
+            | Keyword::PZeron(n)
+            | Keyword::TBcoln(n)
+            | Keyword::TDMaxn(n)
+            | Keyword::TDMinn(n)
+            | Keyword::TDimn(n)
+            | Keyword::TDispn(n)
+            | Keyword::TFormn(n)
+            | Keyword::TLMaxn(n)
+            | Keyword::TLMinn(n)
+            | Keyword::TNulln(n)
+            | Keyword::TScaln(n)
+            | Keyword::TTypen(n)
+            | Keyword::TUnitn(n)
+            | Keyword::TZeron(n)
+            | Keyword::ZCTypn(n)
+            | Keyword::ZFormn(n)
+            | Keyword::ZNAMEi(n)
+            | Keyword::ZTilen(n)
+            | Keyword::ZVALi(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// A HIERARCH keyword with the words of `name` separated by single blanks.
+    pub(crate) fn hierarch(name: &str) -> Self {
+        Keyword::Hierarch(name.split_whitespace().collect::<Vec<_>>().join(" "))
+    }
+
     fn combine(prefix: &str, n: u16) -> Self {
         match prefix {
             "FZALG" => Keyword::FZALGn(n),
@@ -264,6 +395,28 @@ impl From<&[u8]> for Keyword {
     }
 }
 
+/// A keyword name such as `"OBJECT"` or `"TTYPE3"`, lower case letters are converted to upper case.
+/// Names that start with `HIERARCH`, contain blanks or are longer than 8 characters are HIERARCH
+/// keywords, e.g. `"HIERARCH ESO DET CHIP TEMP"` or `"ESO DET CHIP TEMP"`.
+impl From<&str> for Keyword {
+    fn from(name: &str) -> Self {
+        let name = name.trim();
+        match name.get(..9) {
+            Some(prefix) if prefix.eq_ignore_ascii_case("HIERARCH ") => {
+                Keyword::hierarch(&name[9..])
+            }
+            _ if name.len() > 8 || name.contains(' ') => Keyword::hierarch(name),
+            _ => Keyword::from(format!("{:8}", name.to_ascii_uppercase()).as_bytes()),
+        }
+    }
+}
+
+impl From<&Keyword> for Keyword {
+    fn from(keyword: &Keyword) -> Self {
+        keyword.clone()
+    }
+}
+
 impl fmt::Display for Keyword {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -403,9 +556,44 @@ mod test {
 
         for (input, expected) in keywords {
             assert_eq!(Keyword::from(input.as_bytes()), expected);
+            assert_eq!(format!("{:8}", expected.name()).as_bytes(), input);
         }
     }
 
+    #[test]
+    fn keyword_name() {
+        assert_eq!(Keyword::NAxisn(2).name(), "NAXIS2");
+        assert_eq!(Keyword::TFormn(999).name(), "TFORM999");
+        assert_eq!(Keyword::ZVALi(1).name(), "ZVAL1");
+        assert_eq!(Keyword::Unknown(*b"CREATOR ").name(), "CREATOR");
+        assert_eq!(Keyword::Empty.name(), "");
+    }
+
+    #[test]
+    fn keyword_from_str() {
+        assert_eq!(Keyword::from("OBJECT"), Keyword::Object);
+        assert_eq!(Keyword::from("ttype12"), Keyword::TTypen(12));
+        assert_eq!(Keyword::from("CREATOR"), Keyword::Unknown(*b"CREATOR "));
+        assert_eq!(
+            Keyword::from("hierarch ESO  DET CHIP TEMP"),
+            Keyword::Hierarch(String::from("ESO DET CHIP TEMP"))
+        );
+        assert_eq!(
+            Keyword::from("ESO.DET.CHIP.TEMP"),
+            Keyword::Hierarch(String::from("ESO.DET.CHIP.TEMP"))
+        );
+        assert_eq!(
+            Keyword::from("ESO DPR"),
+            Keyword::Hierarch(String::from("ESO DPR"))
+        );
+        assert_eq!(
+            Keyword::Hierarch(String::from("ESO DPR")).name(),
+            "HIERARCH ESO DPR"
+        );
+        assert_eq!(Keyword::TTypen(12).index(), Some(12));
+        assert_eq!(Keyword::NAxis.index(), None);
+    }
+
     #[test]
     fn keyword_display() {
         assert_eq!(format!("{}", Keyword::Author), "Author");
@@ -1,10 +1,24 @@
 use super::{keyword::Keyword, value::Value};
 use std::fmt;
-#[derive(PartialEq, Debug)]
+#[derive(Debug, Clone)]
 pub struct KeywordRecord<'a> {
     keyword: Keyword,
     value: Value<'a>,
     comment: Option<&'a str>,
+    /// The comments of the cards of a continued string, one per part.
+    part_comments: Vec<Option<&'a str>>,
+    card: Option<&'a [u8]>,
+}
+
+/// Two records are equal if keyword, value and comments are equal, regardless of the
+/// original card image they were parsed from.
+impl PartialEq for KeywordRecord<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.keyword == other.keyword
+            && self.value == other.value
+            && self.comment == other.comment
+            && self.part_comments == other.part_comments
+    }
 }
 
 impl fmt::Display for KeywordRecord<'_> {
@@ -23,6 +37,28 @@ impl<'a> KeywordRecord<'a> {
             keyword,
             value,
             comment,
+            part_comments: Vec::new(),
+            card: None,
+        }
+    }
+
+    /// Keeps the comment of every card of a continued string, one per part of
+    /// `Value::ContinuedString`. The comment of the record is the first one that is present.
+    /// FITS standard section 4.2.1.2
+    pub fn with_part_comments(self, part_comments: Vec<Option<&'a str>>) -> Self {
+        Self {
+            comment: part_comments.iter().find_map(|comment| *comment),
+            part_comments,
+            ..self
+        }
+    }
+
+    /// Keeps the 80 byte card image the record was parsed from, so that it can be written
+    /// back unchanged. A continued string keeps the images of all its cards.
+    pub fn with_card(self, card: &'a [u8]) -> Self {
+        Self {
+            card: Some(card),
+            ..self
         }
     }
 
@@ -30,11 +66,39 @@ impl<'a> KeywordRecord<'a> {
         &self.keyword
     }
 
-    pub fn value(&self) -> &Value {
+    pub fn value(&self) -> &Value<'a> {
         &self.value
     }
 
-    pub fn comment(&self) -> &Option<&str> {
+    pub fn comment(&self) -> &Option<&'a str> {
         &self.comment
     }
+
+    /// The comments of the cards of a continued string, empty unless set with
+    /// [`KeywordRecord::with_part_comments`].
+    pub fn part_comments(&self) -> &[Option<&'a str>] {
+        &self.part_comments
+    }
+
+    pub fn card(&self) -> Option<&'a [u8]> {
+        self.card
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyword_record_with_card() {
+        let card = b"NAXIS   =     0";
+        let record = KeywordRecord::new(Keyword::NAxis, Value::Integer(0), None);
+        assert_eq!(record.card(), None);
+        let record = record.with_card(card);
+        assert_eq!(record.card(), Some(&card[..]));
+        assert_eq!(
+            record,
+            KeywordRecord::new(Keyword::NAxis, Value::Integer(0), None)
+        );
+    }
 }
@@ -0,0 +1,137 @@
+use super::data_array::DataArray;
+
+/// Describes a group parameter, as given by PTYPEn, PSCALn and PZEROn.
+/// physical value = PZEROn + PSCALn × stored value. FITS standard section 6.1.2
+#[derive(PartialEq, Debug, Clone)]
+pub struct Parameter {
+    name: Option<String>,
+    scale: f64,
+    zero: f64,
+}
+
+impl Parameter {
+    pub fn new(name: Option<String>, scale: f64, zero: f64) -> Self {
+        Self { name, scale, zero }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    pub fn zero(&self) -> f64 {
+        self.zero
+    }
+}
+
+/// A single group: the physical values of its parameters followed by its data array.
+#[derive(PartialEq, Debug)]
+pub struct Group {
+    parameters: Vec<f64>,
+    data: DataArray,
+}
+
+impl Group {
+    pub fn new(parameters: Vec<f64>, data: DataArray) -> Self {
+        Self { parameters, data }
+    }
+
+    /// Physical values of the parameters, in the order of PTYPEn.
+    pub fn parameters(&self) -> &[f64] {
+        &self.parameters
+    }
+
+    /// The data array of the group, with the dimensions NAXIS2 to NAXISm.
+    pub fn data_array(&self) -> &DataArray {
+        &self.data
+    }
+}
+
+/// The GCOUNT groups of a random groups primary HDU. FITS standard section 6
+#[derive(PartialEq, Debug, Default)]
+pub struct RandomGroups {
+    parameters: Vec<Parameter>,
+    groups: Vec<Group>,
+}
+
+impl RandomGroups {
+    pub fn new(parameters: Vec<Parameter>, groups: Vec<Group>) -> Self {
+        Self { parameters, groups }
+    }
+
+    pub fn parameters(&self) -> &[Parameter] {
+        &self.parameters
+    }
+
+    pub fn groups(&self) -> &[Group] {
+        &self.groups
+    }
+
+    pub fn group(&self, group: usize) -> Option<&Group> {
+        self.groups.get(group)
+    }
+
+    /// Value of the parameter named `name` in `group`. Parameters that share a name are summed,
+    /// which allows values that need more precision than a single parameter provides, e.g. a
+    /// Julian date split into two parameters. Names are compared case-insensitively.
+    /// FITS standard section 6.1.2
+    pub fn parameter(&self, group: usize, name: &str) -> Option<f64> {
+        let values = self.groups.get(group)?.parameters();
+        self.parameters
+            .iter()
+            .zip(values)
+            .filter(|(parameter, _)| {
+                parameter
+                    .name()
+                    .is_some_and(|n| n.eq_ignore_ascii_case(name))
+            })
+            .map(|(_, value)| *value)
+            .reduce(|sum, value| sum + value)
+    }
+
+    /// Values of the parameter named `name` in all groups, see [`RandomGroups::parameter`].
+    pub fn parameter_values(&self, name: &str) -> Option<Vec<f64>> {
+        (0..self.groups.len())
+            .map(|group| self.parameter(group, name))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn random_groups() {
+        let groups = RandomGroups::new(
+            vec![
+                Parameter::new(Some(String::from("UU")), 1.0, 0.0),
+                Parameter::new(Some(String::from("DATE")), 1.0, 2445728.5),
+                Parameter::new(Some(String::from("DATE")), 1.0, 0.0),
+            ],
+            vec![
+                Group::new(
+                    vec![0.5, 2445728.5, 0.25],
+                    DataArray::from_f32(vec![1.0, 2.0], vec![2], None, None),
+                ),
+                Group::new(
+                    vec![-0.5, 2445729.5, 0.75],
+                    DataArray::from_f32(vec![3.0, 4.0], vec![2], None, None),
+                ),
+            ],
+        );
+        assert_eq!(groups.parameter(0, "uu"), Some(0.5));
+        assert_eq!(groups.parameter(1, "DATE"), Some(2445730.25));
+        assert_eq!(groups.parameter(2, "DATE"), None);
+        assert_eq!(groups.parameter(0, "VV"), None);
+        assert_eq!(
+            groups.parameter_values("DATE"),
+            Some(vec![2445728.75, 2445730.25])
+        );
+        assert_eq!(groups.group(1).unwrap().data_array().len(), 2);
+        assert_eq!(groups.parameters()[1].zero(), 2445728.5);
+    }
+}
@@ -0,0 +1,206 @@
+/// Format of a field in an ASCII table. FITS standard section 7.2.5
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum TableFormat {
+    /// `Aw`, a character string of width w.
+    Character(usize),
+    /// `Iw`, a decimal integer of width w.
+    Integer(usize),
+    /// `Fw.d`, a fixed point number of width w with d decimals.
+    FixedPoint(usize, usize),
+    /// `Ew.d`, a single precision number in exponential notation.
+    Exponential(usize, usize),
+    /// `Dw.d`, a double precision number in exponential notation.
+    DoubleExponential(usize, usize),
+}
+
+impl TableFormat {
+    pub fn width(&self) -> usize {
+        match self {
+            Self::Character(w)
+            | Self::Integer(w)
+            | Self::FixedPoint(w, _)
+            | Self::Exponential(w, _)
+            | Self::DoubleExponential(w, _) => *w,
+        }
+    }
+}
+
+/// Describes a field of an ASCII table, as given by TTYPEn, TBCOLn, TFORMn, TUNITn, TNULLn,
+/// TSCALn and TZEROn. FITS standard section 7.2.2
+#[derive(PartialEq, Debug, Clone)]
+pub struct Column {
+    name: Option<String>,
+    unit: Option<String>,
+    start: usize,
+    format: TableFormat,
+    null: Option<String>,
+    scale: f64,
+    zero: f64,
+}
+
+impl Column {
+    pub fn new(start: usize, format: TableFormat) -> Self {
+        Self {
+            name: None,
+            unit: None,
+            start,
+            format,
+            null: None,
+            scale: 1.0,
+            zero: 0.0,
+        }
+    }
+
+    pub fn with_name(self, name: Option<String>) -> Self {
+        Self { name, ..self }
+    }
+
+    pub fn with_unit(self, unit: Option<String>) -> Self {
+        Self { unit, ..self }
+    }
+
+    pub fn with_null(self, null: Option<String>) -> Self {
+        Self { null, ..self }
+    }
+
+    pub fn with_scaling(self, scale: f64, zero: f64) -> Self {
+        Self {
+            scale,
+            zero,
+            ..self
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn unit(&self) -> Option<&str> {
+        self.unit.as_deref()
+    }
+
+    /// Offset of the field from the start of the row, starting at 0.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn format(&self) -> &TableFormat {
+        &self.format
+    }
+
+    pub fn null(&self) -> Option<&str> {
+        self.null.as_deref()
+    }
+
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    pub fn zero(&self) -> f64 {
+        self.zero
+    }
+
+    /// Whether TSCALn or TZEROn change the stored value.
+    pub fn is_scaled(&self) -> bool {
+        self.scale != 1.0 || self.zero != 0.0
+    }
+}
+
+/// A decoded field of an ASCII table.
+/// physical value = TZEROn + TSCALn × field value, so scaled integer fields are real numbers.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Field {
+    Character(String),
+    Integer(i64),
+    Real(f64),
+    /// The field matches TNULLn or is blank.
+    Null,
+}
+
+/// The rows of an ASCII table extension, decoded field by field.
+#[derive(PartialEq, Debug, Default)]
+pub struct Table {
+    columns: Vec<Column>,
+    rows: Vec<Vec<Field>>,
+}
+
+impl Table {
+    pub fn new(columns: Vec<Column>, rows: Vec<Vec<Field>>) -> Self {
+        Self { columns, rows }
+    }
+
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// Index of the column named `name`. Names are compared case-insensitively, as recommended
+    /// for TTYPEn. FITS standard section 7.2.2
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns
+            .iter()
+            .position(|column| column.name().is_some_and(|n| n.eq_ignore_ascii_case(name)))
+    }
+
+    pub fn rows(&self) -> &[Vec<Field>] {
+        &self.rows
+    }
+
+    pub fn row(&self, row: usize) -> Option<&[Field]> {
+        self.rows.get(row).map(|fields| fields.as_slice())
+    }
+
+    pub fn field(&self, row: usize, column: usize) -> Option<&Field> {
+        self.rows.get(row).and_then(|fields| fields.get(column))
+    }
+
+    /// All fields of the column named `name`, from the first to the last row, or `None` if a
+    /// row has no field for it.
+    pub fn column(&self, name: &str) -> Option<Vec<&Field>> {
+        let index = self.column_index(name)?;
+        self.rows.iter().map(|fields| fields.get(index)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table() {
+        let table = Table::new(
+            vec![
+                Column::new(0, TableFormat::Character(8)).with_name(Some(String::from("NAME"))),
+                Column::new(8, TableFormat::FixedPoint(6, 2))
+                    .with_name(Some(String::from("Flux")))
+                    .with_scaling(2.0, 0.0),
+            ],
+            vec![
+                vec![Field::Character(String::from("a")), Field::Real(2.5)],
+                vec![Field::Character(String::from("b")), Field::Null],
+            ],
+        );
+        assert_eq!(table.column_index("FLUX"), Some(1));
+        assert_eq!(table.column_index("missing"), None);
+        assert_eq!(table.field(1, 1), Some(&Field::Null));
+        assert_eq!(table.field(2, 0), None);
+        assert_eq!(
+            table.row(0),
+            Some(&[Field::Character(String::from("a")), Field::Real(2.5)][..])
+        );
+        assert_eq!(
+            table.column("name"),
+            Some(vec![
+                &Field::Character(String::from("a")),
+                &Field::Character(String::from("b"))
+            ])
+        );
+        assert!(table.columns()[1].is_scaled());
+        assert_eq!(table.columns()[1].format().width(), 6);
+
+        let table = Table::new(
+            table.columns().to_vec(),
+            vec![vec![Field::Character(String::from("c"))]],
+        );
+        assert_eq!(table.column("flux"), None);
+    }
+}
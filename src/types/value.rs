@@ -1,9 +1,9 @@
-use std::fmt;
+use std::{borrow::Cow, fmt};
 
 use time::PrimitiveDateTime;
 
 /// The possible values of a KeywordRecord.
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub enum Value<'a> {
     /// An ASCII string 0x20 - 0x7E. FITS standard section 4.2.1.1
     CharacterString(String),
@@ -11,8 +11,9 @@ pub enum Value<'a> {
     ComplexFloat((f64, f64)),
     /// Complex integer with real and imaginary parts. FITS standard section 4.2.5
     ComplexInteger((i64, i64)),
-    /// A multi-part string. FITS standard section 4.2.1.2
-    ContinuedString(Vec<&'a str>),
+    /// A long string that is spread over CONTINUE records, one part per record. Parts that
+    /// contain no escaped quotes are borrowed from the card images. FITS standard section 4.2.1.2
+    ContinuedString(Vec<Cow<'a, str>>),
     /// Date. ISO-8601 string. FITS standard section 4.2.7
     Date(PrimitiveDateTime),
     /// An signed integer. FITS standard section 4.2.3
@@ -56,7 +57,10 @@ mod tests {
         assert_eq!(format!("{}", Value::ComplexFloat((1.0, 2.0))), "1 + 2i");
         assert_eq!(format!("{}", Value::ComplexInteger((1, 2))), "1 + 2i");
         assert_eq!(
-            format!("{}", Value::ContinuedString(vec!["hello", "world"])),
+            format!(
+                "{}",
+                Value::ContinuedString(vec!["hello".into(), "world".into()])
+            ),
             "[\n    \"hello\",\n    \"world\",\n]"
         );
         assert_eq!(
@@ -0,0 +1,193 @@
+use std::io::Write;
+
+use tracing::trace;
+
+use crate::{
+    checksum,
+    types::{
+        header::{FitsHeader, HeaderKind},
+        keyword::Keyword,
+        value::Value,
+        Fits, FitsError, Position, BLOCK_SIZE, HDU,
+    },
+};
+
+mod binary_table;
+mod data_array;
+mod header;
+mod keyword_record;
+mod value;
+
+pub use binary_table::{binary_table, BinaryTableLayout};
+
+pub fn hdu(w: &mut impl Write, hdu: &HDU) -> Result<u64, FitsError> {
+    let mut written = header::header(w, hdu.header())?;
+    // ASCII tables are filled with blanks, everything else with zeros. FITS standard section 7.2.3
+    let fill = match hdu.header().header_kind() {
+        HeaderKind::Table => b' ',
+        _ => 0,
+    };
+    // the fill of a parsed HDU is written back unchanged, even if it is not made of `fill`
+    let fill = Fill::from(fill).with_original(hdu.fill());
+    // the bytes of a data view are written as they are, without decoding the data array
+    if let Some(data_view) = hdu.data_view() {
+        written += data_array::data_view(w, data_view, hdu.heap(), fill)?;
+    } else if let Some(data_array) = hdu.data_array() {
+        written += data_array::data_array(w, data_array, hdu.heap(), fill)?;
+    }
+    trace!("hdu: {} bytes written", written);
+    Ok(written)
+}
+
+pub fn fits(w: &mut impl Write, fits: &Fits) -> Result<u64, FitsError> {
+    let mut written = 0;
+    for (n, h) in fits.hdus().iter().enumerate() {
+        written += hdu(w, h).map_err(|e| match e {
+            FitsError::Io {
+                position: None,
+                source,
+            } => FitsError::Io {
+                position: Some(Position::new(written as usize, n, None)),
+                source,
+            },
+            e => e,
+        })?;
+    }
+    Ok(written)
+}
+
+/// Sets DATASUM and CHECKSUM of `h` to the checksums of the bytes it is written as. Both
+/// keywords are added before END if the header does not have them yet. FITS standard section
+/// 4.4.2.7
+pub fn update_checksum(h: &mut HDU) -> Result<(), FitsError> {
+    let mut bytes = Vec::new();
+    hdu(&mut bytes, h)?;
+    let header_length = header::header(&mut std::io::sink(), h.header())? as usize;
+    let data_sum = checksum::checksum(&bytes[header_length..], 0);
+
+    let header = h.header_mut();
+    header.set(
+        Keyword::CheckSum,
+        Value::CharacterString(checksum::CHECKSUM_PLACEHOLDER.to_string()),
+        Some("HDU checksum"),
+    );
+    header.set(
+        Keyword::DataSum,
+        Value::CharacterString(data_sum.to_string()),
+        Some("data unit checksum"),
+    );
+    let mut bytes = Vec::new();
+    header::header(&mut bytes, header)?;
+    let sum = checksum::checksum(&data_sum.to_be_bytes(), checksum::checksum(&bytes, 0));
+    header.set_value(
+        Keyword::CheckSum,
+        Value::CharacterString(checksum::encode(sum, true)),
+    );
+    Ok(())
+}
+
+/// The bytes that fill the last block of a header or data unit. FITS standard section 3.3.2
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Fill<'a> {
+    byte: u8,
+    /// The fill that was read with the HDU, written instead of `byte` if it has the same length.
+    original: &'a [u8],
+}
+
+impl<'a> Fill<'a> {
+    fn with_original(self, original: &'a [u8]) -> Self {
+        Self { original, ..self }
+    }
+}
+
+impl From<u8> for Fill<'_> {
+    fn from(byte: u8) -> Self {
+        Self {
+            byte,
+            original: &[],
+        }
+    }
+}
+
+/// Fills the last block with `fill` bytes, given the number of bytes written so far.
+fn padding<'a>(
+    w: &mut impl Write,
+    written: u64,
+    fill: impl Into<Fill<'a>>,
+) -> Result<u64, FitsError> {
+    let fill = fill.into();
+    let remainder = (written % BLOCK_SIZE as u64) as usize;
+    if remainder == 0 {
+        return Ok(0);
+    }
+    let length = BLOCK_SIZE - remainder;
+    match fill.original.len() == length {
+        true => w.write_all(fill.original)?,
+        false => w.write_all(&vec![fill.byte; length])?,
+    }
+    Ok(length as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::{
+        data_array::DataArray, header::Header, keyword::Keyword, keyword_record::KeywordRecord,
+        value::Value, Fits, BLOCK_SIZE, HDU,
+    };
+
+    #[test]
+    fn padding() {
+        let mut w = Vec::new();
+        assert_eq!(super::padding(&mut w, 0, 0), Ok(0));
+        assert_eq!(super::padding(&mut w, 2880, 0), Ok(0));
+        assert_eq!(super::padding(&mut w, 2879, b' '), Ok(1));
+        assert_eq!(super::padding(&mut w, 1, 0), Ok(2879));
+        assert_eq!(w.len(), 2880);
+
+        let mut w = Vec::new();
+        let fill = super::Fill::from(0).with_original(&[1, 2]);
+        assert_eq!(super::padding(&mut w, 2878, fill), Ok(2));
+        assert_eq!(super::padding(&mut w, 2877, fill), Ok(3));
+        assert_eq!(w, vec![1, 2, 0, 0, 0]);
+    }
+
+    #[test]
+    fn fits() {
+        let primary = HDU::new(
+            Header::from(vec![
+                KeywordRecord::new(Keyword::Simple, Value::Logical(true), None),
+                KeywordRecord::new(Keyword::BitPix, Value::Integer(16), None),
+                KeywordRecord::new(Keyword::NAxis, Value::Integer(2), None),
+                KeywordRecord::new(Keyword::NAxisn(1), Value::Integer(2), None),
+                KeywordRecord::new(Keyword::NAxisn(2), Value::Integer(2), None),
+            ]),
+            Some(DataArray::from_i16(
+                vec![1, -2, 3, -4],
+                vec![2, 2],
+                None,
+                None,
+            )),
+        );
+        let extension = HDU::new(
+            Header::from(vec![
+                KeywordRecord::new(
+                    Keyword::Xtension,
+                    Value::CharacterString(String::from("IMAGE")),
+                    None,
+                ),
+                KeywordRecord::new(Keyword::BitPix, Value::Integer(8), None),
+                KeywordRecord::new(Keyword::NAxis, Value::Integer(0), None),
+                KeywordRecord::new(Keyword::PCount, Value::Integer(0), None),
+                KeywordRecord::new(Keyword::GCount, Value::Integer(1), None),
+            ]),
+            None,
+        );
+        let fits = Fits::from(vec![primary, extension]);
+        let mut w = Vec::new();
+        assert_eq!(super::fits(&mut w, &fits), Ok(3 * BLOCK_SIZE as u64));
+        assert_eq!(w.len(), 3 * BLOCK_SIZE);
+        assert_eq!(&w[2880..2888], &[0, 1, 0xff, 0xfe, 0, 3, 0xff, 0xfc]);
+        assert!(w[2888..5760].iter().all(|&b| b == 0));
+        assert_eq!(&w[5760..5790], b"XTENSION= 'IMAGE   '          ");
+    }
+}
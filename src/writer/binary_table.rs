@@ -0,0 +1,309 @@
+use crate::types::{
+    binary_table::{ArrayDescriptor, BinaryColumn, BinaryFormat, BinaryTable, BinaryType, Cell},
+    FitsError,
+};
+
+/// The encoded data unit of a binary table.
+#[derive(PartialEq, Debug)]
+pub struct BinaryTableLayout {
+    /// Number of bytes in a row, NAXIS1.
+    pub row_length: usize,
+    /// The main data table, NAXIS1 × NAXIS2 bytes.
+    pub rows: Vec<u8>,
+    /// The heap holding the variable length arrays, PCOUNT bytes.
+    pub heap: Vec<u8>,
+    /// TFORMn of every column, with the maximum array length of variable length arrays.
+    pub formats: Vec<BinaryFormat>,
+}
+
+/// Encodes the cells of a binary table in big-endian byte order. Fields are laid out one after
+/// the other in the order of the columns, variable length arrays are appended to the heap in
+/// the order of the rows. FITS standard sections 7.3.3 and 7.3.5
+pub fn binary_table(table: &BinaryTable) -> Result<BinaryTableLayout, FitsError> {
+    let columns = table.columns();
+    let row_length = columns.iter().map(|c| c.format().width()).sum();
+    let mut rows = Vec::with_capacity(row_length * table.rows().len());
+    let mut heap = Vec::new();
+    let mut max = vec![0; columns.len()];
+    for (r, cells) in table.rows().iter().enumerate() {
+        if cells.len() != columns.len() {
+            return Err(FitsError::WriteError(format!(
+                "row {} has {} cells, expected {}",
+                r + 1,
+                cells.len(),
+                columns.len()
+            )));
+        }
+        for (c, (column, cell)) in columns.iter().zip(cells).enumerate() {
+            let error = |reason: String| {
+                FitsError::WriteError(format!("row {}, field {}: {}", r + 1, c + 1, reason))
+            };
+            match column.format().descriptor() {
+                None => {
+                    let start = rows.len();
+                    let count = encode(column, cell, &mut rows).map_err(error)?;
+                    let repeat = column.format().repeat();
+                    match column.format().data_type() {
+                        BinaryType::Character if count <= repeat => {
+                            rows.resize(start + repeat, b' ');
+                        }
+                        _ if count == repeat => {}
+                        _ => {
+                            return Err(error(format!(
+                                "cell has {} elements, expected {}",
+                                count, repeat
+                            )))
+                        }
+                    }
+                }
+                Some(descriptor) => {
+                    let offset = heap.len();
+                    let count = encode(column, cell, &mut heap).map_err(error)?;
+                    max[c] = max[c].max(count);
+                    match descriptor {
+                        ArrayDescriptor::P(_) => {
+                            let count = u32::try_from(count);
+                            let offset = u32::try_from(offset);
+                            match (count, offset) {
+                                (Ok(count), Ok(offset)) => {
+                                    rows.extend(count.to_be_bytes());
+                                    rows.extend(offset.to_be_bytes());
+                                }
+                                _ => {
+                                    return Err(error(String::from(
+                                        "heap exceeds the range of a P descriptor",
+                                    )))
+                                }
+                            }
+                        }
+                        ArrayDescriptor::Q(_) => {
+                            rows.extend((count as u64).to_be_bytes());
+                            rows.extend((offset as u64).to_be_bytes());
+                        }
+                    }
+                }
+            }
+        }
+    }
+    let formats = columns
+        .iter()
+        .zip(max)
+        .map(|(column, max)| match column.format().descriptor() {
+            Some(ArrayDescriptor::P(_)) => BinaryFormat::variable_length(
+                *column.format().data_type(),
+                ArrayDescriptor::P(Some(max)),
+            ),
+            Some(ArrayDescriptor::Q(_)) => BinaryFormat::variable_length(
+                *column.format().data_type(),
+                ArrayDescriptor::Q(Some(max)),
+            ),
+            None => *column.format(),
+        })
+        .collect();
+    Ok(BinaryTableLayout {
+        row_length,
+        rows,
+        heap,
+        formats,
+    })
+}
+
+/// Appends the stored values of a cell to `out`, reversing TSCALn and TZEROn, and returns the
+/// number of elements.
+fn encode(column: &BinaryColumn, cell: &Cell, out: &mut Vec<u8>) -> Result<usize, String> {
+    macro_rules! be_bytes {
+        ($values:expr) => {{
+            out.extend($values.iter().flat_map(|v| v.to_be_bytes()));
+            $values.len()
+        }};
+    }
+    let unscaled = |v: f64| (v - column.zero()) / column.scale();
+    let unscaled_integer = |v: f64| match (v.is_nan(), column.null()) {
+        (false, _) => Ok(unscaled(v).round() as i64),
+        (true, Some(null)) => Ok(null),
+        (true, None) => Err(String::from("NaN in an integer column without TNULLn")),
+    };
+    let data_type = *column.format().data_type();
+    let offset = column.is_offset_integer();
+    let scaled = column.is_scaled();
+    Ok(match (data_type, cell) {
+        (BinaryType::Logical, Cell::Logical(values)) => {
+            out.extend(values.iter().map(|v| match v {
+                Some(true) => b'T',
+                Some(false) => b'F',
+                None => 0,
+            }));
+            values.len()
+        }
+        (BinaryType::Bit, Cell::Bit(values)) => {
+            for bits in values.chunks(8) {
+                out.push(
+                    bits.iter()
+                        .enumerate()
+                        .fold(0, |byte, (n, &bit)| byte | ((bit as u8) << (7 - n))),
+                );
+            }
+            values.len()
+        }
+        (BinaryType::Character, Cell::Character(s)) => {
+            out.extend(s.bytes());
+            s.len()
+        }
+        (BinaryType::Byte, Cell::U8(values)) if !offset && !scaled => {
+            out.extend(values);
+            values.len()
+        }
+        (BinaryType::Byte, Cell::I8(values)) if offset => {
+            out.extend(values.iter().map(|&v| v as u8 ^ 0x80));
+            values.len()
+        }
+        (BinaryType::I16, Cell::I16(values)) if !offset && !scaled => be_bytes!(values),
+        (BinaryType::I16, Cell::U16(values)) if offset => {
+            be_bytes!(values.iter().map(|&v| v ^ 0x8000).collect::<Vec<u16>>())
+        }
+        (BinaryType::I32, Cell::I32(values)) if !offset && !scaled => be_bytes!(values),
+        (BinaryType::I32, Cell::U32(values)) if offset => {
+            be_bytes!(values
+                .iter()
+                .map(|&v| v ^ 0x8000_0000)
+                .collect::<Vec<u32>>())
+        }
+        (BinaryType::I64, Cell::I64(values)) if !offset && !scaled => be_bytes!(values),
+        (BinaryType::I64, Cell::U64(values)) if offset => be_bytes!(values
+            .iter()
+            .map(|&v| v ^ 0x8000_0000_0000_0000)
+            .collect::<Vec<u64>>()),
+        (
+            BinaryType::Byte | BinaryType::I16 | BinaryType::I32 | BinaryType::I64,
+            Cell::Real(values),
+        ) if scaled => {
+            let values = values
+                .iter()
+                .map(|&v| unscaled_integer(v))
+                .collect::<Result<Vec<i64>, String>>()?;
+            match data_type {
+                BinaryType::Byte => out.extend(values.iter().map(|&v| v as u8)),
+                BinaryType::I16 => {
+                    out.extend(values.iter().flat_map(|&v| (v as i16).to_be_bytes()))
+                }
+                BinaryType::I32 => {
+                    out.extend(values.iter().flat_map(|&v| (v as i32).to_be_bytes()))
+                }
+                _ => out.extend(values.iter().flat_map(|v| v.to_be_bytes())),
+            }
+            values.len()
+        }
+        (BinaryType::F32, Cell::F32(values)) if !scaled => be_bytes!(values),
+        (BinaryType::F32, Cell::Real(values)) if scaled => be_bytes!(values
+            .iter()
+            .map(|&v| unscaled(v) as f32)
+            .collect::<Vec<f32>>()),
+        (BinaryType::F64, Cell::F64(values)) if !scaled => be_bytes!(values),
+        (BinaryType::F64, Cell::Real(values)) if scaled => {
+            be_bytes!(values.iter().map(|&v| unscaled(v)).collect::<Vec<f64>>())
+        }
+        (BinaryType::ComplexF32, Cell::ComplexF32(values)) => {
+            out.extend(
+                values
+                    .iter()
+                    .flat_map(|(r, i)| r.to_be_bytes().into_iter().chain(i.to_be_bytes())),
+            );
+            values.len()
+        }
+        (BinaryType::ComplexF64, Cell::ComplexF64(values)) => {
+            out.extend(
+                values
+                    .iter()
+                    .flat_map(|(r, i)| r.to_be_bytes().into_iter().chain(i.to_be_bytes())),
+            );
+            values.len()
+        }
+        (data_type, cell) => {
+            return Err(format!(
+                "{:?} does not match a column of type {:?}",
+                cell, data_type
+            ))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_table() {
+        let table = BinaryTable::new(
+            vec![
+                BinaryColumn::new(0, BinaryFormat::new(2, BinaryType::Logical)),
+                BinaryColumn::new(2, BinaryFormat::new(10, BinaryType::Bit)),
+                BinaryColumn::new(4, BinaryFormat::new(4, BinaryType::Character)),
+                BinaryColumn::new(8, BinaryFormat::new(1, BinaryType::I16))
+                    .with_scaling(0.5, 0.0)
+                    .with_null(Some(-1)),
+                BinaryColumn::new(10, BinaryFormat::new(1, BinaryType::I16))
+                    .with_scaling(1.0, 32768.0),
+                BinaryColumn::new(
+                    12,
+                    BinaryFormat::variable_length(BinaryType::F32, ArrayDescriptor::P(None)),
+                ),
+            ],
+            vec![
+                vec![
+                    Cell::Logical(vec![Some(true), None]),
+                    Cell::Bit(vec![
+                        true, false, true, false, false, false, false, false, true, true,
+                    ]),
+                    Cell::Character(String::from("ab")),
+                    Cell::Real(vec![2.5]),
+                    Cell::U16(vec![65535]),
+                    Cell::F32(vec![1.0, 2.0]),
+                ],
+                vec![
+                    Cell::Logical(vec![Some(false), Some(true)]),
+                    Cell::Bit(vec![false; 10]),
+                    Cell::Character(String::from("abcd")),
+                    Cell::Real(vec![f64::NAN]),
+                    Cell::U16(vec![0]),
+                    Cell::F32(vec![3.0]),
+                ],
+            ],
+        );
+        let layout = super::binary_table(&table).unwrap();
+        assert_eq!(layout.row_length, 20);
+        let mut row = Vec::new();
+        row.extend(b"T\0");
+        row.extend([0b1010_0000, 0b1100_0000]);
+        row.extend(b"ab  ");
+        row.extend(5i16.to_be_bytes());
+        row.extend(i16::MAX.to_be_bytes());
+        row.extend(2u32.to_be_bytes());
+        row.extend(0u32.to_be_bytes());
+        assert_eq!(&layout.rows[..20], &row[..]);
+        assert_eq!(&layout.rows[28..30], &(-1i16).to_be_bytes());
+        assert_eq!(&layout.rows[32..40], &[0, 0, 0, 1, 0, 0, 0, 8]);
+        assert_eq!(
+            layout.heap,
+            [1.0f32, 2.0, 3.0]
+                .iter()
+                .flat_map(|v| v.to_be_bytes())
+                .collect::<Vec<u8>>()
+        );
+        assert_eq!(layout.formats[5].to_string(), "1PE(2)");
+        assert_eq!(layout.formats[0].to_string(), "2L");
+    }
+
+    #[test]
+    fn binary_table_mismatch() {
+        let column = BinaryColumn::new(0, BinaryFormat::new(2, BinaryType::I32));
+        let table = BinaryTable::new(vec![column.clone()], vec![vec![Cell::I32(vec![1])]]);
+        assert_eq!(
+            super::binary_table(&table),
+            Err(FitsError::WriteError(String::from(
+                "row 1, field 1: cell has 1 elements, expected 2"
+            )))
+        );
+        let table = BinaryTable::new(vec![column], vec![vec![Cell::F32(vec![1.0, 2.0])]]);
+        assert!(super::binary_table(&table).is_err());
+    }
+}
@@ -0,0 +1,144 @@
+use std::io::Write;
+
+use crate::{
+    parallel,
+    types::{data_array::DataArray, data_view::DataView, FitsError},
+};
+
+use super::Fill;
+
+/// Writes the data array in big-endian byte order, followed by the `heap` of a binary table, and
+/// fills the last block with `fill`. FITS standard sections 5.2 and 7.3.5
+pub fn data_array<'a>(
+    w: &mut impl Write,
+    data_array: &DataArray,
+    heap: &[u8],
+    fill: impl Into<Fill<'a>>,
+) -> Result<u64, FitsError> {
+    let written = match data_array {
+        DataArray::U8(data, ..) => {
+            w.write_all(data)?;
+            data.len()
+        }
+        DataArray::I16(data, ..) => be_bytes(w, data, |x| x.to_be_bytes())?,
+        DataArray::I32(data, ..) => be_bytes(w, data, |x| x.to_be_bytes())?,
+        DataArray::I64(data, ..) => be_bytes(w, data, |x| x.to_be_bytes())?,
+        DataArray::F32(data, ..) => be_bytes(w, data, |x| x.to_be_bytes())?,
+        DataArray::F64(data, ..) => be_bytes(w, data, |x| x.to_be_bytes())?,
+        // the sign bit flip subtracts BZERO, FITS standard section 5.2.5
+        DataArray::I8(data, ..) => be_bytes(w, data, |x| [*x as u8 ^ 0x80])?,
+        DataArray::U16(data, ..) => be_bytes(w, data, |x| (x ^ 0x8000).to_be_bytes())?,
+        DataArray::U32(data, ..) => be_bytes(w, data, |x| (x ^ 0x8000_0000).to_be_bytes())?,
+        DataArray::U64(data, ..) => {
+            be_bytes(w, data, |x| (x ^ 0x8000_0000_0000_0000).to_be_bytes())?
+        }
+    } as u64;
+    w.write_all(heap)?;
+    let written = written + heap.len() as u64;
+    Ok(written + super::padding(w, written, fill)?)
+}
+
+/// Writes the undecoded bytes of a data array like [`data_array`].
+pub fn data_view<'a>(
+    w: &mut impl Write,
+    data_view: &DataView,
+    heap: &[u8],
+    fill: impl Into<Fill<'a>>,
+) -> Result<u64, FitsError> {
+    w.write_all(data_view.bytes())?;
+    w.write_all(heap)?;
+    let written = (data_view.bytes().len() + heap.len()) as u64;
+    Ok(written + super::padding(w, written, fill)?)
+}
+
+fn be_bytes<T: Sync, const N: usize>(
+    w: &mut impl Write,
+    data: &[T],
+    to_be_bytes: impl Fn(&T) -> [u8; N] + Send + Sync,
+) -> Result<usize, FitsError> {
+    let mut bytes = vec![0; data.len() * N];
+    parallel::zip_chunks(&mut bytes, N, data, 1, |bytes, data| {
+        for (bytes, value) in bytes.chunks_exact_mut(N).zip(data) {
+            bytes.copy_from_slice(&to_be_bytes(value));
+        }
+    });
+    w.write_all(&bytes)?;
+    Ok(bytes.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn data_array_u8() {
+        let mut w = Vec::new();
+        let array = DataArray::from_u8(vec![1, 2, 3, 4], vec![2, 2], None, None);
+        assert_eq!(super::data_array(&mut w, &array, &[], 0), Ok(2880));
+        assert_eq!(&w[..4], &[1, 2, 3, 4]);
+        assert!(w[4..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn data_array_big_endian() {
+        let mut w = Vec::new();
+        let array = DataArray::from_i16(vec![1, -2], vec![2], None, None);
+        assert_eq!(super::data_array(&mut w, &array, &[], 0), Ok(2880));
+        assert_eq!(&w[..4], &[0, 1, 0xff, 0xfe]);
+
+        let mut w = Vec::new();
+        let array = DataArray::from_i32(vec![1], vec![1], None, None);
+        assert_eq!(super::data_array(&mut w, &array, &[], 0), Ok(2880));
+        assert_eq!(&w[..4], &[0, 0, 0, 1]);
+
+        let mut w = Vec::new();
+        let array = DataArray::from_i64(vec![-1], vec![1], None, None);
+        assert_eq!(super::data_array(&mut w, &array, &[], 0), Ok(2880));
+        assert_eq!(&w[..8], &[0xff; 8]);
+
+        let mut w = Vec::new();
+        let array = DataArray::from_f32(vec![1.0], vec![1], None, None);
+        assert_eq!(super::data_array(&mut w, &array, &[], 0), Ok(2880));
+        assert_eq!(&w[..4], &[0x3f, 0x80, 0, 0]);
+
+        let mut w = Vec::new();
+        let array = DataArray::from_f64(vec![-2.0], vec![1], None, None);
+        assert_eq!(super::data_array(&mut w, &array, &[], 0), Ok(2880));
+        assert_eq!(&w[..8], &[0xc0, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn data_array_blank_fill() {
+        let mut w = Vec::new();
+        let array = DataArray::from_u8(b"1.0 2.0".to_vec(), vec![7, 1], None, None);
+        assert_eq!(super::data_array(&mut w, &array, &[], b' '), Ok(2880));
+        assert_eq!(&w[..7], b"1.0 2.0");
+        assert!(w[7..].iter().all(|&b| b == b' '));
+    }
+
+    #[test]
+    fn data_array_exact_block() {
+        let mut w = Vec::new();
+        let array = DataArray::from_f32(vec![0.0; 720], vec![720], None, None);
+        assert_eq!(super::data_array(&mut w, &array, &[], 0), Ok(2880));
+        assert_eq!(w.len(), 2880);
+    }
+
+    #[test]
+    fn data_array_unsigned() {
+        let mut w = Vec::new();
+        let array = DataArray::from_u16(vec![0, 65535], vec![2]);
+        assert_eq!(super::data_array(&mut w, &array, &[], 0), Ok(2880));
+        assert_eq!(&w[..4], &[0x80, 0, 0x7f, 0xff]);
+
+        let mut w = Vec::new();
+        let array = DataArray::from_u64(vec![1], vec![1]);
+        assert_eq!(super::data_array(&mut w, &array, &[], 0), Ok(2880));
+        assert_eq!(&w[..8], &[0x80, 0, 0, 0, 0, 0, 0, 1]);
+
+        let mut w = Vec::new();
+        let array = DataArray::from_i8(vec![-128, 1], vec![2]);
+        assert_eq!(super::data_array(&mut w, &array, &[], 0), Ok(2880));
+        assert_eq!(&w[..2], &[0, 0x81]);
+    }
+}
@@ -0,0 +1,76 @@
+use std::io::Write;
+
+use crate::types::{
+    header::Header, keyword::Keyword, keyword_record::KeywordRecord, value::Value, FitsError,
+};
+
+use super::keyword_record;
+
+pub fn header(w: &mut impl Write, header: &Header) -> Result<u64, FitsError> {
+    let mut cards = Vec::new();
+    for record in header.keyword_records() {
+        cards.extend(keyword_record::keyword_record(record)?);
+    }
+    if !header
+        .keyword_records()
+        .iter()
+        .any(|record| *record.keyword() == Keyword::End)
+    {
+        cards.extend(keyword_record::keyword_record(&KeywordRecord::new(
+            Keyword::End,
+            Value::CharacterString(String::from("")),
+            None,
+        ))?);
+    }
+    w.write_all(&cards)?;
+    let written = cards.len() as u64;
+    Ok(written + super::padding(w, written, b' ')?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::header::FitsHeader;
+
+    #[test]
+    fn header() {
+        let header = Header::from(vec![
+            KeywordRecord::new(
+                Keyword::Simple,
+                Value::Logical(true),
+                Some("file does conform to FITS standard"),
+            ),
+            KeywordRecord::new(Keyword::BitPix, Value::Integer(-32), None),
+            KeywordRecord::new(Keyword::NAxis, Value::Integer(0), None),
+        ]);
+        let mut w = Vec::new();
+        assert_eq!(super::header(&mut w, &header), Ok(2880));
+        assert_eq!(w.len(), 2880);
+        assert_eq!(
+            &w[..80],
+            b"SIMPLE  =                    T / file does conform to FITS standard             "
+        );
+        assert_eq!(
+            &w[80..160],
+            b"BITPIX  =                  -32                                                  "
+        );
+        assert_eq!(&w[240..243], b"END");
+        assert!(w[243..].iter().all(|&b| b == b' '));
+
+        let (rest, parsed) = crate::parser::hdu(&w).unwrap();
+        assert_eq!(rest, b"");
+        assert_eq!(parsed.header().len(), 36);
+        assert_eq!(parsed.header().bitpix(), Some(-32));
+    }
+
+    #[test]
+    fn header_with_end() {
+        let header = Header::from(vec![
+            KeywordRecord::new(Keyword::Simple, Value::Logical(true), None),
+            KeywordRecord::new(Keyword::End, Value::CharacterString(String::from("")), None),
+        ]);
+        let mut w = Vec::new();
+        assert_eq!(super::header(&mut w, &header), Ok(2880));
+        assert_eq!(w.windows(3).filter(|&s| s == b"END").count(), 1);
+    }
+}
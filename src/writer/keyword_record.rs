@@ -0,0 +1,354 @@
+use std::borrow::Cow;
+
+use crate::types::{
+    keyword::Keyword, keyword_record::KeywordRecord, value::Value, FitsError, CARD_SIZE,
+};
+
+use super::value;
+
+/// Serializes a keyword record into one or more 80 byte cards.
+/// A record that was parsed from a file is written back as the original card image, so that
+/// value alignment, comment spacing and unknown records are preserved byte for byte.
+/// A `Value::ContinuedString` and a character string that is too long for one record are spread
+/// over `CONTINUE` records. FITS standard section 4.2.1.2
+pub fn keyword_record(record: &KeywordRecord) -> Result<Vec<u8>, FitsError> {
+    if let Some(card) = record.card() {
+        return Ok(card.to_vec());
+    }
+    let name = record.keyword().name();
+    if let Keyword::Hierarch(_) = record.keyword() {
+        return hierarch(record, name);
+    }
+    if name.len() > 8 {
+        return Err(FitsError::WriteError(format!(
+            "keyword {} is longer than 8 characters",
+            name
+        )));
+    }
+    let cards = match (record.keyword(), record.value()) {
+        (_, Value::Unknown(s)) => vec![format!("{:8}{}", name, s)],
+        (Keyword::End, _) => vec![name.clone()],
+        (
+            Keyword::Comment | Keyword::History | Keyword::Empty | Keyword::Continue,
+            Value::CharacterString(s),
+        ) => match s.is_empty() {
+            true => vec![name.clone()],
+            false => vec![format!("{:8}  {}", name, value::character_string(s))],
+        },
+        // each part keeps its own comment if the record has one for every part
+        (_, Value::ContinuedString(parts)) if record.part_comments().len() == parts.len() => {
+            return commented_cards(
+                &name,
+                continued_string(&name, parts, record.part_comments()),
+            );
+        }
+        (_, Value::ContinuedString(parts)) => {
+            let mut comments = vec![None; parts.len()];
+            if let Some(last) = comments.last_mut() {
+                *last = *record.comment();
+            }
+            return commented_cards(&name, continued_string(&name, parts, &comments));
+        }
+        (_, Value::CharacterString(s)) if value::character_string(s).len() > CARD_SIZE - 10 => {
+            let parts = [Cow::Borrowed(s.as_str())];
+            return commented_cards(&name, continued_string(&name, &parts, &[*record.comment()]));
+        }
+        (_, value) => vec![format!("{:8}= {}", name, value::value(value)?)],
+    };
+
+    cards_with_comment(record, &name, cards)
+}
+
+/// A HIERARCH keyword is written as `HIERARCH <name> = <value>` with the value right after the
+/// value indicator, so that long names leave room for it. Long strings are not continued.
+fn hierarch(record: &KeywordRecord, name: String) -> Result<Vec<u8>, FitsError> {
+    let card = match record.value() {
+        Value::Unknown(s) => format!("{} {}", name, s),
+        value => format!("{} = {}", name, value::value(value)?.trim_start()),
+    };
+    cards_with_comment(record, &name, vec![card])
+}
+
+/// Pads the cards to 80 bytes and adds the comment of `record` to the last one.
+fn cards_with_comment(
+    record: &KeywordRecord,
+    name: &str,
+    cards: Vec<String>,
+) -> Result<Vec<u8>, FitsError> {
+    let last = cards.len() - 1;
+    let cards = cards
+        .into_iter()
+        .enumerate()
+        .map(|(n, card)| (card, record.comment().filter(|_| n == last)))
+        .collect();
+    commented_cards(name, cards)
+}
+
+/// Pads the cards to 80 bytes after adding their comments.
+fn commented_cards(name: &str, cards: Vec<(String, Option<&str>)>) -> Result<Vec<u8>, FitsError> {
+    let mut bytes = Vec::with_capacity(cards.len() * CARD_SIZE);
+    for (mut card, comment) in cards {
+        if card.len() > CARD_SIZE {
+            return Err(FitsError::WriteError(format!(
+                "value of keyword {} does not fit in a keyword record",
+                name
+            )));
+        }
+        if let Some(comment) = comment {
+            if card.len() < 30 {
+                card = format!("{:30}", card);
+            }
+            match comment.starts_with(' ') {
+                true => card.push_str(" /"),
+                false => card.push_str(" / "),
+            }
+            card.push_str(comment);
+            while card.len() > CARD_SIZE {
+                card.pop();
+            }
+        }
+        bytes.extend(card.bytes());
+        bytes.resize(bytes.len() + CARD_SIZE - card.len(), b' ');
+    }
+    Ok(bytes)
+}
+
+/// One card per part, parts that do not fit are split. All but the last string end in `&`.
+/// The comment of a part goes on the last card of the part.
+fn continued_string<'c>(
+    name: &str,
+    parts: &[Cow<str>],
+    comments: &[Option<&'c str>],
+) -> Vec<(String, Option<&'c str>)> {
+    let mut pieces = parts
+        .iter()
+        .zip(comments)
+        .flat_map(|(part, comment)| {
+            let pieces = split_escaped(part, CARD_SIZE - 13);
+            let last = pieces.len() - 1;
+            pieces
+                .into_iter()
+                .enumerate()
+                .map(move |(n, piece)| (piece, comment.filter(|_| n == last)))
+        })
+        .collect::<Vec<_>>();
+    let (last, comment) = pieces.pop().unwrap_or_default();
+    pieces
+        .into_iter()
+        .map(|(piece, comment)| (format!("'{}&'", piece), comment))
+        .chain([(format!("'{:8}'", last), comment)])
+        .enumerate()
+        .map(|(n, (string, comment))| match n {
+            0 => (format!("{:8}= {}", name, string), comment),
+            _ => (
+                format!("{:8}  {}", Keyword::Continue.name(), string),
+                comment,
+            ),
+        })
+        .collect()
+}
+
+/// Splits `s` into pieces of at most `max` characters once single quotes are doubled, without
+/// separating the two quotes.
+fn split_escaped(s: &str, max: usize) -> Vec<String> {
+    let mut pieces = vec![String::new()];
+    for c in s.chars() {
+        let escaped = match c {
+            '\'' => "''".to_string(),
+            c => c.to_string(),
+        };
+        let piece = pieces.last_mut().unwrap();
+        if piece.len() + escaped.len() > max {
+            pieces.push(escaped);
+        } else {
+            piece.push_str(&escaped);
+        }
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyword_record() {
+        assert_eq!(
+            super::keyword_record(&KeywordRecord::new(
+                Keyword::NAxisn(1),
+                Value::Integer(512),
+                Some(" length of data axis 1")
+            )),
+            Ok(
+                b"NAXIS1  =                  512 / length of data axis 1                          "
+                    .to_vec()
+            )
+        );
+        assert_eq!(
+            super::keyword_record(&KeywordRecord::new(
+                Keyword::Object,
+                Value::CharacterString(String::from("NGC 4151")),
+                Some("Name of observed object")
+            )),
+            Ok(
+                b"OBJECT  = 'NGC 4151'           / Name of observed object                        "
+                    .to_vec()
+            )
+        );
+        assert_eq!(
+            super::keyword_record(&KeywordRecord::new(
+                Keyword::Comment,
+                Value::CharacterString(String::from("Laboratory.")),
+                None
+            )),
+            Ok(
+                b"COMMENT   'Laboratory.'                                                         "
+                    .to_vec()
+            )
+        );
+        assert_eq!(
+            super::keyword_record(&KeywordRecord::new(
+                Keyword::End,
+                Value::CharacterString(String::from("")),
+                None
+            )),
+            Ok(
+                b"END                                                                             "
+                    .to_vec()
+            )
+        );
+        assert_eq!(
+            super::keyword_record(&KeywordRecord::new(
+                Keyword::Unknown(*b"CREATOR "),
+                Value::Unknown(String::from("= 'STWFITS '")),
+                None
+            )),
+            Ok(
+                b"CREATOR = 'STWFITS '                                                            "
+                    .to_vec()
+            )
+        );
+    }
+
+    #[test]
+    fn keyword_record_original_card() {
+        let card =
+            b"NAXIS1  =  512 / length of data axis 1                                          ";
+        let record = KeywordRecord::new(
+            Keyword::NAxisn(1),
+            Value::Integer(512),
+            Some(" length of data axis 1"),
+        )
+        .with_card(card);
+        assert_eq!(super::keyword_record(&record), Ok(card.to_vec()));
+    }
+
+    #[test]
+    fn keyword_record_continued_string() {
+        let bytes = super::keyword_record(&KeywordRecord::new(
+            Keyword::Unknown(*b"STRKEY  "),
+            Value::ContinuedString(vec![
+                "This keyword value is continued ".into(),
+                "over two records.".into(),
+            ]),
+            Some("comment"),
+        ))
+        .unwrap();
+        assert_eq!(
+            bytes,
+            format!(
+                "{:80}{:80}",
+                "STRKEY  = 'This keyword value is continued &'",
+                "CONTINUE  'over two records.'  / comment"
+            )
+            .into_bytes()
+        );
+    }
+
+    #[test]
+    fn keyword_record_continued_string_comments() {
+        let record = KeywordRecord::new(
+            Keyword::Unknown(*b"STRKEY  "),
+            Value::ContinuedString(vec!["first ".into(), "second ".into(), "third".into()]),
+            None,
+        )
+        .with_part_comments(vec![Some("one"), Some(" two"), Some("three")]);
+        assert_eq!(record.comment(), &Some("one"));
+        assert_eq!(
+            super::keyword_record(&record).unwrap(),
+            format!(
+                "{:80}{:80}{:80}",
+                "STRKEY  = 'first &'            / one",
+                "CONTINUE  'second &'           / two",
+                "CONTINUE  'third   '           / three"
+            )
+            .into_bytes()
+        );
+    }
+
+    #[test]
+    fn keyword_record_long_string() {
+        let bytes = super::keyword_record(&KeywordRecord::new(
+            Keyword::Unknown(*b"FILENAME"),
+            Value::CharacterString(format!("/data/{}/it's/raw.fits", "x".repeat(58))),
+            Some("input"),
+        ))
+        .unwrap();
+        assert_eq!(
+            bytes,
+            format!(
+                "{:80}{:80}",
+                format!("FILENAME= '/data/{}/it&'", "x".repeat(58)),
+                "CONTINUE  '''s/raw.fits'       / input"
+            )
+            .into_bytes()
+        );
+    }
+
+    #[test]
+    fn keyword_record_hierarch() {
+        let keyword = Keyword::from("HIERARCH ESO DET CHIP TEMP");
+        let bytes = super::keyword_record(&KeywordRecord::new(
+            keyword.clone(),
+            Value::Real(120.5),
+            Some("Chip temperature"),
+        ))
+        .unwrap();
+        assert_eq!(
+            bytes,
+            format!(
+                "{:80}",
+                "HIERARCH ESO DET CHIP TEMP = 120.5 / Chip temperature"
+            )
+            .into_bytes()
+        );
+        assert!(super::keyword_record(&KeywordRecord::new(
+            keyword,
+            Value::CharacterString("x".repeat(60)),
+            None
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn keyword_record_too_long() {
+        assert!(super::keyword_record(&KeywordRecord::new(
+            Keyword::Comment,
+            Value::CharacterString("x".repeat(70)),
+            None
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn keyword_record_long_comment() {
+        let bytes = super::keyword_record(&KeywordRecord::new(
+            Keyword::NAxis,
+            Value::Integer(2),
+            Some("x".repeat(100).as_str()),
+        ))
+        .unwrap();
+        assert_eq!(bytes.len(), 80);
+        assert_eq!(&bytes[30..34], b" / x");
+    }
+}
@@ -0,0 +1,143 @@
+use time::macros::format_description;
+
+use crate::types::{value::Value, FitsError};
+
+/// Formats a value for bytes 11 to 80 of a keyword record, using the fixed format where the
+/// standard defines one. FITS standard section 4.2
+pub fn value(value: &Value) -> Result<String, FitsError> {
+    Ok(match value {
+        Value::CharacterString(s) => character_string(s),
+        Value::ComplexFloat((r, i)) => {
+            format!("{:>20}", format!("({}, {})", real(*r)?, real(*i)?))
+        }
+        Value::ComplexInteger((r, i)) => format!("{:>20}", format!("({}, {})", r, i)),
+        Value::ContinuedString(parts) => character_string(&parts.concat()),
+        Value::Date(d) => character_string(&date(d)),
+        Value::Integer(i) => format!("{:>20}", i),
+        Value::Logical(b) => format!("{:>20}", if *b { "T" } else { "F" }),
+        Value::Real(r) => format!("{:>20}", real(*r)?),
+        Value::Unknown(s) => s.clone(),
+    })
+}
+
+/// Quotes a string, doubling embedded single quotes and padding it to at least 8 characters.
+/// FITS standard section 4.2.1.1
+pub fn character_string(s: &str) -> String {
+    format!("'{:8}'", s.replace('\'', "''"))
+}
+
+/// Formats a real number so that it always contains a decimal point and fits into 20 characters.
+/// NaN and infinities have no representation as a keyword value. FITS standard section 4.2.4
+fn real(r: f64) -> Result<String, FitsError> {
+    if !r.is_finite() {
+        return Err(FitsError::WriteError(format!(
+            "{} cannot be written as a real keyword value",
+            r
+        )));
+    }
+    let mut s = format!("{:?}", r).replace('e', "E");
+    if let (Some(exponent), false) = (s.find('E'), s.contains('.')) {
+        s.insert_str(exponent, ".0");
+    }
+    let mut precision = 16;
+    while s.len() > 20 && precision > 0 {
+        precision -= 1;
+        s = format!("{:.*E}", precision, r);
+    }
+    Ok(s)
+}
+
+fn date(d: &time::PrimitiveDateTime) -> String {
+    let date = d
+        .format(format_description!(
+            "[year]-[month]-[day]T[hour]:[minute]:[second]"
+        ))
+        .unwrap_or_default();
+    match d.nanosecond() {
+        0 => date,
+        _ => format!(
+            "{}.{}",
+            date,
+            d.format(format_description!("[subsecond]"))
+                .unwrap_or_default()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn value() {
+        let value = |value: Value| super::value(&value).unwrap();
+        assert_eq!(
+            value(Value::CharacterString(String::from("IMAGE"))),
+            "'IMAGE   '"
+        );
+        assert_eq!(
+            value(Value::CharacterString(String::from("it's"))),
+            "'it''s   '"
+        );
+        assert_eq!(value(Value::Logical(true)), format!("{:>20}", "T"));
+        assert_eq!(value(Value::Logical(false)), format!("{:>20}", "F"));
+        assert_eq!(value(Value::Integer(-32)), format!("{:>20}", "-32"));
+        assert_eq!(value(Value::Real(1.0)), format!("{:>20}", "1.0"));
+        assert_eq!(
+            value(Value::ComplexFloat((1.5, -2.0))),
+            format!("{:>20}", "(1.5, -2.0)")
+        );
+        assert_eq!(
+            value(Value::ComplexInteger((1, -2))),
+            format!("{:>20}", "(1, -2)")
+        );
+        assert_eq!(
+            value(Value::Date(datetime!(2019-01-01 0:00))),
+            "'2019-01-01T00:00:00'"
+        );
+        assert_eq!(
+            value(Value::Date(datetime!(2019-01-01 0:00:00.5))),
+            "'2019-01-01T00:00:00.5'"
+        );
+        assert_eq!(value(Value::Unknown(String::from("anything"))), "anything");
+    }
+
+    #[test]
+    fn real() {
+        let real = |r: f64| super::real(r).unwrap();
+        assert_eq!(real(0.0), "0.0");
+        assert_eq!(real(-300.1), "-300.1");
+        assert_eq!(real(1e20), "1.0E20");
+        assert_eq!(real(1.5e-7), "1.5E-7");
+        assert_eq!(real(-1.2345678901234567e-300), "-1.234567890123E-300");
+        assert_eq!(real(-1.2345678901234567e-300).len(), 20);
+    }
+
+    #[test]
+    fn not_finite() {
+        for r in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert!(matches!(
+                super::value(&Value::Real(r)),
+                Err(FitsError::WriteError(_))
+            ));
+            assert!(matches!(
+                super::value(&Value::ComplexFloat((1.0, r))),
+                Err(FitsError::WriteError(_))
+            ));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn real_round_trips(r in proptest::num::f64::NORMAL) {
+            let s = super::real(r).unwrap();
+            prop_assert!(s.len() <= 20);
+            prop_assert!(s.contains('.') || s.contains('E'));
+            let parsed: f64 = s.parse().unwrap();
+            prop_assert!(((parsed - r) / r).abs() < 1e-12);
+        }
+    }
+}